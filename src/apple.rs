@@ -0,0 +1,463 @@
+//! Decoders for the values of the EAs macOS attaches to files
+//!
+//! These work on raw bytes and are available on every platform, so that the
+//! values can be inspected wherever the files end up, e.g., on a Linux file
+//! server where they are stored as `user.com.apple.*` EAs.
+//!
+//! * `com.apple.quarantine`: [`Quarantine`]
+//! * `com.apple.metadata:kMDItemWhereFroms`: a binary property list holding an
+//!   array of strings, see [`decode_string_array()`]
+//! * `com.apple.FinderInfo`: [`FinderInfo`]
+//! * `com.apple.lastuseddate#PS`: [`Timespec`]
+
+use crate::Result;
+use errno::Errno;
+use std::{
+    fmt,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Name of the EA recording where a downloaded file comes from.
+pub const XATTR_NAME_QUARANTINE: &str = "com.apple.quarantine";
+
+/// Name of the EA storing the URLs a file was downloaded from.
+pub const XATTR_NAME_WHERE_FROMS: &str = "com.apple.metadata:kMDItemWhereFroms";
+
+/// Name of the EA storing the Finder information.
+pub const XATTR_NAME_FINDER_INFO: &str = "com.apple.FinderInfo";
+
+/// Name of the EA storing when a file was last opened.
+pub const XATTR_NAME_LAST_USED_DATE: &str = "com.apple.lastuseddate#PS";
+
+/// Value of the `com.apple.quarantine` EA, `flags;timestamp;agent;event id`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Quarantine {
+    /// Quarantine flags, see the `FLAG_*` constants.
+    pub flags: u16,
+    /// When the file was quarantined, in seconds since the UNIX epoch.
+    pub timestamp: u64,
+    /// Name of the application that downloaded the file, e.g., `Safari`.
+    pub agent: String,
+    /// UUID of the event in the quarantine events database, `None` if the
+    /// field is absent.
+    pub event_id: Option<String>,
+}
+
+impl Quarantine {
+    /// The file was downloaded.
+    pub const FLAG_DOWNLOAD: u16 = 0x0001;
+    /// The file was created by a sandboxed application.
+    pub const FLAG_SANDBOX: u16 = 0x0002;
+    /// The quarantine can not be bypassed by the user.
+    pub const FLAG_HARD: u16 = 0x0004;
+    /// The user allowed the file to be opened.
+    pub const FLAG_USER_APPROVED: u16 = 0x0040;
+
+    /// Decodes a raw `com.apple.quarantine` value.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let bytes = bytes.strip_suffix(&[0]).unwrap_or(bytes);
+        std::str::from_utf8(bytes)
+            .map_err(|_| Errno(libc::EINVAL))?
+            .parse()
+    }
+
+    /// Encodes this value into a raw `com.apple.quarantine` value.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.to_string().into_bytes()
+    }
+
+    /// Returns when the file was quarantined, `None` if the timestamp can
+    /// not be represented as a `SystemTime`.
+    pub fn time(&self) -> Option<SystemTime> {
+        UNIX_EPOCH.checked_add(Duration::from_secs(self.timestamp))
+    }
+}
+
+impl FromStr for Quarantine {
+    type Err = Errno;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut fields = s.splitn(4, ';');
+        let (flags, timestamp, agent) =
+            match (fields.next(), fields.next(), fields.next()) {
+                (Some(flags), Some(timestamp), Some(agent)) => {
+                    (flags, timestamp, agent)
+                }
+                _ => return Err(Errno(libc::EINVAL)),
+            };
+        let hex = |field: &str| {
+            if field.is_empty() || !field.bytes().all(|b| b.is_ascii_hexdigit())
+            {
+                return Err(Errno(libc::EINVAL));
+            }
+            u64::from_str_radix(field, 16).map_err(|_| Errno(libc::EINVAL))
+        };
+        let flags = hex(flags)?;
+        if flags > u64::from(u16::MAX) {
+            return Err(Errno(libc::EINVAL));
+        }
+
+        Ok(Quarantine {
+            flags: flags as u16,
+            timestamp: hex(timestamp)?,
+            agent: agent.to_owned(),
+            event_id: fields.next().map(str::to_owned),
+        })
+    }
+}
+
+impl fmt::Display for Quarantine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04x};{:08x};{}",
+            self.flags, self.timestamp, self.agent
+        )?;
+        if let Some(event_id) = self.event_id.as_ref() {
+            write!(f, ";{}", event_id)?;
+        }
+        Ok(())
+    }
+}
+
+const BPLIST_MAGIC: &[u8] = b"bplist00";
+const BPLIST_TRAILER_SIZE: usize = 32;
+
+const BPLIST_INT: u8 = 0x10;
+const BPLIST_ASCII_STRING: u8 = 0x50;
+const BPLIST_UTF16_STRING: u8 = 0x60;
+const BPLIST_ARRAY: u8 = 0xA0;
+
+/// Reads a big-endian unsigned integer of `size` (1 to 8) bytes.
+fn read_be(bytes: &[u8], offset: usize, size: usize) -> Result<u64> {
+    let bytes = bytes
+        .get(offset..offset.saturating_add(size))
+        .ok_or(Errno(libc::EINVAL))?;
+    Ok(bytes.iter().fold(0, |acc, b| (acc << 8) | u64::from(*b)))
+}
+
+/// Reads the marker and the length of the object at `offset`, returns the
+/// object type, the length and the offset of the object data.
+fn read_bplist_header(bytes: &[u8], offset: usize) -> Result<(u8, u64, usize)> {
+    let marker = *bytes.get(offset).ok_or(Errno(libc::EINVAL))?;
+    let (ty, len) = (marker & 0xF0, marker & 0x0F);
+    if len != 0x0F {
+        return Ok((ty, u64::from(len), offset + 1));
+    }
+
+    // the length is stored in the following integer object
+    let int_marker = *bytes.get(offset + 1).ok_or(Errno(libc::EINVAL))?;
+    if int_marker & 0xF0 != BPLIST_INT || int_marker & 0x0F > 3 {
+        return Err(Errno(libc::EINVAL));
+    }
+    let size = 1 << (int_marker & 0x0F);
+    let len = read_be(bytes, offset + 2, size)?;
+    Ok((ty, len, offset + 2 + size))
+}
+
+/// Decodes a binary property list (`bplist00`) whose top object is an array
+/// of strings, like the value of `com.apple.metadata:kMDItemWhereFroms`.
+pub fn decode_string_array(bytes: &[u8]) -> Result<Vec<String>> {
+    if bytes.len() < BPLIST_MAGIC.len() + BPLIST_TRAILER_SIZE
+        || !bytes.starts_with(BPLIST_MAGIC)
+    {
+        return Err(Errno(libc::EINVAL));
+    }
+    let trailer = &bytes[bytes.len() - BPLIST_TRAILER_SIZE..];
+    let offset_size = trailer[6] as usize;
+    let ref_size = trailer[7] as usize;
+    let num_objects = read_be(trailer, 8, 8)?;
+    let top_object = read_be(trailer, 16, 8)?;
+    let table_offset = read_be(trailer, 24, 8)? as usize;
+    if !(1..=8).contains(&offset_size)
+        || !(1..=8).contains(&ref_size)
+        || top_object >= num_objects
+        || num_objects > bytes.len() as u64
+    {
+        return Err(Errno(libc::EINVAL));
+    }
+
+    let object_offset = |object: u64| -> Result<usize> {
+        if object >= num_objects {
+            return Err(Errno(libc::EINVAL));
+        }
+        let offset = table_offset
+            .checked_add(object as usize * offset_size)
+            .ok_or(Errno(libc::EINVAL))?;
+        Ok(read_be(bytes, offset, offset_size)? as usize)
+    };
+
+    let (ty, count, refs) =
+        read_bplist_header(bytes, object_offset(top_object)?)?;
+    if ty != BPLIST_ARRAY || count > num_objects {
+        return Err(Errno(libc::EINVAL));
+    }
+
+    (0..count as usize)
+        .map(|i| {
+            let object = read_be(bytes, refs + i * ref_size, ref_size)?;
+            let (ty, len, data) =
+                read_bplist_header(bytes, object_offset(object)?)?;
+            let len = len as usize;
+            match ty {
+                BPLIST_ASCII_STRING => {
+                    let data = bytes
+                        .get(data..data.saturating_add(len))
+                        .ok_or(Errno(libc::EINVAL))?;
+                    if !data.is_ascii() {
+                        return Err(Errno(libc::EINVAL));
+                    }
+                    Ok(String::from_utf8_lossy(data).into_owned())
+                }
+                BPLIST_UTF16_STRING => {
+                    let data = bytes
+                        .get(data..data.saturating_add(len.saturating_mul(2)))
+                        .ok_or(Errno(libc::EINVAL))?;
+                    let units: Vec<u16> = data
+                        .chunks_exact(2)
+                        .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+                        .collect();
+                    String::from_utf16(&units).map_err(|_| Errno(libc::EINVAL))
+                }
+                _ => Err(Errno(libc::EINVAL)),
+            }
+        })
+        .collect()
+}
+
+/// Returns the smallest of 1, 2, 4 and 8 bytes that can hold `value`.
+fn int_size(value: u64) -> usize {
+    match value {
+        0..=0xFF => 1,
+        0x100..=0xFFFF => 2,
+        0x1_0000..=0xFFFF_FFFF => 4,
+        _ => 8,
+    }
+}
+
+fn write_be(bytes: &mut Vec<u8>, value: u64, size: usize) {
+    bytes.extend_from_slice(&value.to_be_bytes()[8 - size..]);
+}
+
+fn write_bplist_header(bytes: &mut Vec<u8>, ty: u8, len: usize) {
+    if len < 0x0F {
+        bytes.push(ty | len as u8);
+    } else {
+        let size = int_size(len as u64);
+        bytes.push(ty | 0x0F);
+        bytes.push(BPLIST_INT | size.trailing_zeros() as u8);
+        write_be(bytes, len as u64, size);
+    }
+}
+
+/// Encodes `strings` into a binary property list (`bplist00`) whose top
+/// object is an array of strings.
+pub fn encode_string_array<S: AsRef<str>>(strings: &[S]) -> Vec<u8> {
+    let num_objects = strings.len() as u64 + 1;
+    let ref_size = int_size(num_objects);
+    let mut bytes = BPLIST_MAGIC.to_vec();
+    let mut offsets = Vec::with_capacity(num_objects as usize);
+
+    // object 0 is the array, objects 1.. are the strings
+    offsets.push(bytes.len() as u64);
+    write_bplist_header(&mut bytes, BPLIST_ARRAY, strings.len());
+    for i in 1..num_objects {
+        write_be(&mut bytes, i, ref_size);
+    }
+    for s in strings.iter().map(AsRef::as_ref) {
+        offsets.push(bytes.len() as u64);
+        if s.is_ascii() {
+            write_bplist_header(&mut bytes, BPLIST_ASCII_STRING, s.len());
+            bytes.extend_from_slice(s.as_bytes());
+        } else {
+            let units: Vec<u16> = s.encode_utf16().collect();
+            write_bplist_header(&mut bytes, BPLIST_UTF16_STRING, units.len());
+            for unit in units {
+                bytes.extend_from_slice(&unit.to_be_bytes());
+            }
+        }
+    }
+
+    let table_offset = bytes.len() as u64;
+    let offset_size = int_size(table_offset);
+    for offset in offsets {
+        write_be(&mut bytes, offset, offset_size);
+    }
+
+    // trailer
+    bytes.extend_from_slice(&[0; 6]);
+    bytes.push(offset_size as u8);
+    bytes.push(ref_size as u8);
+    bytes.extend_from_slice(&num_objects.to_be_bytes());
+    bytes.extend_from_slice(&0_u64.to_be_bytes());
+    bytes.extend_from_slice(&table_offset.to_be_bytes());
+
+    bytes
+}
+
+/// Size of the `com.apple.FinderInfo` value.
+pub const FINDER_INFO_SIZE: usize = 32;
+
+/// Value of the `com.apple.FinderInfo` EA of a file: a `FileInfo` followed by
+/// an `ExtendedFileInfo`, all big-endian.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct FinderInfo {
+    /// File type, e.g., `TEXT`.
+    pub file_type: [u8; 4],
+    /// Creator code of the application that created the file, e.g., `ttxt`.
+    pub creator: [u8; 4],
+    /// Finder flags, see the `FLAG_*` constants.
+    pub flags: u16,
+    /// Location of the icon in its window, vertical coordinate.
+    pub location_v: i16,
+    /// Location of the icon in its window, horizontal coordinate.
+    pub location_h: i16,
+    /// Reserved.
+    pub reserved: u16,
+    /// The `ExtendedFileInfo`, see [`FinderInfo::extended_flags()`].
+    pub extended: [u8; 16],
+}
+
+impl FinderInfo {
+    /// The file is on the desktop.
+    pub const FLAG_IS_ON_DESK: u16 = 0x0001;
+    /// Mask of the color label, see [`FinderInfo::label()`].
+    pub const FLAG_COLOR: u16 = 0x000E;
+    /// The file is shared.
+    pub const FLAG_IS_SHARED: u16 = 0x0040;
+    /// The file has no `INIT` resources.
+    pub const FLAG_HAS_NO_INITS: u16 = 0x0080;
+    /// The Finder has recorded information about the file.
+    pub const FLAG_HAS_BEEN_INITED: u16 = 0x0100;
+    /// The file has a custom icon.
+    pub const FLAG_HAS_CUSTOM_ICON: u16 = 0x0400;
+    /// The file is a stationery pad.
+    pub const FLAG_IS_STATIONERY: u16 = 0x0800;
+    /// The file can not be renamed.
+    pub const FLAG_NAME_LOCKED: u16 = 0x1000;
+    /// The file has a bundle resource.
+    pub const FLAG_HAS_BUNDLE: u16 = 0x2000;
+    /// The file is hidden.
+    pub const FLAG_IS_INVISIBLE: u16 = 0x4000;
+    /// The file is an alias.
+    pub const FLAG_IS_ALIAS: u16 = 0x8000;
+
+    /// Decodes a raw `com.apple.FinderInfo` value.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != FINDER_INFO_SIZE {
+            return Err(Errno(libc::EINVAL));
+        }
+        let u16_at = |i: usize| u16::from_be_bytes([bytes[i], bytes[i + 1]]);
+        let mut info = FinderInfo {
+            flags: u16_at(8),
+            location_v: u16_at(10) as i16,
+            location_h: u16_at(12) as i16,
+            reserved: u16_at(14),
+            ..FinderInfo::default()
+        };
+        info.file_type.copy_from_slice(&bytes[0..4]);
+        info.creator.copy_from_slice(&bytes[4..8]);
+        info.extended.copy_from_slice(&bytes[16..32]);
+
+        Ok(info)
+    }
+
+    /// Encodes this value into a raw `com.apple.FinderInfo` value.
+    pub fn to_bytes(&self) -> [u8; FINDER_INFO_SIZE] {
+        let mut bytes = [0; FINDER_INFO_SIZE];
+        bytes[0..4].copy_from_slice(&self.file_type);
+        bytes[4..8].copy_from_slice(&self.creator);
+        bytes[8..10].copy_from_slice(&self.flags.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.location_v.to_be_bytes());
+        bytes[12..14].copy_from_slice(&self.location_h.to_be_bytes());
+        bytes[14..16].copy_from_slice(&self.reserved.to_be_bytes());
+        bytes[16..32].copy_from_slice(&self.extended);
+        bytes
+    }
+
+    /// Returns the color label, from 0 (none) to 7.
+    pub fn label(&self) -> u8 {
+        ((self.flags & Self::FLAG_COLOR) >> 1) as u8
+    }
+
+    /// Returns the extended Finder flags.
+    pub fn extended_flags(&self) -> u16 {
+        u16::from_be_bytes([self.extended[8], self.extended[9]])
+    }
+}
+
+/// Size of the `com.apple.lastuseddate#PS` value.
+pub const TIMESPEC_SIZE: usize = 16;
+
+/// Value of the `com.apple.lastuseddate#PS` EA: a little-endian 64-bit
+/// `struct timespec`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Timespec {
+    /// Seconds since the UNIX epoch.
+    pub sec: i64,
+    /// Nanoseconds, from 0 to 999,999,999.
+    pub nsec: i64,
+}
+
+impl Timespec {
+    /// Decodes a raw `com.apple.lastuseddate#PS` value.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != TIMESPEC_SIZE {
+            return Err(Errno(libc::EINVAL));
+        }
+        let mut sec = [0; 8];
+        let mut nsec = [0; 8];
+        sec.copy_from_slice(&bytes[..8]);
+        nsec.copy_from_slice(&bytes[8..]);
+        let timespec = Timespec {
+            sec: i64::from_le_bytes(sec),
+            nsec: i64::from_le_bytes(nsec),
+        };
+        if !(0..1_000_000_000).contains(&timespec.nsec) {
+            return Err(Errno(libc::EINVAL));
+        }
+
+        Ok(timespec)
+    }
+
+    /// Encodes this value into a raw `com.apple.lastuseddate#PS` value.
+    pub fn to_bytes(&self) -> [u8; TIMESPEC_SIZE] {
+        let mut bytes = [0; TIMESPEC_SIZE];
+        bytes[..8].copy_from_slice(&self.sec.to_le_bytes());
+        bytes[8..].copy_from_slice(&self.nsec.to_le_bytes());
+        bytes
+    }
+
+    /// Converts a `SystemTime` to a `Timespec`.
+    pub fn from_system_time(time: SystemTime) -> Self {
+        match time.duration_since(UNIX_EPOCH) {
+            Ok(d) => Timespec {
+                sec: d.as_secs() as i64,
+                nsec: i64::from(d.subsec_nanos()),
+            },
+            Err(e) => {
+                let d = e.duration();
+                let (sec, nsec) = (d.as_secs() as i64, d.subsec_nanos());
+                if nsec == 0 {
+                    Timespec { sec: -sec, nsec: 0 }
+                } else {
+                    Timespec {
+                        sec: -sec - 1,
+                        nsec: i64::from(1_000_000_000 - nsec),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Converts this `Timespec` to a `SystemTime`.
+    pub fn to_system_time(self) -> SystemTime {
+        let nsec = Duration::from_nanos(self.nsec as u64);
+        if self.sec >= 0 {
+            UNIX_EPOCH + Duration::from_secs(self.sec as u64) + nsec
+        } else {
+            UNIX_EPOCH - Duration::from_secs(self.sec.unsigned_abs()) + nsec
+        }
+    }
+}
@@ -0,0 +1,409 @@
+//! Reader and writer of AppleDouble files
+//!
+//! When a file leaves an HFS+ or APFS volume for a file system or an archive
+//! without EAs, e.g., FAT, SMB shares or zip files, macOS stores its EAs in a
+//! sidecar named `._<name>` next to it, in the AppleDouble version 2 format.
+//! All the fields are big-endian:
+//!
+//! * A header: the magic number, the version, 16 filler bytes and the number
+//!   of entries, followed by the entry table, giving the ID, the offset and
+//!   the length of each entry.
+//! * The Finder Info entry, holding the 32 bytes of `com.apple.FinderInfo`.
+//!   macOS extends it with 2 bytes of padding and an `ATTR` block holding the
+//!   other EAs: a header, a table of entries with the offset, length, flags
+//!   and NUL-terminated name of each EA, each aligned to 4 bytes, and the
+//!   values.
+//! * The resource fork entry, holding `com.apple.ResourceFork`.
+//!
+//! [`capture()`] and [`AppleDouble::apply()`] convert between an
+//! [`AppleDouble`] and the EAs of a file through [`crate::portable`], so the
+//! EAs are in the `user.` namespace on Linux, e.g.,
+//! `user.com.apple.FinderInfo`.
+//!
+//! This module is available on every platform.
+
+use crate::{
+    apple::{FINDER_INFO_SIZE, XATTR_NAME_FINDER_INFO},
+    name::{AttrName, Namespace},
+    portable::{self, SetMode, Target, ENOATTR},
+    Result,
+};
+use errno::Errno;
+use std::{
+    convert::TryFrom,
+    ffi::{OsStr, OsString},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+/// Magic number of AppleDouble files.
+pub const MAGIC: u32 = 0x0005_1607;
+
+/// Version of the format, the only one supported.
+pub const VERSION: u32 = 0x0002_0000;
+
+/// Magic number of the `ATTR` block.
+pub const ATTR_MAGIC: u32 = 0x4154_5452;
+
+/// Entry ID of the resource fork.
+pub const ENTRY_RESOURCE_FORK: u32 = 2;
+
+/// Entry ID of the Finder Info.
+pub const ENTRY_FINDER_INFO: u32 = 9;
+
+/// Name of the EA storing the resource fork.
+pub const XATTR_NAME_RESOURCE_FORK: &str = "com.apple.ResourceFork";
+
+/// Prefix of the name of AppleDouble sidecars.
+pub const SIDECAR_PREFIX: &str = "._";
+
+/// The filler written by macOS.
+const FILLER: &[u8; 16] = b"Mac OS X        ";
+
+const HEADER_SIZE: usize = 26;
+const ENTRY_SIZE: usize = 12;
+/// Size of the `ATTR` header, which follows the Finder Info and 2 bytes of
+/// padding.
+const ATTR_HEADER_SIZE: usize = 36;
+/// Size of an `ATTR` entry without its name.
+const ATTR_ENTRY_SIZE: usize = 11;
+
+/// An entry of the entry table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Entry {
+    /// ID of the entry, e.g., [`ENTRY_FINDER_INFO`].
+    pub id: u32,
+    /// Offset of the entry from the start of the file.
+    pub offset: u32,
+    /// Length of the entry.
+    pub length: u32,
+}
+
+/// The EAs stored in an AppleDouble file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct AppleDouble {
+    /// The value of `com.apple.FinderInfo`, `None` if it is all zeros, which
+    /// is how macOS writes a missing one.
+    pub finder_info: Option<[u8; FINDER_INFO_SIZE]>,
+    /// The other EAs, with their macOS names.
+    pub attrs: Vec<(OsString, Vec<u8>)>,
+    /// The value of `com.apple.ResourceFork`, `None` if it is empty.
+    pub resource_fork: Option<Vec<u8>>,
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> Result<u16> {
+    match bytes.get(offset..offset + 2) {
+        Some(b) => Ok(u16::from_be_bytes([b[0], b[1]])),
+        None => Err(Errno(libc::EINVAL)),
+    }
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Result<u32> {
+    match bytes.get(offset..offset + 4) {
+        Some(b) => Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]])),
+        None => Err(Errno(libc::EINVAL)),
+    }
+}
+
+/// Returns `offset..offset + length` of `bytes`, `EINVAL` if it is out of
+/// bounds.
+fn range(bytes: &[u8], offset: u32, length: u32) -> Result<&[u8]> {
+    let start = offset as usize;
+    start
+        .checked_add(length as usize)
+        .and_then(|end| bytes.get(start..end))
+        .ok_or(Errno(libc::EINVAL))
+}
+
+fn to_u32(n: usize) -> Result<u32> {
+    u32::try_from(n).map_err(|_| Errno(libc::EINVAL))
+}
+
+/// Parses the header and the entry table of an AppleDouble file.
+///
+/// Returns `EINVAL` if the magic number or the version is wrong, or if the
+/// table or an entry is truncated.
+pub fn parse_entries(bytes: &[u8]) -> Result<Vec<Entry>> {
+    if u32_at(bytes, 0)? != MAGIC || u32_at(bytes, 4)? != VERSION {
+        return Err(Errno(libc::EINVAL));
+    }
+    let count = u16_at(bytes, 24)? as usize;
+
+    let mut entries = Vec::with_capacity(count);
+    for i in 0..count {
+        let at = HEADER_SIZE + i * ENTRY_SIZE;
+        let entry = Entry {
+            id: u32_at(bytes, at)?,
+            offset: u32_at(bytes, at + 4)?,
+            length: u32_at(bytes, at + 8)?,
+        };
+        range(bytes, entry.offset, entry.length)?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Parses the `ATTR` block following the Finder Info at `offset`, whose entry
+/// is `length` bytes long.
+fn parse_attrs(
+    bytes: &[u8],
+    offset: u32,
+    length: u32,
+) -> Result<Vec<(OsString, Vec<u8>)>> {
+    let invalid = || Errno(libc::EINVAL);
+    let header = offset as usize + FINDER_INFO_SIZE + 2;
+    if (length as usize) < FINDER_INFO_SIZE + 2 + ATTR_HEADER_SIZE
+        || u32_at(bytes, header)? != ATTR_MAGIC
+    {
+        return Ok(Vec::new());
+    }
+    let count = u16_at(bytes, header + 34)?;
+
+    let mut attrs = Vec::with_capacity(usize::from(count));
+    let mut at = header + ATTR_HEADER_SIZE;
+    for _ in 0..count {
+        let value_offset = u32_at(bytes, at)?;
+        let value_length = u32_at(bytes, at + 4)?;
+        let name_len = *bytes.get(at + 10).ok_or_else(invalid)? as usize;
+        let name = bytes
+            .get(at + ATTR_ENTRY_SIZE..at + ATTR_ENTRY_SIZE + name_len)
+            .ok_or_else(invalid)?;
+        // the name is NUL-terminated, and its length includes the NUL
+        let name = name.strip_suffix(&[0]).unwrap_or(name);
+        if name.is_empty() || name.contains(&0) {
+            return Err(invalid());
+        }
+        let value = range(bytes, value_offset, value_length)?;
+        attrs.push((OsStr::from_bytes(name).to_owned(), value.to_vec()));
+
+        at += (ATTR_ENTRY_SIZE + name_len + 3) & !3;
+    }
+    Ok(attrs)
+}
+
+impl AppleDouble {
+    /// Parses an AppleDouble file. Entries other than the Finder Info and the
+    /// resource fork are ignored.
+    ///
+    /// Returns `EINVAL` if the file is malformed.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut res = AppleDouble::default();
+
+        for entry in parse_entries(bytes)? {
+            match entry.id {
+                ENTRY_FINDER_INFO => {
+                    let data = range(bytes, entry.offset, entry.length)?;
+                    let info = data
+                        .get(..FINDER_INFO_SIZE)
+                        .ok_or(Errno(libc::EINVAL))?;
+                    if info.iter().any(|b| *b != 0) {
+                        let mut finder_info = [0; FINDER_INFO_SIZE];
+                        finder_info.copy_from_slice(info);
+                        res.finder_info = Some(finder_info);
+                    }
+                    res.attrs = parse_attrs(bytes, entry.offset, entry.length)?;
+                }
+                ENTRY_RESOURCE_FORK if entry.length != 0 => {
+                    let data = range(bytes, entry.offset, entry.length)?;
+                    res.resource_fork = Some(data.to_vec());
+                }
+                _ => (),
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// Encodes this value into an AppleDouble file, laid out like macOS does:
+    /// the Finder Info entry, followed by the `ATTR` block if there are other
+    /// EAs, and the resource fork entry.
+    ///
+    /// Returns `EINVAL` if a name is empty, contains a NUL byte, or is longer
+    /// than 254 bytes, or if there are too many EAs or the file would exceed
+    /// 4 GiB.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        if self.attrs.len() > usize::from(u16::MAX) {
+            return Err(Errno(libc::EINVAL));
+        }
+        for (name, _) in self.attrs.iter() {
+            let name = name.as_bytes();
+            if name.is_empty() || name.contains(&0) || name.len() >= 255 {
+                return Err(Errno(libc::EINVAL));
+            }
+        }
+
+        let finder_info_offset = HEADER_SIZE + 2 * ENTRY_SIZE;
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&MAGIC.to_be_bytes());
+        bytes.extend_from_slice(&VERSION.to_be_bytes());
+        bytes.extend_from_slice(FILLER);
+        bytes.extend_from_slice(&2_u16.to_be_bytes());
+        // the entry table is filled in once the offsets are known
+        bytes.resize(finder_info_offset, 0);
+
+        bytes.extend_from_slice(&self.finder_info.unwrap_or_default());
+        if !self.attrs.is_empty() {
+            bytes.extend_from_slice(&[0; 2]);
+            let header = bytes.len();
+            let entries = header + ATTR_HEADER_SIZE;
+            let data_start = entries
+                + self
+                    .attrs
+                    .iter()
+                    .map(|(name, _)| {
+                        (ATTR_ENTRY_SIZE + name.len() + 1 + 3) & !3
+                    })
+                    .sum::<usize>();
+            let data_length = self
+                .attrs
+                .iter()
+                .map(|(_, value)| value.len())
+                .sum::<usize>();
+            let total_size = data_start + data_length;
+
+            bytes.extend_from_slice(&ATTR_MAGIC.to_be_bytes());
+            // debug tag
+            bytes.extend_from_slice(&0_u32.to_be_bytes());
+            bytes.extend_from_slice(&to_u32(total_size)?.to_be_bytes());
+            bytes.extend_from_slice(&to_u32(data_start)?.to_be_bytes());
+            bytes.extend_from_slice(&to_u32(data_length)?.to_be_bytes());
+            // reserved and flags
+            bytes.extend_from_slice(&[0; 14]);
+            bytes.extend_from_slice(&(self.attrs.len() as u16).to_be_bytes());
+
+            let mut value_offset = data_start;
+            for (name, value) in self.attrs.iter() {
+                let name = name.as_bytes();
+                bytes.extend_from_slice(&to_u32(value_offset)?.to_be_bytes());
+                bytes.extend_from_slice(&to_u32(value.len())?.to_be_bytes());
+                // flags
+                bytes.extend_from_slice(&0_u16.to_be_bytes());
+                bytes.push((name.len() + 1) as u8);
+                bytes.extend_from_slice(name);
+                bytes.push(0);
+                bytes.resize((bytes.len() + 3) & !3, 0);
+                value_offset += value.len();
+            }
+            for (_, value) in self.attrs.iter() {
+                bytes.extend_from_slice(value);
+            }
+        }
+
+        let resource_fork_offset = bytes.len();
+        let resource_fork = self.resource_fork.as_deref().unwrap_or_default();
+        bytes.extend_from_slice(resource_fork);
+
+        let table = [
+            (
+                ENTRY_FINDER_INFO,
+                finder_info_offset,
+                resource_fork_offset - finder_info_offset,
+            ),
+            (
+                ENTRY_RESOURCE_FORK,
+                resource_fork_offset,
+                resource_fork.len(),
+            ),
+        ];
+        for (i, (id, offset, length)) in table.iter().enumerate() {
+            let at = HEADER_SIZE + i * ENTRY_SIZE;
+            bytes[at..at + 4].copy_from_slice(&id.to_be_bytes());
+            bytes[at + 4..at + 8]
+                .copy_from_slice(&to_u32(*offset)?.to_be_bytes());
+            bytes[at + 8..at + 12]
+                .copy_from_slice(&to_u32(*length)?.to_be_bytes());
+        }
+
+        Ok(bytes)
+    }
+
+    /// Sets the EAs of this value on `target`, replacing the existing ones
+    /// with the same names. The EAs of `target` that are not in this value
+    /// are left alone.
+    pub fn apply(&self, target: Target<'_>) -> Result<()> {
+        if let Some(finder_info) = self.finder_info {
+            let name = AttrName::from_darwin(XATTR_NAME_FINDER_INFO)?;
+            portable::set(
+                target,
+                &name,
+                &finder_info,
+                SetMode::CreateOrReplace,
+            )?;
+        }
+        if let Some(resource_fork) = self.resource_fork.as_ref() {
+            let name = AttrName::from_darwin(XATTR_NAME_RESOURCE_FORK)?;
+            portable::set(
+                target,
+                &name,
+                resource_fork,
+                SetMode::CreateOrReplace,
+            )?;
+        }
+        for (name, value) in self.attrs.iter() {
+            let name = AttrName::from_darwin(name)?;
+            portable::set(target, &name, value, SetMode::CreateOrReplace)?;
+        }
+        Ok(())
+    }
+}
+
+/// Retrieves the EA `name` of `target`, `None` if it does not exist.
+fn get_optional(target: Target<'_>, name: &str) -> Result<Option<Vec<u8>>> {
+    match portable::get(target, &AttrName::from_darwin(name)?) {
+        Ok(value) => Ok(Some(value)),
+        Err(Errno(ENOATTR)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Reads the EAs of `target` in the user namespace into an [`AppleDouble`],
+/// the other EAs sorted by name.
+///
+/// Returns `EINVAL` if `com.apple.FinderInfo` is not 32 bytes long.
+pub fn capture(target: Target<'_>) -> Result<AppleDouble> {
+    let mut res = AppleDouble::default();
+
+    if let Some(value) = get_optional(target, XATTR_NAME_FINDER_INFO)? {
+        let mut finder_info = [0; FINDER_INFO_SIZE];
+        if value.len() != FINDER_INFO_SIZE {
+            return Err(Errno(libc::EINVAL));
+        }
+        finder_info.copy_from_slice(&value);
+        res.finder_info =
+            Some(finder_info).filter(|info| info != &[0; FINDER_INFO_SIZE]);
+    }
+    res.resource_fork = get_optional(target, XATTR_NAME_RESOURCE_FORK)?
+        .filter(|value| !value.is_empty());
+
+    let mut names = portable::list(target)?
+        .into_iter()
+        .filter(|name| name.namespace() == Namespace::User)
+        .map(|name| name.name().to_owned())
+        .filter(|name| {
+            name != XATTR_NAME_FINDER_INFO && name != XATTR_NAME_RESOURCE_FORK
+        })
+        .collect::<Vec<_>>();
+    names.sort();
+    for name in names {
+        match portable::get(target, &AttrName::from_darwin(&name)?) {
+            Ok(value) => res.attrs.push((name, value)),
+            // removed since it was listed
+            Err(Errno(ENOATTR)) => (),
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(res)
+}
+
+/// Returns the path of the AppleDouble sidecar of `path`, `._<name>` in the
+/// same directory.
+///
+/// Returns `EINVAL` if `path` has no file name, e.g., `/` or `..`.
+pub fn sidecar_path<P: AsRef<Path>>(path: P) -> Result<PathBuf> {
+    let path = path.as_ref();
+    let file_name = path.file_name().ok_or(Errno(libc::EINVAL))?;
+    let mut sidecar = OsString::from(SIDECAR_PREFIX);
+    sidecar.push(file_name);
+    Ok(path.with_file_name(sidecar))
+}
@@ -0,0 +1,391 @@
+//! Pluggable implementations of the Linux EA operations
+//!
+//! [`XattrBackend`] abstracts the `*xattr()` syscalls, so that code using EAs
+//! can be tested without a file system supporting them:
+//!
+//! * [`SyscallBackend`] calls the syscalls.
+//! * [`MemoryBackend`] keeps everything in memory, including the files, and
+//!   reproduces what the kernel does: error numbers, `Flags`, size limits and
+//!   the permission rules of each namespace.
+
+use crate::{
+    fgetxattr, flistxattr, fremovexattr, fsetxattr, getxattr, lgetxattr,
+    listxattr, llistxattr, lremovexattr, lsetxattr, name::AttrName,
+    name::Namespace, portable::Target, removexattr, setxattr, Flags, Result,
+};
+use errno::Errno;
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::{OsStr, OsString},
+    os::unix::{ffi::OsStrExt, io::RawFd},
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+};
+
+/// The EA operations of Linux.
+///
+/// [`Target::Path`] follows symbolic links like `getxattr(2)`,
+/// [`Target::Link`] does not like `lgetxattr(2)`, and [`Target::Fd`] works on
+/// an open file like `fgetxattr(2)`.
+pub trait XattrBackend {
+    /// Retrieves the value of the EA `name` of `target`.
+    fn get(&self, target: Target<'_>, name: &OsStr) -> Result<Vec<u8>>;
+
+    /// Sets the value of the EA `name` of `target` to `value`.
+    fn set(
+        &self,
+        target: Target<'_>,
+        name: &OsStr,
+        value: &[u8],
+        flags: Flags,
+    ) -> Result<()>;
+
+    /// Lists the EAs of `target`.
+    fn list(&self, target: Target<'_>) -> Result<Vec<OsString>>;
+
+    /// Removes the EA `name` of `target`.
+    fn remove(&self, target: Target<'_>, name: &OsStr) -> Result<()>;
+}
+
+/// The backend calling the syscalls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct SyscallBackend;
+
+impl XattrBackend for SyscallBackend {
+    fn get(&self, target: Target<'_>, name: &OsStr) -> Result<Vec<u8>> {
+        match target {
+            Target::Path(path) => getxattr(path, name),
+            Target::Link(path) => lgetxattr(path, name),
+            Target::Fd(fd) => fgetxattr(fd, name),
+        }
+    }
+
+    fn set(
+        &self,
+        target: Target<'_>,
+        name: &OsStr,
+        value: &[u8],
+        flags: Flags,
+    ) -> Result<()> {
+        match target {
+            Target::Path(path) => setxattr(path, name, value, flags),
+            Target::Link(path) => lsetxattr(path, name, value, flags),
+            Target::Fd(fd) => fsetxattr(fd, name, value, flags),
+        }
+    }
+
+    fn list(&self, target: Target<'_>) -> Result<Vec<OsString>> {
+        match target {
+            Target::Path(path) => listxattr(path),
+            Target::Link(path) => llistxattr(path),
+            Target::Fd(fd) => flistxattr(fd),
+        }
+    }
+
+    fn remove(&self, target: Target<'_>, name: &OsStr) -> Result<()> {
+        match target {
+            Target::Path(path) => removexattr(path, name),
+            Target::Link(path) => lremovexattr(path, name),
+            Target::Fd(fd) => fremovexattr(fd, name),
+        }
+    }
+}
+
+/// `XATTR_NAME_MAX`, the maximum length of an EA name.
+pub const XATTR_NAME_MAX: usize = 255;
+
+/// `XATTR_SIZE_MAX`, the maximum size of an EA value.
+pub const XATTR_SIZE_MAX: usize = 65536;
+
+/// `XATTR_LIST_MAX`, the maximum size of the name list of a file.
+pub const XATTR_LIST_MAX: usize = 65536;
+
+/// Type of a file of a [`MemoryBackend`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FileKind {
+    /// A regular file.
+    File,
+    /// A directory.
+    Directory,
+    /// A symbolic link to the path, relative to its parent if it is relative.
+    Symlink(PathBuf),
+    /// A device, a FIFO or a socket, which can not have `user.*` EAs.
+    Special,
+}
+
+/// Configuration of a [`MemoryBackend`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MemoryConfig {
+    /// Whether the caller has `CAP_SYS_ADMIN`, which is required to access
+    /// `trusted.*` EAs and to set `security.*` EAs.
+    pub privileged: bool,
+    /// Maximum size of a value, `E2BIG` beyond it, capped by
+    /// [`XATTR_SIZE_MAX`].
+    pub max_value_size: usize,
+    /// Maximum total size of the names and values of a file, `ENOSPC` beyond
+    /// it. ext4 stores the EAs of a file in a single block, so about 4 KiB.
+    pub max_total_size: usize,
+}
+
+impl Default for MemoryConfig {
+    /// Unprivileged, with the limits of the VFS.
+    fn default() -> Self {
+        MemoryConfig {
+            privileged: false,
+            max_value_size: XATTR_SIZE_MAX,
+            max_total_size: usize::MAX,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Node {
+    kind: FileKind,
+    attrs: BTreeMap<OsString, Vec<u8>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    files: HashMap<PathBuf, Node>,
+    fds: HashMap<RawFd, PathBuf>,
+    next_fd: RawFd,
+}
+
+/// Maximum number of symbolic links followed, `ELOOP` beyond it.
+const MAX_SYMLINKS: usize = 40;
+
+/// First descriptor returned by [`MemoryBackend::open()`], far from the real
+/// ones.
+const FIRST_FD: RawFd = 1 << 20;
+
+/// A backend keeping the files and their EAs in memory.
+///
+/// Files are created with [`MemoryBackend::create()`] and identified by
+/// their path as given, which is not normalized. Symbolic links are only
+/// followed when they are the target itself, not when they are a component of
+/// its path.
+#[derive(Debug, Default)]
+pub struct MemoryBackend {
+    config: MemoryConfig,
+    state: Mutex<State>,
+}
+
+impl MemoryBackend {
+    /// Creates a backend with no file.
+    pub fn new(config: MemoryConfig) -> Self {
+        MemoryBackend {
+            config,
+            state: Mutex::default(),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        // the state is consistent after every operation
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Creates a file of type `kind`, with no EA.
+    ///
+    /// Returns `EEXIST` if `path` exists.
+    pub fn create<P: AsRef<Path>>(
+        &self,
+        path: P,
+        kind: FileKind,
+    ) -> Result<()> {
+        let mut state = self.state();
+        let path = path.as_ref().to_owned();
+        if state.files.contains_key(&path) {
+            return Err(Errno(libc::EEXIST));
+        }
+        let attrs = BTreeMap::new();
+        state.files.insert(path, Node { kind, attrs });
+        Ok(())
+    }
+
+    /// Opens the file `path`, following symbolic links, and returns a file
+    /// descriptor for [`Target::Fd`], which is only valid for this backend.
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> Result<RawFd> {
+        let mut state = self.state();
+        let path = resolve(&state, Target::Path(path.as_ref()))?;
+        let fd = FIRST_FD + state.next_fd;
+        state.next_fd += 1;
+        state.fds.insert(fd, path);
+        Ok(fd)
+    }
+
+    /// Closes a file descriptor returned by [`MemoryBackend::open()`].
+    ///
+    /// Returns `EBADF` if it is not open.
+    pub fn close(&self, fd: RawFd) -> Result<()> {
+        match self.state().fds.remove(&fd) {
+            Some(_) => Ok(()),
+            None => Err(Errno(libc::EBADF)),
+        }
+    }
+
+    /// Checks that the caller may access `name` of `node`, returns the error
+    /// of a write or of a read depending on `write`.
+    fn check_access(
+        &self,
+        node: &Node,
+        name: &AttrName,
+        write: bool,
+    ) -> Result<()> {
+        match name.namespace() {
+            Namespace::User => match node.kind {
+                FileKind::File | FileKind::Directory => Ok(()),
+                _ if write => Err(Errno(libc::EPERM)),
+                _ => Err(Errno(libc::ENODATA)),
+            },
+            // they are hidden from unprivileged readers
+            Namespace::Trusted if !self.config.privileged => {
+                if write {
+                    Err(Errno(libc::EPERM))
+                } else {
+                    Err(Errno(libc::ENODATA))
+                }
+            }
+            Namespace::Security if write && !self.config.privileged => {
+                Err(Errno(libc::EPERM))
+            }
+            Namespace::System => {
+                // only the POSIX ACLs have a handler
+                match name.name().as_bytes() {
+                    b"posix_acl_access" | b"posix_acl_default" => Ok(()),
+                    _ => Err(Errno(libc::ENOTSUP)),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Returns the path of the file `target` refers to.
+fn resolve(state: &State, target: Target<'_>) -> Result<PathBuf> {
+    let (mut path, follow) = match target {
+        Target::Path(path) => (path.to_owned(), true),
+        Target::Link(path) => (path.to_owned(), false),
+        Target::Fd(fd) => {
+            return state.fds.get(&fd).cloned().ok_or(Errno(libc::EBADF))
+        }
+    };
+
+    for _ in 0..=MAX_SYMLINKS {
+        let node = state
+            .files
+            .get(&path)
+            .ok_or_else(|| missing(state, &path))?;
+        match &node.kind {
+            FileKind::Symlink(dest) if follow => {
+                path = match path.parent() {
+                    Some(parent) => parent.join(dest),
+                    None => dest.clone(),
+                };
+            }
+            _ => return Ok(path),
+        }
+    }
+    Err(Errno(libc::ELOOP))
+}
+
+/// Returns the error of a lookup of the missing `path`, `ENOTDIR` if one of
+/// its ancestors is a file that is not a directory, `ENOENT` otherwise.
+fn missing(state: &State, path: &Path) -> Errno {
+    let not_dir = path.ancestors().skip(1).any(|dir| {
+        matches!(
+            state.files.get(dir),
+            Some(node) if matches!(node.kind, FileKind::File | FileKind::Special)
+        )
+    });
+    if not_dir {
+        Errno(libc::ENOTDIR)
+    } else {
+        Errno(libc::ENOENT)
+    }
+}
+
+/// Validates `name` like the VFS does.
+fn parse_name(name: &OsStr) -> Result<AttrName> {
+    if name.is_empty() || name.len() > XATTR_NAME_MAX {
+        return Err(Errno(libc::ERANGE));
+    }
+    AttrName::from_linux(name)
+}
+
+impl XattrBackend for MemoryBackend {
+    fn get(&self, target: Target<'_>, name: &OsStr) -> Result<Vec<u8>> {
+        let attr = parse_name(name)?;
+        let state = self.state();
+        let node = &state.files[&resolve(&state, target)?];
+        self.check_access(node, &attr, false)?;
+        node.attrs.get(name).cloned().ok_or(Errno(libc::ENODATA))
+    }
+
+    fn set(
+        &self,
+        target: Target<'_>,
+        name: &OsStr,
+        value: &[u8],
+        flags: Flags,
+    ) -> Result<()> {
+        let attr = parse_name(name)?;
+        if value.len() > self.config.max_value_size.min(XATTR_SIZE_MAX) {
+            return Err(Errno(libc::E2BIG));
+        }
+        let mut state = self.state();
+        let path = resolve(&state, target)?;
+        self.check_access(&state.files[&path], &attr, true)?;
+
+        let node = state.files.get_mut(&path).unwrap();
+        let old = node.attrs.get(name);
+        if flags.contains(Flags::XATTR_CREATE) && old.is_some() {
+            return Err(Errno(libc::EEXIST));
+        }
+        if flags.contains(Flags::XATTR_REPLACE) && old.is_none() {
+            return Err(Errno(libc::ENODATA));
+        }
+        let total = node
+            .attrs
+            .iter()
+            .filter(|(n, _)| *n != name)
+            .map(|(n, v)| n.len() + v.len())
+            .sum::<usize>();
+        if total + name.len() + value.len() > self.config.max_total_size {
+            return Err(Errno(libc::ENOSPC));
+        }
+        node.attrs.insert(name.to_owned(), value.to_vec());
+        Ok(())
+    }
+
+    fn list(&self, target: Target<'_>) -> Result<Vec<OsString>> {
+        let state = self.state();
+        let node = &state.files[&resolve(&state, target)?];
+        let names = node
+            .attrs
+            .keys()
+            .filter(|name| match parse_name(name) {
+                Ok(attr) => self.check_access(node, &attr, false).is_ok(),
+                Err(_) => false,
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+
+        // the names are NUL-terminated in the buffer of the syscall
+        let size = names.iter().map(|name| name.len() + 1).sum::<usize>();
+        if size > XATTR_LIST_MAX {
+            return Err(Errno(libc::E2BIG));
+        }
+        Ok(names)
+    }
+
+    fn remove(&self, target: Target<'_>, name: &OsStr) -> Result<()> {
+        let attr = parse_name(name)?;
+        let mut state = self.state();
+        let path = resolve(&state, target)?;
+        self.check_access(&state.files[&path], &attr, true)?;
+        match state.files.get_mut(&path).unwrap().attrs.remove(name) {
+            Some(_) => Ok(()),
+            None => Err(Errno(libc::ENODATA)),
+        }
+    }
+}
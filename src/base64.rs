@@ -0,0 +1,101 @@
+//! Minimal base64 codec (RFC 4648, standard alphabet with padding), used to
+//! store binary EA values in text formats.
+
+const ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes `bytes`.
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() / 3 * 4 + 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = u32::from(b[0]) << 16 | u32::from(b[1]) << 8 | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn sextet(c: u8) -> Option<u32> {
+    let value = match c {
+        b'A'..=b'Z' => c - b'A',
+        b'a'..=b'z' => c - b'a' + 26,
+        b'0'..=b'9' => c - b'0' + 52,
+        b'+' => 62,
+        b'/' => 63,
+        _ => return None,
+    };
+    Some(u32::from(value))
+}
+
+/// Decodes `s`, returns `None` if it is not canonical base64: the padding is
+/// required, and the unused bits of the last group must be zero.
+pub(crate) fn decode(s: &str) -> Option<Vec<u8>> {
+    let s = s.as_bytes();
+    if !s.chunks_exact(4).remainder().is_empty() {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(s.len() / 4 * 3);
+    let groups = s.len() / 4;
+    for (i, group) in s.chunks(4).enumerate() {
+        let padding = match group {
+            [_, _, b'=', b'='] => 2,
+            [_, _, _, b'='] => 1,
+            _ => 0,
+        };
+        if padding != 0 && i + 1 != groups {
+            return None;
+        }
+
+        let mut n = 0;
+        for &c in &group[..4 - padding] {
+            n = n << 6 | sextet(c)?;
+        }
+        n <<= 6 * padding as u32;
+        let bytes = [(n >> 16) as u8, (n >> 8) as u8, n as u8];
+        if bytes[3 - padding..].iter().any(|b| *b != 0) {
+            return None;
+        }
+        out.extend_from_slice(&bytes[..3 - padding]);
+    }
+
+    Some(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode, encode};
+
+    #[test]
+    fn test_base64() {
+        for (plain, encoded) in [
+            (&b""[..], ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="),
+            (b"fooba", "Zm9vYmE="),
+            (b"foobar", "Zm9vYmFy"),
+            (b"\xff\x00\xfe", "/wD+"),
+        ] {
+            assert_eq!(encode(plain), encoded);
+            assert_eq!(decode(encoded).unwrap(), plain);
+        }
+
+        for invalid in
+            ["Zg", "Zg=", "Zh==", "Zg==Zg==", "Z===", "Zm9v!A==", "===="]
+        {
+            assert_eq!(decode(invalid), None);
+        }
+    }
+}
@@ -0,0 +1,244 @@
+//! Helpers for the btrfs per-inode properties exposed as `btrfs.*` EAs
+//!
+//! Only `btrfs.compression` is exposed this way, it selects the compression
+//! algorithm (and optionally its level) used for data written to a file from
+//! now on, and is inherited by files created in a directory.
+//!
+//! File systems other than btrfs do not know the `btrfs.` prefix and reject it
+//! with `EOPNOTSUPP`, while btrfs rejects invalid values with `EINVAL`. To keep
+//! the two apart, the functions of this module check that the target is on
+//! btrfs first, and return `ENOTSUP` if it is not.
+
+use crate::{io_errno, lgetxattr, lremovexattr, lsetxattr, Flags, Result};
+use errno::{errno, Errno};
+use std::{
+    ffi::CString,
+    fmt,
+    fs::{self, File},
+    io,
+    mem::MaybeUninit,
+    os::unix::{
+        ffi::OsStrExt,
+        io::{AsRawFd, FromRawFd},
+    },
+    path::Path,
+    str::FromStr,
+};
+
+/// Name of the EA used to store the compression property.
+pub const XATTR_NAME_BTRFS_COMPRESSION: &str = "btrfs.compression";
+
+/// `f_type` of btrfs, from `<linux/magic.h>`.
+const BTRFS_SUPER_MAGIC: u32 = 0x9123_683E;
+
+/// Value of the `btrfs.compression` property.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Compression {
+    /// `zlib` or `zlib:<level>`, the level ranges from 1 to 9.
+    Zlib(Option<u8>),
+    /// `lzo`
+    Lzo,
+    /// `zstd` or `zstd:<level>`, the level ranges from 1 to 15.
+    Zstd(Option<u8>),
+    /// `none`, never compress the file, even if the file system is mounted
+    /// with `compress-force`. `no` is accepted as an alias when parsing.
+    Disabled,
+}
+
+impl Compression {
+    /// Maximum `zlib` level.
+    pub const ZLIB_MAX_LEVEL: u8 = 9;
+    /// Maximum `zstd` level.
+    pub const ZSTD_MAX_LEVEL: u8 = 15;
+
+    /// Checks that the level, if any, is in range.
+    pub fn validate(self) -> Result<()> {
+        let valid = match self {
+            Compression::Zlib(Some(level)) => {
+                (1..=Self::ZLIB_MAX_LEVEL).contains(&level)
+            }
+            Compression::Zstd(Some(level)) => {
+                (1..=Self::ZSTD_MAX_LEVEL).contains(&level)
+            }
+            _ => true,
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(Errno(libc::EINVAL))
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = Errno;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (algo, level) = match s.split_once(':') {
+            Some((algo, level)) => {
+                if level.is_empty()
+                    || !level.bytes().all(|b| b.is_ascii_digit())
+                {
+                    return Err(Errno(libc::EINVAL));
+                }
+                let level = level.parse().map_err(|_| Errno(libc::EINVAL))?;
+                (algo, Some(level))
+            }
+            None => (s, None),
+        };
+        let compression = match (algo, level) {
+            ("zlib", level) => Compression::Zlib(level),
+            ("lzo", None) => Compression::Lzo,
+            ("zstd", level) => Compression::Zstd(level),
+            ("none" | "no", None) => Compression::Disabled,
+            _ => return Err(Errno(libc::EINVAL)),
+        };
+        compression.validate()?;
+
+        Ok(compression)
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Compression::Zlib(None) => write!(f, "zlib"),
+            Compression::Zlib(Some(level)) => write!(f, "zlib:{}", level),
+            Compression::Lzo => write!(f, "lzo"),
+            Compression::Zstd(None) => write!(f, "zstd"),
+            Compression::Zstd(Some(level)) => write!(f, "zstd:{}", level),
+            Compression::Disabled => write!(f, "none"),
+        }
+    }
+}
+
+/// Returns true if `path` is on a btrfs file system. If `path` is a symbolic
+/// link, the link itself is checked, like the EAs are accessed by the other
+/// functions of this module.
+pub fn is_btrfs<P: AsRef<Path>>(path: P) -> Result<bool> {
+    let path = match CString::new(path.as_ref().as_os_str().as_bytes()) {
+        Ok(p) => p,
+        _ => return Err(Errno(libc::EINVAL)),
+    };
+    let flags = libc::O_PATH | libc::O_NOFOLLOW | libc::O_CLOEXEC;
+    let file = match unsafe { libc::open(path.as_ptr(), flags) } {
+        -1 => return Err(errno()),
+        fd => unsafe { File::from_raw_fd(fd) },
+    };
+    let mut stat = MaybeUninit::<libc::statfs>::uninit();
+
+    match unsafe { libc::fstatfs(file.as_raw_fd(), stat.as_mut_ptr()) } {
+        -1 => Err(errno()),
+        _ => {
+            let stat = unsafe { stat.assume_init() };
+            Ok(stat.f_type as u32 == BTRFS_SUPER_MAGIC)
+        }
+    }
+}
+
+fn ensure_btrfs(path: &Path) -> Result<()> {
+    if is_btrfs(path)? {
+        Ok(())
+    } else {
+        Err(Errno(libc::ENOTSUP))
+    }
+}
+
+/// Retrieves the compression property of `path`, `None` if it is not set.
+///
+/// Returns `ENOTSUP` if `path` is not on btrfs.
+pub fn get_compression<P: AsRef<Path>>(path: P) -> Result<Option<Compression>> {
+    let path = path.as_ref();
+    ensure_btrfs(path)?;
+
+    match lgetxattr(path, XATTR_NAME_BTRFS_COMPRESSION) {
+        Ok(value) => std::str::from_utf8(&value)
+            .map_err(|_| Errno(libc::EINVAL))?
+            .trim_end_matches('\0')
+            .parse()
+            .map(Some),
+        Err(Errno(libc::ENODATA)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn set_compression_unchecked(
+    path: &Path,
+    compression: Option<Compression>,
+) -> Result<()> {
+    match compression {
+        Some(compression) => lsetxattr(
+            path,
+            XATTR_NAME_BTRFS_COMPRESSION,
+            compression.to_string(),
+            Flags::empty(),
+        ),
+        None => match lremovexattr(path, XATTR_NAME_BTRFS_COMPRESSION) {
+            Err(Errno(libc::ENODATA)) => Ok(()),
+            res => res,
+        },
+    }
+}
+
+/// Sets the compression property of the regular file or directory `path`,
+/// `None` resets it, so that the mount options apply again.
+///
+/// Returns `ENOTSUP` if `path` is not on btrfs, and `EINVAL` if the level of
+/// `compression` is out of range or the kernel rejects the value (e.g., `zstd`
+/// is not supported before Linux 4.14, nor levels before Linux 5.1).
+pub fn set_compression<P: AsRef<Path>>(
+    path: P,
+    compression: Option<Compression>,
+) -> Result<()> {
+    let path = path.as_ref();
+    if let Some(compression) = compression {
+        compression.validate()?;
+    }
+    ensure_btrfs(path)?;
+
+    set_compression_unchecked(path, compression)
+}
+
+fn set_compression_tree(
+    dir: &Path,
+    compression: Option<Compression>,
+) -> Result<()> {
+    let mut entries = fs::read_dir(dir)
+        .map_err(io_errno)?
+        .collect::<io::Result<Vec<fs::DirEntry>>>()
+        .map_err(io_errno)?;
+    entries.sort_by_key(fs::DirEntry::file_name);
+
+    for entry in entries {
+        let file_type = entry.file_type().map_err(io_errno)?;
+        if file_type.is_file() {
+            set_compression_unchecked(&entry.path(), compression)?;
+        } else if file_type.is_dir() {
+            set_compression_unchecked(&entry.path(), compression)?;
+            set_compression_tree(&entry.path(), compression)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Sets the compression property of `root` and every regular file and
+/// directory below it, stopping at the first error. Symbolic links are never
+/// followed, and special files are skipped, since they can not have
+/// properties.
+///
+/// Returns `ENOTSUP` if `root` is not on btrfs, see [`set_compression()`] for
+/// the other errors.
+pub fn set_compression_recursive<P: AsRef<Path>>(
+    root: P,
+    compression: Option<Compression>,
+) -> Result<()> {
+    let root = root.as_ref();
+    set_compression(root, compression)?;
+
+    if fs::symlink_metadata(root).map_err(io_errno)?.is_dir() {
+        set_compression_tree(root, compression)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,359 @@
+//! Parsers and writers for the virtual EAs exposed by CephFS
+//!
+//! CephFS does not store these EAs, it computes them from the metadata of the
+//! inode: file layouts, recursive statistics of directories, quotas and
+//! metadata server pinning. All values are text, the parsers work on the raw
+//! bytes returned by `getxattr()` so that they can be used without a cluster.
+
+use crate::{getxattr, removexattr, setxattr, Flags, Result};
+use errno::Errno;
+use std::{
+    fmt,
+    path::Path,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Name of the EA storing the layout of a file.
+pub const XATTR_NAME_CEPH_FILE_LAYOUT: &str = "ceph.file.layout";
+
+/// Name of the EA storing the layout inherited by new files in a directory.
+pub const XATTR_NAME_CEPH_DIR_LAYOUT: &str = "ceph.dir.layout";
+
+/// Name of the EA storing the maximum size of a directory tree.
+pub const XATTR_NAME_CEPH_QUOTA_MAX_BYTES: &str = "ceph.quota.max_bytes";
+
+/// Name of the EA storing the maximum number of files of a directory tree.
+pub const XATTR_NAME_CEPH_QUOTA_MAX_FILES: &str = "ceph.quota.max_files";
+
+/// Name of the EA storing the metadata server rank a directory is pinned to.
+pub const XATTR_NAME_CEPH_DIR_PIN: &str = "ceph.dir.pin";
+
+/// Name of the EA storing the most recent ctime of a directory tree.
+pub const XATTR_NAME_CEPH_DIR_RCTIME: &str = "ceph.dir.rctime";
+
+/// Returns the text of a value, without the trailing NUL some tools add.
+fn text(bytes: &[u8]) -> Result<&str> {
+    let bytes = bytes.strip_suffix(&[0]).unwrap_or(bytes);
+    std::str::from_utf8(bytes)
+        .map(str::trim)
+        .map_err(|_| Errno(libc::EINVAL))
+}
+
+fn parse_decimal<T: FromStr>(s: &str) -> Result<T> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(Errno(libc::EINVAL));
+    }
+    s.parse().map_err(|_| Errno(libc::EINVAL))
+}
+
+/// Layout of a file: how its data is striped over RADOS objects.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Layout {
+    /// Size of the chunks written to each object in turn, in bytes.
+    pub stripe_unit: u64,
+    /// Number of objects a stripe spans.
+    pub stripe_count: u64,
+    /// Size of the RADOS objects, in bytes.
+    pub object_size: u64,
+    /// Name or id of the data pool.
+    pub pool: String,
+    /// RADOS namespace in the pool, if any.
+    pub pool_namespace: Option<String>,
+}
+
+impl Layout {
+    /// Decodes a raw `ceph.file.layout` or `ceph.dir.layout` value.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        text(bytes)?.parse()
+    }
+
+    /// Encodes this layout into a raw `ceph.file.layout` or
+    /// `ceph.dir.layout` value.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.to_string().into_bytes()
+    }
+}
+
+impl FromStr for Layout {
+    type Err = Errno;
+
+    /// Parses `stripe_unit=4194304 stripe_count=1 object_size=4194304
+    /// pool=cephfs_data [pool_namespace=ns]`, the fields may come in any
+    /// order.
+    fn from_str(s: &str) -> Result<Self> {
+        let mut stripe_unit = None;
+        let mut stripe_count = None;
+        let mut object_size = None;
+        let mut pool = None;
+        let mut pool_namespace = None;
+
+        for field in s.split_ascii_whitespace() {
+            let (key, value) =
+                field.split_once('=').ok_or(Errno(libc::EINVAL))?;
+            let duplicated = match key {
+                "stripe_unit" => {
+                    stripe_unit.replace(parse_decimal(value)?).is_some()
+                }
+                "stripe_count" => {
+                    stripe_count.replace(parse_decimal(value)?).is_some()
+                }
+                "object_size" => {
+                    object_size.replace(parse_decimal(value)?).is_some()
+                }
+                "pool" if !value.is_empty() => {
+                    pool.replace(value.to_owned()).is_some()
+                }
+                "pool_namespace" => {
+                    pool_namespace.replace(value.to_owned()).is_some()
+                }
+                _ => return Err(Errno(libc::EINVAL)),
+            };
+            if duplicated {
+                return Err(Errno(libc::EINVAL));
+            }
+        }
+
+        match (stripe_unit, stripe_count, object_size, pool) {
+            (
+                Some(stripe_unit),
+                Some(stripe_count),
+                Some(object_size),
+                Some(pool),
+            ) => Ok(Layout {
+                stripe_unit,
+                stripe_count,
+                object_size,
+                pool,
+                pool_namespace,
+            }),
+            _ => Err(Errno(libc::EINVAL)),
+        }
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "stripe_unit={} stripe_count={} object_size={} pool={}",
+            self.stripe_unit, self.stripe_count, self.object_size, self.pool
+        )?;
+        if let Some(pool_namespace) = self.pool_namespace.as_ref() {
+            write!(f, " pool_namespace={}", pool_namespace)?;
+        }
+        Ok(())
+    }
+}
+
+/// Statistics of a directory, maintained by the metadata servers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DirStat {
+    /// `ceph.dir.entries`, number of entries in the directory.
+    Entries,
+    /// `ceph.dir.files`, number of files in the directory.
+    Files,
+    /// `ceph.dir.subdirs`, number of subdirectories in the directory.
+    Subdirs,
+    /// `ceph.dir.rentries`, number of entries in the tree.
+    Rentries,
+    /// `ceph.dir.rfiles`, number of files in the tree.
+    Rfiles,
+    /// `ceph.dir.rsubdirs`, number of directories in the tree.
+    Rsubdirs,
+    /// `ceph.dir.rbytes`, total size of the files in the tree.
+    Rbytes,
+}
+
+impl DirStat {
+    /// Returns the EA name of this statistic.
+    pub fn name(self) -> &'static str {
+        match self {
+            DirStat::Entries => "ceph.dir.entries",
+            DirStat::Files => "ceph.dir.files",
+            DirStat::Subdirs => "ceph.dir.subdirs",
+            DirStat::Rentries => "ceph.dir.rentries",
+            DirStat::Rfiles => "ceph.dir.rfiles",
+            DirStat::Rsubdirs => "ceph.dir.rsubdirs",
+            DirStat::Rbytes => "ceph.dir.rbytes",
+        }
+    }
+}
+
+/// Decodes a raw counter value, e.g., `ceph.dir.rbytes`.
+pub fn parse_counter(bytes: &[u8]) -> Result<u64> {
+    parse_decimal(text(bytes)?)
+}
+
+/// Decodes a raw `ceph.quota.max_bytes` or `ceph.quota.max_files` value,
+/// `None` if there is no limit.
+pub fn parse_quota(bytes: &[u8]) -> Result<Option<u64>> {
+    match parse_counter(bytes)? {
+        0 => Ok(None),
+        limit => Ok(Some(limit)),
+    }
+}
+
+/// Decodes a raw `ceph.dir.rctime` value, `<seconds>.<nanoseconds>`.
+pub fn parse_rctime(bytes: &[u8]) -> Result<SystemTime> {
+    let text = text(bytes)?;
+    let (secs, nsecs) = match text.split_once('.') {
+        Some((secs, nsecs)) => (secs, nsecs),
+        None => (text, "0"),
+    };
+    // a decimal fraction of a second
+    if nsecs.len() > 9 {
+        return Err(Errno(libc::EINVAL));
+    }
+    let nsecs =
+        parse_decimal::<u32>(nsecs)? * 10_u32.pow(9 - nsecs.len() as u32);
+
+    UNIX_EPOCH
+        .checked_add(Duration::new(parse_decimal(secs)?, nsecs))
+        .ok_or(Errno(libc::EINVAL))
+}
+
+/// Value of `ceph.dir.pin`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Pin {
+    /// `-1`, the directory inherits the pin of its parent.
+    Unpinned,
+    /// The directory is pinned to the metadata server of this rank.
+    Rank(u32),
+}
+
+impl Pin {
+    /// Decodes a raw `ceph.dir.pin` value.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        match text(bytes)? {
+            "-1" => Ok(Pin::Unpinned),
+            rank => Ok(Pin::Rank(parse_decimal(rank)?)),
+        }
+    }
+
+    /// Encodes this value into a raw `ceph.dir.pin` value.
+    pub fn to_bytes(self) -> Vec<u8> {
+        match self {
+            Pin::Unpinned => b"-1".to_vec(),
+            Pin::Rank(rank) => rank.to_string().into_bytes(),
+        }
+    }
+}
+
+/// Retrieves the layout of the file `path`.
+pub fn get_file_layout<P: AsRef<Path>>(path: P) -> Result<Layout> {
+    Layout::from_bytes(&getxattr(path, XATTR_NAME_CEPH_FILE_LAYOUT)?)
+}
+
+/// Sets the layout of the file `path`, which is only allowed while the file
+/// is empty.
+pub fn set_file_layout<P: AsRef<Path>>(path: P, layout: &Layout) -> Result<()> {
+    setxattr(
+        path,
+        XATTR_NAME_CEPH_FILE_LAYOUT,
+        layout.to_bytes(),
+        Flags::empty(),
+    )
+}
+
+/// Retrieves the layout of the directory `path`, `None` if the directory
+/// inherits the layout of its parent.
+pub fn get_dir_layout<P: AsRef<Path>>(path: P) -> Result<Option<Layout>> {
+    match getxattr(path, XATTR_NAME_CEPH_DIR_LAYOUT) {
+        Ok(value) => Layout::from_bytes(&value).map(Some),
+        Err(Errno(libc::ENODATA)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Sets the layout of the directory `path`, `None` removes it so that the
+/// directory inherits the layout of its parent again.
+pub fn set_dir_layout<P: AsRef<Path>>(
+    path: P,
+    layout: Option<&Layout>,
+) -> Result<()> {
+    match layout {
+        Some(layout) => setxattr(
+            path,
+            XATTR_NAME_CEPH_DIR_LAYOUT,
+            layout.to_bytes(),
+            Flags::empty(),
+        ),
+        None => match removexattr(path, XATTR_NAME_CEPH_DIR_LAYOUT) {
+            Err(Errno(libc::ENODATA)) => Ok(()),
+            res => res,
+        },
+    }
+}
+
+/// Retrieves the statistic `stat` of the directory `path`.
+pub fn get_dir_stat<P: AsRef<Path>>(path: P, stat: DirStat) -> Result<u64> {
+    parse_counter(&getxattr(path, stat.name())?)
+}
+
+/// Retrieves the most recent ctime of the tree rooted at the directory `path`.
+pub fn get_rctime<P: AsRef<Path>>(path: P) -> Result<SystemTime> {
+    parse_rctime(&getxattr(path, XATTR_NAME_CEPH_DIR_RCTIME)?)
+}
+
+fn get_quota(path: &Path, name: &str) -> Result<Option<u64>> {
+    match getxattr(path, name) {
+        Ok(value) => parse_quota(&value),
+        Err(Errno(libc::ENODATA)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn set_quota(path: &Path, name: &str, limit: Option<u64>) -> Result<()> {
+    let limit = match limit {
+        Some(0) => return Err(Errno(libc::EINVAL)),
+        Some(limit) => limit,
+        None => 0,
+    };
+    setxattr(path, name, limit.to_string(), Flags::empty())
+}
+
+/// Retrieves the maximum size of the tree rooted at the directory `path`,
+/// `None` if there is no limit.
+pub fn get_quota_max_bytes<P: AsRef<Path>>(path: P) -> Result<Option<u64>> {
+    get_quota(path.as_ref(), XATTR_NAME_CEPH_QUOTA_MAX_BYTES)
+}
+
+/// Sets the maximum size of the tree rooted at the directory `path`, `None`
+/// removes the limit. A limit of 0 is rejected with `EINVAL`.
+pub fn set_quota_max_bytes<P: AsRef<Path>>(
+    path: P,
+    limit: Option<u64>,
+) -> Result<()> {
+    set_quota(path.as_ref(), XATTR_NAME_CEPH_QUOTA_MAX_BYTES, limit)
+}
+
+/// Retrieves the maximum number of files of the tree rooted at the directory
+/// `path`, `None` if there is no limit.
+pub fn get_quota_max_files<P: AsRef<Path>>(path: P) -> Result<Option<u64>> {
+    get_quota(path.as_ref(), XATTR_NAME_CEPH_QUOTA_MAX_FILES)
+}
+
+/// Sets the maximum number of files of the tree rooted at the directory
+/// `path`, `None` removes the limit. A limit of 0 is rejected with `EINVAL`.
+pub fn set_quota_max_files<P: AsRef<Path>>(
+    path: P,
+    limit: Option<u64>,
+) -> Result<()> {
+    set_quota(path.as_ref(), XATTR_NAME_CEPH_QUOTA_MAX_FILES, limit)
+}
+
+/// Retrieves the pin of the directory `path`.
+pub fn get_pin<P: AsRef<Path>>(path: P) -> Result<Pin> {
+    Pin::from_bytes(&getxattr(path, XATTR_NAME_CEPH_DIR_PIN)?)
+}
+
+/// Pins the directory `path` to a metadata server, or unpins it.
+pub fn set_pin<P: AsRef<Path>>(path: P, pin: Pin) -> Result<()> {
+    setxattr(
+        path,
+        XATTR_NAME_CEPH_DIR_PIN,
+        pin.to_bytes(),
+        Flags::empty(),
+    )
+}
@@ -0,0 +1,310 @@
+//! Helpers for the EAs systemd sets on cgroup v2 directories
+//!
+//! cgroupfs supports `trusted.*` and `user.*` EAs, systemd uses them to
+//! communicate with other programs managing cgroups:
+//!
+//! * `delegate`: the cgroup of the unit is delegated (`Delegate=yes`), its
+//!   subtree is managed by the processes of the unit, not by systemd.
+//! * `invocation_id`: the ID of the current invocation of the unit.
+//! * `oomd_avoid` and `oomd_omit`: `ManagedOOMPreference=` of the unit, tells
+//!   `systemd-oomd` to avoid killing the cgroup, or to never kill it.
+//! * `survive_final_kill_signal`: the processes of the cgroup are not killed
+//!   when the system shuts down.
+//!
+//! systemd sets the `trusted.*` variants, and the `user.*` ones as well for
+//! consumers that lack `CAP_SYS_ADMIN`, which is needed to read `trusted.*`
+//! EAs. The readers of this module check the `trusted.*` variant first, and
+//! fall back on the `user.*` one, like systemd does.
+
+use crate::{getxattr, io_errno, removexattr, setxattr, Flags, Result};
+use errno::Errno;
+use std::{
+    collections::VecDeque,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Mount point of the cgroup v2 hierarchy.
+pub const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Prefix of the cgroup EAs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CgroupPrefix {
+    /// `trusted.`, which requires `CAP_SYS_ADMIN`.
+    Trusted,
+    /// `user.`
+    User,
+}
+
+impl CgroupPrefix {
+    /// Returns the prefix, including the trailing dot.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CgroupPrefix::Trusted => "trusted.",
+            CgroupPrefix::User => "user.",
+        }
+    }
+}
+
+/// Boolean markers set on cgroups.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CgroupMarker {
+    /// `delegate`
+    Delegate,
+    /// `oomd_avoid`
+    OomdAvoid,
+    /// `oomd_omit`
+    OomdOmit,
+    /// `survive_final_kill_signal`
+    SurviveFinalKillSignal,
+}
+
+impl CgroupMarker {
+    /// Returns the name of this marker without the prefix.
+    pub fn suffix(self) -> &'static str {
+        match self {
+            CgroupMarker::Delegate => "delegate",
+            CgroupMarker::OomdAvoid => "oomd_avoid",
+            CgroupMarker::OomdOmit => "oomd_omit",
+            CgroupMarker::SurviveFinalKillSignal => "survive_final_kill_signal",
+        }
+    }
+
+    /// Returns the full EA name of this marker under `prefix`.
+    pub fn name(self, prefix: CgroupPrefix) -> String {
+        format!("{}{}", prefix.as_str(), self.suffix())
+    }
+}
+
+/// Parses a boolean the way systemd's `parse_boolean()` does.
+fn parse_boolean(bytes: &[u8]) -> Result<bool> {
+    match bytes {
+        b"1" | b"yes" | b"y" | b"true" | b"t" | b"on" => Ok(true),
+        b"0" | b"no" | b"n" | b"false" | b"f" | b"off" => Ok(false),
+        _ => Err(Errno(libc::EINVAL)),
+    }
+}
+
+/// Retrieves the EA `suffix` of `path`, trying `trusted.` first, then `user.`.
+fn get_trusted_or_user(path: &Path, suffix: &str) -> Result<Option<Vec<u8>>> {
+    for prefix in [CgroupPrefix::Trusted, CgroupPrefix::User] {
+        match getxattr(path, format!("{}{}", prefix.as_str(), suffix)) {
+            Ok(value) => return Ok(Some(value)),
+            // unprivileged processes can't see `trusted.*` EAs
+            Err(Errno(libc::ENODATA | libc::EPERM)) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(None)
+}
+
+/// Returns whether `marker` is set on the cgroup directory `path`, under
+/// either prefix.
+pub fn is_marked<P: AsRef<Path>>(
+    path: P,
+    marker: CgroupMarker,
+) -> Result<bool> {
+    match get_trusted_or_user(path.as_ref(), marker.suffix())? {
+        Some(value) => parse_boolean(&value),
+        None => Ok(false),
+    }
+}
+
+/// Sets `marker` under `prefix` on the cgroup directory `path`, or removes it.
+/// Removing a marker that is not set is not an error.
+pub fn set_marker<P: AsRef<Path>>(
+    path: P,
+    prefix: CgroupPrefix,
+    marker: CgroupMarker,
+    value: bool,
+) -> Result<()> {
+    if value {
+        setxattr(path, marker.name(prefix), "1", Flags::empty())
+    } else {
+        match removexattr(path, marker.name(prefix)) {
+            Err(Errno(libc::ENODATA)) => Ok(()),
+            res => res,
+        }
+    }
+}
+
+/// Returns whether the cgroup directory `path` is delegated.
+pub fn is_delegated<P: AsRef<Path>>(path: P) -> Result<bool> {
+    is_marked(path, CgroupMarker::Delegate)
+}
+
+/// Suffix of the invocation ID EAs.
+const INVOCATION_ID: &str = "invocation_id";
+
+/// A 128-bit ID, e.g., the invocation ID of a unit, written as 32 lowercase
+/// hexadecimal digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Id128(pub [u8; 16]);
+
+impl Id128 {
+    /// Decodes a raw `invocation_id` value.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        std::str::from_utf8(bytes)
+            .map_err(|_| Errno(libc::EINVAL))?
+            .parse()
+    }
+
+    /// Encodes this ID into a raw `invocation_id` value.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.to_string().into_bytes()
+    }
+}
+
+impl FromStr for Id128 {
+    type Err = Errno;
+
+    /// Parses 32 hexadecimal digits, or the UUID format with dashes.
+    fn from_str(s: &str) -> Result<Self> {
+        let digits: Vec<u8> = match s.len() {
+            32 => s.bytes().collect(),
+            36 if [8, 13, 18, 23].iter().all(|i| s.as_bytes()[*i] == b'-') => {
+                s.bytes().filter(|b| *b != b'-').collect()
+            }
+            _ => return Err(Errno(libc::EINVAL)),
+        };
+        if digits.len() != 32 {
+            return Err(Errno(libc::EINVAL));
+        }
+
+        let mut id = [0; 16];
+        for (byte, pair) in id.iter_mut().zip(digits.chunks_exact(2)) {
+            let pair =
+                std::str::from_utf8(pair).map_err(|_| Errno(libc::EINVAL))?;
+            if !pair.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(Errno(libc::EINVAL));
+            }
+            *byte = u8::from_str_radix(pair, 16)
+                .map_err(|_| Errno(libc::EINVAL))?;
+        }
+
+        Ok(Id128(id))
+    }
+}
+
+impl fmt::Display for Id128 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0.iter() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// Retrieves the invocation ID of the unit owning the cgroup directory
+/// `path`, under either prefix.
+pub fn get_invocation_id<P: AsRef<Path>>(path: P) -> Result<Option<Id128>> {
+    get_trusted_or_user(path.as_ref(), INVOCATION_ID)?
+        .map(|value| Id128::from_bytes(&value))
+        .transpose()
+}
+
+/// Sets the invocation ID under `prefix` on the cgroup directory `path`.
+pub fn set_invocation_id<P: AsRef<Path>>(
+    path: P,
+    prefix: CgroupPrefix,
+    id: Id128,
+) -> Result<()> {
+    setxattr(
+        path,
+        format!("{}{}", prefix.as_str(), INVOCATION_ID),
+        id.to_bytes(),
+        Flags::empty(),
+    )
+}
+
+/// Unit types that have a cgroup.
+const CGROUP_UNIT_SUFFIXES: [&str; 3] = [".service", ".scope", ".slice"];
+
+fn check_unit_name(unit: &str) -> Result<()> {
+    let valid = CGROUP_UNIT_SUFFIXES.iter().any(|suffix| {
+        matches!(unit.strip_suffix(suffix), Some(prefix) if !prefix.is_empty())
+    }) && !unit.contains('/');
+    if valid {
+        Ok(())
+    } else {
+        Err(Errno(libc::EINVAL))
+    }
+}
+
+/// Returns the path of the slice `slice` relative to the root of the cgroup
+/// hierarchy, e.g., `a.slice/a-b.slice` for `a-b.slice`, and an empty path
+/// for the root slice `-.slice`.
+pub fn slice_path(slice: &str) -> Result<PathBuf> {
+    if slice == "-.slice" {
+        return Ok(PathBuf::new());
+    }
+    let prefix = slice.strip_suffix(".slice").ok_or(Errno(libc::EINVAL))?;
+    if prefix.is_empty()
+        || prefix.starts_with('-')
+        || prefix.ends_with('-')
+        || prefix.contains("--")
+        || prefix.contains('/')
+    {
+        return Err(Errno(libc::EINVAL));
+    }
+
+    let mut path = PathBuf::new();
+    for (i, _) in prefix.match_indices('-') {
+        path.push(format!("{}.slice", &prefix[..i]));
+    }
+    path.push(slice);
+    Ok(path)
+}
+
+/// Searches the cgroup hierarchy mounted at `root` (usually [`CGROUP_ROOT`])
+/// for the cgroup of `unit`, e.g., `sshd.service`, returns `None` if the unit
+/// has no cgroup.
+///
+/// The search is breadth-first so that the unit of the system manager is
+/// found before any unit with the same name in a user manager or a delegated
+/// subtree. Symbolic links are never followed, and the cgroups that are
+/// removed or can not be read during the search are skipped.
+pub fn find_unit_cgroup<P: AsRef<Path>>(
+    root: P,
+    unit: &str,
+) -> Result<Option<PathBuf>> {
+    check_unit_name(unit)?;
+    // systemd escapes names that could clash with cgroupfs files
+    let escaped = format!("_{}", unit);
+    let root = root.as_ref();
+    let mut queue = VecDeque::from(vec![root.to_owned()]);
+    // cgroups are removed while being walked, and delegated subtrees may be
+    // unreadable, neither of which prevents finding the unit elsewhere
+    let skip = |e: &io::Error| {
+        matches!(e.raw_os_error(), Some(libc::ENOENT) | Some(libc::EACCES))
+    };
+
+    while let Some(dir) = queue.pop_front() {
+        let entries = fs::read_dir(&dir).and_then(|entries| {
+            entries.collect::<io::Result<Vec<fs::DirEntry>>>()
+        });
+        let mut entries = match entries {
+            Ok(entries) => entries,
+            Err(e) if dir != root && skip(&e) => continue,
+            Err(e) => return Err(io_errno(e)),
+        };
+        entries.sort_by_key(fs::DirEntry::file_name);
+
+        for entry in entries {
+            match entry.file_type() {
+                Ok(file_type) if file_type.is_dir() => (),
+                Ok(_) => continue,
+                Err(e) if skip(&e) => continue,
+                Err(e) => return Err(io_errno(e)),
+            }
+            let name = entry.file_name();
+            if name == unit || name.to_str() == Some(&escaped) {
+                return Ok(Some(entry.path()));
+            }
+            queue.push_back(entry.path());
+        }
+    }
+
+    Ok(None)
+}
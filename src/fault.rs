@@ -0,0 +1,306 @@
+//! Fault injection for testing the error paths of EA code
+//!
+//! [`FaultBackend`] wraps another [`XattrBackend`] and makes chosen calls
+//! fail, so that the handling of errors that are hard to provoke on a real
+//! file system, e.g., `ENOSPC`, `EDQUOT`, `EINTR` or the `ERANGE` caused by a
+//! value growing between the size probe and the read, can be tested. Code
+//! under test has to be generic over [`XattrBackend`], and is given a
+//! [`FaultBackend`] wrapping a [`MemoryBackend`](crate::backend::MemoryBackend)
+//! or a [`SyscallBackend`](crate::backend::SyscallBackend).
+//!
+//! Each [`Fault`] selects calls by operation, name and path, decides which of
+//! them fail with a [`Trigger`], and how with an [`Action`]. Probabilistic
+//! triggers draw from a generator seeded by [`FaultBackend::with_seed()`], so
+//! a test fails the same way on every run.
+
+use crate::{backend::XattrBackend, portable::Target, Flags, Result};
+use errno::Errno;
+use std::{
+    ffi::{OsStr, OsString},
+    fmt,
+    path::PathBuf,
+    sync::{Mutex, MutexGuard},
+};
+
+/// An EA operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Op {
+    /// [`XattrBackend::get()`]
+    Get,
+    /// [`XattrBackend::set()`]
+    Set,
+    /// [`XattrBackend::list()`]
+    List,
+    /// [`XattrBackend::remove()`]
+    Remove,
+}
+
+/// A call made to a [`FaultBackend`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Call<'a> {
+    /// The operation.
+    pub op: Op,
+    /// The file.
+    pub target: Target<'a>,
+    /// The EA name, `None` for [`Op::List`].
+    pub name: Option<&'a OsStr>,
+}
+
+/// Which of the calls selected by a [`Fault`] fail.
+pub enum Trigger {
+    /// All of them.
+    Always,
+    /// Only the nth one, counting from 1.
+    Nth(usize),
+    /// The first n ones, e.g., to test retrying on `EINTR`.
+    FirstN(usize),
+    /// Each one with this probability, from 0.0 to 1.0.
+    Probability(f64),
+    /// Those for which the predicate returns true. It is called while the
+    /// [`FaultBackend`] is locked, so it must not call it.
+    Predicate(Box<dyn Fn(&Call<'_>) -> bool + Send + Sync>),
+}
+
+impl fmt::Debug for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trigger::Always => write!(f, "Always"),
+            Trigger::Nth(n) => write!(f, "Nth({})", n),
+            Trigger::FirstN(n) => write!(f, "FirstN({})", n),
+            Trigger::Probability(p) => write!(f, "Probability({})", p),
+            Trigger::Predicate(_) => write!(f, "Predicate(..)"),
+        }
+    }
+}
+
+/// What happens to a call that fails.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Action {
+    /// The call fails with the error, without reaching the inner backend.
+    Fail(Errno),
+    /// Only for [`Op::Get`], the other calls are not selected, so they do not
+    /// count towards [`Trigger::Nth`] and the like: a concurrent writer
+    /// replaces the value between the size probe and the read. The value is
+    /// set on the inner backend, and the call fails with `ERANGE` if it grew,
+    /// like [`crate::getxattr()`] does, or returns it otherwise.
+    ResizeValue(Vec<u8>),
+}
+
+/// A fault to inject.
+#[derive(Debug)]
+pub struct Fault {
+    /// The operation of the calls, `None` for all of them.
+    pub op: Option<Op>,
+    /// The EA name of the calls, `None` for all of them.
+    pub name: Option<OsString>,
+    /// The path of the calls, `None` for all of them. Calls on a
+    /// [`Target::Fd`] only match `None`.
+    pub path: Option<PathBuf>,
+    /// Which of the matching calls fail.
+    pub trigger: Trigger,
+    /// How they fail.
+    pub action: Action,
+}
+
+impl Fault {
+    /// Creates a fault matching every call.
+    pub fn new(trigger: Trigger, action: Action) -> Self {
+        Fault {
+            op: None,
+            name: None,
+            path: None,
+            trigger,
+            action,
+        }
+    }
+
+    /// Returns true if `call` is selected by the filters of this fault.
+    fn matches(&self, call: &Call<'_>) -> bool {
+        let path = match call.target {
+            Target::Path(path) | Target::Link(path) => Some(path),
+            Target::Fd(_) => None,
+        };
+        let resize = matches!(self.action, Action::ResizeValue(_));
+        (!resize || call.op == Op::Get)
+            && (self.op.is_none() || self.op == Some(call.op))
+            && (self.name.is_none() || self.name.as_deref() == call.name)
+            && (self.path.is_none() || self.path.as_deref() == path)
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    /// The faults, with the number of calls they matched.
+    faults: Vec<(Fault, usize)>,
+    /// State of the splitmix64 generator.
+    seed: u64,
+    injected: usize,
+}
+
+impl State {
+    /// Returns a number in `[0, 1)`.
+    fn random(&mut self) -> f64 {
+        self.seed = self.seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.seed;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1_u64 << 53) as f64
+    }
+
+    /// Returns the action of the first fault triggered by `call`.
+    fn check(&mut self, call: &Call<'_>) -> Option<Action> {
+        for i in 0..self.faults.len() {
+            if !self.faults[i].0.matches(call) {
+                continue;
+            }
+            self.faults[i].1 += 1;
+            let count = self.faults[i].1;
+            let fire = match &self.faults[i].0.trigger {
+                Trigger::Always => true,
+                Trigger::Nth(n) => count == *n,
+                Trigger::FirstN(n) => count <= *n,
+                Trigger::Probability(p) => {
+                    let p = *p;
+                    self.random() < p
+                }
+                Trigger::Predicate(predicate) => predicate(call),
+            };
+            if fire {
+                self.injected += 1;
+                return Some(self.faults[i].0.action.clone());
+            }
+        }
+        None
+    }
+}
+
+/// A backend injecting faults into the calls made to another one.
+///
+/// The faults are checked in the order they were injected, and the first one
+/// triggered by a call applies. The calls no fault applies to are passed to
+/// the inner backend.
+#[derive(Debug)]
+pub struct FaultBackend<B> {
+    inner: B,
+    state: Mutex<State>,
+}
+
+impl<B: XattrBackend> FaultBackend<B> {
+    /// Wraps `inner`, with no fault and a seed of 0.
+    pub fn new(inner: B) -> Self {
+        FaultBackend::with_seed(inner, 0)
+    }
+
+    /// Wraps `inner`, with no fault and the given seed for
+    /// [`Trigger::Probability`].
+    pub fn with_seed(inner: B, seed: u64) -> Self {
+        FaultBackend {
+            inner,
+            state: Mutex::new(State {
+                faults: Vec::new(),
+                seed,
+                injected: 0,
+            }),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        // a panicking predicate leaves the state consistent
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Adds a fault, checked after the ones already injected.
+    pub fn inject(&self, fault: Fault) {
+        self.state().faults.push((fault, 0));
+    }
+
+    /// Removes all the faults.
+    pub fn clear(&self) {
+        self.state().faults.clear();
+    }
+
+    /// Returns the number of faults injected so far.
+    pub fn injected(&self) -> usize {
+        self.state().injected
+    }
+
+    /// Returns the inner backend.
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    /// Returns the inner backend, consuming the wrapper.
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+
+    fn check(&self, call: Call<'_>) -> Option<Action> {
+        self.state().check(&call)
+    }
+}
+
+impl<B: XattrBackend> XattrBackend for FaultBackend<B> {
+    fn get(&self, target: Target<'_>, name: &OsStr) -> Result<Vec<u8>> {
+        let call = Call {
+            op: Op::Get,
+            target,
+            name: Some(name),
+        };
+        match self.check(call) {
+            Some(Action::Fail(e)) => Err(e),
+            Some(Action::ResizeValue(value)) => {
+                let probed = self.inner.get(target, name)?.len();
+                self.inner.set(target, name, &value, Flags::empty())?;
+                if value.len() > probed {
+                    Err(Errno(libc::ERANGE))
+                } else {
+                    Ok(value)
+                }
+            }
+            None => self.inner.get(target, name),
+        }
+    }
+
+    fn set(
+        &self,
+        target: Target<'_>,
+        name: &OsStr,
+        value: &[u8],
+        flags: Flags,
+    ) -> Result<()> {
+        let call = Call {
+            op: Op::Set,
+            target,
+            name: Some(name),
+        };
+        match self.check(call) {
+            Some(Action::Fail(e)) => Err(e),
+            _ => self.inner.set(target, name, value, flags),
+        }
+    }
+
+    fn list(&self, target: Target<'_>) -> Result<Vec<OsString>> {
+        let call = Call {
+            op: Op::List,
+            target,
+            name: None,
+        };
+        match self.check(call) {
+            Some(Action::Fail(e)) => Err(e),
+            _ => self.inner.list(target),
+        }
+    }
+
+    fn remove(&self, target: Target<'_>, name: &OsStr) -> Result<()> {
+        let call = Call {
+            op: Op::Remove,
+            target,
+            name: Some(name),
+        };
+        match self.check(call) {
+            Some(Action::Fail(e)) => Err(e),
+            _ => self.inner.remove(target, name),
+        }
+    }
+}
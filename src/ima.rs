@@ -0,0 +1,415 @@
+//! Decoders for the IMA and EVM values stored in `security.ima` and
+//! `security.evm`
+//!
+//! Every value starts with a byte identifying its format
+//! (`enum evm_ima_xattr_type` in the kernel):
+//!
+//! | type   | format                                              |
+//! |--------|-----------------------------------------------------|
+//! | `0x01` | `IMA_XATTR_DIGEST`, a SHA-1 digest                  |
+//! | `0x02` | `EVM_XATTR_HMAC`, a HMAC-SHA1 digest                |
+//! | `0x03` | `EVM_IMA_XATTR_DIGSIG`, a signature                 |
+//! | `0x04` | `IMA_XATTR_DIGEST_NG`, a hash algorithm id + digest |
+//! | `0x05` | `EVM_XATTR_PORTABLE_DIGSIG`, a portable signature   |
+//! | `0x06` | `IMA_VERITY_DIGSIG`, a fs-verity signature          |
+//!
+//! Signatures use the `signature_v2_hdr` layout: type, version, hash
+//! algorithm id, a big-endian 32-bit key id, a big-endian 16-bit signature
+//! size and then the signature itself.
+
+use crate::{
+    digest::{Algorithm, Hasher},
+    fgetxattr, getxattr, io_errno, Result,
+};
+use errno::Errno;
+use std::{fs::File, io::Read, os::unix::io::RawFd, path::Path};
+
+/// Name of the EA used to store the IMA value.
+pub const XATTR_NAME_IMA: &str = "security.ima";
+
+/// Name of the EA used to store the EVM value.
+pub const XATTR_NAME_EVM: &str = "security.evm";
+
+const IMA_XATTR_DIGEST: u8 = 0x01;
+const EVM_XATTR_HMAC: u8 = 0x02;
+const EVM_IMA_XATTR_DIGSIG: u8 = 0x03;
+const IMA_XATTR_DIGEST_NG: u8 = 0x04;
+const EVM_XATTR_PORTABLE_DIGSIG: u8 = 0x05;
+const IMA_VERITY_DIGSIG: u8 = 0x06;
+
+/// Size of a SHA-1 digest.
+const SHA1_DIGEST_SIZE: usize = 20;
+const MD5_DIGEST_SIZE: usize = 16;
+
+/// Hash algorithms, as defined in `include/uapi/linux/hash_info.h`.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HashAlgo {
+    Md4,
+    Md5,
+    Sha1,
+    RipeMd160,
+    Sha256,
+    Sha384,
+    Sha512,
+    Sha224,
+    RipeMd128,
+    RipeMd256,
+    RipeMd320,
+    Wp256,
+    Wp384,
+    Wp512,
+    Tgr128,
+    Tgr160,
+    Tgr192,
+    Sm3_256,
+    Streebog256,
+    Streebog512,
+    Sha3_256,
+    Sha3_384,
+    Sha3_512,
+}
+
+const HASH_ALGOS: [HashAlgo; 23] = [
+    HashAlgo::Md4,
+    HashAlgo::Md5,
+    HashAlgo::Sha1,
+    HashAlgo::RipeMd160,
+    HashAlgo::Sha256,
+    HashAlgo::Sha384,
+    HashAlgo::Sha512,
+    HashAlgo::Sha224,
+    HashAlgo::RipeMd128,
+    HashAlgo::RipeMd256,
+    HashAlgo::RipeMd320,
+    HashAlgo::Wp256,
+    HashAlgo::Wp384,
+    HashAlgo::Wp512,
+    HashAlgo::Tgr128,
+    HashAlgo::Tgr160,
+    HashAlgo::Tgr192,
+    HashAlgo::Sm3_256,
+    HashAlgo::Streebog256,
+    HashAlgo::Streebog512,
+    HashAlgo::Sha3_256,
+    HashAlgo::Sha3_384,
+    HashAlgo::Sha3_512,
+];
+
+impl HashAlgo {
+    /// Returns the algorithm identified by `id`, `EINVAL` if unknown.
+    pub fn from_id(id: u8) -> Result<Self> {
+        HASH_ALGOS
+            .get(id as usize)
+            .copied()
+            .ok_or(Errno(libc::EINVAL))
+    }
+
+    /// Returns the id of this algorithm.
+    pub fn id(self) -> u8 {
+        self as u8
+    }
+
+    /// Returns the size of a digest produced by this algorithm.
+    pub fn digest_size(self) -> usize {
+        match self {
+            HashAlgo::Md4
+            | HashAlgo::Md5
+            | HashAlgo::RipeMd128
+            | HashAlgo::Tgr128 => 16,
+            HashAlgo::Sha1 | HashAlgo::RipeMd160 | HashAlgo::Tgr160 => 20,
+            HashAlgo::Tgr192 => 24,
+            HashAlgo::Sha224 => 28,
+            HashAlgo::Sha256
+            | HashAlgo::RipeMd256
+            | HashAlgo::Wp256
+            | HashAlgo::Sm3_256
+            | HashAlgo::Streebog256
+            | HashAlgo::Sha3_256 => 32,
+            HashAlgo::RipeMd320 => 40,
+            HashAlgo::Sha384 | HashAlgo::Wp384 | HashAlgo::Sha3_384 => 48,
+            HashAlgo::Sha512
+            | HashAlgo::Wp512
+            | HashAlgo::Streebog512
+            | HashAlgo::Sha3_512 => 64,
+        }
+    }
+
+    fn algorithm(self) -> Option<Algorithm> {
+        match self {
+            HashAlgo::Sha1 => Some(Algorithm::Sha1),
+            HashAlgo::Sha224 => Some(Algorithm::Sha224),
+            HashAlgo::Sha256 => Some(Algorithm::Sha256),
+            HashAlgo::Sha384 => Some(Algorithm::Sha384),
+            HashAlgo::Sha512 => Some(Algorithm::Sha512),
+            _ => None,
+        }
+    }
+}
+
+/// A signature in the `signature_v2_hdr` format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    /// Format version, 2 for file signatures, 3 for fs-verity signatures.
+    pub version: u8,
+    /// Hash algorithm of the signed digest.
+    pub hash_algo: HashAlgo,
+    /// The last 4 bytes of the signing key's subject key identifier.
+    pub keyid: u32,
+    /// The signature itself.
+    pub signature: Vec<u8>,
+}
+
+impl Signature {
+    /// Decodes the header and signature following the type byte.
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 8 {
+            return Err(Errno(libc::EINVAL));
+        }
+        let version = bytes[0];
+        if version != 2 && version != 3 {
+            return Err(Errno(libc::EINVAL));
+        }
+        let hash_algo = HashAlgo::from_id(bytes[1])?;
+        let keyid =
+            u32::from_be_bytes([bytes[2], bytes[3], bytes[4], bytes[5]]);
+        let sig_size = u16::from_be_bytes([bytes[6], bytes[7]]) as usize;
+        let signature = &bytes[8..];
+        if signature.len() != sig_size {
+            return Err(Errno(libc::EINVAL));
+        }
+
+        Ok(Signature {
+            version,
+            hash_algo,
+            keyid,
+            signature: signature.to_vec(),
+        })
+    }
+
+    fn encode(&self, ty: u8) -> Result<Vec<u8>> {
+        if self.signature.len() > u16::MAX as usize {
+            return Err(Errno(libc::EINVAL));
+        }
+
+        let mut bytes = vec![ty, self.version, self.hash_algo.id()];
+        bytes.extend_from_slice(&self.keyid.to_be_bytes());
+        bytes.extend_from_slice(&(self.signature.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(&self.signature);
+        Ok(bytes)
+    }
+}
+
+/// A decoded `security.ima` value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImaXattr {
+    /// `IMA_XATTR_DIGEST`, a SHA-1 digest of the file content.
+    Digest(Vec<u8>),
+    /// `IMA_XATTR_DIGEST` with a 16-byte digest, which the kernel takes as a
+    /// legacy MD5 digest of the file content.
+    LegacyMd5(Vec<u8>),
+    /// `IMA_XATTR_DIGEST_NG`, a digest of the file content.
+    DigestNg {
+        /// Hash algorithm of the digest.
+        algo: HashAlgo,
+        /// The digest.
+        digest: Vec<u8>,
+    },
+    /// `EVM_IMA_XATTR_DIGSIG`, a signature of the file content digest.
+    Signature(Signature),
+    /// `IMA_VERITY_DIGSIG`, a signature of the fs-verity file digest.
+    VeritySignature(Signature),
+}
+
+impl ImaXattr {
+    /// Decodes a raw `security.ima` value.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        match bytes.split_first() {
+            Some((&IMA_XATTR_DIGEST, digest)) => match digest.len() {
+                SHA1_DIGEST_SIZE => Ok(ImaXattr::Digest(digest.to_vec())),
+                MD5_DIGEST_SIZE => Ok(ImaXattr::LegacyMd5(digest.to_vec())),
+                _ => Err(Errno(libc::EINVAL)),
+            },
+            Some((&IMA_XATTR_DIGEST_NG, rest)) => {
+                let (&algo, digest) =
+                    rest.split_first().ok_or(Errno(libc::EINVAL))?;
+                let algo = HashAlgo::from_id(algo)?;
+                if digest.len() != algo.digest_size() {
+                    return Err(Errno(libc::EINVAL));
+                }
+                Ok(ImaXattr::DigestNg {
+                    algo,
+                    digest: digest.to_vec(),
+                })
+            }
+            Some((&EVM_IMA_XATTR_DIGSIG, rest)) => {
+                Ok(ImaXattr::Signature(Signature::decode(rest)?))
+            }
+            Some((&IMA_VERITY_DIGSIG, rest)) => {
+                Ok(ImaXattr::VeritySignature(Signature::decode(rest)?))
+            }
+            _ => Err(Errno(libc::EINVAL)),
+        }
+    }
+
+    /// Encodes this value into a raw `security.ima` value.
+    ///
+    /// Returns `EINVAL` if the size of a digest does not match its algorithm.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        match self {
+            ImaXattr::Digest(_) | ImaXattr::LegacyMd5(_) => {
+                // `digest()` returns the algorithm matching the variant
+                let (algo, digest) =
+                    self.digest().ok_or(Errno(libc::EINVAL))?;
+                if digest.len() != algo.digest_size() {
+                    return Err(Errno(libc::EINVAL));
+                }
+                let mut bytes = vec![IMA_XATTR_DIGEST];
+                bytes.extend_from_slice(digest);
+                Ok(bytes)
+            }
+            ImaXattr::DigestNg { algo, digest } => {
+                if digest.len() != algo.digest_size() {
+                    return Err(Errno(libc::EINVAL));
+                }
+                let mut bytes = vec![IMA_XATTR_DIGEST_NG, algo.id()];
+                bytes.extend_from_slice(digest);
+                Ok(bytes)
+            }
+            ImaXattr::Signature(sig) => sig.encode(EVM_IMA_XATTR_DIGSIG),
+            ImaXattr::VeritySignature(sig) => sig.encode(IMA_VERITY_DIGSIG),
+        }
+    }
+
+    /// Returns the hash algorithm and the file content digest, if this value
+    /// carries one.
+    pub fn digest(&self) -> Option<(HashAlgo, &[u8])> {
+        match self {
+            ImaXattr::Digest(digest) => Some((HashAlgo::Sha1, digest)),
+            ImaXattr::LegacyMd5(digest) => Some((HashAlgo::Md5, digest)),
+            ImaXattr::DigestNg { algo, digest } => Some((*algo, digest)),
+            _ => None,
+        }
+    }
+}
+
+/// A decoded `security.evm` value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvmXattr {
+    /// `EVM_XATTR_HMAC`, a HMAC-SHA1 of the file metadata.
+    Hmac(Vec<u8>),
+    /// `EVM_IMA_XATTR_DIGSIG`, a signature of the file metadata.
+    Signature(Signature),
+    /// `EVM_XATTR_PORTABLE_DIGSIG`, a signature of the file metadata that
+    /// does not cover the inode number and generation, so it survives being
+    /// copied to another file system.
+    PortableSignature(Signature),
+}
+
+impl EvmXattr {
+    /// Decodes a raw `security.evm` value.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        match bytes.split_first() {
+            Some((&EVM_XATTR_HMAC, hmac)) => {
+                if hmac.len() != SHA1_DIGEST_SIZE {
+                    return Err(Errno(libc::EINVAL));
+                }
+                Ok(EvmXattr::Hmac(hmac.to_vec()))
+            }
+            Some((&EVM_IMA_XATTR_DIGSIG, rest)) => {
+                Ok(EvmXattr::Signature(Signature::decode(rest)?))
+            }
+            Some((&EVM_XATTR_PORTABLE_DIGSIG, rest)) => {
+                Ok(EvmXattr::PortableSignature(Signature::decode(rest)?))
+            }
+            _ => Err(Errno(libc::EINVAL)),
+        }
+    }
+
+    /// Encodes this value into a raw `security.evm` value.
+    ///
+    /// Returns `EINVAL` if the size of the HMAC is not that of SHA-1.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        match self {
+            EvmXattr::Hmac(hmac) => {
+                if hmac.len() != SHA1_DIGEST_SIZE {
+                    return Err(Errno(libc::EINVAL));
+                }
+                let mut bytes = vec![EVM_XATTR_HMAC];
+                bytes.extend_from_slice(hmac);
+                Ok(bytes)
+            }
+            EvmXattr::Signature(sig) => sig.encode(EVM_IMA_XATTR_DIGSIG),
+            EvmXattr::PortableSignature(sig) => {
+                sig.encode(EVM_XATTR_PORTABLE_DIGSIG)
+            }
+        }
+    }
+}
+
+/// Retrieves and decodes the `security.ima` value of `path`. If `path` is a
+/// symbolic link, it will be dereferenced.
+pub fn getima<P: AsRef<Path>>(path: P) -> Result<ImaXattr> {
+    ImaXattr::from_bytes(&getxattr(path, XATTR_NAME_IMA)?)
+}
+
+/// Retrieves and decodes the `security.ima` value of the file specified by the
+/// open file descriptor `fd`.
+pub fn fgetima(fd: RawFd) -> Result<ImaXattr> {
+    ImaXattr::from_bytes(&fgetxattr(fd, XATTR_NAME_IMA)?)
+}
+
+/// Retrieves and decodes the `security.evm` value of `path`. If `path` is a
+/// symbolic link, it will be dereferenced.
+pub fn getevm<P: AsRef<Path>>(path: P) -> Result<EvmXattr> {
+    EvmXattr::from_bytes(&getxattr(path, XATTR_NAME_EVM)?)
+}
+
+/// Retrieves and decodes the `security.evm` value of the file specified by the
+/// open file descriptor `fd`.
+pub fn fgetevm(fd: RawFd) -> Result<EvmXattr> {
+    EvmXattr::from_bytes(&fgetxattr(fd, XATTR_NAME_EVM)?)
+}
+
+/// Computes the digest of the content of the file at `path`.
+///
+/// SHA-1, SHA-224, SHA-256, SHA-384 and SHA-512 are supported, other
+/// algorithms fail with `ENOTSUP`.
+pub fn file_digest<P: AsRef<Path>>(path: P, algo: HashAlgo) -> Result<Vec<u8>> {
+    let algorithm = algo.algorithm().ok_or(Errno(libc::ENOTSUP))?;
+    let mut file = File::open(path).map_err(io_errno)?;
+    let mut hasher = Hasher::new(algorithm);
+    let mut buffer = vec![0_u8; 64 * 1024];
+
+    loop {
+        match file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => hasher.update(&buffer[..n]),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(io_errno(e)),
+        }
+    }
+
+    Ok(hasher.finish())
+}
+
+/// Recomputes the content digest of the file at `path` and checks it against
+/// the digest carried by `ima`.
+///
+/// `ima` has to carry a digest, see [`ImaXattr::digest()`], otherwise `EINVAL`
+/// is returned, as signatures cannot be verified without the key. Digests of
+/// an algorithm [`file_digest()`] does not support fail with `ENOTSUP`.
+pub fn verify_ima_digest<P: AsRef<Path>>(
+    path: P,
+    ima: &ImaXattr,
+) -> Result<bool> {
+    let (algo, expected) = ima.digest().ok_or(Errno(libc::EINVAL))?;
+    Ok(file_digest(path, algo)? == expected)
+}
+
+/// Reads the `security.ima` value of `path` and checks the file content
+/// against it, see [`verify_ima_digest()`].
+pub fn verify_ima<P: AsRef<Path>>(path: P) -> Result<bool> {
+    let path = path.as_ref();
+    verify_ima_digest(path, &getima(path)?)
+}
@@ -73,11 +73,90 @@
 
 mod platforms;
 
+pub mod apple;
+
+pub mod appledouble;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod backend;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod base64;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod btrfs;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod ceph;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod cgroup;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod digest;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod fault;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod ima;
+
+pub mod limit;
+
+pub mod name;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod nfs4_acl;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod oci;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod overlay;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod pax;
+
+pub mod portable;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod posix_acl;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod rootless;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod s3;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod samba;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod selinux;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod sidecar;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod smack;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod trace;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod xdg;
+
 use errno::Errno;
 
 /// Customized `Result` type for `extattr`.
 pub type Result<T> = std::result::Result<T, Errno>;
 
+/// Converts an I/O error to its error number, `EIO` if it has none.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn io_errno(e: std::io::Error) -> Errno {
+    Errno(e.raw_os_error().unwrap_or(libc::EIO))
+}
+
 // Platform-dependent re-export
 
 #[cfg(target_os = "freebsd")]
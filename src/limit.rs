@@ -0,0 +1,78 @@
+//! Upper bound on the buffers allocated to retrieve EA values and name lists
+//!
+//! The syscalls that retrieve a value or a name list are called twice, first
+//! with an empty buffer to query the size, then with a buffer of that size.
+//! FUSE and network file systems can report any size from the first call, so
+//! the size is checked against a maximum before allocating the buffer. A size
+//! that exceeds it fails with `EOVERFLOW`, which the EA syscalls do not
+//! return, so that it can not be mistaken for the `E2BIG` or `ERANGE` of the
+//! kernel.
+//!
+//! The maximum is global, see [`set_max_size()`], and can be overridden for
+//! the calls made by a closure on the current thread, see
+//! [`with_max_size()`].
+
+use crate::Result;
+use errno::Errno;
+use std::{
+    cell::Cell,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Default maximum size, 64 MiB, which is far beyond what Linux allows (64
+/// KiB) but lets macOS resource forks through.
+pub const DEFAULT_MAX_SIZE: usize = 64 << 20;
+
+static MAX_SIZE: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_SIZE);
+
+thread_local! {
+    // `const` initializers require Rust 1.59
+    #[allow(clippy::missing_const_for_thread_local)]
+    static MAX_SIZE_OVERRIDE: Cell<Option<usize>> = Cell::new(None);
+}
+
+/// Returns the maximum size in effect on the current thread.
+pub fn max_size() -> usize {
+    MAX_SIZE_OVERRIDE
+        .with(Cell::get)
+        .unwrap_or_else(|| MAX_SIZE.load(Ordering::Relaxed))
+}
+
+/// Sets the global maximum size, for every thread that does not override it.
+pub fn set_max_size(max: usize) {
+    MAX_SIZE.store(max, Ordering::Relaxed);
+}
+
+/// Restores the previous override when dropped, even if `f` panics.
+struct Restore(Option<usize>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        MAX_SIZE_OVERRIDE.with(|max_size| max_size.set(self.0));
+    }
+}
+
+/// Calls `f` with the maximum size set to `max` for the calls it makes on the
+/// current thread.
+///
+/// The override is not inherited by the threads `f` spawns or hands work to,
+/// which use the global maximum.
+///
+/// ```ignore
+/// // allow a single large value
+/// let value = with_max_size(1 << 30, || getxattr(path, "user.big"))?;
+/// ```
+pub fn with_max_size<T, F: FnOnce() -> T>(max: usize, f: F) -> T {
+    let _restore =
+        Restore(MAX_SIZE_OVERRIDE.with(|max_size| max_size.replace(Some(max))));
+    f()
+}
+
+/// Checks the size reported by the size query against the maximum size.
+pub(crate) fn check_size(size: usize) -> Result<()> {
+    if size > max_size() {
+        Err(Errno(libc::EOVERFLOW))
+    } else {
+        Ok(())
+    }
+}
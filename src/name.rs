@@ -0,0 +1,235 @@
+//! Platform-neutral EA names
+//!
+//! Every platform names EAs differently:
+//!
+//! * Linux prefixes the name with its namespace, e.g., `user.foo`.
+//! * FreeBSD and NetBSD pass the namespace separately, e.g.,
+//!   `(EXTATTR_NAMESPACE_USER, "foo")`.
+//! * macOS has a single flat namespace, e.g., `com.apple.quarantine`, whose
+//!   EAs are stored under `user.` when copied to Linux.
+//!
+//! [`AttrName`] holds a name from any of them, so that it can be carried to
+//! another platform. Converting a name back to the platform it comes from
+//! always gives the original name, converting it to another platform fails
+//! with `ENOTSUP` if the namespace does not exist there.
+//!
+//! The parser of the name lists returned by the FreeBSD and NetBSD
+//! `extattr_list_*()` syscalls lives here as well, so that they can be decoded
+//! on any platform.
+//!
+//! This module is available on every platform.
+
+use crate::Result;
+use errno::Errno;
+use std::ffi::{OsStr, OsString};
+use std::os::unix::ffi::OsStrExt;
+
+/// `attrnamespace` argument of the FreeBSD and NetBSD syscalls.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(non_camel_case_types)]
+pub enum AttrNamespace {
+    /// User namespace EA
+    EXTATTR_NAMESPACE_USER = 1,
+    /// System namespace EA
+    EXTATTR_NAMESPACE_SYSTEM = 2,
+}
+
+/// Namespace of an EA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Namespace {
+    /// `user.` on Linux, `EXTATTR_NAMESPACE_USER` on the BSDs, and every EA on
+    /// macOS.
+    User,
+    /// `system.` on Linux, `EXTATTR_NAMESPACE_SYSTEM` on the BSDs.
+    System,
+    /// `security.` on Linux.
+    Security,
+    /// `trusted.` on Linux.
+    Trusted,
+}
+
+impl Namespace {
+    /// Returns the Linux prefix of this namespace, including the trailing dot.
+    pub fn linux_prefix(self) -> &'static str {
+        match self {
+            Namespace::User => "user.",
+            Namespace::System => "system.",
+            Namespace::Security => "security.",
+            Namespace::Trusted => "trusted.",
+        }
+    }
+
+    /// Returns the FreeBSD and NetBSD namespace of this namespace, `None` if
+    /// it only exists on Linux.
+    pub fn to_bsd(self) -> Option<AttrNamespace> {
+        match self {
+            Namespace::User => Some(AttrNamespace::EXTATTR_NAMESPACE_USER),
+            Namespace::System => Some(AttrNamespace::EXTATTR_NAMESPACE_SYSTEM),
+            Namespace::Security | Namespace::Trusted => None,
+        }
+    }
+}
+
+impl From<AttrNamespace> for Namespace {
+    fn from(namespace: AttrNamespace) -> Self {
+        match namespace {
+            AttrNamespace::EXTATTR_NAMESPACE_USER => Namespace::User,
+            AttrNamespace::EXTATTR_NAMESPACE_SYSTEM => Namespace::System,
+        }
+    }
+}
+
+/// A platform-neutral EA name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AttrName {
+    namespace: Namespace,
+    name: OsString,
+}
+
+impl AttrName {
+    /// Creates a name, `name` does not include any namespace prefix.
+    ///
+    /// Returns `EINVAL` if `name` is empty or contains a NUL byte.
+    pub fn new<N: AsRef<OsStr>>(namespace: Namespace, name: N) -> Result<Self> {
+        let name = name.as_ref();
+        if name.is_empty() || name.as_bytes().contains(&0) {
+            return Err(Errno(libc::EINVAL));
+        }
+        Ok(AttrName {
+            namespace,
+            name: name.to_owned(),
+        })
+    }
+
+    /// Returns the namespace.
+    pub fn namespace(&self) -> Namespace {
+        self.namespace
+    }
+
+    /// Returns the name, without any namespace prefix.
+    pub fn name(&self) -> &OsStr {
+        &self.name
+    }
+
+    /// Parses a Linux name, e.g., `user.foo`.
+    ///
+    /// Returns `ENOTSUP` if the prefix is not a known namespace, like Linux
+    /// does, and `EINVAL` if the name after the prefix is invalid.
+    pub fn from_linux<N: AsRef<OsStr>>(name: N) -> Result<Self> {
+        let name = name.as_ref().as_bytes();
+        for namespace in [
+            Namespace::User,
+            Namespace::System,
+            Namespace::Security,
+            Namespace::Trusted,
+        ] {
+            if let Some(name) =
+                name.strip_prefix(namespace.linux_prefix().as_bytes())
+            {
+                return AttrName::new(namespace, OsStr::from_bytes(name));
+            }
+        }
+        Err(Errno(libc::ENOTSUP))
+    }
+
+    /// Returns the Linux name, e.g., `user.foo`.
+    pub fn to_linux(&self) -> OsString {
+        let mut name = OsString::from(self.namespace.linux_prefix());
+        name.push(&self.name);
+        name
+    }
+
+    /// Creates a name from the arguments of the FreeBSD and NetBSD syscalls.
+    pub fn from_bsd<N: AsRef<OsStr>>(
+        namespace: AttrNamespace,
+        name: N,
+    ) -> Result<Self> {
+        AttrName::new(namespace.into(), name)
+    }
+
+    /// Returns the arguments of the FreeBSD and NetBSD syscalls.
+    ///
+    /// Returns `ENOTSUP` if the namespace only exists on Linux.
+    pub fn to_bsd(&self) -> Result<(AttrNamespace, &OsStr)> {
+        match self.namespace.to_bsd() {
+            Some(namespace) => Ok((namespace, &self.name)),
+            None => Err(Errno(libc::ENOTSUP)),
+        }
+    }
+
+    /// Creates a name from a macOS name, in the user namespace.
+    pub fn from_darwin<N: AsRef<OsStr>>(name: N) -> Result<Self> {
+        AttrName::new(Namespace::User, name)
+    }
+
+    /// Returns the macOS name.
+    ///
+    /// Returns `ENOTSUP` if the name is not in the user namespace, as macOS
+    /// has no other namespace.
+    pub fn to_darwin(&self) -> Result<&OsStr> {
+        match self.namespace {
+            Namespace::User => Ok(&self.name),
+            _ => Err(Errno(libc::ENOTSUP)),
+        }
+    }
+}
+
+/// Parses the name list returned by the FreeBSD and NetBSD `extattr_list_*()`
+/// syscalls, and returns the names of the complete entries at the start of
+/// `bytes`, along with the number of bytes they take.
+///
+/// > Each list entry consists of a single byte containing the length of the
+/// > attribute name, followed by the attribute name. The attribute name is not
+/// > terminated by ASCII 0 (nul).
+///
+/// Parsing stops at the first entry that is truncated or has an empty name,
+/// so the returned length is less than `bytes.len()` if `bytes` is invalid.
+pub fn parse_extattr_list_prefix(bytes: &[u8]) -> (Vec<OsString>, usize) {
+    let mut names = Vec::new();
+    let mut rest = bytes;
+
+    while let Some((&len, entries)) = rest.split_first() {
+        let len = len as usize;
+        if len == 0 || len > entries.len() {
+            break;
+        }
+        let (name, entries) = entries.split_at(len);
+        names.push(OsStr::from_bytes(name).to_owned());
+        rest = entries;
+    }
+
+    (names, bytes.len() - rest.len())
+}
+
+/// Parses the name list returned by the FreeBSD and NetBSD `extattr_list_*()`
+/// syscalls, see [`parse_extattr_list_prefix()`] for the format.
+///
+/// Returns `EINVAL` if an entry is truncated or has an empty name.
+pub fn parse_extattr_list(bytes: &[u8]) -> Result<Vec<OsString>> {
+    match parse_extattr_list_prefix(bytes) {
+        (names, len) if len == bytes.len() => Ok(names),
+        _ => Err(Errno(libc::EINVAL)),
+    }
+}
+
+/// Encodes `names` in the format of the FreeBSD and NetBSD `extattr_list_*()`
+/// syscalls, see [`parse_extattr_list_prefix()`].
+///
+/// Returns `EINVAL` if a name is empty or longer than 255 bytes.
+pub fn encode_extattr_list<I, N>(names: I) -> Result<Vec<u8>>
+where
+    I: IntoIterator<Item = N>,
+    N: AsRef<OsStr>,
+{
+    let mut bytes = Vec::new();
+    for name in names {
+        let name = name.as_ref().as_bytes();
+        if name.is_empty() || name.len() > usize::from(u8::MAX) {
+            return Err(Errno(libc::EINVAL));
+        }
+        bytes.push(name.len() as u8);
+        bytes.extend_from_slice(name);
+    }
+    Ok(bytes)
+}
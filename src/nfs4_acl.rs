@@ -0,0 +1,887 @@
+//! Codec for the NFSv4 ACL stored in `system.nfs4_acl`, and mapping between
+//! NFSv4 ACLs and POSIX ACLs
+//!
+//! The value is XDR-encoded (all integers are big-endian): the number of ACEs
+//! followed by the ACEs, each of which consists of a type, flags, an access
+//! mask and a `who` string (a length followed by the bytes, padded to a
+//! multiple of 4).
+//!
+//! The text format is the one used by `nfs4_getfacl(1)` and
+//! `nfs4_setfacl(1)`, one ACE per line:
+//!
+//! ```text
+//! A::OWNER@:rwatTnNcCy
+//! A:g:GROUP@:rtncy
+//! D:fdi:alice@example.com:wa
+//! ```
+//!
+//! The mapping between NFSv4 ACLs and POSIX ACLs follows
+//! [draft-ietf-nfsv4-acl-mapping](https://datatracker.ietf.org/doc/html/draft-ietf-nfsv4-acl-mapping-05),
+//! as implemented by the Linux NFS server.
+
+use crate::{
+    fgetxattr, fsetxattr, getxattr,
+    posix_acl::{Perm, PosixAcl, PosixAclEntry, Tag},
+    setxattr, Flags, Result,
+};
+use bitflags::bitflags;
+use errno::Errno;
+use std::{fmt, os::unix::io::RawFd, path::Path, str::FromStr};
+
+/// Name of the EA used to store the NFSv4 ACL.
+pub const XATTR_NAME_NFS4_ACL: &str = "system.nfs4_acl";
+
+/// Special principal: the owner of the file.
+pub const WHO_OWNER: &str = "OWNER@";
+/// Special principal: the owning group of the file.
+pub const WHO_GROUP: &str = "GROUP@";
+/// Special principal: everyone, including the owner and the owning group.
+pub const WHO_EVERYONE: &str = "EVERYONE@";
+
+/// Type of an ACE.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AceType {
+    /// `A`, `ACE4_ACCESS_ALLOWED_ACE_TYPE`
+    Allow,
+    /// `D`, `ACE4_ACCESS_DENIED_ACE_TYPE`
+    Deny,
+    /// `U`, `ACE4_SYSTEM_AUDIT_ACE_TYPE`
+    Audit,
+    /// `L`, `ACE4_SYSTEM_ALARM_ACE_TYPE`
+    Alarm,
+}
+
+impl AceType {
+    fn from_u32(ty: u32) -> Result<Self> {
+        match ty {
+            0 => Ok(AceType::Allow),
+            1 => Ok(AceType::Deny),
+            2 => Ok(AceType::Audit),
+            3 => Ok(AceType::Alarm),
+            _ => Err(Errno(libc::EINVAL)),
+        }
+    }
+
+    fn to_u32(self) -> u32 {
+        match self {
+            AceType::Allow => 0,
+            AceType::Deny => 1,
+            AceType::Audit => 2,
+            AceType::Alarm => 3,
+        }
+    }
+}
+
+bitflags! {
+    /// ACE flags
+    pub struct AceFlags: u32 {
+        /// `f`, inherited by files.
+        const FILE_INHERIT = 0x0000_0001;
+        /// `d`, inherited by directories.
+        const DIRECTORY_INHERIT = 0x0000_0002;
+        /// `n`, not propagated beyond the next level.
+        const NO_PROPAGATE_INHERIT = 0x0000_0004;
+        /// `i`, only used for inheritance, not for access checks.
+        const INHERIT_ONLY = 0x0000_0008;
+        /// `S`, audit or alarm on successful access.
+        const SUCCESSFUL_ACCESS = 0x0000_0010;
+        /// `F`, audit or alarm on failed access.
+        const FAILED_ACCESS = 0x0000_0020;
+        /// `g`, `who` is a group.
+        const IDENTIFIER_GROUP = 0x0000_0040;
+        /// `O`, the ACE was inherited.
+        const INHERITED_ACE = 0x0000_0080;
+    }
+}
+
+bitflags! {
+    /// ACE access mask
+    pub struct AccessMask: u32 {
+        /// `r`, read data of a file or list a directory.
+        const READ_DATA = 0x0000_0001;
+        /// `w`, write data of a file or create a file in a directory.
+        const WRITE_DATA = 0x0000_0002;
+        /// `a`, append data to a file or create a subdirectory.
+        const APPEND_DATA = 0x0000_0004;
+        /// `n`, read named attributes.
+        const READ_NAMED_ATTRS = 0x0000_0008;
+        /// `N`, write named attributes.
+        const WRITE_NAMED_ATTRS = 0x0000_0010;
+        /// `x`, execute a file or traverse a directory.
+        const EXECUTE = 0x0000_0020;
+        /// `D`, delete a file or directory within a directory.
+        const DELETE_CHILD = 0x0000_0040;
+        /// `t`, read basic attributes.
+        const READ_ATTRIBUTES = 0x0000_0080;
+        /// `T`, write basic attributes.
+        const WRITE_ATTRIBUTES = 0x0000_0100;
+        /// Set the retention period of a file, NFSv4.1, no text form.
+        const WRITE_RETENTION = 0x0000_0200;
+        /// Set the retention hold of a file, NFSv4.1, no text form.
+        const WRITE_RETENTION_HOLD = 0x0000_0400;
+        /// `d`, delete the file or directory.
+        const DELETE = 0x0001_0000;
+        /// `c`, read the ACL.
+        const READ_ACL = 0x0002_0000;
+        /// `C`, write the ACL.
+        const WRITE_ACL = 0x0004_0000;
+        /// `o`, change the owner.
+        const WRITE_OWNER = 0x0008_0000;
+        /// `y`, synchronous I/O.
+        const SYNCHRONIZE = 0x0010_0000;
+    }
+}
+
+const FLAG_CHARS: [(char, AceFlags); 8] = [
+    ('f', AceFlags::FILE_INHERIT),
+    ('d', AceFlags::DIRECTORY_INHERIT),
+    ('n', AceFlags::NO_PROPAGATE_INHERIT),
+    ('i', AceFlags::INHERIT_ONLY),
+    ('S', AceFlags::SUCCESSFUL_ACCESS),
+    ('F', AceFlags::FAILED_ACCESS),
+    ('g', AceFlags::IDENTIFIER_GROUP),
+    ('O', AceFlags::INHERITED_ACE),
+];
+
+/// In the order `nfs4_getfacl(1)` prints them.
+const MASK_CHARS: [(char, AccessMask); 14] = [
+    ('r', AccessMask::READ_DATA),
+    ('w', AccessMask::WRITE_DATA),
+    ('a', AccessMask::APPEND_DATA),
+    ('D', AccessMask::DELETE_CHILD),
+    ('d', AccessMask::DELETE),
+    ('x', AccessMask::EXECUTE),
+    ('t', AccessMask::READ_ATTRIBUTES),
+    ('T', AccessMask::WRITE_ATTRIBUTES),
+    ('n', AccessMask::READ_NAMED_ATTRS),
+    ('N', AccessMask::WRITE_NAMED_ATTRS),
+    ('c', AccessMask::READ_ACL),
+    ('C', AccessMask::WRITE_ACL),
+    ('o', AccessMask::WRITE_OWNER),
+    ('y', AccessMask::SYNCHRONIZE),
+];
+
+/// An NFSv4 access control entry.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Ace {
+    /// Type of this ACE.
+    pub ace_type: AceType,
+    /// Flags of this ACE.
+    pub flags: AceFlags,
+    /// Permissions allowed, denied, audited or alarmed by this ACE.
+    pub access_mask: AccessMask,
+    /// The principal, a special principal like [`WHO_OWNER`], or a user or
+    /// group name, e.g., `alice@example.com`.
+    pub who: String,
+}
+
+impl FromStr for Ace {
+    type Err = Errno;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut fields = s.splitn(3, ':');
+        let (ace_type, flags, rest) =
+            match (fields.next(), fields.next(), fields.next()) {
+                (Some(ace_type), Some(flags), Some(rest)) => {
+                    (ace_type, flags, rest)
+                }
+                _ => return Err(Errno(libc::EINVAL)),
+            };
+        // `who` may contain colons, the access mask does not
+        let (who, mask) = rest.rsplit_once(':').ok_or(Errno(libc::EINVAL))?;
+
+        let ace_type = match ace_type {
+            "A" => AceType::Allow,
+            "D" => AceType::Deny,
+            "U" => AceType::Audit,
+            "L" => AceType::Alarm,
+            _ => return Err(Errno(libc::EINVAL)),
+        };
+        let flags = flags.chars().try_fold(AceFlags::empty(), |acc, c| {
+            FLAG_CHARS
+                .iter()
+                .find(|(flag_char, _)| *flag_char == c)
+                .map(|(_, flag)| acc | *flag)
+                .ok_or(Errno(libc::EINVAL))
+        })?;
+        let access_mask =
+            mask.chars().try_fold(AccessMask::empty(), |acc, c| {
+                MASK_CHARS
+                    .iter()
+                    .find(|(mask_char, _)| *mask_char == c)
+                    .map(|(_, mask)| acc | *mask)
+                    .ok_or(Errno(libc::EINVAL))
+            })?;
+        if who.is_empty() {
+            return Err(Errno(libc::EINVAL));
+        }
+
+        Ok(Ace {
+            ace_type,
+            flags,
+            access_mask,
+            who: who.to_owned(),
+        })
+    }
+}
+
+impl fmt::Display for Ace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ace_type = match self.ace_type {
+            AceType::Allow => 'A',
+            AceType::Deny => 'D',
+            AceType::Audit => 'U',
+            AceType::Alarm => 'L',
+        };
+        write!(f, "{}:", ace_type)?;
+        for (c, flag) in FLAG_CHARS.iter() {
+            if self.flags.contains(*flag) {
+                write!(f, "{}", c)?;
+            }
+        }
+        write!(f, ":{}:", self.who)?;
+        for (c, mask) in MASK_CHARS.iter() {
+            if self.access_mask.contains(*mask) {
+                write!(f, "{}", c)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An NFSv4 ACL.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Nfs4Acl {
+    /// ACEs of this ACL, in evaluation order.
+    pub aces: Vec<Ace>,
+}
+
+/// Reads a big-endian `u32` at `*offset` and advances `*offset`.
+fn read_u32(bytes: &[u8], offset: &mut usize) -> Result<u32> {
+    match bytes.get(*offset..*offset + 4) {
+        Some(b) => {
+            *offset += 4;
+            Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        }
+        None => Err(Errno(libc::EINVAL)),
+    }
+}
+
+impl Nfs4Acl {
+    /// Decodes a raw `system.nfs4_acl` value.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut offset = 0;
+        let naces = read_u32(bytes, &mut offset)? as usize;
+        // every ACE takes at least 16 bytes, don't trust `naces` blindly
+        if naces > (bytes.len() - offset) / 16 {
+            return Err(Errno(libc::EINVAL));
+        }
+        let mut aces = Vec::with_capacity(naces);
+
+        for _ in 0..naces {
+            let ace_type = AceType::from_u32(read_u32(bytes, &mut offset)?)?;
+            // bits unknown to this crate, e.g., from a later minor version,
+            // are kept so that `to_bytes()` gives back the same ACL
+            let flags = unsafe {
+                AceFlags::from_bits_unchecked(read_u32(bytes, &mut offset)?)
+            };
+            let access_mask = unsafe {
+                AccessMask::from_bits_unchecked(read_u32(bytes, &mut offset)?)
+            };
+            let who_len = read_u32(bytes, &mut offset)? as usize;
+            let who = bytes
+                .get(offset..offset.saturating_add(who_len))
+                .ok_or(Errno(libc::EINVAL))?;
+            let who = std::str::from_utf8(who)
+                .map_err(|_| Errno(libc::EINVAL))?
+                .to_owned();
+            offset += (who_len + 3) & !3;
+
+            aces.push(Ace {
+                ace_type,
+                flags,
+                access_mask,
+                who,
+            });
+        }
+
+        if offset != bytes.len() {
+            return Err(Errno(libc::EINVAL));
+        }
+
+        Ok(Nfs4Acl { aces })
+    }
+
+    /// Encodes this ACL into a raw `system.nfs4_acl` value.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = (self.aces.len() as u32).to_be_bytes().to_vec();
+
+        for ace in self.aces.iter() {
+            bytes.extend_from_slice(&ace.ace_type.to_u32().to_be_bytes());
+            bytes.extend_from_slice(&ace.flags.bits().to_be_bytes());
+            bytes.extend_from_slice(&ace.access_mask.bits().to_be_bytes());
+            bytes.extend_from_slice(&(ace.who.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(ace.who.as_bytes());
+            bytes.resize((bytes.len() + 3) & !3, 0);
+        }
+
+        bytes
+    }
+}
+
+impl FromStr for Nfs4Acl {
+    type Err = Errno;
+
+    /// Parses the `nfs4_getfacl(1)` text format, blank lines and comments
+    /// starting with `#` are ignored.
+    fn from_str(s: &str) -> Result<Self> {
+        let aces = s
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(Ace::from_str)
+            .collect::<Result<Vec<Ace>>>()?;
+
+        Ok(Nfs4Acl { aces })
+    }
+}
+
+impl fmt::Display for Nfs4Acl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for ace in self.aces.iter() {
+            writeln!(f, "{}", ace)?;
+        }
+        Ok(())
+    }
+}
+
+/// Maps between uids/gids and NFSv4 principal names.
+pub trait IdMapper {
+    /// Returns the principal name of the user `uid`.
+    fn user_to_who(&self, uid: u32) -> String;
+    /// Returns the principal name of the group `gid`.
+    fn group_to_who(&self, gid: u32) -> String;
+    /// Returns the uid of the user principal `who`.
+    fn who_to_user(&self, who: &str) -> Result<u32>;
+    /// Returns the gid of the group principal `who`.
+    fn who_to_group(&self, who: &str) -> Result<u32>;
+}
+
+/// An [`IdMapper`] that uses numeric principal names, e.g., `1000`, as the
+/// Linux NFS client does when idmapping is disabled. A `@domain` suffix is
+/// ignored when parsing.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NumericIdMapper;
+
+impl NumericIdMapper {
+    fn parse(who: &str) -> Result<u32> {
+        let id = who.split('@').next().unwrap_or(who);
+        if id.is_empty() || !id.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(Errno(libc::EINVAL));
+        }
+        id.parse().map_err(|_| Errno(libc::EINVAL))
+    }
+}
+
+impl IdMapper for NumericIdMapper {
+    fn user_to_who(&self, uid: u32) -> String {
+        uid.to_string()
+    }
+
+    fn group_to_who(&self, gid: u32) -> String {
+        gid.to_string()
+    }
+
+    fn who_to_user(&self, who: &str) -> Result<u32> {
+        Self::parse(who)
+    }
+
+    fn who_to_group(&self, who: &str) -> Result<u32> {
+        Self::parse(who)
+    }
+}
+
+/// Access mask bits granted to everyone regardless of the POSIX permissions.
+const ANYONE_MODE: AccessMask = AccessMask::from_bits_truncate(
+    AccessMask::READ_ATTRIBUTES.bits()
+        | AccessMask::READ_ACL.bits()
+        | AccessMask::SYNCHRONIZE.bits(),
+);
+/// Access mask bits granted to the owner regardless of the POSIX permissions.
+const OWNER_MODE: AccessMask = AccessMask::from_bits_truncate(
+    AccessMask::WRITE_ATTRIBUTES.bits() | AccessMask::WRITE_ACL.bits(),
+);
+const WRITE_MODE: AccessMask = AccessMask::from_bits_truncate(
+    AccessMask::WRITE_DATA.bits() | AccessMask::APPEND_DATA.bits(),
+);
+
+fn deny_mask_from_posix(perm: Perm, is_dir: bool) -> AccessMask {
+    let mut mask = AccessMask::empty();
+    if perm.contains(Perm::READ) {
+        mask |= AccessMask::READ_DATA;
+    }
+    if perm.contains(Perm::WRITE) {
+        mask |= WRITE_MODE;
+        if is_dir {
+            mask |= AccessMask::DELETE_CHILD;
+        }
+    }
+    if perm.contains(Perm::EXECUTE) {
+        mask |= AccessMask::EXECUTE;
+    }
+    mask
+}
+
+fn mask_from_posix(perm: Perm, is_dir: bool, is_owner: bool) -> AccessMask {
+    let mut mask = ANYONE_MODE | deny_mask_from_posix(perm, is_dir);
+    if is_owner {
+        mask |= OWNER_MODE;
+    }
+    mask
+}
+
+fn perm_from_mask(mask: AccessMask, is_dir: bool) -> Perm {
+    let mut write_mode = WRITE_MODE;
+    if is_dir {
+        write_mode |= AccessMask::DELETE_CHILD;
+    }
+
+    let mut perm = Perm::empty();
+    if mask.contains(AccessMask::READ_DATA) {
+        perm |= Perm::READ;
+    }
+    if mask.contains(write_mode) {
+        perm |= Perm::WRITE;
+    }
+    if mask.contains(AccessMask::EXECUTE) {
+        perm |= Perm::EXECUTE;
+    }
+    perm
+}
+
+/// Appends the ACEs equivalent to `acl` to `aces`.
+fn posix_to_nfs4_one<M: IdMapper>(
+    acl: &PosixAcl,
+    is_dir: bool,
+    flags: AceFlags,
+    mapper: &M,
+    aces: &mut Vec<Ace>,
+) -> Result<()> {
+    let mut acl = acl.clone();
+    acl.normalize()?;
+
+    // summarize the ACL
+    let perm_of = |tag| acl.get(tag).unwrap_or_else(Perm::empty);
+    let mask = acl.get(Tag::Mask).unwrap_or_else(Perm::all);
+    let owner = perm_of(Tag::UserObj);
+    let group = perm_of(Tag::GroupObj) & mask;
+    let other = perm_of(Tag::Other);
+    let (users, groups) = acl.entries.iter().fold(
+        (Perm::empty(), Perm::empty()),
+        |(users, groups), entry| match entry.tag {
+            Tag::User(_) => (users | (entry.perm & mask), groups),
+            Tag::Group(_) => (users, groups | (entry.perm & mask)),
+            _ => (users, groups),
+        },
+    );
+
+    let mut push = |ace_type, extra_flags, access_mask, who: String| {
+        aces.push(Ace {
+            ace_type,
+            flags: flags | extra_flags,
+            access_mask,
+            who,
+        })
+    };
+
+    // We could deny everything not granted by the owner, but it is equivalent
+    // (and simpler) to deny only what is granted by the later entries.
+    let deny = !owner & (users | group | groups | other);
+    if !deny.is_empty() {
+        push(
+            AceType::Deny,
+            AceFlags::empty(),
+            deny_mask_from_posix(deny, is_dir),
+            WHO_OWNER.to_owned(),
+        );
+    }
+    push(
+        AceType::Allow,
+        AceFlags::empty(),
+        mask_from_posix(owner, is_dir, true),
+        WHO_OWNER.to_owned(),
+    );
+
+    for entry in acl.entries.iter() {
+        if let Tag::User(uid) = entry.tag {
+            let perm = entry.perm & mask;
+            let deny = !perm & (group | groups | other);
+            if !deny.is_empty() {
+                push(
+                    AceType::Deny,
+                    AceFlags::empty(),
+                    deny_mask_from_posix(deny, is_dir),
+                    mapper.user_to_who(uid),
+                );
+            }
+            push(
+                AceType::Allow,
+                AceFlags::empty(),
+                mask_from_posix(perm, is_dir, false),
+                mapper.user_to_who(uid),
+            );
+        }
+    }
+
+    // A user can be in more than one group, so all the allow ACEs of the
+    // group class come before the deny ACEs.
+    push(
+        AceType::Allow,
+        AceFlags::IDENTIFIER_GROUP,
+        mask_from_posix(group, is_dir, false),
+        WHO_GROUP.to_owned(),
+    );
+    for entry in acl.entries.iter() {
+        if let Tag::Group(gid) = entry.tag {
+            push(
+                AceType::Allow,
+                AceFlags::IDENTIFIER_GROUP,
+                mask_from_posix(entry.perm & mask, is_dir, false),
+                mapper.group_to_who(gid),
+            );
+        }
+    }
+    let deny = !group & other;
+    if !deny.is_empty() {
+        push(
+            AceType::Deny,
+            AceFlags::IDENTIFIER_GROUP,
+            deny_mask_from_posix(deny, is_dir),
+            WHO_GROUP.to_owned(),
+        );
+    }
+    for entry in acl.entries.iter() {
+        if let Tag::Group(gid) = entry.tag {
+            let deny = !(entry.perm & mask) & other;
+            if !deny.is_empty() {
+                push(
+                    AceType::Deny,
+                    AceFlags::IDENTIFIER_GROUP,
+                    deny_mask_from_posix(deny, is_dir),
+                    mapper.group_to_who(gid),
+                );
+            }
+        }
+    }
+
+    push(
+        AceType::Allow,
+        AceFlags::empty(),
+        mask_from_posix(other, is_dir, false),
+        WHO_EVERYONE.to_owned(),
+    );
+
+    Ok(())
+}
+
+impl Nfs4Acl {
+    /// Converts a POSIX access ACL, and the default ACL of a directory, into
+    /// an equivalent NFSv4 ACL.
+    pub fn from_posix<M: IdMapper>(
+        access: &PosixAcl,
+        default: Option<&PosixAcl>,
+        is_dir: bool,
+        mapper: &M,
+    ) -> Result<Self> {
+        let mut aces = Vec::new();
+
+        posix_to_nfs4_one(
+            access,
+            is_dir,
+            AceFlags::empty(),
+            mapper,
+            &mut aces,
+        )?;
+        if let Some(default) = default {
+            if !is_dir {
+                return Err(Errno(libc::EINVAL));
+            }
+            posix_to_nfs4_one(
+                default,
+                is_dir,
+                AceFlags::FILE_INHERIT
+                    | AceFlags::DIRECTORY_INHERIT
+                    | AceFlags::INHERIT_ONLY,
+                mapper,
+                &mut aces,
+            )?;
+        }
+
+        Ok(Nfs4Acl { aces })
+    }
+
+    /// Converts this ACL into a POSIX access ACL and, for a directory with
+    /// inheritable ACEs, a POSIX default ACL.
+    ///
+    /// Only allow and deny ACEs can be converted, the flags must be limited
+    /// to the inheritance flags and [`AceFlags::IDENTIFIER_GROUP`], otherwise
+    /// `EINVAL` is returned.
+    pub fn to_posix<M: IdMapper>(
+        &self,
+        is_dir: bool,
+        mapper: &M,
+    ) -> Result<(PosixAcl, Option<PosixAcl>)> {
+        let inherit = AceFlags::FILE_INHERIT | AceFlags::DIRECTORY_INHERIT;
+        let supported = inherit
+            | AceFlags::INHERIT_ONLY
+            | AceFlags::IDENTIFIER_GROUP
+            | AceFlags::INHERITED_ACE;
+        let mut access = AclState::default();
+        let mut default = AclState::default();
+        let mut has_default = false;
+
+        for ace in self.aces.iter() {
+            if !matches!(ace.ace_type, AceType::Allow | AceType::Deny)
+                || !supported.contains(ace.flags)
+            {
+                return Err(Errno(libc::EINVAL));
+            }
+            if ace.flags.contains(AceFlags::INHERIT_ONLY)
+                && !ace.flags.intersects(inherit)
+            {
+                return Err(Errno(libc::EINVAL));
+            }
+            let principal = Principal::from_ace(ace, mapper)?;
+
+            if !ace.flags.contains(AceFlags::INHERIT_ONLY) {
+                access.process(ace, principal);
+            }
+            if ace.flags.intersects(inherit) {
+                if !is_dir {
+                    return Err(Errno(libc::EINVAL));
+                }
+                has_default = true;
+                default.process(ace, principal);
+            }
+        }
+
+        let default = if has_default {
+            Some(default.to_posix(is_dir))
+        } else {
+            None
+        };
+
+        Ok((access.to_posix(is_dir), default))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct AllowDeny {
+    allow: AccessMask,
+    deny: AccessMask,
+}
+
+impl Default for AllowDeny {
+    fn default() -> Self {
+        AllowDeny {
+            allow: AccessMask::empty(),
+            deny: AccessMask::empty(),
+        }
+    }
+}
+
+impl AllowDeny {
+    fn allow(&mut self, mask: AccessMask) {
+        self.allow |= mask & !self.deny;
+    }
+
+    fn deny(&mut self, mask: AccessMask) {
+        self.deny |= mask & !self.allow;
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Principal {
+    Owner,
+    User(u32),
+    Group,
+    NamedGroup(u32),
+    Everyone,
+}
+
+impl Principal {
+    fn from_ace<M: IdMapper>(ace: &Ace, mapper: &M) -> Result<Self> {
+        let is_group = ace.flags.contains(AceFlags::IDENTIFIER_GROUP);
+        match ace.who.as_str() {
+            WHO_OWNER => Ok(Principal::Owner),
+            WHO_GROUP => Ok(Principal::Group),
+            WHO_EVERYONE => Ok(Principal::Everyone),
+            who if is_group => {
+                Ok(Principal::NamedGroup(mapper.who_to_group(who)?))
+            }
+            who => Ok(Principal::User(mapper.who_to_user(who)?)),
+        }
+    }
+}
+
+/// Per-principal allow/deny state while walking an NFSv4 ACL.
+#[derive(Debug, Default)]
+struct AclState {
+    owner: AllowDeny,
+    group: AllowDeny,
+    other: AllowDeny,
+    everyone: AllowDeny,
+    users: Vec<(u32, AllowDeny)>,
+    groups: Vec<(u32, AllowDeny)>,
+}
+
+impl AclState {
+    /// Returns the state of a named principal, a new one starts with what has
+    /// been allowed or denied to everyone so far.
+    fn find(
+        entries: &mut Vec<(u32, AllowDeny)>,
+        everyone: AllowDeny,
+        id: u32,
+    ) -> &mut AllowDeny {
+        let idx = match entries.iter().position(|(i, _)| *i == id) {
+            Some(idx) => idx,
+            None => {
+                entries.push((id, everyone));
+                entries.len() - 1
+            }
+        };
+        &mut entries[idx].1
+    }
+
+    fn deny_named(&mut self, mask: AccessMask) {
+        for (_, state) in self.users.iter_mut().chain(self.groups.iter_mut()) {
+            state.deny(mask);
+        }
+    }
+
+    fn process(&mut self, ace: &Ace, principal: Principal) {
+        let mask = ace.access_mask;
+        let allow = ace.ace_type == AceType::Allow;
+
+        match principal {
+            Principal::Owner => {
+                if allow {
+                    self.owner.allow(mask)
+                } else {
+                    self.owner.deny(mask)
+                }
+            }
+            Principal::User(uid) => {
+                let state = Self::find(&mut self.users, self.everyone, uid);
+                if allow {
+                    state.allow(mask);
+                } else {
+                    state.deny(mask);
+                    let mask = state.deny;
+                    self.owner.deny(mask);
+                }
+            }
+            Principal::Group => {
+                if allow {
+                    self.group.allow(mask);
+                } else {
+                    self.group.deny(mask);
+                    let mask = self.group.deny;
+                    self.owner.deny(mask);
+                    self.everyone.deny(mask);
+                    self.deny_named(mask);
+                }
+            }
+            Principal::NamedGroup(gid) => {
+                let state = Self::find(&mut self.groups, self.everyone, gid);
+                if allow {
+                    state.allow(mask);
+                } else {
+                    state.deny(mask);
+                    let mask = state.deny;
+                    self.owner.deny(mask);
+                    self.group.deny(mask);
+                    self.everyone.deny(mask);
+                    self.deny_named(mask);
+                }
+            }
+            Principal::Everyone => {
+                let states = [
+                    &mut self.owner,
+                    &mut self.group,
+                    &mut self.other,
+                    &mut self.everyone,
+                ];
+                for state in states {
+                    if allow {
+                        state.allow(mask);
+                    } else {
+                        state.deny(mask);
+                    }
+                }
+                for (_, state) in
+                    self.users.iter_mut().chain(self.groups.iter_mut())
+                {
+                    if allow {
+                        state.allow(mask);
+                    } else {
+                        state.deny(mask);
+                    }
+                }
+            }
+        }
+    }
+
+    fn to_posix(&self, is_dir: bool) -> PosixAcl {
+        let entry = |tag, state: &AllowDeny| PosixAclEntry {
+            tag,
+            perm: perm_from_mask(state.allow, is_dir),
+        };
+        let mut mask = self.group.allow;
+        let mut entries = vec![entry(Tag::UserObj, &self.owner)];
+
+        for (uid, state) in self.users.iter() {
+            entries.push(entry(Tag::User(*uid), state));
+            mask |= state.allow;
+        }
+        entries.push(entry(Tag::GroupObj, &self.group));
+        for (gid, state) in self.groups.iter() {
+            entries.push(entry(Tag::Group(*gid), state));
+            mask |= state.allow;
+        }
+        if !self.users.is_empty() || !self.groups.is_empty() {
+            entries.push(PosixAclEntry {
+                tag: Tag::Mask,
+                perm: perm_from_mask(mask, is_dir),
+            });
+        }
+        entries.push(entry(Tag::Other, &self.other));
+
+        let mut acl = PosixAcl { entries };
+        acl.entries.sort_by_key(|entry| entry.tag);
+        acl
+    }
+}
+
+/// Retrieves the NFSv4 ACL of `path`. If `path` is a symbolic link, it will be
+/// dereferenced.
+pub fn get_nfs4_acl<P: AsRef<Path>>(path: P) -> Result<Nfs4Acl> {
+    Nfs4Acl::from_bytes(&getxattr(path, XATTR_NAME_NFS4_ACL)?)
+}
+
+/// Retrieves the NFSv4 ACL of the file specified by the open file descriptor
+/// `fd`.
+pub fn fget_nfs4_acl(fd: RawFd) -> Result<Nfs4Acl> {
+    Nfs4Acl::from_bytes(&fgetxattr(fd, XATTR_NAME_NFS4_ACL)?)
+}
+
+/// Sets the NFSv4 ACL of `path`. If `path` is a symbolic link, it will be
+/// dereferenced.
+pub fn set_nfs4_acl<P: AsRef<Path>>(path: P, acl: &Nfs4Acl) -> Result<()> {
+    setxattr(path, XATTR_NAME_NFS4_ACL, acl.to_bytes(), Flags::empty())
+}
+
+/// Sets the NFSv4 ACL of the file specified by the open file descriptor `fd`.
+pub fn fset_nfs4_acl(fd: RawFd, acl: &Nfs4Acl) -> Result<()> {
+    fsetxattr(fd, XATTR_NAME_NFS4_ACL, acl.to_bytes(), Flags::empty())
+}
@@ -0,0 +1,333 @@
+//! Translation between overlayfs upper directories and OCI image layers
+//!
+//! overlayfs and OCI layers record deletions differently:
+//!
+//! * overlayfs marks a deleted file with a whiteout, a character device with
+//!   device number 0/0, or a zero-size regular file with the `whiteout` EA
+//!   in a directory whose `opaque` EA is `x`. A directory that hides the
+//!   lower layers has its `opaque` EA set to `y`.
+//! * An OCI layer contains a `.wh.<name>` entry for each deleted file, and a
+//!   `.wh..wh..opq` entry in each opaque directory.
+//!
+//! [`walk_upper()`] turns an upper directory into the entries of a layer, and
+//! [`extract_marker()`] turns the whiteout entries of a layer back into
+//! overlayfs whiteouts. [`layer_xattrs()`] reads the EAs to store in a layer,
+//! dropping the ones that must not be carried to another host, like the
+//! overlayfs metadata and the SELinux label.
+//!
+//! For more information, see
+//! [Image Layer Filesystem Changeset](https://github.com/opencontainers/image-spec/blob/main/layer.md).
+
+use crate::{
+    io_errno, lgetxattr, llistxattr,
+    overlay::{get_opaque, is_whiteout, set_opaque, set_whiteout},
+    overlay::{Opaque, OverlayPrefix},
+    Result,
+};
+use errno::{errno, Errno};
+use std::{
+    ffi::{CString, OsStr, OsString},
+    fs,
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{FileTypeExt, MetadataExt},
+    },
+    path::{Path, PathBuf},
+};
+
+/// Prefix of the whiteout entries of a layer.
+pub const WHITEOUT_PREFIX: &str = ".wh.";
+
+/// Name of the entry marking a directory of a layer as opaque.
+pub const OPAQUE_WHITEOUT: &str = ".wh..wh..opq";
+
+/// Prefix reserved by the OCI specification, [`OPAQUE_WHITEOUT`] is the only
+/// name using it.
+const RESERVED_PREFIX: &str = ".wh..wh.";
+
+/// A whiteout entry of a layer.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LayerWhiteout {
+    /// `.wh.<name>`, `<name>` is deleted.
+    Whiteout(OsString),
+    /// `.wh..wh..opq`, the directory is opaque.
+    Opaque,
+}
+
+/// Parses the file name of a layer entry, `None` if it is a regular entry.
+///
+/// Returns `EINVAL` for the other names starting with `.wh..wh.`, which are
+/// reserved, and for `.wh.` alone.
+pub fn parse_entry_name<N: AsRef<OsStr>>(
+    file_name: N,
+) -> Result<Option<LayerWhiteout>> {
+    let file_name = file_name.as_ref().as_bytes();
+    if file_name == OPAQUE_WHITEOUT.as_bytes() {
+        return Ok(Some(LayerWhiteout::Opaque));
+    }
+    if file_name.starts_with(RESERVED_PREFIX.as_bytes()) {
+        return Err(Errno(libc::EINVAL));
+    }
+    match file_name.strip_prefix(WHITEOUT_PREFIX.as_bytes()) {
+        Some(name) if name.is_empty() || name.contains(&b'/') => {
+            Err(Errno(libc::EINVAL))
+        }
+        Some(name) => Ok(Some(LayerWhiteout::Whiteout(
+            OsStr::from_bytes(name).to_owned(),
+        ))),
+        None => Ok(None),
+    }
+}
+
+/// Returns the name of the whiteout entry of `file_name`.
+pub fn whiteout_name<N: AsRef<OsStr>>(file_name: N) -> OsString {
+    let mut name = OsString::from(WHITEOUT_PREFIX);
+    name.push(file_name);
+    name
+}
+
+/// Kind of an entry of an upper directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UpperEntry {
+    /// A whiteout, either kind.
+    Whiteout,
+    /// A directory whose `opaque` EA is `y`.
+    OpaqueDir,
+    /// Anything else, copied to the layer as it is.
+    Other,
+}
+
+/// Classifies the entry `path` of an upper directory, whose overlayfs EAs are
+/// under `prefix`. Symbolic links are not followed.
+pub fn classify<P: AsRef<Path>>(
+    path: P,
+    prefix: OverlayPrefix,
+) -> Result<UpperEntry> {
+    let path = path.as_ref();
+    let metadata = fs::symlink_metadata(path).map_err(io_errno)?;
+    let file_type = metadata.file_type();
+
+    let device_whiteout = file_type.is_char_device() && metadata.rdev() == 0;
+    let entry = if device_whiteout
+        || (file_type.is_file()
+            && metadata.len() == 0
+            && is_whiteout(path, prefix)?)
+    {
+        UpperEntry::Whiteout
+    } else if file_type.is_dir()
+        && get_opaque(path, prefix)? == Some(Opaque::Opaque)
+    {
+        UpperEntry::OpaqueDir
+    } else {
+        UpperEntry::Other
+    };
+    Ok(entry)
+}
+
+/// An entry of a layer, its path is relative to the root of the layer.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Change {
+    /// An entry to copy from the upper directory as it is.
+    Entry(PathBuf),
+    /// A `.wh.<name>` entry, the path ends with it.
+    Whiteout(PathBuf),
+    /// A `.wh..wh..opq` entry, the path ends with it.
+    Opaque(PathBuf),
+}
+
+/// Walks the upper directory `upper`, whose overlayfs EAs are under `prefix`,
+/// and returns the entries of the matching layer.
+///
+/// Directories are listed before their content, the [`Change::Opaque`] entry
+/// of an opaque directory coming first, and the entries of a directory are
+/// sorted by name. The root of the layer itself is not listed.
+pub fn walk_upper<P: AsRef<Path>>(
+    upper: P,
+    prefix: OverlayPrefix,
+) -> Result<Vec<Change>> {
+    let upper = upper.as_ref();
+    let mut changes = Vec::new();
+    if classify(upper, prefix)? == UpperEntry::OpaqueDir {
+        changes.push(Change::Opaque(PathBuf::from(OPAQUE_WHITEOUT)));
+    }
+    walk_dir(upper, Path::new(""), prefix, &mut changes)?;
+    Ok(changes)
+}
+
+fn walk_dir(
+    dir: &Path,
+    relative: &Path,
+    prefix: OverlayPrefix,
+    changes: &mut Vec<Change>,
+) -> Result<()> {
+    let mut names = Vec::new();
+    for entry in fs::read_dir(dir).map_err(io_errno)? {
+        names.push(entry.map_err(io_errno)?.file_name());
+    }
+    names.sort();
+
+    for name in names {
+        let path = dir.join(&name);
+        match classify(&path, prefix)? {
+            UpperEntry::Whiteout => changes
+                .push(Change::Whiteout(relative.join(whiteout_name(&name)))),
+            UpperEntry::OpaqueDir => {
+                changes.push(Change::Entry(relative.join(&name)));
+                changes.push(Change::Opaque(
+                    relative.join(&name).join(OPAQUE_WHITEOUT),
+                ));
+                walk_dir(&path, &relative.join(&name), prefix, changes)?;
+            }
+            UpperEntry::Other => {
+                changes.push(Change::Entry(relative.join(&name)));
+                let is_dir = fs::symlink_metadata(&path)
+                    .map_err(io_errno)?
+                    .file_type()
+                    .is_dir();
+                if is_dir {
+                    walk_dir(&path, &relative.join(&name), prefix, changes)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// How [`extract_marker()`] creates whiteouts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WhiteoutStyle {
+    /// A character device 0/0, which requires `CAP_MKNOD`.
+    Device,
+    /// A zero-size regular file with the `whiteout` EA, supported by overlayfs
+    /// since Linux 6.7 and usable without privileges with
+    /// [`OverlayPrefix::User`].
+    Xattr,
+}
+
+/// Creates a whiteout for the entry `file_name` of the directory `dir`.
+///
+/// With [`WhiteoutStyle::Xattr`], `dir` is marked with
+/// [`Opaque::ContainsWhiteouts`], unless it is already opaque. Returns
+/// `EEXIST` if the entry exists.
+pub fn create_whiteout<P: AsRef<Path>, N: AsRef<OsStr>>(
+    dir: P,
+    file_name: N,
+    prefix: OverlayPrefix,
+    style: WhiteoutStyle,
+) -> Result<()> {
+    let dir = dir.as_ref();
+    let path = dir.join(file_name.as_ref());
+
+    match style {
+        WhiteoutStyle::Device => {
+            let path = match CString::new(path.as_os_str().as_bytes()) {
+                Ok(p) => p,
+                _ => return Err(Errno(libc::EINVAL)),
+            };
+            match unsafe { libc::mknod(path.as_ptr(), libc::S_IFCHR, 0) } {
+                -1 => Err(errno()),
+                _ => Ok(()),
+            }
+        }
+        WhiteoutStyle::Xattr => {
+            fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+                .map_err(io_errno)?;
+            set_whiteout(&path, prefix, true)?;
+            if get_opaque(dir, prefix)?.is_none() {
+                set_opaque(dir, prefix, Opaque::ContainsWhiteouts)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Translates the entry `file_name` of a layer being extracted into the
+/// directory `dir`.
+///
+/// A `.wh.<name>` entry becomes a whiteout of `<name>` in the given `style`,
+/// and a `.wh..wh..opq` entry marks `dir` as opaque. Returns true if the entry
+/// was one of them, in which case it must not be extracted, and false if it
+/// is a regular entry, which is left to the caller.
+pub fn extract_marker<P: AsRef<Path>, N: AsRef<OsStr>>(
+    dir: P,
+    file_name: N,
+    prefix: OverlayPrefix,
+    style: WhiteoutStyle,
+) -> Result<bool> {
+    match parse_entry_name(file_name)? {
+        Some(LayerWhiteout::Whiteout(name)) => {
+            create_whiteout(dir, name, prefix, style)?;
+            Ok(true)
+        }
+        Some(LayerWhiteout::Opaque) => {
+            set_opaque(dir, prefix, Opaque::Opaque)?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// EAs that are not stored in layers.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct XattrFilter {
+    /// Names dropped exactly.
+    pub names: Vec<OsString>,
+    /// Prefixes of the names dropped.
+    pub prefixes: Vec<OsString>,
+}
+
+impl Default for XattrFilter {
+    /// Drops the overlayfs metadata, and the labels of the Linux security
+    /// modules, which are assigned by the host the layer is extracted on.
+    fn default() -> Self {
+        XattrFilter {
+            names: vec![
+                OsString::from("security.selinux"),
+                OsString::from("security.apparmor"),
+            ],
+            prefixes: vec![
+                OsString::from(OverlayPrefix::Trusted.as_str()),
+                OsString::from(OverlayPrefix::User.as_str()),
+                OsString::from("security.SMACK64"),
+            ],
+        }
+    }
+}
+
+impl XattrFilter {
+    /// Returns true if the EA `name` is kept.
+    pub fn allows<N: AsRef<OsStr>>(&self, name: N) -> bool {
+        let name = name.as_ref();
+        !self.names.iter().any(|n| n == name)
+            && !self
+                .prefixes
+                .iter()
+                .any(|p| name.as_bytes().starts_with(p.as_bytes()))
+    }
+}
+
+/// Reads the EAs of `path` that `filter` allows, sorted by name. If `path` is
+/// a symbolic link, the EAs of the link *itself* are read.
+pub fn layer_xattrs<P: AsRef<Path>>(
+    path: P,
+    filter: &XattrFilter,
+) -> Result<Vec<(OsString, Vec<u8>)>> {
+    let path = path.as_ref();
+    let mut names = llistxattr(path)?;
+    names.retain(|name| filter.allows(name));
+    names.sort();
+
+    let mut attrs = Vec::with_capacity(names.len());
+    for name in names {
+        match lgetxattr(path, &name) {
+            Ok(value) => attrs.push((name, value)),
+            // removed since it was listed
+            Err(Errno(libc::ENODATA)) => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(attrs)
+}
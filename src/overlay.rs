@@ -0,0 +1,526 @@
+//! Codecs for the overlayfs metadata EAs
+//!
+//! overlayfs stores its metadata in `trusted.overlay.*` EAs, or in
+//! `user.overlay.*` EAs if the overlay is mounted with `userxattr`, which is
+//! chosen by [`OverlayPrefix`]. The functions in this module never follow
+//! symbolic links, as they are meant to inspect and repair a layer without
+//! mounting it.
+//!
+//! For more information, see
+//! [Overlay Filesystem](https://docs.kernel.org/filesystems/overlayfs.html).
+
+use crate::{lgetxattr, lremovexattr, lsetxattr, Flags, Result};
+use errno::Errno;
+use std::{
+    ffi::OsStr,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+/// Prefix of the overlayfs EAs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OverlayPrefix {
+    /// `trusted.overlay.`, the default, requires `CAP_SYS_ADMIN`.
+    Trusted,
+    /// `user.overlay.`, used by overlays mounted with `userxattr`.
+    User,
+}
+
+impl OverlayPrefix {
+    /// Returns the prefix as a string.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OverlayPrefix::Trusted => "trusted.overlay.",
+            OverlayPrefix::User => "user.overlay.",
+        }
+    }
+
+    /// Returns the full EA name of `attr` under this prefix.
+    pub fn name(self, attr: OverlayAttr) -> String {
+        format!("{}{}", self.as_str(), attr.suffix())
+    }
+}
+
+/// overlayfs metadata EAs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OverlayAttr {
+    /// `opaque`, the directory hides the lower directories.
+    Opaque,
+    /// `redirect`, the directory or file was renamed from another path.
+    Redirect,
+    /// `origin`, file handle of the lower file that was copied up.
+    Origin,
+    /// `upper`, file handle of the upper file, used by the index directory.
+    Upper,
+    /// `metacopy`, only the metadata was copied up.
+    Metacopy,
+    /// `nlink`, difference between the overlay and the real link count.
+    Nlink,
+    /// `impure`, the directory may contain copied up or redirected entries.
+    Impure,
+    /// `whiteout`, the file is a whiteout (an "xwhiteout").
+    Whiteout,
+}
+
+impl OverlayAttr {
+    /// Returns the name of this EA without prefix.
+    pub fn suffix(self) -> &'static str {
+        match self {
+            OverlayAttr::Opaque => "opaque",
+            OverlayAttr::Redirect => "redirect",
+            OverlayAttr::Origin => "origin",
+            OverlayAttr::Upper => "upper",
+            OverlayAttr::Metacopy => "metacopy",
+            OverlayAttr::Nlink => "nlink",
+            OverlayAttr::Impure => "impure",
+            OverlayAttr::Whiteout => "whiteout",
+        }
+    }
+}
+
+/// Value of the `opaque` EA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Opaque {
+    /// `y`, the directory is opaque.
+    Opaque,
+    /// `x`, the directory is not opaque but may contain whiteout files marked
+    /// with the `whiteout` EA.
+    ContainsWhiteouts,
+}
+
+impl Opaque {
+    /// Decodes a raw `opaque` value.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        match bytes {
+            b"y" => Ok(Opaque::Opaque),
+            b"x" => Ok(Opaque::ContainsWhiteouts),
+            _ => Err(Errno(libc::EINVAL)),
+        }
+    }
+
+    /// Encodes this value into a raw `opaque` value.
+    pub fn to_bytes(self) -> &'static [u8] {
+        match self {
+            Opaque::Opaque => b"y",
+            Opaque::ContainsWhiteouts => b"x",
+        }
+    }
+}
+
+const OVL_FH_VERSION: u8 = 0;
+const OVL_FH_MAGIC: u8 = 0xfb;
+/// Size of `struct ovl_fb` without the file identifier.
+const OVL_FB_HEADER_SIZE: usize = 21;
+
+/// A file handle stored in the `origin` and `upper` EAs (`struct ovl_fb`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FileHandle {
+    /// `OVL_FH_FLAG_*` flags.
+    pub flags: u8,
+    /// Type of the file identifier, as returned by `name_to_handle_at(2)`.
+    pub fid_type: u8,
+    /// UUID of the file system that the file lives on.
+    pub uuid: [u8; 16],
+    /// The file identifier.
+    pub fid: Vec<u8>,
+}
+
+impl FileHandle {
+    /// The file handle is encoded on a big endian machine.
+    pub const FLAG_BIG_ENDIAN: u8 = 1 << 0;
+    /// The file handle is endian independent.
+    pub const FLAG_ANY_ENDIAN: u8 = 1 << 1;
+    /// The file handle refers to an upper file.
+    pub const FLAG_PATH_UPPER: u8 = 1 << 2;
+
+    /// Decodes a raw `origin` or `upper` value.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < OVL_FB_HEADER_SIZE
+            || bytes.len() > u8::MAX as usize
+            || bytes[0] != OVL_FH_VERSION
+            || bytes[1] != OVL_FH_MAGIC
+            || bytes[2] as usize != bytes.len()
+        {
+            return Err(Errno(libc::EINVAL));
+        }
+
+        let mut uuid = [0_u8; 16];
+        uuid.copy_from_slice(&bytes[5..OVL_FB_HEADER_SIZE]);
+
+        Ok(FileHandle {
+            flags: bytes[3],
+            fid_type: bytes[4],
+            uuid,
+            fid: bytes[OVL_FB_HEADER_SIZE..].to_vec(),
+        })
+    }
+
+    /// Encodes this file handle into a raw `origin` or `upper` value.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let len = OVL_FB_HEADER_SIZE + self.fid.len();
+        if len > u8::MAX as usize {
+            return Err(Errno(libc::EINVAL));
+        }
+
+        let mut bytes = vec![
+            OVL_FH_VERSION,
+            OVL_FH_MAGIC,
+            len as u8,
+            self.flags,
+            self.fid_type,
+        ];
+        bytes.extend_from_slice(&self.uuid);
+        bytes.extend_from_slice(&self.fid);
+        Ok(bytes)
+    }
+}
+
+/// fs-verity hash algorithms used by the `metacopy` digest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VerityAlgo {
+    /// `FS_VERITY_HASH_ALG_SHA256`
+    Sha256 = 1,
+    /// `FS_VERITY_HASH_ALG_SHA512`
+    Sha512 = 2,
+}
+
+impl VerityAlgo {
+    fn from_id(id: u8) -> Result<Self> {
+        match id {
+            1 => Ok(VerityAlgo::Sha256),
+            2 => Ok(VerityAlgo::Sha512),
+            _ => Err(Errno(libc::EINVAL)),
+        }
+    }
+
+    /// Returns the size of a digest produced by this algorithm.
+    pub fn digest_size(self) -> usize {
+        match self {
+            VerityAlgo::Sha256 => 32,
+            VerityAlgo::Sha512 => 64,
+        }
+    }
+}
+
+const OVL_METACOPY_VERSION: u8 = 0;
+/// Size of `struct ovl_metacopy` without the digest.
+const OVL_METACOPY_MIN_SIZE: usize = 4;
+
+/// Value of the `metacopy` EA (`struct ovl_metacopy`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Metacopy {
+    /// fs-verity digest of the lower data file, `None` if not recorded.
+    pub digest: Option<(VerityAlgo, Vec<u8>)>,
+}
+
+impl Metacopy {
+    /// Decodes a raw `metacopy` value, an empty value is the legacy format
+    /// without a digest.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.is_empty() {
+            return Ok(Metacopy { digest: None });
+        }
+        if bytes.len() < OVL_METACOPY_MIN_SIZE
+            || bytes[0] != OVL_METACOPY_VERSION
+            || bytes[1] as usize != bytes.len()
+            || bytes[2] != 0
+        {
+            return Err(Errno(libc::EINVAL));
+        }
+
+        let digest = &bytes[OVL_METACOPY_MIN_SIZE..];
+        if digest.is_empty() {
+            return Ok(Metacopy { digest: None });
+        }
+        let algo = VerityAlgo::from_id(bytes[3])?;
+        if digest.len() != algo.digest_size() {
+            return Err(Errno(libc::EINVAL));
+        }
+
+        Ok(Metacopy {
+            digest: Some((algo, digest.to_vec())),
+        })
+    }
+
+    /// Encodes this value into a raw `metacopy` value, the legacy empty value
+    /// is used if there is no digest.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        match &self.digest {
+            None => Ok(Vec::new()),
+            Some((algo, digest)) => {
+                if digest.len() != algo.digest_size() {
+                    return Err(Errno(libc::EINVAL));
+                }
+                let mut bytes = vec![
+                    OVL_METACOPY_VERSION,
+                    (OVL_METACOPY_MIN_SIZE + digest.len()) as u8,
+                    0,
+                    *algo as u8,
+                ];
+                bytes.extend_from_slice(digest);
+                Ok(bytes)
+            }
+        }
+    }
+}
+
+/// Value of the `nlink` EA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Nlink {
+    /// `U%+i`, relative to the link count of the upper inode.
+    Upper(i32),
+    /// `L%+i`, relative to the link count of the lower inode.
+    Lower(i32),
+}
+
+impl Nlink {
+    /// Decodes a raw `nlink` value.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let (base, diff) = bytes.split_first().ok_or(Errno(libc::EINVAL))?;
+        let diff = std::str::from_utf8(diff)
+            .ok()
+            .and_then(|diff| diff.parse::<i32>().ok())
+            .ok_or(Errno(libc::EINVAL))?;
+
+        match base {
+            b'U' => Ok(Nlink::Upper(diff)),
+            b'L' => Ok(Nlink::Lower(diff)),
+            _ => Err(Errno(libc::EINVAL)),
+        }
+    }
+
+    /// Encodes this value into a raw `nlink` value.
+    pub fn to_bytes(self) -> Vec<u8> {
+        match self {
+            Nlink::Upper(diff) => format!("U{:+}", diff),
+            Nlink::Lower(diff) => format!("L{:+}", diff),
+        }
+        .into_bytes()
+    }
+}
+
+/// Value of the `impure` marker.
+const MARKER_YES: &[u8] = b"y";
+
+/// Retrieves `attr` of `path`, `None` if it is not set.
+fn get(
+    path: &Path,
+    prefix: OverlayPrefix,
+    attr: OverlayAttr,
+) -> Result<Option<Vec<u8>>> {
+    match lgetxattr(path, prefix.name(attr)) {
+        Ok(value) => Ok(Some(value)),
+        Err(Errno(libc::ENODATA)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn set(
+    path: &Path,
+    prefix: OverlayPrefix,
+    attr: OverlayAttr,
+    value: &[u8],
+) -> Result<()> {
+    lsetxattr(path, prefix.name(attr), value, Flags::empty())
+}
+
+/// Removes `attr` from `path`. Removing an EA that is not set is not an error.
+pub fn remove<P: AsRef<Path>>(
+    path: P,
+    prefix: OverlayPrefix,
+    attr: OverlayAttr,
+) -> Result<()> {
+    match lremovexattr(path, prefix.name(attr)) {
+        Err(Errno(libc::ENODATA)) => Ok(()),
+        res => res,
+    }
+}
+
+/// Retrieves the `opaque` EA of the directory `path`.
+pub fn get_opaque<P: AsRef<Path>>(
+    path: P,
+    prefix: OverlayPrefix,
+) -> Result<Option<Opaque>> {
+    get(path.as_ref(), prefix, OverlayAttr::Opaque)?
+        .map(|value| Opaque::from_bytes(&value))
+        .transpose()
+}
+
+/// Sets the `opaque` EA of the directory `path`.
+pub fn set_opaque<P: AsRef<Path>>(
+    path: P,
+    prefix: OverlayPrefix,
+    opaque: Opaque,
+) -> Result<()> {
+    set(
+        path.as_ref(),
+        prefix,
+        OverlayAttr::Opaque,
+        opaque.to_bytes(),
+    )
+}
+
+/// Retrieves the `redirect` EA of `path`, an absolute redirect is relative to
+/// the root of the layer, a relative one is a name in the same directory.
+pub fn get_redirect<P: AsRef<Path>>(
+    path: P,
+    prefix: OverlayPrefix,
+) -> Result<Option<PathBuf>> {
+    Ok(get(path.as_ref(), prefix, OverlayAttr::Redirect)?
+        .map(|value| PathBuf::from(OsStr::from_bytes(&value))))
+}
+
+/// Sets the `redirect` EA of `path`.
+pub fn set_redirect<P, R>(
+    path: P,
+    prefix: OverlayPrefix,
+    redirect: R,
+) -> Result<()>
+where
+    P: AsRef<Path>,
+    R: AsRef<Path>,
+{
+    let redirect = redirect.as_ref().as_os_str().as_bytes();
+    if redirect.is_empty() {
+        return Err(Errno(libc::EINVAL));
+    }
+    set(path.as_ref(), prefix, OverlayAttr::Redirect, redirect)
+}
+
+/// Retrieves the `origin` EA of `path`, `Some(None)` for a null origin, which
+/// is recorded when the lower file system does not support file handles.
+pub fn get_origin<P: AsRef<Path>>(
+    path: P,
+    prefix: OverlayPrefix,
+) -> Result<Option<Option<FileHandle>>> {
+    match get(path.as_ref(), prefix, OverlayAttr::Origin)? {
+        Some(value) if value.is_empty() => Ok(Some(None)),
+        Some(value) => Ok(Some(Some(FileHandle::from_bytes(&value)?))),
+        None => Ok(None),
+    }
+}
+
+/// Sets the `origin` EA of `path`, `None` records a null origin.
+pub fn set_origin<P: AsRef<Path>>(
+    path: P,
+    prefix: OverlayPrefix,
+    origin: Option<&FileHandle>,
+) -> Result<()> {
+    let value = match origin {
+        Some(fh) => fh.to_bytes()?,
+        None => Vec::new(),
+    };
+    set(path.as_ref(), prefix, OverlayAttr::Origin, &value)
+}
+
+/// Retrieves the `upper` EA of the index entry `path`.
+pub fn get_upper<P: AsRef<Path>>(
+    path: P,
+    prefix: OverlayPrefix,
+) -> Result<Option<FileHandle>> {
+    get(path.as_ref(), prefix, OverlayAttr::Upper)?
+        .map(|value| FileHandle::from_bytes(&value))
+        .transpose()
+}
+
+/// Sets the `upper` EA of the index entry `path`.
+pub fn set_upper<P: AsRef<Path>>(
+    path: P,
+    prefix: OverlayPrefix,
+    upper: &FileHandle,
+) -> Result<()> {
+    set(
+        path.as_ref(),
+        prefix,
+        OverlayAttr::Upper,
+        &upper.to_bytes()?,
+    )
+}
+
+/// Retrieves the `metacopy` EA of `path`.
+pub fn get_metacopy<P: AsRef<Path>>(
+    path: P,
+    prefix: OverlayPrefix,
+) -> Result<Option<Metacopy>> {
+    get(path.as_ref(), prefix, OverlayAttr::Metacopy)?
+        .map(|value| Metacopy::from_bytes(&value))
+        .transpose()
+}
+
+/// Sets the `metacopy` EA of `path`.
+pub fn set_metacopy<P: AsRef<Path>>(
+    path: P,
+    prefix: OverlayPrefix,
+    metacopy: &Metacopy,
+) -> Result<()> {
+    set(
+        path.as_ref(),
+        prefix,
+        OverlayAttr::Metacopy,
+        &metacopy.to_bytes()?,
+    )
+}
+
+/// Retrieves the `nlink` EA of `path`.
+pub fn get_nlink<P: AsRef<Path>>(
+    path: P,
+    prefix: OverlayPrefix,
+) -> Result<Option<Nlink>> {
+    get(path.as_ref(), prefix, OverlayAttr::Nlink)?
+        .map(|value| Nlink::from_bytes(&value))
+        .transpose()
+}
+
+/// Sets the `nlink` EA of `path`.
+pub fn set_nlink<P: AsRef<Path>>(
+    path: P,
+    prefix: OverlayPrefix,
+    nlink: Nlink,
+) -> Result<()> {
+    set(path.as_ref(), prefix, OverlayAttr::Nlink, &nlink.to_bytes())
+}
+
+/// Returns whether the directory `path` is marked as impure.
+pub fn is_impure<P: AsRef<Path>>(
+    path: P,
+    prefix: OverlayPrefix,
+) -> Result<bool> {
+    Ok(get(path.as_ref(), prefix, OverlayAttr::Impure)?.is_some())
+}
+
+/// Marks the directory `path` as impure or not.
+pub fn set_impure<P: AsRef<Path>>(
+    path: P,
+    prefix: OverlayPrefix,
+    impure: bool,
+) -> Result<()> {
+    if impure {
+        set(path.as_ref(), prefix, OverlayAttr::Impure, MARKER_YES)
+    } else {
+        remove(path, prefix, OverlayAttr::Impure)
+    }
+}
+
+/// Returns whether the file `path` is marked as a whiteout.
+pub fn is_whiteout<P: AsRef<Path>>(
+    path: P,
+    prefix: OverlayPrefix,
+) -> Result<bool> {
+    Ok(get(path.as_ref(), prefix, OverlayAttr::Whiteout)?.is_some())
+}
+
+/// Marks the file `path` as a whiteout or not.
+///
+/// For overlayfs to honor this marker, the parent directory has to be marked
+/// with [`Opaque::ContainsWhiteouts`].
+pub fn set_whiteout<P: AsRef<Path>>(
+    path: P,
+    prefix: OverlayPrefix,
+    whiteout: bool,
+) -> Result<()> {
+    if whiteout {
+        set(path.as_ref(), prefix, OverlayAttr::Whiteout, &[])
+    } else {
+        remove(path, prefix, OverlayAttr::Whiteout)
+    }
+}
@@ -0,0 +1,299 @@
+//! Helpers for the EAs stored in the PAX extended headers of tar archives
+//!
+//! A PAX extended header is a sequence of `"<length> <key>=<value>\n"`
+//! records, where `<length>` is the decimal length of the whole record,
+//! including itself and the trailing newline. EAs are stored in two kinds of
+//! records:
+//!
+//! * `SCHILY.xattr.<name>=<value>`, written by star and GNU tar, where the
+//!   name and the value are stored as they are. Names containing `=` can not
+//!   be stored this way.
+//! * `LIBARCHIVE.xattr.<name>=<value>`, written by libarchive, where the name
+//!   is URL-encoded and the value is encoded in base64 without padding.
+//!
+//! libarchive writes both records for every EA, and prefers its own when
+//! reading, which is what this module does too.
+
+use crate::{
+    base64, lgetxattr, llistxattr, lsetxattr, name::AttrName, name::Namespace,
+    Flags, Result,
+};
+use bitflags::bitflags;
+use errno::Errno;
+use std::{
+    ffi::{OsStr, OsString},
+    os::unix::ffi::{OsStrExt, OsStringExt},
+    path::Path,
+};
+
+/// Key prefix of the records written by star and GNU tar.
+pub const SCHILY_XATTR_PREFIX: &str = "SCHILY.xattr.";
+
+/// Key prefix of the records written by libarchive.
+pub const LIBARCHIVE_XATTR_PREFIX: &str = "LIBARCHIVE.xattr.";
+
+/// Records written for each EA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PaxFormat {
+    /// `SCHILY.xattr.*`
+    Schily,
+    /// `LIBARCHIVE.xattr.*`
+    Libarchive,
+    /// Both, like libarchive does. Names containing `=` only get a
+    /// `LIBARCHIVE.xattr.*` record.
+    Both,
+}
+
+bitflags! {
+    /// Namespaces whose EAs [`apply()`] sets.
+    pub struct NamespacePolicy: u8 {
+        /// `user.*`
+        const USER = 0x01;
+        /// `trusted.*`, which requires `CAP_SYS_ADMIN`.
+        const TRUSTED = 0x02;
+        /// `security.*`, e.g., SELinux labels and file capabilities.
+        const SECURITY = 0x04;
+        /// `system.*`, e.g., POSIX ACLs.
+        const SYSTEM = 0x08;
+    }
+}
+
+impl Default for NamespacePolicy {
+    /// Only `user.*`, which is safe to restore from an untrusted archive.
+    fn default() -> Self {
+        NamespacePolicy::USER
+    }
+}
+
+impl NamespacePolicy {
+    /// Returns true if EAs in `namespace` are allowed.
+    pub fn allows(self, namespace: Namespace) -> bool {
+        let flag = match namespace {
+            Namespace::User => NamespacePolicy::USER,
+            Namespace::Trusted => NamespacePolicy::TRUSTED,
+            Namespace::Security => NamespacePolicy::SECURITY,
+            Namespace::System => NamespacePolicy::SYSTEM,
+        };
+        self.contains(flag)
+    }
+}
+
+/// Encodes a single record.
+pub fn encode_record(key: &[u8], value: &[u8]) -> Vec<u8> {
+    // " " + "=" + "\n"
+    let payload = key.len() + value.len() + 3;
+    let mut len = payload + 1;
+    while len != payload + len.to_string().len() {
+        len = payload + len.to_string().len();
+    }
+
+    let mut record = len.to_string().into_bytes();
+    record.push(b' ');
+    record.extend_from_slice(key);
+    record.push(b'=');
+    record.extend_from_slice(value);
+    record.push(b'\n');
+    record
+}
+
+/// Decodes the records of an extended header, in order.
+///
+/// Returns `EINVAL` if a record is malformed.
+pub fn decode_records(mut data: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+    let invalid = || Errno(libc::EINVAL);
+    let mut records = Vec::new();
+
+    // the header is padded with NULs to a multiple of the block size
+    while let Some(&first) = data.first() {
+        if first == 0 {
+            break;
+        }
+        let space = data.iter().position(|b| *b == b' ').ok_or_else(invalid)?;
+        let len = std::str::from_utf8(&data[..space])
+            .ok()
+            .filter(|len| len.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|len| len.parse::<usize>().ok())
+            .ok_or_else(invalid)?;
+        let record = match data.get(space + 1..len) {
+            Some(record) if record.last() == Some(&b'\n') => {
+                &record[..record.len() - 1]
+            }
+            _ => return Err(invalid()),
+        };
+        let equal =
+            record.iter().position(|b| *b == b'=').ok_or_else(invalid)?;
+        records.push((record[..equal].to_vec(), record[equal + 1..].to_vec()));
+        data = &data[len..];
+    }
+
+    Ok(records)
+}
+
+fn url_encode(name: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(name.len());
+    for &b in name {
+        if b <= b' ' || b >= 0x7f || b == b'%' || b == b'=' {
+            encoded.extend_from_slice(format!("%{:02X}", b).as_bytes());
+        } else {
+            encoded.push(b);
+        }
+    }
+    encoded
+}
+
+fn url_decode(encoded: &[u8]) -> Result<Vec<u8>> {
+    let mut name = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        if encoded[i] == b'%' {
+            let byte = encoded
+                .get(i + 1..i + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or(Errno(libc::EINVAL))?;
+            name.push(byte);
+            i += 3;
+        } else {
+            name.push(encoded[i]);
+            i += 1;
+        }
+    }
+    Ok(name)
+}
+
+/// Encodes the records of the EA `name` with value `value`.
+///
+/// Returns `EINVAL` if `name` is empty, contains a NUL byte, or contains `=`
+/// and `format` is [`PaxFormat::Schily`].
+pub fn encode_xattr<N: AsRef<OsStr>>(
+    name: N,
+    value: &[u8],
+    format: PaxFormat,
+) -> Result<Vec<u8>> {
+    let name = name.as_ref().as_bytes();
+    if name.is_empty() || name.contains(&0) {
+        return Err(Errno(libc::EINVAL));
+    }
+    let schily = match format {
+        PaxFormat::Schily if name.contains(&b'=') => {
+            return Err(Errno(libc::EINVAL))
+        }
+        PaxFormat::Schily => true,
+        PaxFormat::Libarchive => false,
+        PaxFormat::Both => !name.contains(&b'='),
+    };
+
+    let mut records = Vec::new();
+    if format != PaxFormat::Schily {
+        let mut key = LIBARCHIVE_XATTR_PREFIX.as_bytes().to_vec();
+        key.extend_from_slice(&url_encode(name));
+        let value = base64::encode(value);
+        let value = value.trim_end_matches('=');
+        records.extend_from_slice(&encode_record(&key, value.as_bytes()));
+    }
+    if schily {
+        let mut key = SCHILY_XATTR_PREFIX.as_bytes().to_vec();
+        key.extend_from_slice(name);
+        records.extend_from_slice(&encode_record(&key, value));
+    }
+    Ok(records)
+}
+
+/// Extracts the EAs from the records of an extended header, in the order of
+/// their first record. Records of other kinds are ignored.
+///
+/// Returns `EINVAL` if a `LIBARCHIVE.xattr.*` record can not be decoded.
+pub fn decode_xattrs(
+    records: &[(Vec<u8>, Vec<u8>)],
+) -> Result<Vec<(OsString, Vec<u8>)>> {
+    let mut attrs: Vec<(OsString, Vec<u8>, bool)> = Vec::new();
+
+    for (key, value) in records {
+        let (name, value, libarchive) = if let Some(name) =
+            key.strip_prefix(LIBARCHIVE_XATTR_PREFIX.as_bytes())
+        {
+            let mut value = value.clone();
+            // libarchive omits the padding
+            value.resize(value.len() + (4 - value.len() % 4) % 4, b'=');
+            let value = std::str::from_utf8(&value)
+                .ok()
+                .and_then(base64::decode)
+                .ok_or(Errno(libc::EINVAL))?;
+            (url_decode(name)?, value, true)
+        } else if let Some(name) =
+            key.strip_prefix(SCHILY_XATTR_PREFIX.as_bytes())
+        {
+            (name.to_vec(), value.clone(), false)
+        } else {
+            continue;
+        };
+        if name.is_empty() {
+            return Err(Errno(libc::EINVAL));
+        }
+        let name = OsString::from_vec(name);
+
+        match attrs.iter_mut().find(|(n, _, _)| *n == name) {
+            // a `LIBARCHIVE.xattr.*` record replaces a `SCHILY.xattr.*` one
+            Some(attr) if libarchive || !attr.2 => {
+                *attr = (name, value, libarchive)
+            }
+            Some(_) => (),
+            None => attrs.push((name, value, libarchive)),
+        }
+    }
+
+    Ok(attrs
+        .into_iter()
+        .map(|(name, value, _)| (name, value))
+        .collect())
+}
+
+/// Encodes the EAs of `path` as the records of an extended header. If `path`
+/// is a symbolic link, the EAs of the link *itself* are captured, like tar
+/// does.
+///
+/// Returns `EINVAL` if the name of an EA can not be stored in `format`.
+pub fn capture<P: AsRef<Path>>(path: P, format: PaxFormat) -> Result<Vec<u8>> {
+    let path = path.as_ref();
+    let mut names = llistxattr(path)?;
+    names.sort();
+
+    let mut records = Vec::new();
+    for name in names {
+        let value = match lgetxattr(path, &name) {
+            Ok(value) => value,
+            // removed since it was listed
+            Err(Errno(libc::ENODATA)) => continue,
+            Err(e) => return Err(e),
+        };
+        records.extend_from_slice(&encode_xattr(&name, &value, format)?);
+    }
+    Ok(records)
+}
+
+/// Sets the EAs found in the records of an extended header on `path`, if
+/// their namespace is allowed by `policy`. If `path` is a symbolic link, the
+/// EAs are set on the link *itself*.
+///
+/// Returns the names of the EAs that were skipped, because of `policy` or
+/// because their namespace is unknown, and stops at the first error.
+pub fn apply<P: AsRef<Path>>(
+    path: P,
+    records: &[(Vec<u8>, Vec<u8>)],
+    policy: NamespacePolicy,
+) -> Result<Vec<OsString>> {
+    let path = path.as_ref();
+    let mut skipped = Vec::new();
+
+    for (name, value) in decode_xattrs(records)? {
+        match AttrName::from_linux(&name) {
+            Ok(attr) if policy.allows(attr.namespace()) => {
+                lsetxattr(path, &name, value, Flags::empty())?
+            }
+            _ => skipped.push(name),
+        }
+    }
+
+    Ok(skipped)
+}
@@ -1,6 +1,6 @@
 //! EA syscall bindings for macOS and iOS
 
-use crate::Result;
+use crate::{limit::check_size, Result};
 use bitflags::bitflags;
 use errno::{errno, Errno};
 use std::{
@@ -48,6 +48,7 @@ pub fn listxattr<P: AsRef<Path>>(
             buffer_size => buffer_size as usize,
         };
 
+    check_size(buffer_size)?;
     let mut buffer: Vec<u8> = Vec::with_capacity(buffer_size);
     let res = unsafe {
         libc::listxattr(
@@ -87,6 +88,7 @@ pub fn flistxattr(fd: RawFd, options: Options) -> Result<Vec<OsString>> {
             buffer_size => buffer_size as usize,
         };
 
+    check_size(buffer_size)?;
     let mut buffer: Vec<u8> = Vec::with_capacity(buffer_size);
     let res = unsafe {
         libc::flistxattr(fd, buffer.as_mut_ptr().cast(), buffer_size, options)
@@ -146,6 +148,7 @@ where
         buffer_size => buffer_size as usize,
     };
 
+    check_size(buffer_size)?;
     let mut buffer: Vec<u8> = Vec::with_capacity(buffer_size);
 
     let res = unsafe {
@@ -195,6 +198,7 @@ pub fn fgetxattr<S: AsRef<OsStr>>(
         buffer_size => buffer_size as usize,
     };
 
+    check_size(buffer_size)?;
     let mut buffer: Vec<u8> = Vec::with_capacity(buffer_size);
 
     let res = unsafe {
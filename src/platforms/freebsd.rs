@@ -1,6 +1,6 @@
 //! EA syscall bindings for FreeBSD
 
-use crate::Result;
+use crate::{limit::check_size, name::parse_extattr_list, Result};
 use errno::{errno, Errno};
 use std::{
     ffi::{CString, OsStr, OsString},
@@ -9,16 +9,14 @@ use std::{
     ptr::null_mut,
 };
 
-#[repr(i32)]
-#[derive(Debug, Clone, Copy)]
-#[allow(non_camel_case_types)]
-/// `attrnamespace` argument
-pub enum AttrNamespace {
-    /// User namespace EA
-    EXTATTR_NAMESPACE_USER = libc::EXTATTR_NAMESPACE_USER,
-    /// System namespace EA
-    EXTATTR_NAMESPACE_SYSTEM = libc::EXTATTR_NAMESPACE_SYSTEM,
-}
+pub use crate::name::AttrNamespace;
+
+// `AttrNamespace` is defined on every platform, check that its values agree
+// with the ones of the syscalls.
+const _: [(); 1] = [(); (AttrNamespace::EXTATTR_NAMESPACE_USER as i32
+    == libc::EXTATTR_NAMESPACE_USER) as usize];
+const _: [(); 1] = [(); (AttrNamespace::EXTATTR_NAMESPACE_SYSTEM as i32
+    == libc::EXTATTR_NAMESPACE_SYSTEM) as usize];
 
 /// Deletes the extended attribute specified in `attrnamespace` and `attrname`
 /// for the file referred by the open file descriptor `fd`.
@@ -112,37 +110,6 @@ where
     }
 }
 
-/// A function to help us parse the return value of `extattr_list_xx(2)`
-///
-/// On FreeBSD, the return value of `extattr_list_xx(2)` is something like
-/// "\x08attrname\x0fanotherattrname"
-///
-/// > Cite the man page:
-/// >
-/// > The `extattr_list_file()` returns a list of attributes present in the
-/// > requested namespace. **Each list entry consists of a single byte containing
-/// > the length of the attribute name, followed by the attribute name.** The
-/// > attribute name is not terminated by ASCII 0 (nul).
-///
-/// We need to parse it into `vec!["attrname", "antherattrname"]`
-fn parse_ea_entries(bytes: &[u8]) -> Vec<OsString> {
-    let mut ret = Vec::new();
-    let mut idx = 0;
-    let len = bytes.len();
-
-    while idx < len {
-        let entry_len = bytes[idx] as usize;
-        ret.push(
-            OsStr::from_bytes(&bytes[idx + 1..idx + entry_len + 1]).to_owned(),
-        );
-
-        // update idx to parse the next entry
-        idx += entry_len + 1;
-    }
-
-    ret
-}
-
 /// Returns a list of attribute names present in the requested namespace for
 /// the file specified in the open file descriptor `fd`.
 ///
@@ -161,6 +128,7 @@ pub fn extattr_list_fd(
             size => size as usize,
         };
 
+    check_size(buffer_size)?;
     let mut buffer: Vec<u8> = Vec::with_capacity(buffer_size);
 
     let res = unsafe {
@@ -176,7 +144,7 @@ pub fn extattr_list_fd(
         -1 => Err(errno()),
         len => {
             unsafe { buffer.set_len(len as usize) };
-            Ok(parse_ea_entries(&buffer))
+            parse_extattr_list(&buffer)
         }
     }
 }
@@ -207,6 +175,7 @@ where
         size => size as usize,
     };
 
+    check_size(buffer_size)?;
     let mut buffer: Vec<u8> = Vec::with_capacity(buffer_size);
 
     let res = unsafe {
@@ -222,7 +191,7 @@ where
         -1 => Err(errno()),
         len => {
             unsafe { buffer.set_len(len as usize) };
-            Ok(parse_ea_entries(&buffer))
+            parse_extattr_list(&buffer)
         }
     }
 }
@@ -254,6 +223,7 @@ where
         size => size as usize,
     };
 
+    check_size(buffer_size)?;
     let mut buffer: Vec<u8> = Vec::with_capacity(buffer_size);
 
     let res = unsafe {
@@ -269,7 +239,7 @@ where
         -1 => Err(errno()),
         len => {
             unsafe { buffer.set_len(len as usize) };
-            Ok(parse_ea_entries(&buffer))
+            parse_extattr_list(&buffer)
         }
     }
 }
@@ -297,6 +267,7 @@ pub fn extattr_get_fd<S: AsRef<OsStr>>(
         0 => return Ok(Vec::new()),
         size => size as usize,
     };
+    check_size(buffer_size)?;
     let mut buffer: Vec<u8> = Vec::with_capacity(buffer_size);
 
     let res = unsafe {
@@ -355,6 +326,7 @@ where
         0 => return Ok(Vec::new()),
         size => size as usize,
     };
+    check_size(buffer_size)?;
     let mut buffer: Vec<u8> = Vec::with_capacity(buffer_size);
 
     let res = unsafe {
@@ -414,6 +386,7 @@ where
         0 => return Ok(Vec::new()),
         size => size as usize,
     };
+    check_size(buffer_size)?;
     let mut buffer: Vec<u8> = Vec::with_capacity(buffer_size);
 
     let res = unsafe {
@@ -566,9 +539,9 @@ mod test {
     use std::ffi::OsStr;
 
     #[test]
-    fn test_parse_ea_entries() {
+    fn test_parse_extattr_list() {
         let list = "\x08attrname\x0fanotherattrname";
-        let ret = super::parse_ea_entries(list.as_bytes());
+        let ret = super::parse_extattr_list(list.as_bytes()).unwrap();
 
         assert_eq!(
             vec![
@@ -1,6 +1,6 @@
 //! EA syscall bindings for Linux and Android
 
-use crate::Result;
+use crate::{limit::check_size, Result};
 use bitflags::bitflags;
 use errno::{errno, Errno};
 use std::{
@@ -40,6 +40,7 @@ pub fn listxattr<P: AsRef<Path>>(path: P) -> Result<Vec<OsString>> {
             buffer_size => buffer_size as usize,
         };
 
+    check_size(buffer_size)?;
     let mut buffer: Vec<u8> = Vec::with_capacity(buffer_size);
     let res = unsafe {
         libc::listxattr(path.as_ptr(), buffer.as_mut_ptr().cast(), buffer_size)
@@ -77,6 +78,7 @@ pub fn llistxattr<P: AsRef<Path>>(path: P) -> Result<Vec<OsString>> {
             buffer_size => buffer_size as usize,
         };
 
+    check_size(buffer_size)?;
     let mut buffer: Vec<u8> = Vec::with_capacity(buffer_size);
     let res = unsafe {
         libc::llistxattr(path.as_ptr(), buffer.as_mut_ptr().cast(), buffer_size)
@@ -107,6 +109,7 @@ pub fn flistxattr(fd: RawFd) -> Result<Vec<OsString>> {
         buffer_size => buffer_size as usize,
     };
 
+    check_size(buffer_size)?;
     let mut buffer: Vec<u8> = Vec::with_capacity(buffer_size);
     let res = unsafe {
         libc::flistxattr(fd, buffer.as_mut_ptr().cast(), buffer_size)
@@ -153,6 +156,7 @@ where
         buffer_size => buffer_size as usize,
     };
 
+    check_size(buffer_size)?;
     let mut buffer: Vec<u8> = Vec::with_capacity(buffer_size);
 
     let res = unsafe {
@@ -201,6 +205,7 @@ where
         buffer_size => buffer_size as usize,
     };
 
+    check_size(buffer_size)?;
     let mut buffer: Vec<u8> = Vec::with_capacity(buffer_size);
 
     let res = unsafe {
@@ -243,6 +248,7 @@ where
             buffer_size => buffer_size as usize,
         };
 
+    check_size(buffer_size)?;
     let mut buffer: Vec<u8> = Vec::with_capacity(buffer_size);
 
     let res = unsafe {
@@ -85,7 +85,7 @@ mod bindings {
 
 mod linux {
     //! EA syscall bindings for Linux and Android
-    use crate::Result;
+    use crate::{limit::check_size, Result};
     use bitflags::bitflags;
     use errno::{errno, Errno};
     use std::{
@@ -125,6 +125,7 @@ mod linux {
             buffer_size => buffer_size as usize,
         };
 
+        check_size(buffer_size)?;
         let mut buffer: Vec<u8> = Vec::with_capacity(buffer_size);
         let res = unsafe {
             super::bindings::listxattr(
@@ -167,6 +168,7 @@ mod linux {
             buffer_size => buffer_size as usize,
         };
 
+        check_size(buffer_size)?;
         let mut buffer: Vec<u8> = Vec::with_capacity(buffer_size);
         let res = unsafe {
             super::bindings::llistxattr(
@@ -202,6 +204,7 @@ mod linux {
                 buffer_size => buffer_size as usize,
             };
 
+        check_size(buffer_size)?;
         let mut buffer: Vec<u8> = Vec::with_capacity(buffer_size);
         let res = unsafe {
             super::bindings::flistxattr(
@@ -257,6 +260,7 @@ mod linux {
             buffer_size => buffer_size as usize,
         };
 
+        check_size(buffer_size)?;
         let mut buffer: Vec<u8> = Vec::with_capacity(buffer_size);
 
         let res = unsafe {
@@ -310,6 +314,7 @@ mod linux {
             buffer_size => buffer_size as usize,
         };
 
+        check_size(buffer_size)?;
         let mut buffer: Vec<u8> = Vec::with_capacity(buffer_size);
 
         let res = unsafe {
@@ -353,6 +358,7 @@ mod linux {
             buffer_size => buffer_size as usize,
         };
 
+        check_size(buffer_size)?;
         let mut buffer: Vec<u8> = Vec::with_capacity(buffer_size);
 
         let res = unsafe {
@@ -0,0 +1,356 @@
+//! A single EA API for every supported platform
+//!
+//! The functions of this module have the same signatures everywhere and map
+//! onto the native bindings:
+//!
+//! * Linux and Android: the `*xattr()` functions and their `Flags`.
+//! * macOS and iOS: the `*xattr()` functions, with `position` 0 and their
+//!   `Options`.
+//! * FreeBSD and NetBSD: the `extattr_*_{file,link,fd}()` functions.
+//!
+//! Names are [`AttrName`]s, see [`crate::name`] for how they map onto each
+//! platform. On the other platforms, every function fails with `ENOTSUP`.
+
+use crate::{name::AttrName, Result};
+use std::{os::unix::io::RawFd, path::Path};
+
+/// The error number returned when an EA does not exist, `ENODATA` on Linux
+/// and Android, `ENOATTR` elsewhere.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub const ENOATTR: i32 = libc::ENODATA;
+
+/// The error number returned when an EA does not exist, `ENODATA` on Linux
+/// and Android, `ENOATTR` elsewhere.
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "netbsd"
+))]
+pub const ENOATTR: i32 = libc::ENOATTR;
+
+/// The error number returned when an EA does not exist, `ENODATA` on Linux
+/// and Android, `ENOATTR` elsewhere.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "netbsd"
+)))]
+pub const ENOATTR: i32 = libc::ENODATA;
+
+/// The file whose EAs are accessed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Target<'a> {
+    /// The file at the path, dereferenced if it is a symbolic link.
+    Path(&'a Path),
+    /// The file at the path, not dereferenced if it is a symbolic link.
+    Link(&'a Path),
+    /// The file referred by the open file descriptor.
+    Fd(RawFd),
+}
+
+/// How [`set()`] behaves depending on whether the EA exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SetMode {
+    /// Create the EA, or replace its value if it exists.
+    CreateOrReplace,
+    /// Create the EA, fail with `EEXIST` if it exists.
+    Create,
+    /// Replace the value of the EA, fail with [`ENOATTR`] if it does not
+    /// exist.
+    Replace,
+}
+
+/// Retrieves the value of the EA `name` of `target`.
+pub fn get(target: Target<'_>, name: &AttrName) -> Result<Vec<u8>> {
+    imp::get(target, name)
+}
+
+/// Sets the value of the EA `name` of `target` to `value`.
+///
+/// FreeBSD and NetBSD have no equivalent of [`SetMode::Create`] and
+/// [`SetMode::Replace`], so they are emulated by checking whether the EA exists
+/// first, which is racy.
+pub fn set(
+    target: Target<'_>,
+    name: &AttrName,
+    value: &[u8],
+    mode: SetMode,
+) -> Result<()> {
+    imp::set(target, name, value, mode)
+}
+
+/// Lists the EAs of `target`.
+///
+/// Names that [`AttrName`] can not represent, e.g., in namespaces it does not
+/// know, are skipped. On FreeBSD and NetBSD, the system namespace is skipped
+/// as well if the caller is not allowed to list it.
+pub fn list(target: Target<'_>) -> Result<Vec<AttrName>> {
+    imp::list(target)
+}
+
+/// Removes the EA `name` of `target`.
+pub fn remove(target: Target<'_>, name: &AttrName) -> Result<()> {
+    imp::remove(target, name)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod imp {
+    use super::{SetMode, Target};
+    use crate::{
+        fgetxattr, flistxattr, fremovexattr, fsetxattr, getxattr, lgetxattr,
+        listxattr, llistxattr, lremovexattr, lsetxattr, name::AttrName,
+        removexattr, setxattr, Flags, Result,
+    };
+
+    pub(super) fn get(target: Target<'_>, name: &AttrName) -> Result<Vec<u8>> {
+        let name = name.to_linux();
+        match target {
+            Target::Path(path) => getxattr(path, name),
+            Target::Link(path) => lgetxattr(path, name),
+            Target::Fd(fd) => fgetxattr(fd, name),
+        }
+    }
+
+    pub(super) fn set(
+        target: Target<'_>,
+        name: &AttrName,
+        value: &[u8],
+        mode: SetMode,
+    ) -> Result<()> {
+        let name = name.to_linux();
+        let flags = match mode {
+            SetMode::CreateOrReplace => Flags::empty(),
+            SetMode::Create => Flags::XATTR_CREATE,
+            SetMode::Replace => Flags::XATTR_REPLACE,
+        };
+        match target {
+            Target::Path(path) => setxattr(path, name, value, flags),
+            Target::Link(path) => lsetxattr(path, name, value, flags),
+            Target::Fd(fd) => fsetxattr(fd, name, value, flags),
+        }
+    }
+
+    pub(super) fn list(target: Target<'_>) -> Result<Vec<AttrName>> {
+        let names = match target {
+            Target::Path(path) => listxattr(path)?,
+            Target::Link(path) => llistxattr(path)?,
+            Target::Fd(fd) => flistxattr(fd)?,
+        };
+        Ok(names
+            .iter()
+            .filter_map(|name| AttrName::from_linux(name).ok())
+            .collect())
+    }
+
+    pub(super) fn remove(target: Target<'_>, name: &AttrName) -> Result<()> {
+        let name = name.to_linux();
+        match target {
+            Target::Path(path) => removexattr(path, name),
+            Target::Link(path) => lremovexattr(path, name),
+            Target::Fd(fd) => fremovexattr(fd, name),
+        }
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+mod imp {
+    use super::{SetMode, Target};
+    use crate::{
+        fgetxattr, flistxattr, fremovexattr, fsetxattr, getxattr, listxattr,
+        name::AttrName, removexattr, setxattr, Options, Result,
+    };
+
+    fn options(target: Target<'_>) -> Options {
+        match target {
+            Target::Link(_) => Options::XATTR_NOFOLLOW,
+            Target::Path(_) | Target::Fd(_) => Options::empty(),
+        }
+    }
+
+    pub(super) fn get(target: Target<'_>, name: &AttrName) -> Result<Vec<u8>> {
+        let name = name.to_darwin()?;
+        let options = options(target);
+        match target {
+            Target::Path(path) | Target::Link(path) => {
+                getxattr(path, name, 0, options)
+            }
+            Target::Fd(fd) => fgetxattr(fd, name, 0, options),
+        }
+    }
+
+    pub(super) fn set(
+        target: Target<'_>,
+        name: &AttrName,
+        value: &[u8],
+        mode: SetMode,
+    ) -> Result<()> {
+        let name = name.to_darwin()?;
+        let options = options(target)
+            | match mode {
+                SetMode::CreateOrReplace => Options::empty(),
+                SetMode::Create => Options::XATTR_CREATE,
+                SetMode::Replace => Options::XATTR_REPLACE,
+            };
+        match target {
+            Target::Path(path) | Target::Link(path) => {
+                setxattr(path, name, value, 0, options)
+            }
+            Target::Fd(fd) => fsetxattr(fd, name, value, 0, options),
+        }
+    }
+
+    pub(super) fn list(target: Target<'_>) -> Result<Vec<AttrName>> {
+        let options = options(target);
+        let names = match target {
+            Target::Path(path) | Target::Link(path) => {
+                listxattr(path, options)?
+            }
+            Target::Fd(fd) => flistxattr(fd, options)?,
+        };
+        Ok(names
+            .iter()
+            .filter_map(|name| AttrName::from_darwin(name).ok())
+            .collect())
+    }
+
+    pub(super) fn remove(target: Target<'_>, name: &AttrName) -> Result<()> {
+        let name = name.to_darwin()?;
+        let options = options(target);
+        match target {
+            Target::Path(path) | Target::Link(path) => {
+                removexattr(path, name, options)
+            }
+            Target::Fd(fd) => fremovexattr(fd, name, options),
+        }
+    }
+}
+
+#[cfg(any(target_os = "freebsd", target_os = "netbsd"))]
+mod imp {
+    use super::{SetMode, Target, ENOATTR};
+    use crate::{
+        extattr_delete_fd, extattr_delete_file, extattr_delete_link,
+        extattr_get_fd, extattr_get_file, extattr_get_link, extattr_list_fd,
+        extattr_list_file, extattr_list_link, extattr_set_fd, extattr_set_file,
+        extattr_set_link, name::AttrName, AttrNamespace, Result,
+    };
+    use errno::Errno;
+
+    pub(super) fn get(target: Target<'_>, name: &AttrName) -> Result<Vec<u8>> {
+        let (namespace, name) = name.to_bsd()?;
+        match target {
+            Target::Path(path) => extattr_get_file(path, namespace, name),
+            Target::Link(path) => extattr_get_link(path, namespace, name),
+            Target::Fd(fd) => extattr_get_fd(fd, namespace, name),
+        }
+    }
+
+    pub(super) fn set(
+        target: Target<'_>,
+        name: &AttrName,
+        value: &[u8],
+        mode: SetMode,
+    ) -> Result<()> {
+        if mode != SetMode::CreateOrReplace {
+            let exists = match get(target, name) {
+                Ok(_) => true,
+                Err(Errno(ENOATTR)) => false,
+                Err(e) => return Err(e),
+            };
+            match (mode, exists) {
+                (SetMode::Create, true) => return Err(Errno(libc::EEXIST)),
+                (SetMode::Replace, false) => return Err(Errno(ENOATTR)),
+                _ => (),
+            }
+        }
+
+        let (namespace, name) = name.to_bsd()?;
+        match target {
+            Target::Path(path) => {
+                extattr_set_file(path, namespace, name, value)
+            }
+            Target::Link(path) => {
+                extattr_set_link(path, namespace, name, value)
+            }
+            Target::Fd(fd) => extattr_set_fd(fd, namespace, name, value),
+        }
+    }
+
+    pub(super) fn list(target: Target<'_>) -> Result<Vec<AttrName>> {
+        let mut attrs = Vec::new();
+        for namespace in [
+            AttrNamespace::EXTATTR_NAMESPACE_USER,
+            AttrNamespace::EXTATTR_NAMESPACE_SYSTEM,
+        ] {
+            let names = match target {
+                Target::Path(path) => extattr_list_file(path, namespace),
+                Target::Link(path) => extattr_list_link(path, namespace),
+                Target::Fd(fd) => extattr_list_fd(fd, namespace),
+            };
+            let names = match names {
+                Ok(names) => names,
+                // listing the system namespace requires privileges
+                Err(Errno(libc::EPERM))
+                    if namespace == AttrNamespace::EXTATTR_NAMESPACE_SYSTEM =>
+                {
+                    continue
+                }
+                Err(e) => return Err(e),
+            };
+            attrs.extend(
+                names.iter().filter_map(|name| {
+                    AttrName::from_bsd(namespace, name).ok()
+                }),
+            );
+        }
+        Ok(attrs)
+    }
+
+    pub(super) fn remove(target: Target<'_>, name: &AttrName) -> Result<()> {
+        let (namespace, name) = name.to_bsd()?;
+        match target {
+            Target::Path(path) => extattr_delete_file(path, namespace, name),
+            Target::Link(path) => extattr_delete_link(path, namespace, name),
+            Target::Fd(fd) => extattr_delete_fd(fd, namespace, name),
+        }
+    }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "netbsd"
+)))]
+mod imp {
+    use super::{SetMode, Target};
+    use crate::{name::AttrName, Result};
+    use errno::Errno;
+
+    pub(super) fn get(_: Target<'_>, _: &AttrName) -> Result<Vec<u8>> {
+        Err(Errno(libc::ENOTSUP))
+    }
+
+    pub(super) fn set(
+        _: Target<'_>,
+        _: &AttrName,
+        _: &[u8],
+        _: SetMode,
+    ) -> Result<()> {
+        Err(Errno(libc::ENOTSUP))
+    }
+
+    pub(super) fn list(_: Target<'_>) -> Result<Vec<AttrName>> {
+        Err(Errno(libc::ENOTSUP))
+    }
+
+    pub(super) fn remove(_: Target<'_>, _: &AttrName) -> Result<()> {
+        Err(Errno(libc::ENOTSUP))
+    }
+}
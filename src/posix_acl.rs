@@ -0,0 +1,192 @@
+//! Codec for the POSIX ACLs stored in `system.posix_acl_access` and
+//! `system.posix_acl_default`
+//!
+//! The value is a little-endian `posix_acl_xattr_header` (a 32-bit version,
+//! which is 2) followed by `posix_acl_xattr_entry`s, each of which consists of
+//! a 16-bit tag, 16-bit permissions and a 32-bit id.
+
+use crate::Result;
+use bitflags::bitflags;
+use errno::Errno;
+
+/// Name of the EA used to store the access ACL.
+pub const XATTR_NAME_POSIX_ACL_ACCESS: &str = "system.posix_acl_access";
+
+/// Name of the EA used to store the default ACL of a directory.
+pub const XATTR_NAME_POSIX_ACL_DEFAULT: &str = "system.posix_acl_default";
+
+const POSIX_ACL_XATTR_VERSION: u32 = 2;
+const ACL_UNDEFINED_ID: u32 = u32::MAX;
+
+const ACL_USER_OBJ: u16 = 0x01;
+const ACL_USER: u16 = 0x02;
+const ACL_GROUP_OBJ: u16 = 0x04;
+const ACL_GROUP: u16 = 0x08;
+const ACL_MASK: u16 = 0x10;
+const ACL_OTHER: u16 = 0x20;
+
+bitflags! {
+    /// Permissions of an ACL entry
+    pub struct Perm: u16 {
+        /// Read permission.
+        const READ = 0x04;
+        /// Write permission.
+        const WRITE = 0x02;
+        /// Execute permission.
+        const EXECUTE = 0x01;
+    }
+}
+
+/// Tag of an ACL entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Tag {
+    /// The owner of the file.
+    UserObj,
+    /// A user identified by its uid.
+    User(u32),
+    /// The owning group of the file.
+    GroupObj,
+    /// A group identified by its gid.
+    Group(u32),
+    /// The maximum permissions granted to the group class.
+    Mask,
+    /// Everyone else.
+    Other,
+}
+
+/// An ACL entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PosixAclEntry {
+    /// Whom this entry applies to.
+    pub tag: Tag,
+    /// Permissions granted by this entry.
+    pub perm: Perm,
+}
+
+/// A POSIX ACL.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct PosixAcl {
+    /// Entries of this ACL.
+    pub entries: Vec<PosixAclEntry>,
+}
+
+impl PosixAcl {
+    /// Creates the minimal ACL equivalent to the permission bits of `mode`.
+    pub fn from_mode(mode: u32) -> Self {
+        let perm =
+            |shift: u32| Perm::from_bits_truncate((mode >> shift) as u16 & 0o7);
+
+        PosixAcl {
+            entries: vec![
+                PosixAclEntry {
+                    tag: Tag::UserObj,
+                    perm: perm(6),
+                },
+                PosixAclEntry {
+                    tag: Tag::GroupObj,
+                    perm: perm(3),
+                },
+                PosixAclEntry {
+                    tag: Tag::Other,
+                    perm: perm(0),
+                },
+            ],
+        }
+    }
+
+    /// Returns the permissions of the first entry tagged with `tag`.
+    pub fn get(&self, tag: Tag) -> Option<Perm> {
+        self.entries
+            .iter()
+            .find(|entry| entry.tag == tag)
+            .map(|entry| entry.perm)
+    }
+
+    /// Sorts the entries in the canonical order (owner, named users, owning
+    /// group, named groups, mask, other), and checks that the ACL is valid:
+    /// exactly one owner, owning group and other entry, no duplicated named
+    /// entries, and a mask entry if there are named entries.
+    pub fn normalize(&mut self) -> Result<()> {
+        self.entries.sort_by_key(|entry| entry.tag);
+
+        if self
+            .entries
+            .windows(2)
+            .any(|pair| pair[0].tag == pair[1].tag)
+        {
+            return Err(Errno(libc::EINVAL));
+        }
+        let has_named = self
+            .entries
+            .iter()
+            .any(|entry| matches!(entry.tag, Tag::User(_) | Tag::Group(_)));
+        if self.get(Tag::UserObj).is_none()
+            || self.get(Tag::GroupObj).is_none()
+            || self.get(Tag::Other).is_none()
+            || (has_named && self.get(Tag::Mask).is_none())
+        {
+            return Err(Errno(libc::EINVAL));
+        }
+
+        Ok(())
+    }
+
+    /// Decodes a raw `system.posix_acl_*` value.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 4 {
+            return Err(Errno(libc::EINVAL));
+        }
+        let version =
+            u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let entries = bytes[4..].chunks_exact(8);
+        if version != POSIX_ACL_XATTR_VERSION || !entries.remainder().is_empty()
+        {
+            return Err(Errno(libc::EINVAL));
+        }
+
+        let entries = entries
+            .map(|entry| {
+                let tag = u16::from_le_bytes([entry[0], entry[1]]);
+                let perm = u16::from_le_bytes([entry[2], entry[3]]);
+                let id = u32::from_le_bytes([
+                    entry[4], entry[5], entry[6], entry[7],
+                ]);
+                let tag = match tag {
+                    ACL_USER_OBJ => Tag::UserObj,
+                    ACL_USER => Tag::User(id),
+                    ACL_GROUP_OBJ => Tag::GroupObj,
+                    ACL_GROUP => Tag::Group(id),
+                    ACL_MASK => Tag::Mask,
+                    ACL_OTHER => Tag::Other,
+                    _ => return Err(Errno(libc::EINVAL)),
+                };
+                let perm = Perm::from_bits(perm).ok_or(Errno(libc::EINVAL))?;
+
+                Ok(PosixAclEntry { tag, perm })
+            })
+            .collect::<Result<Vec<PosixAclEntry>>>()?;
+
+        Ok(PosixAcl { entries })
+    }
+
+    /// Encodes this ACL into a raw `system.posix_acl_*` value.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = POSIX_ACL_XATTR_VERSION.to_le_bytes().to_vec();
+
+        for entry in self.entries.iter() {
+            let (tag, id) = match entry.tag {
+                Tag::UserObj => (ACL_USER_OBJ, ACL_UNDEFINED_ID),
+                Tag::User(uid) => (ACL_USER, uid),
+                Tag::GroupObj => (ACL_GROUP_OBJ, ACL_UNDEFINED_ID),
+                Tag::Group(gid) => (ACL_GROUP, gid),
+                Tag::Mask => (ACL_MASK, ACL_UNDEFINED_ID),
+                Tag::Other => (ACL_OTHER, ACL_UNDEFINED_ID),
+            };
+            bytes.extend_from_slice(&tag.to_le_bytes());
+            bytes.extend_from_slice(&entry.perm.bits().to_le_bytes());
+            bytes.extend_from_slice(&id.to_le_bytes());
+        }
+
+        bytes
+    }
+}
@@ -0,0 +1,504 @@
+//! Helpers for the EAs used to emulate ownership and modes without privileges
+//!
+//! Tools running without root can not `chown(2)` files or create device nodes,
+//! so they record the metadata the files should have in `user.*` EAs instead:
+//!
+//! * rsync, with `--fake-super`, stores the mode (including the file type),
+//!   the device numbers and the ownership in `user.rsync.%stat`, formatted as
+//!   `"<octal mode> <major>,<minor> <uid>:<gid>"`.
+//! * containers/storage and fuse-overlayfs store the ownership and the
+//!   permission bits in `user.containers.override_stat`, formatted as
+//!   `"<uid>:<gid>:<octal mode>"`, optionally followed by `:<file type>`.
+//!
+//! [`effective_stat()`] overlays these values on the ones returned by
+//! `lstat(2)`. Since the kernel does not allow `user.*` EAs on symbolic links,
+//! they always report their real metadata.
+
+use crate::{io_errno, lgetxattr, lremovexattr, lsetxattr, Flags, Result};
+use errno::Errno;
+use std::{fmt, fs, os::unix::fs::MetadataExt, path::Path, str::FromStr};
+
+/// Name of the EA used by rsync's `--fake-super`.
+pub const XATTR_NAME_RSYNC_STAT: &str = "user.rsync.%stat";
+
+/// Name of the EA used by containers/storage and fuse-overlayfs.
+pub const XATTR_NAME_CONTAINERS_OVERRIDE_STAT: &str =
+    "user.containers.override_stat";
+
+/// Permission bits, including the set-user-ID, set-group-ID and sticky bits.
+const PERM_MASK: u32 = 0o7777;
+
+/// File type bits of a mode.
+// `mode_t` is `u16` on 32-bit Android
+#[allow(clippy::unnecessary_cast)]
+const S_IFMT: u32 = libc::S_IFMT as u32;
+
+fn parse_decimal(s: &str) -> Result<u32> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(Errno(libc::EINVAL));
+    }
+    s.parse().map_err(|_| Errno(libc::EINVAL))
+}
+
+fn parse_octal(s: &str) -> Result<u32> {
+    if s.is_empty() || !s.bytes().all(|b| (b'0'..=b'7').contains(&b)) {
+        return Err(Errno(libc::EINVAL));
+    }
+    u32::from_str_radix(s, 8).map_err(|_| Errno(libc::EINVAL))
+}
+
+fn text(bytes: &[u8]) -> Result<&str> {
+    std::str::from_utf8(bytes)
+        .map(|s| s.trim_end_matches('\0'))
+        .map_err(|_| Errno(libc::EINVAL))
+}
+
+/// Value of `user.rsync.%stat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RsyncStat {
+    /// Mode of the file, including the file type bits.
+    pub mode: u32,
+    /// Major device number, 0 unless the file is a device.
+    pub rdev_major: u32,
+    /// Minor device number, 0 unless the file is a device.
+    pub rdev_minor: u32,
+    /// Owner of the file.
+    pub uid: u32,
+    /// Group of the file.
+    pub gid: u32,
+}
+
+impl RsyncStat {
+    /// Decodes a raw `user.rsync.%stat` value.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        text(bytes)?.parse()
+    }
+
+    /// Encodes this value into a raw `user.rsync.%stat` value.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.to_string().into_bytes()
+    }
+}
+
+impl FromStr for RsyncStat {
+    type Err = Errno;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut fields = s.split(' ');
+        let (mode, rdev, owner) = match (
+            fields.next(),
+            fields.next(),
+            fields.next(),
+            fields.next(),
+        ) {
+            (Some(mode), Some(rdev), Some(owner), None) => (mode, rdev, owner),
+            _ => return Err(Errno(libc::EINVAL)),
+        };
+        let (rdev_major, rdev_minor) =
+            rdev.split_once(',').ok_or(Errno(libc::EINVAL))?;
+        let (uid, gid) = owner.split_once(':').ok_or(Errno(libc::EINVAL))?;
+
+        Ok(RsyncStat {
+            mode: parse_octal(mode)?,
+            rdev_major: parse_decimal(rdev_major)?,
+            rdev_minor: parse_decimal(rdev_minor)?,
+            uid: parse_decimal(uid)?,
+            gid: parse_decimal(gid)?,
+        })
+    }
+}
+
+impl fmt::Display for RsyncStat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:o} {},{} {}:{}",
+            self.mode, self.rdev_major, self.rdev_minor, self.uid, self.gid
+        )
+    }
+}
+
+/// File type recorded in `user.containers.override_stat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OverrideFileType {
+    /// `dir`
+    Directory,
+    /// `file`
+    File,
+    /// `symlink`
+    Symlink,
+    /// `pipe`
+    Pipe,
+    /// `socket`
+    Socket,
+    /// `block-<major>-<minor>`
+    Block(u32, u32),
+    /// `char-<major>-<minor>`
+    Char(u32, u32),
+}
+
+impl OverrideFileType {
+    /// Returns the file type bits of this type.
+    #[allow(clippy::unnecessary_cast)]
+    pub fn mode(self) -> u32 {
+        let mode = match self {
+            OverrideFileType::Directory => libc::S_IFDIR,
+            OverrideFileType::File => libc::S_IFREG,
+            OverrideFileType::Symlink => libc::S_IFLNK,
+            OverrideFileType::Pipe => libc::S_IFIFO,
+            OverrideFileType::Socket => libc::S_IFSOCK,
+            OverrideFileType::Block(..) => libc::S_IFBLK,
+            OverrideFileType::Char(..) => libc::S_IFCHR,
+        };
+        mode as u32
+    }
+
+    /// Returns the major and minor device numbers of this type, `(0, 0)` if it
+    /// is not a device.
+    pub fn rdev(self) -> (u32, u32) {
+        match self {
+            OverrideFileType::Block(major, minor)
+            | OverrideFileType::Char(major, minor) => (major, minor),
+            _ => (0, 0),
+        }
+    }
+}
+
+impl FromStr for OverrideFileType {
+    type Err = Errno;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let device = |rdev: &str| -> Result<(u32, u32)> {
+            let (major, minor) =
+                rdev.split_once('-').ok_or(Errno(libc::EINVAL))?;
+            Ok((parse_decimal(major)?, parse_decimal(minor)?))
+        };
+
+        match s {
+            "dir" => Ok(OverrideFileType::Directory),
+            "file" => Ok(OverrideFileType::File),
+            "symlink" => Ok(OverrideFileType::Symlink),
+            "pipe" => Ok(OverrideFileType::Pipe),
+            "socket" => Ok(OverrideFileType::Socket),
+            _ => {
+                if let Some(rdev) = s.strip_prefix("block-") {
+                    let (major, minor) = device(rdev)?;
+                    Ok(OverrideFileType::Block(major, minor))
+                } else if let Some(rdev) = s.strip_prefix("char-") {
+                    let (major, minor) = device(rdev)?;
+                    Ok(OverrideFileType::Char(major, minor))
+                } else {
+                    Err(Errno(libc::EINVAL))
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for OverrideFileType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OverrideFileType::Directory => write!(f, "dir"),
+            OverrideFileType::File => write!(f, "file"),
+            OverrideFileType::Symlink => write!(f, "symlink"),
+            OverrideFileType::Pipe => write!(f, "pipe"),
+            OverrideFileType::Socket => write!(f, "socket"),
+            OverrideFileType::Block(major, minor) => {
+                write!(f, "block-{}-{}", major, minor)
+            }
+            OverrideFileType::Char(major, minor) => {
+                write!(f, "char-{}-{}", major, minor)
+            }
+        }
+    }
+}
+
+/// Value of `user.containers.override_stat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OverrideStat {
+    /// Owner of the file.
+    pub uid: u32,
+    /// Group of the file.
+    pub gid: u32,
+    /// Permission bits of the file, file type bits are rejected.
+    pub mode: u32,
+    /// File type, `None` if the real one applies.
+    pub file_type: Option<OverrideFileType>,
+}
+
+impl OverrideStat {
+    /// Decodes a raw `user.containers.override_stat` value.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        text(bytes)?.parse()
+    }
+
+    /// Encodes this value into a raw `user.containers.override_stat` value.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.to_string().into_bytes()
+    }
+}
+
+impl FromStr for OverrideStat {
+    type Err = Errno;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut fields = s.splitn(4, ':');
+        let (uid, gid, mode) =
+            match (fields.next(), fields.next(), fields.next()) {
+                (Some(uid), Some(gid), Some(mode)) => (uid, gid, mode),
+                _ => return Err(Errno(libc::EINVAL)),
+            };
+        let mode = parse_octal(mode)?;
+        if mode & !PERM_MASK != 0 {
+            return Err(Errno(libc::EINVAL));
+        }
+
+        Ok(OverrideStat {
+            uid: parse_decimal(uid)?,
+            gid: parse_decimal(gid)?,
+            mode,
+            file_type: fields.next().map(str::parse).transpose()?,
+        })
+    }
+}
+
+impl fmt::Display for OverrideStat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{:o}", self.uid, self.gid, self.mode)?;
+        if let Some(file_type) = self.file_type {
+            write!(f, ":{}", file_type)?;
+        }
+        Ok(())
+    }
+}
+
+fn get_value<T>(
+    path: &Path,
+    name: &str,
+    from_bytes: fn(&[u8]) -> Result<T>,
+) -> Result<Option<T>> {
+    match lgetxattr(path, name) {
+        Ok(value) => from_bytes(&value).map(Some),
+        Err(Errno(libc::ENODATA)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Retrieves `user.rsync.%stat` of `path`, `None` if it is not set. If `path`
+/// is a symbolic link, it will not be dereferenced.
+pub fn get_rsync_stat<P: AsRef<Path>>(path: P) -> Result<Option<RsyncStat>> {
+    get_value(path.as_ref(), XATTR_NAME_RSYNC_STAT, RsyncStat::from_bytes)
+}
+
+/// Sets `user.rsync.%stat` of `path`. If `path` is a symbolic link, it will
+/// not be dereferenced.
+pub fn set_rsync_stat<P: AsRef<Path>>(path: P, stat: &RsyncStat) -> Result<()> {
+    lsetxattr(path, XATTR_NAME_RSYNC_STAT, stat.to_bytes(), Flags::empty())
+}
+
+/// Retrieves `user.containers.override_stat` of `path`, `None` if it is not
+/// set. If `path` is a symbolic link, it will not be dereferenced.
+pub fn get_override_stat<P: AsRef<Path>>(
+    path: P,
+) -> Result<Option<OverrideStat>> {
+    get_value(
+        path.as_ref(),
+        XATTR_NAME_CONTAINERS_OVERRIDE_STAT,
+        OverrideStat::from_bytes,
+    )
+}
+
+/// Sets `user.containers.override_stat` of `path`. If `path` is a symbolic
+/// link, it will not be dereferenced.
+///
+/// Returns `EINVAL` if `stat.mode` has bits other than the permission bits.
+pub fn set_override_stat<P: AsRef<Path>>(
+    path: P,
+    stat: &OverrideStat,
+) -> Result<()> {
+    if stat.mode & !PERM_MASK != 0 {
+        return Err(Errno(libc::EINVAL));
+    }
+    lsetxattr(
+        path,
+        XATTR_NAME_CONTAINERS_OVERRIDE_STAT,
+        stat.to_bytes(),
+        Flags::empty(),
+    )
+}
+
+/// Where the values of an [`EffectiveStat`] come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StatSource {
+    /// `lstat(2)`, no EA is set.
+    Lstat,
+    /// `user.rsync.%stat`
+    Rsync,
+    /// `user.containers.override_stat`
+    Containers,
+}
+
+/// Metadata of a file once the emulated values are applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EffectiveStat {
+    /// Mode of the file, including the file type bits.
+    pub mode: u32,
+    /// Owner of the file.
+    pub uid: u32,
+    /// Group of the file.
+    pub gid: u32,
+    /// Major device number, 0 unless the file is a device.
+    pub rdev_major: u32,
+    /// Minor device number, 0 unless the file is a device.
+    pub rdev_minor: u32,
+    /// Where the values come from.
+    pub source: StatSource,
+}
+
+/// Tolerates file systems without EA support, whose files have no emulated
+/// values.
+fn ignore_enotsup<T>(res: Result<Option<T>>) -> Result<Option<T>> {
+    match res {
+        Err(Errno(libc::ENOTSUP)) => Ok(None),
+        res => res,
+    }
+}
+
+/// Returns the metadata of `path` from `lstat(2)`, with the values stored in
+/// `user.containers.override_stat` or, if it is not set,
+/// `user.rsync.%stat` applied on top of it.
+///
+/// `user.containers.override_stat` only replaces the file type when it
+/// records one, while `user.rsync.%stat` always does.
+pub fn effective_stat<P: AsRef<Path>>(path: P) -> Result<EffectiveStat> {
+    let path = path.as_ref();
+    let metadata = fs::symlink_metadata(path).map_err(io_errno)?;
+
+    if let Some(stat) = ignore_enotsup(get_override_stat(path))? {
+        let (mode, (rdev_major, rdev_minor)) = match stat.file_type {
+            Some(file_type) => (file_type.mode(), file_type.rdev()),
+            None => (metadata.mode() & S_IFMT, rdev(&metadata)),
+        };
+        return Ok(EffectiveStat {
+            mode: mode | stat.mode,
+            uid: stat.uid,
+            gid: stat.gid,
+            rdev_major,
+            rdev_minor,
+            source: StatSource::Containers,
+        });
+    }
+
+    if let Some(stat) = ignore_enotsup(get_rsync_stat(path))? {
+        return Ok(EffectiveStat {
+            mode: stat.mode,
+            uid: stat.uid,
+            gid: stat.gid,
+            rdev_major: stat.rdev_major,
+            rdev_minor: stat.rdev_minor,
+            source: StatSource::Rsync,
+        });
+    }
+
+    let (rdev_major, rdev_minor) = rdev(&metadata);
+    Ok(EffectiveStat {
+        mode: metadata.mode(),
+        uid: metadata.uid(),
+        gid: metadata.gid(),
+        rdev_major,
+        rdev_minor,
+        source: StatSource::Lstat,
+    })
+}
+
+/// Splits `st_rdev` into its major and minor numbers, using the encoding of
+/// glibc, musl and bionic, as rsync does.
+fn rdev(metadata: &fs::Metadata) -> (u32, u32) {
+    let rdev = metadata.rdev();
+    let major = ((rdev >> 8) & 0xfff) | ((rdev >> 32) & 0xffff_f000);
+    let minor = (rdev & 0xff) | ((rdev >> 12) & 0xffff_ff00);
+    (major as u32, minor as u32)
+}
+
+/// Convention used to record emulated metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StatFormat {
+    /// `user.rsync.%stat`
+    Rsync,
+    /// `user.containers.override_stat`
+    Containers,
+}
+
+/// Records `uid` and `gid` as the ownership of `path` in the EA of `format`,
+/// which does not require any privilege. The mode and the device numbers are
+/// taken from [`effective_stat()`], so that previously recorded ones are kept.
+///
+/// The EA of the other format is removed, since it would no longer agree.
+pub fn set_ownership<P: AsRef<Path>>(
+    path: P,
+    uid: u32,
+    gid: u32,
+    format: StatFormat,
+) -> Result<()> {
+    let path = path.as_ref();
+    let current = effective_stat(path)?;
+
+    let stale = match format {
+        StatFormat::Rsync => {
+            set_rsync_stat(
+                path,
+                &RsyncStat {
+                    mode: current.mode,
+                    rdev_major: current.rdev_major,
+                    rdev_minor: current.rdev_minor,
+                    uid,
+                    gid,
+                },
+            )?;
+            XATTR_NAME_CONTAINERS_OVERRIDE_STAT
+        }
+        StatFormat::Containers => {
+            let real_type =
+                fs::symlink_metadata(path).map_err(io_errno)?.mode() & S_IFMT;
+            let file_type = if current.mode & S_IFMT == real_type {
+                None
+            } else {
+                Some(file_type(current)?)
+            };
+            set_override_stat(
+                path,
+                &OverrideStat {
+                    uid,
+                    gid,
+                    mode: current.mode & PERM_MASK,
+                    file_type,
+                },
+            )?;
+            XATTR_NAME_RSYNC_STAT
+        }
+    };
+
+    match lremovexattr(path, stale) {
+        Err(Errno(libc::ENODATA)) => Ok(()),
+        res => res,
+    }
+}
+
+/// Converts the file type of `stat` for `user.containers.override_stat`.
+fn file_type(stat: EffectiveStat) -> Result<OverrideFileType> {
+    let file_type = match (stat.mode & S_IFMT) as libc::mode_t {
+        libc::S_IFDIR => OverrideFileType::Directory,
+        libc::S_IFREG => OverrideFileType::File,
+        libc::S_IFLNK => OverrideFileType::Symlink,
+        libc::S_IFIFO => OverrideFileType::Pipe,
+        libc::S_IFSOCK => OverrideFileType::Socket,
+        libc::S_IFBLK => {
+            OverrideFileType::Block(stat.rdev_major, stat.rdev_minor)
+        }
+        libc::S_IFCHR => {
+            OverrideFileType::Char(stat.rdev_major, stat.rdev_minor)
+        }
+        _ => return Err(Errno(libc::EINVAL)),
+    };
+    Ok(file_type)
+}
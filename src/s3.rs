@@ -0,0 +1,282 @@
+//! Mapping between `user.*` EAs and the user metadata of S3-compatible object
+//! stores
+//!
+//! Object stores keep user metadata in `x-amz-meta-<key>` headers, whose keys
+//! are case-insensitive ASCII tokens and whose values are printable ASCII. The
+//! total size of the metadata is limited too, to 2 KiB on S3, measured as the
+//! sum of the lengths of the keys (without `x-amz-meta-`) and the values.
+//!
+//! EA names map to keys by dropping `user.`, the bytes other than lowercase
+//! ASCII letters, digits, `-`, `_` and `.` are percent-encoded with lowercase
+//! hexadecimal digits, e.g., `user.Foo` maps to `x-amz-meta-%46oo`. Values that
+//! are not printable ASCII, that have leading or trailing spaces (which HTTP
+//! strips), or that start with [`BASE64_MARKER`] are encoded in base64 and
+//! prefixed with [`BASE64_MARKER`]. Whatever can not be mapped is reported
+//! instead of failing the whole conversion.
+
+use crate::{base64, getxattr, listxattr, setxattr, Flags, Result};
+use std::{
+    ffi::{OsStr, OsString},
+    os::unix::ffi::{OsStrExt, OsStringExt},
+    path::Path,
+};
+
+/// Prefix of the user metadata headers.
+pub const HEADER_PREFIX: &str = "x-amz-meta-";
+
+/// Prefix of the values encoded in base64.
+pub const BASE64_MARKER: &str = "b64:";
+
+/// Namespace of the EAs that are mapped.
+const USER_PREFIX: &[u8] = b"user.";
+
+/// Size limits of the metadata of an object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Limits {
+    /// Maximum size of a single key and its value.
+    pub max_entry_size: usize,
+    /// Maximum total size of the keys and values.
+    pub max_total_size: usize,
+}
+
+impl Limits {
+    /// Limits of Amazon S3.
+    pub const S3: Limits = Limits {
+        max_entry_size: 2048,
+        max_total_size: 2048,
+    };
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits::S3
+    }
+}
+
+/// Reason an EA or a header could not be mapped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Issue {
+    /// The EA is not in the `user.` namespace.
+    NotUserAttr,
+    /// The EA name is `user.` alone, or the key is empty or not a valid
+    /// escaped name.
+    InvalidName,
+    /// The key and value exceed [`Limits::max_entry_size`].
+    EntryTooLarge,
+    /// The entry would make the metadata exceed [`Limits::max_total_size`].
+    TotalTooLarge,
+    /// The header is not a user metadata header.
+    NotMetadataHeader,
+    /// The value is marked as base64 but is not valid base64.
+    InvalidValue,
+    /// The key maps to the same EA as a previous header, since keys are
+    /// case-insensitive.
+    Duplicate,
+}
+
+/// Result of [`to_headers()`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Headers {
+    /// The headers, sorted by key.
+    pub headers: Vec<(String, String)>,
+    /// The EAs that could not be mapped.
+    pub rejected: Vec<(OsString, Issue)>,
+}
+
+/// Result of [`from_headers()`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Attrs {
+    /// The EAs, in the order of the headers.
+    pub attrs: Vec<(OsString, Vec<u8>)>,
+    /// The headers that could not be mapped.
+    pub rejected: Vec<(String, Issue)>,
+}
+
+fn is_literal(b: u8) -> bool {
+    matches!(b, b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.')
+}
+
+/// Converts the EA `name` into a metadata header key, including
+/// [`HEADER_PREFIX`].
+pub fn encode_key<N: AsRef<OsStr>>(
+    name: N,
+) -> std::result::Result<String, Issue> {
+    let name = name
+        .as_ref()
+        .as_bytes()
+        .strip_prefix(USER_PREFIX)
+        .ok_or(Issue::NotUserAttr)?;
+    if name.is_empty() {
+        return Err(Issue::InvalidName);
+    }
+
+    let mut key = String::from(HEADER_PREFIX);
+    for &b in name {
+        if is_literal(b) {
+            key.push(b as char);
+        } else {
+            key.push_str(&format!("%{:02x}", b));
+        }
+    }
+    Ok(key)
+}
+
+/// Converts the metadata header key `key` into an EA name, `key` is matched
+/// case-insensitively.
+pub fn decode_key(key: &str) -> std::result::Result<OsString, Issue> {
+    let key = key.to_ascii_lowercase();
+    let key = key
+        .strip_prefix(HEADER_PREFIX)
+        .ok_or(Issue::NotMetadataHeader)?
+        .as_bytes();
+    if key.is_empty() {
+        return Err(Issue::InvalidName);
+    }
+
+    let mut name = USER_PREFIX.to_vec();
+    let mut i = 0;
+    while i < key.len() {
+        match key[i] {
+            b'%' => {
+                let byte = key
+                    .get(i + 1..i + 3)
+                    .and_then(|hex| std::str::from_utf8(hex).ok())
+                    .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or(Issue::InvalidName)?;
+                name.push(byte);
+                i += 3;
+            }
+            b @ 0x21..=0x7e => {
+                name.push(b);
+                i += 1;
+            }
+            _ => return Err(Issue::InvalidName),
+        }
+    }
+    Ok(OsString::from_vec(name))
+}
+
+/// Converts the EA value `value` into a header value.
+pub fn encode_value(value: &[u8]) -> String {
+    let printable = value.iter().all(|b| (0x20..=0x7e).contains(b));
+    let padded = value.first() == Some(&b' ') || value.last() == Some(&b' ');
+    if printable && !padded && !value.starts_with(BASE64_MARKER.as_bytes()) {
+        // printable ASCII is valid UTF-8
+        String::from_utf8(value.to_vec()).unwrap()
+    } else {
+        format!("{}{}", BASE64_MARKER, base64::encode(value))
+    }
+}
+
+/// Converts the header value `value` into an EA value.
+pub fn decode_value(value: &str) -> std::result::Result<Vec<u8>, Issue> {
+    match value.strip_prefix(BASE64_MARKER) {
+        Some(encoded) => base64::decode(encoded).ok_or(Issue::InvalidValue),
+        None => Ok(value.as_bytes().to_vec()),
+    }
+}
+
+/// Converts the EAs `attrs` into metadata headers within `limits`.
+///
+/// The EAs are processed sorted by name, and an EA that would exceed the
+/// total size is skipped, while the following ones may still fit.
+pub fn to_headers<I, N, V>(attrs: I, limits: Limits) -> Headers
+where
+    I: IntoIterator<Item = (N, V)>,
+    N: AsRef<OsStr>,
+    V: AsRef<[u8]>,
+{
+    let mut attrs = attrs
+        .into_iter()
+        .map(|(name, value)| (name.as_ref().to_owned(), value))
+        .collect::<Vec<_>>();
+    attrs.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut res = Headers::default();
+    let mut total = 0;
+    for (name, value) in attrs {
+        let key = match encode_key(&name) {
+            Ok(key) => key,
+            Err(issue) => {
+                res.rejected.push((name, issue));
+                continue;
+            }
+        };
+        let value = encode_value(value.as_ref());
+
+        let size = key.len() - HEADER_PREFIX.len() + value.len();
+        if size > limits.max_entry_size {
+            res.rejected.push((name, Issue::EntryTooLarge));
+        } else if total + size > limits.max_total_size {
+            res.rejected.push((name, Issue::TotalTooLarge));
+        } else {
+            total += size;
+            res.headers.push((key, value));
+        }
+    }
+
+    res
+}
+
+/// Converts the headers `headers` into EAs, headers other than user metadata
+/// are reported as [`Issue::NotMetadataHeader`].
+pub fn from_headers<I, K, V>(headers: I) -> Attrs
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    let mut res = Attrs::default();
+    for (key, value) in headers {
+        let key = key.as_ref();
+        let attr = decode_key(key).and_then(|name| {
+            if res.attrs.iter().any(|(n, _)| *n == name) {
+                return Err(Issue::Duplicate);
+            }
+            Ok((name, decode_value(value.as_ref())?))
+        });
+        match attr {
+            Ok(attr) => res.attrs.push(attr),
+            Err(issue) => res.rejected.push((key.to_owned(), issue)),
+        }
+    }
+
+    res
+}
+
+/// Reads the EAs of `path` and converts them into metadata headers, see
+/// [`to_headers()`]. If `path` is a symbolic link, it will be dereferenced.
+pub fn read_headers<P: AsRef<Path>>(
+    path: P,
+    limits: Limits,
+) -> Result<Headers> {
+    let path = path.as_ref();
+    let mut attrs = Vec::new();
+    for name in listxattr(path)? {
+        let value = getxattr(path, &name)?;
+        attrs.push((name, value));
+    }
+
+    Ok(to_headers(attrs, limits))
+}
+
+/// Converts the headers `headers` into EAs and sets them on `path`, see
+/// [`from_headers()`]. If `path` is a symbolic link, it will be dereferenced.
+///
+/// Returns the mapping, the EAs of `path` that have no header are left alone.
+pub fn write_headers<P, I, K, V>(path: P, headers: I) -> Result<Attrs>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    let path = path.as_ref();
+    let res = from_headers(headers);
+    for (name, value) in res.attrs.iter() {
+        setxattr(path, name, value, Flags::empty())?;
+    }
+
+    Ok(res)
+}
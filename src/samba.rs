@@ -0,0 +1,1050 @@
+//! Codecs for the EAs Samba uses to store Windows metadata
+//!
+//! * `user.DOSATTRIB` stores the DOS attributes (read-only, hidden, archive,
+//!   ...) and the creation time of a file as an NDR-encoded `xattr_DOSATTRIB`.
+//! * `security.NTACL` stores the NT security descriptor of a file (written by
+//!   the `acl_xattr` VFS module) as an NDR-encoded `xattr_NTACL`, along with a
+//!   hash that allows Samba to detect changes made to the file mode or POSIX
+//!   ACL behind its back.
+//!
+//! Both formats are defined in `librpc/idl/xattr.idl` of the Samba source.
+
+use crate::{
+    digest::{Algorithm, Hasher},
+    fgetxattr, fsetxattr, getxattr, setxattr, Flags, Result,
+};
+use bitflags::bitflags;
+use errno::Errno;
+use std::{
+    fmt,
+    os::unix::io::RawFd,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Name of the EA used to store the DOS attributes.
+pub const XATTR_NAME_DOSATTRIB: &str = "user.DOSATTRIB";
+
+/// Name of the EA used to store the NT security descriptor.
+pub const XATTR_NAME_NTACL: &str = "security.NTACL";
+
+/// Size of the hashes stored in `security.NTACL` version 3 and 4.
+pub const XATTR_SD_HASH_SIZE: usize = 64;
+
+/// `security.NTACL` hash type: no hash.
+pub const XATTR_SD_HASH_TYPE_NONE: u16 = 0;
+
+/// `security.NTACL` hash type: SHA-256, zero-padded to
+/// [`XATTR_SD_HASH_SIZE`] bytes.
+pub const XATTR_SD_HASH_TYPE_SHA256: u16 = 1;
+
+/// Number of 100-nanosecond intervals between 1601-01-01 and 1970-01-01.
+const NTTIME_EPOCH_OFFSET: u64 = 116_444_736_000_000_000;
+
+/// A Windows `FILETIME`: the number of 100-nanosecond intervals since
+/// 1601-01-01 00:00:00 UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct NtTime(pub u64);
+
+impl NtTime {
+    /// Converts a `SystemTime` to an `NtTime`, saturating at the bounds of
+    /// the `NtTime` range.
+    pub fn from_system_time(time: SystemTime) -> Self {
+        let ticks = |d: Duration| {
+            d.as_secs()
+                .saturating_mul(10_000_000)
+                .saturating_add(u64::from(d.subsec_nanos() / 100))
+        };
+        match time.duration_since(UNIX_EPOCH) {
+            Ok(d) => NtTime(NTTIME_EPOCH_OFFSET.saturating_add(ticks(d))),
+            Err(e) => {
+                NtTime(NTTIME_EPOCH_OFFSET.saturating_sub(ticks(e.duration())))
+            }
+        }
+    }
+
+    /// Converts this `NtTime` to a `SystemTime`.
+    pub fn to_system_time(self) -> SystemTime {
+        let ticks = |t: u64| {
+            Duration::new(t / 10_000_000, (t % 10_000_000) as u32 * 100)
+        };
+        if self.0 >= NTTIME_EPOCH_OFFSET {
+            UNIX_EPOCH + ticks(self.0 - NTTIME_EPOCH_OFFSET)
+        } else {
+            UNIX_EPOCH - ticks(NTTIME_EPOCH_OFFSET - self.0)
+        }
+    }
+}
+
+bitflags! {
+    /// DOS file attributes (`FILE_ATTRIBUTE_*`)
+    pub struct FileAttributes: u32 {
+        /// The file is read-only.
+        const READONLY = 0x0000_0001;
+        /// The file is hidden.
+        const HIDDEN = 0x0000_0002;
+        /// The file is used by the operating system.
+        const SYSTEM = 0x0000_0004;
+        /// The file is a volume label.
+        const VOLUME = 0x0000_0008;
+        /// The file is a directory.
+        const DIRECTORY = 0x0000_0010;
+        /// The file should be archived.
+        const ARCHIVE = 0x0000_0020;
+        /// Reserved.
+        const DEVICE = 0x0000_0040;
+        /// The file has no other attributes.
+        const NORMAL = 0x0000_0080;
+        /// The file is temporary.
+        const TEMPORARY = 0x0000_0100;
+        /// The file is sparse.
+        const SPARSE_FILE = 0x0000_0200;
+        /// The file is a reparse point.
+        const REPARSE_POINT = 0x0000_0400;
+        /// The file is compressed.
+        const COMPRESSED = 0x0000_0800;
+        /// The data of the file is not immediately available.
+        const OFFLINE = 0x0000_1000;
+        /// The file is not to be indexed.
+        const NOT_CONTENT_INDEXED = 0x0000_2000;
+        /// The file is encrypted.
+        const ENCRYPTED = 0x0000_4000;
+        /// The file is an integrity stream.
+        const INTEGRITY_STREAM = 0x0000_8000;
+        /// The file is excluded from the data integrity scan.
+        const NO_SCRUB_DATA = 0x0002_0000;
+    }
+}
+
+bitflags! {
+    /// Fields of a `user.DOSATTRIB` version 3, 4 or 5 value that are valid
+    /// (`XATTR_DOSINFO_*`)
+    pub struct DosInfoValid: u32 {
+        /// `attrib` is valid.
+        const ATTRIB = 0x0000_0001;
+        /// `ea_size` is valid.
+        const EA_SIZE = 0x0000_0002;
+        /// `size` is valid.
+        const SIZE = 0x0000_0004;
+        /// `alloc_size` is valid.
+        const ALLOC_SIZE = 0x0000_0008;
+        /// `create_time` is valid.
+        const CREATE_TIME = 0x0000_0010;
+        /// `change_time` is valid.
+        const CHANGE_TIME = 0x0000_0020;
+        /// `itime` is valid.
+        const ITIME = 0x0000_0040;
+    }
+}
+
+/// Decoded `user.DOSATTRIB` value.
+///
+/// The DOS attributes are kept as a raw `u32` so that unknown bits survive a
+/// round trip, use [`DosInfo::attributes()`] to get them as
+/// [`FileAttributes`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DosInfo {
+    /// Only the attributes as a hexadecimal string, as written by Samba 3.0.
+    Legacy {
+        /// DOS attributes.
+        attrib: u32,
+    },
+    /// `xattr_DosInfoFFFFCompat`
+    Compat {
+        /// DOS attributes.
+        attrib: u32,
+    },
+    /// `xattr_DosInfo1`
+    V1 {
+        /// DOS attributes.
+        attrib: u32,
+        /// Size of the EAs.
+        ea_size: u32,
+        /// File size.
+        size: u64,
+        /// Allocation size.
+        alloc_size: u64,
+        /// Creation time.
+        create_time: NtTime,
+        /// Change time.
+        change_time: NtTime,
+    },
+    /// `xattr_DosInfo2Old`
+    V2 {
+        /// Flags, unused by Samba.
+        flags: u32,
+        /// DOS attributes.
+        attrib: u32,
+        /// Size of the EAs.
+        ea_size: u32,
+        /// File size.
+        size: u64,
+        /// Allocation size.
+        alloc_size: u64,
+        /// Creation time.
+        create_time: NtTime,
+        /// Change time.
+        change_time: NtTime,
+        /// Write time.
+        write_time: NtTime,
+        /// File name.
+        name: String,
+    },
+    /// `xattr_DosInfo3`
+    V3 {
+        /// Fields that are valid.
+        valid_flags: DosInfoValid,
+        /// DOS attributes.
+        attrib: u32,
+        /// Size of the EAs.
+        ea_size: u32,
+        /// File size.
+        size: u64,
+        /// Allocation size.
+        alloc_size: u64,
+        /// Creation time.
+        create_time: NtTime,
+        /// Change time.
+        change_time: NtTime,
+    },
+    /// `xattr_DosInfo4`
+    V4 {
+        /// Fields that are valid.
+        valid_flags: DosInfoValid,
+        /// DOS attributes.
+        attrib: u32,
+        /// Time used to build the file ID.
+        itime: NtTime,
+        /// Creation time.
+        create_time: NtTime,
+    },
+    /// `xattr_DosInfo5`, written by current Samba versions
+    V5 {
+        /// Fields that are valid.
+        valid_flags: DosInfoValid,
+        /// DOS attributes.
+        attrib: u32,
+        /// Creation time.
+        create_time: NtTime,
+    },
+}
+
+/// Cursor over an NDR (little-endian, 32-bit) buffer.
+struct NdrReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> NdrReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        NdrReader { bytes, offset: 0 }
+    }
+
+    fn align(&mut self, n: usize) -> Result<()> {
+        let aligned = (self.offset + n - 1) & !(n - 1);
+        if aligned > self.bytes.len() {
+            return Err(Errno(libc::EINVAL));
+        }
+        self.offset = aligned;
+        Ok(())
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .bytes
+            .get(self.offset..self.offset.saturating_add(n))
+            .ok_or(Errno(libc::EINVAL))?;
+        self.offset += n;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.bytes(N)?);
+        Ok(array)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        self.align(2)?;
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        self.align(4)?;
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    /// `udlong` and `NTTIME` are only 4-byte aligned.
+    fn u64(&mut self) -> Result<u64> {
+        self.align(4)?;
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    /// A NUL-terminated UTF-8 string without conformance information.
+    fn nul_terminated(&mut self) -> Result<String> {
+        let rest = &self.bytes[self.offset..];
+        let len = rest
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or(Errno(libc::EINVAL))?;
+        let s = std::str::from_utf8(&rest[..len])
+            .map_err(|_| Errno(libc::EINVAL))?
+            .to_owned();
+        self.offset += len + 1;
+        Ok(s)
+    }
+
+    /// A conformant varying NUL-terminated UTF-8 string.
+    fn string(&mut self) -> Result<String> {
+        let max_count = self.u32()?;
+        let offset = self.u32()?;
+        let actual_count = self.u32()?;
+        if offset != 0 || actual_count == 0 || actual_count > max_count {
+            return Err(Errno(libc::EINVAL));
+        }
+        let bytes = self.bytes(actual_count as usize)?;
+        let (nul, s) = bytes.split_last().ok_or(Errno(libc::EINVAL))?;
+        if *nul != 0 {
+            return Err(Errno(libc::EINVAL));
+        }
+        std::str::from_utf8(s)
+            .map(str::to_owned)
+            .map_err(|_| Errno(libc::EINVAL))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.offset == self.bytes.len()
+    }
+}
+
+/// Builder of an NDR (little-endian, 32-bit) buffer.
+#[derive(Default)]
+struct NdrWriter {
+    bytes: Vec<u8>,
+}
+
+impl NdrWriter {
+    fn align(&mut self, n: usize) {
+        let aligned = (self.bytes.len() + n - 1) & !(n - 1);
+        self.bytes.resize(aligned, 0);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.align(2);
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.align(4);
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.align(4);
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn nul_terminated(&mut self, s: &str) {
+        self.bytes.extend_from_slice(s.as_bytes());
+        self.bytes.push(0);
+    }
+
+    fn string(&mut self, s: &str) {
+        let count = s.len() as u32 + 1;
+        self.u32(count);
+        self.u32(0);
+        self.u32(count);
+        self.nul_terminated(s);
+    }
+}
+
+const DOSINFO_COMPAT: u16 = 0xFFFF;
+
+impl DosInfo {
+    /// Creates a version 5 value, the one written by current Samba versions.
+    pub fn new(attrib: FileAttributes, create_time: Option<NtTime>) -> Self {
+        let mut valid_flags = DosInfoValid::ATTRIB;
+        if create_time.is_some() {
+            valid_flags |= DosInfoValid::CREATE_TIME;
+        }
+
+        DosInfo::V5 {
+            valid_flags,
+            attrib: attrib.bits(),
+            create_time: create_time.unwrap_or_default(),
+        }
+    }
+
+    /// Returns the raw DOS attributes.
+    pub fn attrib(&self) -> u32 {
+        match self {
+            DosInfo::Legacy { attrib }
+            | DosInfo::Compat { attrib }
+            | DosInfo::V1 { attrib, .. }
+            | DosInfo::V2 { attrib, .. }
+            | DosInfo::V3 { attrib, .. }
+            | DosInfo::V4 { attrib, .. }
+            | DosInfo::V5 { attrib, .. } => *attrib,
+        }
+    }
+
+    /// Returns the DOS attributes, unknown bits are dropped.
+    pub fn attributes(&self) -> FileAttributes {
+        FileAttributes::from_bits_truncate(self.attrib())
+    }
+
+    /// Returns the creation time, if this value has a valid one.
+    pub fn create_time(&self) -> Option<NtTime> {
+        match self {
+            DosInfo::Legacy { .. } | DosInfo::Compat { .. } => None,
+            DosInfo::V1 { create_time, .. }
+            | DosInfo::V2 { create_time, .. } => Some(*create_time),
+            DosInfo::V3 {
+                valid_flags,
+                create_time,
+                ..
+            }
+            | DosInfo::V4 {
+                valid_flags,
+                create_time,
+                ..
+            }
+            | DosInfo::V5 {
+                valid_flags,
+                create_time,
+                ..
+            } => {
+                if valid_flags.contains(DosInfoValid::CREATE_TIME) {
+                    Some(*create_time)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Returns the `itime` (used by Samba to build file IDs), if this value has
+    /// a valid one.
+    pub fn itime(&self) -> Option<NtTime> {
+        match self {
+            DosInfo::V4 {
+                valid_flags, itime, ..
+            } if valid_flags.contains(DosInfoValid::ITIME) => Some(*itime),
+            _ => None,
+        }
+    }
+
+    /// Decodes a raw `user.DOSATTRIB` value.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = NdrReader::new(bytes);
+        // Samba 3.0 stored the hex string alone, maybe without the NUL
+        let parse_hex = |hex: &str| {
+            hex.strip_prefix("0x")
+                .or_else(|| hex.strip_prefix("0X"))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .ok_or(Errno(libc::EINVAL))
+        };
+        match reader.nul_terminated() {
+            Ok(hex) => {
+                let attrib = parse_hex(&hex)?;
+                if reader.is_empty() {
+                    return Ok(DosInfo::Legacy { attrib });
+                }
+            }
+            Err(_) => {
+                let hex = std::str::from_utf8(bytes)
+                    .map_err(|_| Errno(libc::EINVAL))?;
+                return Ok(DosInfo::Legacy {
+                    attrib: parse_hex(hex)?,
+                });
+            }
+        }
+
+        let version = reader.u16()?;
+        if reader.u16()? != version {
+            return Err(Errno(libc::EINVAL));
+        }
+        let info = match version {
+            DOSINFO_COMPAT => DosInfo::Compat {
+                attrib: reader.u32()?,
+            },
+            1 => DosInfo::V1 {
+                attrib: reader.u32()?,
+                ea_size: reader.u32()?,
+                size: reader.u64()?,
+                alloc_size: reader.u64()?,
+                create_time: NtTime(reader.u64()?),
+                change_time: NtTime(reader.u64()?),
+            },
+            2 => DosInfo::V2 {
+                flags: reader.u32()?,
+                attrib: reader.u32()?,
+                ea_size: reader.u32()?,
+                size: reader.u64()?,
+                alloc_size: reader.u64()?,
+                create_time: NtTime(reader.u64()?),
+                change_time: NtTime(reader.u64()?),
+                write_time: NtTime(reader.u64()?),
+                name: reader.nul_terminated()?,
+            },
+            3 => DosInfo::V3 {
+                valid_flags: DosInfoValid::from_bits_truncate(reader.u32()?),
+                attrib: reader.u32()?,
+                ea_size: reader.u32()?,
+                size: reader.u64()?,
+                alloc_size: reader.u64()?,
+                create_time: NtTime(reader.u64()?),
+                change_time: NtTime(reader.u64()?),
+            },
+            4 => DosInfo::V4 {
+                valid_flags: DosInfoValid::from_bits_truncate(reader.u32()?),
+                attrib: reader.u32()?,
+                itime: NtTime(reader.u64()?),
+                create_time: NtTime(reader.u64()?),
+            },
+            5 => DosInfo::V5 {
+                valid_flags: DosInfoValid::from_bits_truncate(reader.u32()?),
+                attrib: reader.u32()?,
+                create_time: NtTime(reader.u64()?),
+            },
+            _ => return Err(Errno(libc::EINVAL)),
+        };
+        if !reader.is_empty() {
+            return Err(Errno(libc::EINVAL));
+        }
+
+        Ok(info)
+    }
+
+    /// Encodes this value into a raw `user.DOSATTRIB` value.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = NdrWriter::default();
+        writer.nul_terminated(&format!("0x{:x}", self.attrib()));
+
+        // the legacy format is the attribute string alone
+        let version = match self {
+            DosInfo::Legacy { .. } => None,
+            DosInfo::Compat { .. } => Some(DOSINFO_COMPAT),
+            DosInfo::V1 { .. } => Some(1),
+            DosInfo::V2 { .. } => Some(2),
+            DosInfo::V3 { .. } => Some(3),
+            DosInfo::V4 { .. } => Some(4),
+            DosInfo::V5 { .. } => Some(5),
+        };
+        if let Some(version) = version {
+            writer.u16(version);
+            writer.u16(version);
+        }
+
+        match self {
+            DosInfo::Legacy { .. } => (),
+            DosInfo::Compat { attrib } => writer.u32(*attrib),
+            DosInfo::V1 {
+                attrib,
+                ea_size,
+                size,
+                alloc_size,
+                create_time,
+                change_time,
+            } => {
+                writer.u32(*attrib);
+                writer.u32(*ea_size);
+                writer.u64(*size);
+                writer.u64(*alloc_size);
+                writer.u64(create_time.0);
+                writer.u64(change_time.0);
+            }
+            DosInfo::V2 {
+                flags,
+                attrib,
+                ea_size,
+                size,
+                alloc_size,
+                create_time,
+                change_time,
+                write_time,
+                name,
+            } => {
+                writer.u32(*flags);
+                writer.u32(*attrib);
+                writer.u32(*ea_size);
+                writer.u64(*size);
+                writer.u64(*alloc_size);
+                writer.u64(create_time.0);
+                writer.u64(change_time.0);
+                writer.u64(write_time.0);
+                writer.nul_terminated(name);
+            }
+            DosInfo::V3 {
+                valid_flags,
+                attrib,
+                ea_size,
+                size,
+                alloc_size,
+                create_time,
+                change_time,
+            } => {
+                writer.u32(valid_flags.bits());
+                writer.u32(*attrib);
+                writer.u32(*ea_size);
+                writer.u64(*size);
+                writer.u64(*alloc_size);
+                writer.u64(create_time.0);
+                writer.u64(change_time.0);
+            }
+            DosInfo::V4 {
+                valid_flags,
+                attrib,
+                itime,
+                create_time,
+            } => {
+                writer.u32(valid_flags.bits());
+                writer.u32(*attrib);
+                writer.u64(itime.0);
+                writer.u64(create_time.0);
+            }
+            DosInfo::V5 {
+                valid_flags,
+                attrib,
+                create_time,
+            } => {
+                writer.u32(valid_flags.bits());
+                writer.u32(*attrib);
+                writer.u64(create_time.0);
+            }
+        }
+
+        writer.bytes
+    }
+}
+
+/// A security identifier, e.g., `S-1-5-32-544`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Sid {
+    /// Revision, always 1.
+    pub revision: u8,
+    /// Identifier authority.
+    pub authority: u64,
+    /// Sub-authorities.
+    pub sub_authorities: Vec<u32>,
+}
+
+impl Sid {
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let (revision, count) = match bytes {
+            [revision, count, ..] => (*revision, *count as usize),
+            _ => return Err(Errno(libc::EINVAL)),
+        };
+        let bytes = bytes.get(..8 + 4 * count).ok_or(Errno(libc::EINVAL))?;
+        let authority = bytes[2..8]
+            .iter()
+            .fold(0_u64, |acc, b| (acc << 8) | u64::from(*b));
+        let sub_authorities = bytes[8..]
+            .chunks_exact(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+
+        Ok(Sid {
+            revision,
+            authority,
+            sub_authorities,
+        })
+    }
+}
+
+impl fmt::Display for Sid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "S-{}-", self.revision)?;
+        if self.authority >= 1 << 32 {
+            write!(f, "0x{:012X}", self.authority)?;
+        } else {
+            write!(f, "{}", self.authority)?;
+        }
+        for sub_authority in self.sub_authorities.iter() {
+            write!(f, "-{}", sub_authority)?;
+        }
+        Ok(())
+    }
+}
+
+/// Size of the fixed part of a self-relative security descriptor.
+const SD_HEADER_SIZE: usize = 20;
+
+/// A self-relative NT security descriptor.
+///
+/// The descriptor is kept in its binary form, which is what Samba hashes,
+/// accessors decode the owner, the group and the control flags.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SecurityDescriptor {
+    bytes: Vec<u8>,
+}
+
+impl SecurityDescriptor {
+    /// Validates a self-relative security descriptor, `bytes` must not
+    /// contain anything after the descriptor.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if Self::len(bytes)? != bytes.len() {
+            return Err(Errno(libc::EINVAL));
+        }
+
+        Ok(SecurityDescriptor {
+            bytes: bytes.to_vec(),
+        })
+    }
+
+    /// Returns the length of the descriptor at the start of `bytes`.
+    fn len(bytes: &[u8]) -> Result<usize> {
+        let header = bytes.get(..SD_HEADER_SIZE).ok_or(Errno(libc::EINVAL))?;
+        if header[0] != 1 {
+            return Err(Errno(libc::EINVAL));
+        }
+        let offset = |i: usize| {
+            u32::from_le_bytes([
+                header[i],
+                header[i + 1],
+                header[i + 2],
+                header[i + 3],
+            ]) as usize
+        };
+
+        let mut len = SD_HEADER_SIZE;
+        // owner, group, SACL, DACL
+        for (i, is_sid) in [(4, true), (8, true), (12, false), (16, false)] {
+            let offset = offset(i);
+            if offset == 0 {
+                continue;
+            }
+            if offset < SD_HEADER_SIZE {
+                return Err(Errno(libc::EINVAL));
+            }
+            // the offsets come from the attribute, don't trust them blindly
+            let size = match offset
+                .checked_add(4)
+                .and_then(|end| bytes.get(offset..end))
+            {
+                Some(b) if is_sid => 8 + 4 * b[1] as usize,
+                Some(b) => u16::from_le_bytes([b[2], b[3]]) as usize,
+                None => return Err(Errno(libc::EINVAL)),
+            };
+            let end = offset.checked_add(size).ok_or(Errno(libc::EINVAL))?;
+            if bytes.len() < end {
+                return Err(Errno(libc::EINVAL));
+            }
+            len = len.max(end);
+        }
+
+        Ok(len)
+    }
+
+    fn sid(&self, i: usize) -> Option<Sid> {
+        let offset = u32::from_le_bytes([
+            self.bytes[i],
+            self.bytes[i + 1],
+            self.bytes[i + 2],
+            self.bytes[i + 3],
+        ]) as usize;
+        if offset == 0 {
+            None
+        } else {
+            // validated in `from_bytes()`
+            Sid::from_bytes(&self.bytes[offset..]).ok()
+        }
+    }
+
+    /// Returns the binary form of this descriptor.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns the control flags (`SEC_DESC_*`).
+    pub fn control(&self) -> u16 {
+        u16::from_le_bytes([self.bytes[2], self.bytes[3]])
+    }
+
+    /// Returns the owner.
+    pub fn owner(&self) -> Option<Sid> {
+        self.sid(4)
+    }
+
+    /// Returns the group.
+    pub fn group(&self) -> Option<Sid> {
+        self.sid(8)
+    }
+
+    /// Computes the hash Samba stores with the descriptor for
+    /// [`XATTR_SD_HASH_TYPE_SHA256`].
+    pub fn sha256(&self) -> [u8; XATTR_SD_HASH_SIZE] {
+        let mut hasher = Hasher::new(Algorithm::Sha256);
+        hasher.update(&self.bytes);
+        let mut hash = [0; XATTR_SD_HASH_SIZE];
+        let digest = hasher.finish();
+        hash[..digest.len()].copy_from_slice(&digest);
+        hash
+    }
+}
+
+/// Decoded `security.NTACL` value.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum NtAcl {
+    /// Version 1: the descriptor alone.
+    V1 {
+        /// The security descriptor.
+        sd: SecurityDescriptor,
+    },
+    /// Version 2: the descriptor and its MD4 hash (never used by Samba).
+    V2 {
+        /// The security descriptor.
+        sd: SecurityDescriptor,
+        /// Hash of the descriptor.
+        hash: [u8; 16],
+    },
+    /// Version 3: the descriptor and its hash.
+    V3 {
+        /// The security descriptor.
+        sd: SecurityDescriptor,
+        /// Type of `hash`, e.g., [`XATTR_SD_HASH_TYPE_SHA256`].
+        hash_type: u16,
+        /// Hash of the descriptor.
+        hash: [u8; XATTR_SD_HASH_SIZE],
+    },
+    /// Version 4: the descriptor, its hash, and the hash of the POSIX ACL.
+    V4 {
+        /// The security descriptor.
+        sd: SecurityDescriptor,
+        /// Type of `hash` and `sys_acl_hash`.
+        hash_type: u16,
+        /// Hash of the descriptor.
+        hash: [u8; XATTR_SD_HASH_SIZE],
+        /// Description of what created the hash, e.g., `posix_acl`.
+        description: Option<String>,
+        /// When the hash was created.
+        time: NtTime,
+        /// Hash of the POSIX ACL of the file.
+        sys_acl_hash: [u8; XATTR_SD_HASH_SIZE],
+    },
+}
+
+/// Referent IDs of the NDR pointers, any non-zero value would do.
+const NDR_REFERENT_ID: u32 = 0x0002_0000;
+
+impl NtAcl {
+    /// Creates a version 3 value with the SHA-256 hash of `sd`, as written by
+    /// the `acl_xattr` VFS module for a descriptor it doesn't need to track
+    /// the POSIX ACL of.
+    pub fn new(sd: SecurityDescriptor) -> Self {
+        NtAcl::V3 {
+            hash: sd.sha256(),
+            sd,
+            hash_type: XATTR_SD_HASH_TYPE_SHA256,
+        }
+    }
+
+    /// Returns the security descriptor.
+    pub fn sd(&self) -> &SecurityDescriptor {
+        match self {
+            NtAcl::V1 { sd }
+            | NtAcl::V2 { sd, .. }
+            | NtAcl::V3 { sd, .. }
+            | NtAcl::V4 { sd, .. } => sd,
+        }
+    }
+
+    /// Returns true if the stored hash of the descriptor is a SHA-256 hash
+    /// that matches the descriptor. Versions without a hash never match.
+    pub fn hash_matches(&self) -> bool {
+        match self {
+            NtAcl::V3 {
+                sd,
+                hash_type,
+                hash,
+            }
+            | NtAcl::V4 {
+                sd,
+                hash_type,
+                hash,
+                ..
+            } => {
+                *hash_type == XATTR_SD_HASH_TYPE_SHA256 && sd.sha256() == *hash
+            }
+            _ => false,
+        }
+    }
+
+    /// Decodes a raw `security.NTACL` value.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = NdrReader::new(bytes);
+        let version = reader.u16()?;
+        if reader.u16()? != version || reader.u32()? == 0 {
+            return Err(Errno(libc::EINVAL));
+        }
+        let sd = |reader: &mut NdrReader| -> Result<SecurityDescriptor> {
+            reader.align(4)?;
+            let rest = &bytes[reader.offset..];
+            let len = SecurityDescriptor::len(rest)?;
+            reader.offset += len;
+            SecurityDescriptor::from_bytes(&rest[..len])
+        };
+
+        let acl = match version {
+            1 => NtAcl::V1 {
+                sd: sd(&mut reader)?,
+            },
+            2..=4 => {
+                // the hash structure is pointed to by the union, and points to
+                // the descriptor
+                if reader.u32()? == 0 {
+                    return Err(Errno(libc::EINVAL));
+                }
+                match version {
+                    2 => {
+                        let hash = reader.array()?;
+                        NtAcl::V2 {
+                            sd: sd(&mut reader)?,
+                            hash,
+                        }
+                    }
+                    3 => {
+                        let hash_type = reader.u16()?;
+                        let hash = reader.array()?;
+                        NtAcl::V3 {
+                            sd: sd(&mut reader)?,
+                            hash_type,
+                            hash,
+                        }
+                    }
+                    _ => {
+                        let hash_type = reader.u16()?;
+                        let hash = reader.array()?;
+                        let has_description = reader.u32()? != 0;
+                        let time = NtTime(reader.u64()?);
+                        let sys_acl_hash = reader.array()?;
+                        let sd = sd(&mut reader)?;
+                        let description = if has_description {
+                            Some(reader.string()?)
+                        } else {
+                            None
+                        };
+                        NtAcl::V4 {
+                            sd,
+                            hash_type,
+                            hash,
+                            description,
+                            time,
+                            sys_acl_hash,
+                        }
+                    }
+                }
+            }
+            _ => return Err(Errno(libc::EINVAL)),
+        };
+        if !reader.is_empty() {
+            return Err(Errno(libc::EINVAL));
+        }
+
+        Ok(acl)
+    }
+
+    /// Encodes this value into a raw `security.NTACL` value.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = NdrWriter::default();
+        let version = match self {
+            NtAcl::V1 { .. } => 1,
+            NtAcl::V2 { .. } => 2,
+            NtAcl::V3 { .. } => 3,
+            NtAcl::V4 { .. } => 4,
+        };
+        writer.u16(version);
+        writer.u16(version);
+        writer.u32(NDR_REFERENT_ID);
+        if version != 1 {
+            writer.u32(NDR_REFERENT_ID + 4);
+        }
+
+        match self {
+            NtAcl::V1 { .. } => (),
+            NtAcl::V2 { hash, .. } => writer.bytes.extend_from_slice(hash),
+            NtAcl::V3 {
+                hash_type, hash, ..
+            } => {
+                writer.u16(*hash_type);
+                writer.bytes.extend_from_slice(hash);
+            }
+            NtAcl::V4 {
+                hash_type,
+                hash,
+                description,
+                time,
+                sys_acl_hash,
+                ..
+            } => {
+                writer.u16(*hash_type);
+                writer.bytes.extend_from_slice(hash);
+                writer.u32(match description {
+                    Some(_) => NDR_REFERENT_ID + 8,
+                    None => 0,
+                });
+                writer.u64(time.0);
+                writer.bytes.extend_from_slice(sys_acl_hash);
+            }
+        }
+
+        writer.align(4);
+        writer.bytes.extend_from_slice(self.sd().as_bytes());
+        if let NtAcl::V4 {
+            description: Some(description),
+            ..
+        } = self
+        {
+            writer.string(description);
+        }
+
+        writer.bytes
+    }
+}
+
+/// Retrieves the DOS attributes of `path`. If `path` is a symbolic link, it
+/// will be dereferenced.
+pub fn get_dosattrib<P: AsRef<Path>>(path: P) -> Result<DosInfo> {
+    DosInfo::from_bytes(&getxattr(path, XATTR_NAME_DOSATTRIB)?)
+}
+
+/// Retrieves the DOS attributes of the file specified by the open file
+/// descriptor `fd`.
+pub fn fget_dosattrib(fd: RawFd) -> Result<DosInfo> {
+    DosInfo::from_bytes(&fgetxattr(fd, XATTR_NAME_DOSATTRIB)?)
+}
+
+/// Sets the DOS attributes of `path`. If `path` is a symbolic link, it will be
+/// dereferenced.
+pub fn set_dosattrib<P: AsRef<Path>>(path: P, info: &DosInfo) -> Result<()> {
+    setxattr(path, XATTR_NAME_DOSATTRIB, info.to_bytes(), Flags::empty())
+}
+
+/// Sets the DOS attributes of the file specified by the open file descriptor
+/// `fd`.
+pub fn fset_dosattrib(fd: RawFd, info: &DosInfo) -> Result<()> {
+    fsetxattr(fd, XATTR_NAME_DOSATTRIB, info.to_bytes(), Flags::empty())
+}
+
+/// Retrieves the NT ACL of `path`. If `path` is a symbolic link, it will be
+/// dereferenced.
+pub fn get_ntacl<P: AsRef<Path>>(path: P) -> Result<NtAcl> {
+    NtAcl::from_bytes(&getxattr(path, XATTR_NAME_NTACL)?)
+}
+
+/// Retrieves the NT ACL of the file specified by the open file descriptor
+/// `fd`.
+pub fn fget_ntacl(fd: RawFd) -> Result<NtAcl> {
+    NtAcl::from_bytes(&fgetxattr(fd, XATTR_NAME_NTACL)?)
+}
+
+/// Sets the NT ACL of `path`. If `path` is a symbolic link, it will be
+/// dereferenced.
+pub fn set_ntacl<P: AsRef<Path>>(path: P, acl: &NtAcl) -> Result<()> {
+    setxattr(path, XATTR_NAME_NTACL, acl.to_bytes(), Flags::empty())
+}
+
+/// Sets the NT ACL of the file specified by the open file descriptor `fd`.
+pub fn fset_ntacl(fd: RawFd, acl: &NtAcl) -> Result<()> {
+    fsetxattr(fd, XATTR_NAME_NTACL, acl.to_bytes(), Flags::empty())
+}
@@ -0,0 +1,436 @@
+//! `restorecon`-style labeling driven by a `file_contexts` specification
+//!
+//! A `file_contexts` file consists of lines like:
+//!
+//! ```text
+//! # regex                 [file type]  context
+//! /etc(/.*)?                           system_u:object_r:etc_t:s0
+//! /etc/shadow.*           --           system_u:object_r:shadow_t:s0
+//! /run                    -d           system_u:object_r:var_run_t:s0
+//! /proc(/.*)?                          <<none>>
+//! ```
+//!
+//! Regular expressions are POSIX extended regular expressions that have to
+//! match the whole path. When multiple specifications match a path, the most
+//! specific one wins, using the same ordering as the `fc_sort` tool of the
+//! reference policy: specifications without regex meta characters are more
+//! specific than those with, then a longer fixed prefix (stem), a longer
+//! expression and an explicit file type make a specification more specific.
+//! Among equally specific specifications, the last one wins.
+//!
+//! Since this module does not rely on `libselinux`, it works on hosts that do
+//! not have SELinux enabled, e.g., when building a root file system image.
+
+use super::{context_bytes_eq, SecurityContext, XATTR_NAME_SELINUX};
+use crate::{io_errno, lgetxattr, lsetxattr, Flags, Result};
+use bitflags::bitflags;
+use errno::Errno;
+use std::{
+    ffi::CString,
+    fmt, fs, io,
+    mem::MaybeUninit,
+    os::unix::{ffi::OsStrExt, fs::FileTypeExt},
+    path::{Path, PathBuf},
+    ptr::null_mut,
+    str::FromStr,
+};
+
+bitflags! {
+    /// `flags` used by [`restorecon()`]
+    pub struct RestoreconFlags: u32 {
+        /// Descend into directories.
+        const RECURSE = 0x1;
+        /// Do not change any labels, only report the differences.
+        const NOCHANGE = 0x2;
+    }
+}
+
+/// File type qualifier of a specification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileType {
+    /// Regular file, `--`
+    Regular,
+    /// Directory, `-d`
+    Directory,
+    /// Character device, `-c`
+    CharDevice,
+    /// Block device, `-b`
+    BlockDevice,
+    /// Socket, `-s`
+    Socket,
+    /// Symbolic link, `-l`
+    Symlink,
+    /// Named pipe, `-p`
+    Fifo,
+}
+
+impl FileType {
+    /// Returns the type of the file described by `file_type`.
+    pub fn from_std(file_type: fs::FileType) -> Self {
+        if file_type.is_dir() {
+            FileType::Directory
+        } else if file_type.is_symlink() {
+            FileType::Symlink
+        } else if file_type.is_char_device() {
+            FileType::CharDevice
+        } else if file_type.is_block_device() {
+            FileType::BlockDevice
+        } else if file_type.is_socket() {
+            FileType::Socket
+        } else if file_type.is_fifo() {
+            FileType::Fifo
+        } else {
+            FileType::Regular
+        }
+    }
+}
+
+impl FromStr for FileType {
+    type Err = Errno;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "--" => Ok(FileType::Regular),
+            "-d" => Ok(FileType::Directory),
+            "-c" => Ok(FileType::CharDevice),
+            "-b" => Ok(FileType::BlockDevice),
+            "-s" => Ok(FileType::Socket),
+            "-l" => Ok(FileType::Symlink),
+            "-p" => Ok(FileType::Fifo),
+            _ => Err(Errno(libc::EINVAL)),
+        }
+    }
+}
+
+impl fmt::Display for FileType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let qualifier = match self {
+            FileType::Regular => "--",
+            FileType::Directory => "-d",
+            FileType::CharDevice => "-c",
+            FileType::BlockDevice => "-b",
+            FileType::Socket => "-s",
+            FileType::Symlink => "-l",
+            FileType::Fifo => "-p",
+        };
+        f.write_str(qualifier)
+    }
+}
+
+/// A compiled POSIX extended regular expression, anchored at both ends.
+struct Regex(Box<libc::regex_t>);
+
+impl Regex {
+    fn new(pattern: &str) -> Result<Self> {
+        let pattern = match CString::new(format!("^({})$", pattern)) {
+            Ok(p) => p,
+            _ => return Err(Errno(libc::EINVAL)),
+        };
+        let mut regex = Box::new(MaybeUninit::<libc::regex_t>::uninit());
+
+        let res = unsafe {
+            libc::regcomp(
+                regex.as_mut_ptr(),
+                pattern.as_ptr(),
+                libc::REG_EXTENDED | libc::REG_NOSUB,
+            )
+        };
+
+        match res {
+            0 => {
+                Ok(Regex(unsafe { Box::from_raw(Box::into_raw(regex).cast()) }))
+            }
+            _ => Err(Errno(libc::EINVAL)),
+        }
+    }
+
+    fn is_match(&self, s: &CString) -> bool {
+        let res =
+            unsafe { libc::regexec(&*self.0, s.as_ptr(), 0, null_mut(), 0) };
+        res == 0
+    }
+}
+
+impl Drop for Regex {
+    fn drop(&mut self) {
+        unsafe { libc::regfree(&mut *self.0) };
+    }
+}
+
+/// A single line of a `file_contexts` file.
+pub struct FileContextSpec {
+    /// The regular expression, as written.
+    pub regex: String,
+    /// The file type qualifier, `None` matches every file type.
+    pub file_type: Option<FileType>,
+    /// The label, `None` for `<<none>>`, which means the matched files should
+    /// be left alone.
+    pub context: Option<SecurityContext>,
+    compiled: Regex,
+    has_meta: bool,
+    stem_len: usize,
+    str_len: usize,
+}
+
+impl fmt::Debug for FileContextSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileContextSpec")
+            .field("regex", &self.regex)
+            .field("file_type", &self.file_type)
+            .field("context", &self.context)
+            .finish()
+    }
+}
+
+impl FileContextSpec {
+    fn new(
+        regex: &str,
+        file_type: Option<FileType>,
+        context: Option<SecurityContext>,
+    ) -> Result<Self> {
+        let compiled = Regex::new(regex)?;
+        let mut has_meta = false;
+        let mut stem_len = 0;
+        let mut str_len = 0;
+        let mut bytes = regex.bytes();
+
+        while let Some(byte) = bytes.next() {
+            match byte {
+                // An escaped character counts as one literal character.
+                b'\\' => {
+                    bytes.next();
+                }
+                b'.' | b'^' | b'$' | b'?' | b'*' | b'+' | b'|' | b'['
+                | b'(' | b'{' => has_meta = true,
+                _ => {}
+            }
+            str_len += 1;
+            if !has_meta {
+                stem_len += 1;
+            }
+        }
+
+        Ok(FileContextSpec {
+            regex: regex.to_owned(),
+            file_type,
+            context,
+            compiled,
+            has_meta,
+            stem_len,
+            str_len,
+        })
+    }
+
+    /// Returns the key that orders specifications from the least specific to
+    /// the most specific.
+    fn specificity(&self) -> (bool, usize, usize, bool) {
+        (
+            !self.has_meta,
+            self.stem_len,
+            self.str_len,
+            self.file_type.is_some(),
+        )
+    }
+}
+
+/// A parsed `file_contexts` specification.
+#[derive(Debug)]
+pub struct FileContexts {
+    /// Specifications sorted from the least specific to the most specific.
+    specs: Vec<FileContextSpec>,
+}
+
+impl FileContexts {
+    /// Parses a `file_contexts` specification.
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut specs = Vec::new();
+
+        for line in s.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields = line.split_whitespace().collect::<Vec<&str>>();
+            let (regex, file_type, context) = match fields.as_slice() {
+                [regex, context] => (*regex, None, *context),
+                [regex, file_type, context] => {
+                    (*regex, Some(file_type.parse()?), *context)
+                }
+                _ => return Err(Errno(libc::EINVAL)),
+            };
+            let context = match context {
+                "<<none>>" => None,
+                context => Some(context.parse()?),
+            };
+
+            specs.push(FileContextSpec::new(regex, file_type, context)?);
+        }
+
+        // a stable sort, so the last one wins among equally specific ones
+        specs.sort_by_key(FileContextSpec::specificity);
+
+        Ok(FileContexts { specs })
+    }
+
+    /// Reads and parses the `file_contexts` file at `path`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let contents = fs::read_to_string(path).map_err(io_errno)?;
+        Self::parse(&contents)
+    }
+
+    /// Returns the specifications, sorted from the least specific to the most
+    /// specific.
+    pub fn specs(&self) -> &[FileContextSpec] {
+        &self.specs
+    }
+
+    /// Returns the specification that applies to the file at `path` (as it
+    /// would be seen on the target system, e.g., `/etc/passwd`) of type
+    /// `file_type`, if any.
+    pub fn lookup<P: AsRef<Path>>(
+        &self,
+        path: P,
+        file_type: FileType,
+    ) -> Option<&FileContextSpec> {
+        let path = CString::new(path.as_ref().as_os_str().as_bytes()).ok()?;
+
+        self.specs.iter().rev().find(|spec| {
+            let type_matches = match spec.file_type {
+                Some(ft) => ft == file_type,
+                None => true,
+            };
+            type_matches && spec.compiled.is_match(&path)
+        })
+    }
+
+    /// Returns the label that the file at `path` of type `file_type` should
+    /// have, `None` if no specification applies or the file should be left
+    /// alone (`<<none>>`).
+    pub fn expected_label<P: AsRef<Path>>(
+        &self,
+        path: P,
+        file_type: FileType,
+    ) -> Option<&SecurityContext> {
+        self.lookup(path, file_type)
+            .and_then(|spec| spec.context.as_ref())
+    }
+}
+
+/// A file whose label differs from the one expected by `file_contexts`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relabel {
+    /// Path of the file on this host.
+    pub path: PathBuf,
+    /// The raw current label, `None` if the file is not labeled.
+    pub current: Option<Vec<u8>>,
+    /// The expected label.
+    pub expected: SecurityContext,
+}
+
+fn restorecon_one(
+    path: &Path,
+    target_path: &Path,
+    file_type: FileType,
+    contexts: &FileContexts,
+    flags: RestoreconFlags,
+    relabels: &mut Vec<Relabel>,
+) -> Result<()> {
+    let expected = match contexts.expected_label(target_path, file_type) {
+        Some(expected) => expected,
+        None => return Ok(()),
+    };
+    let current = match lgetxattr(path, XATTR_NAME_SELINUX) {
+        Ok(current) => Some(current),
+        Err(Errno(libc::ENODATA)) => None,
+        Err(e) => return Err(e),
+    };
+    let expected_bytes = expected.to_bytes();
+
+    if let Some(current) = current.as_ref() {
+        if context_bytes_eq(current, &expected_bytes) {
+            return Ok(());
+        }
+    }
+
+    if !flags.contains(RestoreconFlags::NOCHANGE) {
+        lsetxattr(path, XATTR_NAME_SELINUX, &expected_bytes, Flags::empty())?;
+    }
+    relabels.push(Relabel {
+        path: path.to_owned(),
+        current,
+        expected: expected.clone(),
+    });
+
+    Ok(())
+}
+
+fn restorecon_tree(
+    dir: &Path,
+    target_dir: &Path,
+    contexts: &FileContexts,
+    flags: RestoreconFlags,
+    relabels: &mut Vec<Relabel>,
+) -> Result<()> {
+    let mut entries = fs::read_dir(dir)
+        .map_err(io_errno)?
+        .collect::<io::Result<Vec<fs::DirEntry>>>()
+        .map_err(io_errno)?;
+    entries.sort_by_key(fs::DirEntry::file_name);
+
+    for entry in entries {
+        let path = entry.path();
+        let target_path = target_dir.join(entry.file_name());
+        let file_type =
+            FileType::from_std(entry.file_type().map_err(io_errno)?);
+
+        restorecon_one(
+            &path,
+            &target_path,
+            file_type,
+            contexts,
+            flags,
+            relabels,
+        )?;
+        if file_type == FileType::Directory {
+            restorecon_tree(&path, &target_path, contexts, flags, relabels)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Labels the tree rooted at `root` according to `contexts`, `root` is treated
+/// as `/` of the target system, and symbolic links are never followed.
+///
+/// Returns the files whose labels differ from the expected ones, with
+/// [`RestoreconFlags::NOCHANGE`], these files are only reported and left
+/// untouched, which does not require any privilege.
+pub fn restorecon<P: AsRef<Path>>(
+    root: P,
+    contexts: &FileContexts,
+    flags: RestoreconFlags,
+) -> Result<Vec<Relabel>> {
+    let root = root.as_ref();
+    let target_root = Path::new("/");
+    let file_type = FileType::from_std(
+        fs::symlink_metadata(root).map_err(io_errno)?.file_type(),
+    );
+    let mut relabels = Vec::new();
+
+    restorecon_one(
+        root,
+        target_root,
+        file_type,
+        contexts,
+        flags,
+        &mut relabels,
+    )?;
+    if flags.contains(RestoreconFlags::RECURSE)
+        && file_type == FileType::Directory
+    {
+        restorecon_tree(root, target_root, contexts, flags, &mut relabels)?;
+    }
+
+    Ok(relabels)
+}
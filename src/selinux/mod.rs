@@ -0,0 +1,272 @@
+//! Helpers for the SELinux label stored in the `security.selinux` EA
+//!
+//! The kernel and `libselinux` store a security context as a NUL-terminated
+//! string, e.g. `"system_u:object_r:etc_t:s0\0"`. The functions in this module
+//! take care of that trailing NUL so that callers only deal with
+//! [`SecurityContext`].
+
+use crate::{
+    fgetxattr, fsetxattr, getxattr, lgetxattr, lsetxattr, setxattr, Flags,
+    Result,
+};
+use errno::Errno;
+use std::{fmt, os::unix::io::RawFd, path::Path, str::FromStr};
+
+pub mod file_contexts;
+
+/// Name of the EA used to store the SELinux label.
+pub const XATTR_NAME_SELINUX: &str = "security.selinux";
+
+/// A category span in an MLS/MCS level, `c3` or `c0.c1023`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CategorySpan {
+    /// The first category in this span.
+    pub low: u32,
+    /// The last category in this span, equals to `low` for a single category.
+    pub high: u32,
+}
+
+/// A MLS/MCS level, e.g., `s0:c0,c3.c5`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MlsLevel {
+    /// Sensitivity, `s0` is stored as `0`.
+    pub sensitivity: u32,
+    /// Categories in the order they are written.
+    pub categories: Vec<CategorySpan>,
+}
+
+/// A MLS/MCS range, e.g., `s0-s0:c0.c1023`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MlsRange {
+    /// The low (current) level.
+    pub low: MlsLevel,
+    /// The high (clearance) level, `None` if the range has only one level.
+    pub high: Option<MlsLevel>,
+}
+
+/// A SELinux security context, `user:role:type[:range]`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SecurityContext {
+    /// SELinux user, e.g., `system_u`.
+    pub user: String,
+    /// SELinux role, e.g., `object_r`.
+    pub role: String,
+    /// SELinux type, e.g., `etc_t`.
+    pub type_: String,
+    /// MLS/MCS range, `None` if the policy does not use MLS.
+    pub range: Option<MlsRange>,
+}
+
+/// Parses `prefix` + number, e.g., `s0` or `c1023`.
+fn parse_prefixed_number(s: &str, prefix: char) -> Result<u32> {
+    match s.strip_prefix(prefix) {
+        Some(num)
+            if !num.is_empty() && num.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            num.parse().map_err(|_| Errno(libc::EINVAL))
+        }
+        _ => Err(Errno(libc::EINVAL)),
+    }
+}
+
+impl FromStr for CategorySpan {
+    type Err = Errno;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (low, high) = match s.split_once('.') {
+            Some((low, high)) => (
+                parse_prefixed_number(low, 'c')?,
+                parse_prefixed_number(high, 'c')?,
+            ),
+            None => {
+                let c = parse_prefixed_number(s, 'c')?;
+                (c, c)
+            }
+        };
+
+        if low > high {
+            return Err(Errno(libc::EINVAL));
+        }
+
+        Ok(CategorySpan { low, high })
+    }
+}
+
+impl fmt::Display for CategorySpan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.low == self.high {
+            write!(f, "c{}", self.low)
+        } else {
+            write!(f, "c{}.c{}", self.low, self.high)
+        }
+    }
+}
+
+impl FromStr for MlsLevel {
+    type Err = Errno;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (sensitivity, categories) = match s.split_once(':') {
+            Some((sensitivity, categories)) => (
+                sensitivity,
+                categories
+                    .split(',')
+                    .map(CategorySpan::from_str)
+                    .collect::<Result<Vec<CategorySpan>>>()?,
+            ),
+            None => (s, Vec::new()),
+        };
+
+        Ok(MlsLevel {
+            sensitivity: parse_prefixed_number(sensitivity, 's')?,
+            categories,
+        })
+    }
+}
+
+impl fmt::Display for MlsLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "s{}", self.sensitivity)?;
+        for (idx, span) in self.categories.iter().enumerate() {
+            let sep = if idx == 0 { ':' } else { ',' };
+            write!(f, "{}{}", sep, span)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for MlsRange {
+    type Err = Errno;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once('-') {
+            Some((low, high)) => Ok(MlsRange {
+                low: low.parse()?,
+                high: Some(high.parse()?),
+            }),
+            None => Ok(MlsRange {
+                low: s.parse()?,
+                high: None,
+            }),
+        }
+    }
+}
+
+impl fmt::Display for MlsRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.low)?;
+        if let Some(high) = &self.high {
+            write!(f, "-{}", high)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SecurityContext {
+    type Err = Errno;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut fields = s.splitn(4, ':');
+        let mut next_field = || match fields.next() {
+            Some(field) if !field.is_empty() => Ok(field),
+            _ => Err(Errno(libc::EINVAL)),
+        };
+        let user = next_field()?.to_owned();
+        let role = next_field()?.to_owned();
+        let type_ = next_field()?.to_owned();
+        let range = match fields.next() {
+            Some(range) => Some(range.parse()?),
+            None => None,
+        };
+
+        Ok(SecurityContext {
+            user,
+            role,
+            type_,
+            range,
+        })
+    }
+}
+
+impl fmt::Display for SecurityContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.user, self.role, self.type_)?;
+        if let Some(range) = &self.range {
+            write!(f, ":{}", range)?;
+        }
+        Ok(())
+    }
+}
+
+impl SecurityContext {
+    /// Parses a raw `security.selinux` value, the trailing NUL is optional.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        std::str::from_utf8(strip_nul(bytes))
+            .map_err(|_| Errno(libc::EINVAL))?
+            .parse()
+    }
+
+    /// Encodes this context into a NUL-terminated `security.selinux` value.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.to_string().into_bytes();
+        bytes.push(0);
+        bytes
+    }
+}
+
+/// Strips the trailing NUL (if any) from a raw `security.selinux` value.
+fn strip_nul(bytes: &[u8]) -> &[u8] {
+    bytes.strip_suffix(&[0]).unwrap_or(bytes)
+}
+
+/// Compares two raw `security.selinux` values, ignoring the difference of
+/// the trailing NUL.
+pub fn context_bytes_eq<A, B>(a: A, b: B) -> bool
+where
+    A: AsRef<[u8]>,
+    B: AsRef<[u8]>,
+{
+    strip_nul(a.as_ref()) == strip_nul(b.as_ref())
+}
+
+/// Retrieves the SELinux label of `path`. If `path` is a symbolic link, it will
+/// be dereferenced.
+pub fn getfilecon<P: AsRef<Path>>(path: P) -> Result<SecurityContext> {
+    SecurityContext::from_bytes(&getxattr(path, XATTR_NAME_SELINUX)?)
+}
+
+/// Retrieves the SELinux label of `path`. If `path` is a symbolic link, the
+/// label of the link *itself* will be returned.
+pub fn lgetfilecon<P: AsRef<Path>>(path: P) -> Result<SecurityContext> {
+    SecurityContext::from_bytes(&lgetxattr(path, XATTR_NAME_SELINUX)?)
+}
+
+/// Retrieves the SELinux label of the file specified by the open file
+/// descriptor `fd`.
+pub fn fgetfilecon(fd: RawFd) -> Result<SecurityContext> {
+    SecurityContext::from_bytes(&fgetxattr(fd, XATTR_NAME_SELINUX)?)
+}
+
+/// Sets the SELinux label of `path`. If `path` is a symbolic link, it will be
+/// dereferenced.
+pub fn setfilecon<P: AsRef<Path>>(
+    path: P,
+    context: &SecurityContext,
+) -> Result<()> {
+    setxattr(path, XATTR_NAME_SELINUX, context.to_bytes(), Flags::empty())
+}
+
+/// Sets the SELinux label of `path`. If `path` is a symbolic link, the label
+/// is set on the link *itself*.
+pub fn lsetfilecon<P: AsRef<Path>>(
+    path: P,
+    context: &SecurityContext,
+) -> Result<()> {
+    lsetxattr(path, XATTR_NAME_SELINUX, context.to_bytes(), Flags::empty())
+}
+
+/// Sets the SELinux label of the file specified by the open file descriptor
+/// `fd`.
+pub fn fsetfilecon(fd: RawFd, context: &SecurityContext) -> Result<()> {
+    fsetxattr(fd, XATTR_NAME_SELINUX, context.to_bytes(), Flags::empty())
+}
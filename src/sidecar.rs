@@ -0,0 +1,491 @@
+//! Fallback storage for file systems without EA support
+//!
+//! Some file systems, e.g., tmpfs on older kernels, NFSv3 and many FUSE
+//! mounts, fail with `ENOTSUP` on `setxattr(2)`. The functions of this module
+//! call the real syscalls first, and on `ENOTSUP` store the EAs in a sidecar
+//! file named [`STORE_NAME`] in the directory containing the file, keyed by
+//! the inode number and the birth time of the file, and the EA name. Reads
+//! merge the real and the stored EAs, the real ones taking precedence.
+//!
+//! The birth time tells a file from a newer one reusing the inode number of
+//! a deleted file, whose stored EAs are then ignored, and dropped by the next
+//! update of the store. File systems that do not report birth times, e.g.,
+//! NFSv3, can not tell them apart, so [`migrate()`] or [`remove()`] should be
+//! used before deleting a file. The stored EAs stay in the directory: a file
+//! renamed within it keeps them, but a file moved to another directory loses
+//! them.
+//!
+//! Symbolic links are never followed, the EAs of the link *itself* are
+//! accessed. Only `user.*` EAs are stored and migrated, the others fail with
+//! `ENOTSUP` as usual: anyone able to write to the directory can plant a
+//! store, which must not turn into `security.capability` or `trusted.*` EAs.
+//! [`Sidecar::with_namespaces()`] opts in to other namespaces.
+//!
+//! The store is created with mode 0600, and a store not owned by the
+//! effective user is rejected with `EPERM`.
+//!
+//! The store is updated by writing a new file and renaming it over the old
+//! one, so readers never see a partial update. Writers hold an exclusive
+//! `flock(2)` on [`LOCK_NAME`], a regular file created next to the store and
+//! left in place, since locking the directory itself fails on NFSv3. Its
+//! format is [`STORE_MAGIC`] followed by one
+//! record per EA: the inode number and the birth time, in nanoseconds since
+//! the UNIX epoch or 0 if unknown, as little-endian `u64`s, the lengths of the
+//! name and of the value as little-endian `u32`s, the name and the value.
+//!
+//! Once the file system supports EAs, [`migrate()`] moves the stored EAs to
+//! the files.
+//!
+//! [`Sidecar`] does the same on top of any [`XattrBackend`].
+
+use crate::{
+    backend::{SyscallBackend, XattrBackend},
+    io_errno,
+    name::{AttrName, Namespace},
+    portable::Target,
+    Flags, Result,
+};
+use errno::{errno, Errno};
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    ffi::{OsStr, OsString},
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    os::unix::{
+        ffi::{OsStrExt, OsStringExt},
+        fs::{MetadataExt, OpenOptionsExt},
+        io::AsRawFd,
+    },
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+/// Name of the sidecar file in each directory.
+pub const STORE_NAME: &str = ".extattr-sidecar";
+
+/// Name of the lock file serializing the updates of the store.
+pub const LOCK_NAME: &str = ".extattr-sidecar.lock";
+
+/// Magic number at the start of the sidecar file.
+pub const STORE_MAGIC: &[u8; 8] = b"XATTRSC1";
+
+/// Inode number and birth time of a file.
+type FileId = (u64, u64);
+
+type Entries = BTreeMap<(FileId, OsString), Vec<u8>>;
+
+fn file_id(metadata: &fs::Metadata) -> FileId {
+    let birth = metadata
+        .created()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_nanos() as u64);
+    (metadata.ino(), birth)
+}
+
+/// Returns the directory holding the store of `path`, and the identity of
+/// `path`.
+fn locate(path: &Path) -> Result<(PathBuf, FileId)> {
+    let id = file_id(&fs::symlink_metadata(path).map_err(io_errno)?);
+    let dir = match path.parent() {
+        Some(dir) if dir.as_os_str().is_empty() => Path::new("."),
+        Some(dir) => dir,
+        // `/`, whose file system can not be changed to something else anyway
+        None => return Err(Errno(libc::EINVAL)),
+    };
+    Ok((dir.to_owned(), id))
+}
+
+/// Takes an exclusive `flock(2)` on the lock file of `dir`, creating it if
+/// needed, released when the returned file is closed.
+fn lock(dir: &Path) -> Result<File> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .mode(0o600)
+        .custom_flags(libc::O_NOFOLLOW | libc::O_CLOEXEC)
+        .open(dir.join(LOCK_NAME))
+        .map_err(io_errno)?;
+    loop {
+        match unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } {
+            -1 if errno() == Errno(libc::EINTR) => continue,
+            -1 => return Err(errno()),
+            _ => return Ok(file),
+        }
+    }
+}
+
+/// Decodes the content of a store.
+fn decode(bytes: &[u8]) -> Result<Entries> {
+    let invalid = || Errno(libc::EINVAL);
+    let mut rest = bytes.strip_prefix(&STORE_MAGIC[..]).ok_or_else(invalid)?;
+    let mut entries = Entries::new();
+
+    while !rest.is_empty() {
+        if rest.len() < 24 {
+            return Err(invalid());
+        }
+        let mut ino = [0; 8];
+        let mut birth = [0; 8];
+        let mut name_len = [0; 4];
+        let mut value_len = [0; 4];
+        ino.copy_from_slice(&rest[..8]);
+        birth.copy_from_slice(&rest[8..16]);
+        name_len.copy_from_slice(&rest[16..20]);
+        value_len.copy_from_slice(&rest[20..24]);
+        let id = (u64::from_le_bytes(ino), u64::from_le_bytes(birth));
+        let name_len = u32::from_le_bytes(name_len) as usize;
+        let value_len = u32::from_le_bytes(value_len) as usize;
+        rest = &rest[24..];
+
+        let total = name_len.checked_add(value_len).ok_or_else(invalid)?;
+        if name_len == 0 || rest.len() < total {
+            return Err(invalid());
+        }
+        let (name, value) = rest[..total].split_at(name_len);
+        entries.insert((id, OsString::from_vec(name.to_vec())), value.to_vec());
+        rest = &rest[total..];
+    }
+    Ok(entries)
+}
+
+/// Encodes the content of a store.
+fn encode(entries: &Entries) -> Result<Vec<u8>> {
+    let too_big = |_| Errno(libc::E2BIG);
+    let mut bytes = STORE_MAGIC.to_vec();
+    for (((ino, birth), name), value) in entries {
+        let name = name.as_bytes();
+        bytes.extend_from_slice(&ino.to_le_bytes());
+        bytes.extend_from_slice(&birth.to_le_bytes());
+        bytes.extend_from_slice(
+            &u32::try_from(name.len()).map_err(too_big)?.to_le_bytes(),
+        );
+        bytes.extend_from_slice(
+            &u32::try_from(value.len()).map_err(too_big)?.to_le_bytes(),
+        );
+        bytes.extend_from_slice(name);
+        bytes.extend_from_slice(value);
+    }
+    Ok(bytes)
+}
+
+/// Reads the store of `dir`, empty if there is none. A store owned by
+/// another user fails with `EPERM`.
+fn load(dir: &Path) -> Result<Entries> {
+    let mut file = match OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOFOLLOW | libc::O_CLOEXEC)
+        .open(dir.join(STORE_NAME))
+    {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Ok(Entries::new())
+        }
+        Err(e) => return Err(io_errno(e)),
+    };
+    if file.metadata().map_err(io_errno)?.uid() != unsafe { libc::geteuid() } {
+        return Err(Errno(libc::EPERM));
+    }
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes).map_err(io_errno)?;
+    decode(&bytes)
+}
+
+/// Replaces the store of `dir` with `entries`, removing it if it is empty.
+/// The caller must hold the [`lock()`].
+fn save(dir: &Path, entries: &Entries) -> Result<()> {
+    let path = dir.join(STORE_NAME);
+    if entries.is_empty() {
+        return match fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(io_errno(e)),
+            _ => Ok(()),
+        };
+    }
+
+    let bytes = encode(entries)?;
+    let tmp = dir.join(format!("{}.{}", STORE_NAME, std::process::id()));
+    // a file left by a crashed process may have another mode
+    let _ = fs::remove_file(&tmp);
+    let res = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .custom_flags(libc::O_CLOEXEC)
+        .open(&tmp)
+        .and_then(|mut file| {
+            file.write_all(&bytes)?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&tmp, &path));
+    if let Err(e) = res {
+        let _ = fs::remove_file(&tmp);
+        return Err(io_errno(e));
+    }
+    Ok(())
+}
+
+/// Runs `f` on the store of `path`, and saves it if `f` succeeds. The EAs
+/// of a deleted file whose inode number was reused by `path` are dropped.
+fn update<F>(path: &Path, f: F) -> Result<()>
+where
+    F: FnOnce(&mut Entries, FileId) -> Result<()>,
+{
+    let (dir, id) = locate(path)?;
+    let _lock = lock(&dir)?;
+    let mut entries = load(&dir)?;
+    entries.retain(|(i, _), _| *i == id || i.0 != id.0);
+    f(&mut entries, id)?;
+    save(&dir, &entries)
+}
+
+/// Returns the stored EAs of `path`. No lock is needed, the store being
+/// replaced atomically.
+fn stored(path: &Path) -> Result<Vec<(OsString, Vec<u8>)>> {
+    let (dir, id) = locate(path)?;
+    Ok(load(&dir)?
+        .into_iter()
+        .filter(|((i, _), _)| *i == id)
+        .map(|((_, name), value)| (name, value))
+        .collect())
+}
+
+/// The fallback storage on top of a backend making the real calls.
+///
+/// The functions of this module use a [`SyscallBackend`]. Another backend,
+/// e.g., a [`FaultBackend`](crate::fault::FaultBackend) failing with
+/// `ENOTSUP`, exercises the store on a file system supporting EAs.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Sidecar<B> {
+    backend: B,
+    namespaces: Vec<Namespace>,
+}
+
+impl<B: XattrBackend> Sidecar<B> {
+    /// Makes the real calls on `backend`, storing only `user.*` EAs.
+    pub fn new(backend: B) -> Self {
+        Sidecar::with_namespaces(backend, &[Namespace::User])
+    }
+
+    /// Makes the real calls on `backend`, storing the EAs in `namespaces`.
+    ///
+    /// Allowing a namespace other than [`Namespace::User`] lets anyone able
+    /// to write to a directory choose, e.g., the `security.capability` EAs
+    /// that [`Sidecar::migrate()`] sets on its files, so it should only be
+    /// done for directories writable by trusted users alone.
+    pub fn with_namespaces(backend: B, namespaces: &[Namespace]) -> Self {
+        Sidecar {
+            backend,
+            namespaces: namespaces.to_vec(),
+        }
+    }
+
+    /// Returns the backend making the real calls.
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Returns the namespaces whose EAs are stored.
+    pub fn namespaces(&self) -> &[Namespace] {
+        &self.namespaces
+    }
+
+    fn allowed(&self, name: &OsStr) -> bool {
+        match AttrName::from_linux(name) {
+            Ok(name) => self.namespaces.contains(&name.namespace()),
+            Err(_) => false,
+        }
+    }
+
+    /// Returns the stored EAs of `path` in the allowed namespaces.
+    fn stored(&self, path: &Path) -> Result<Vec<(OsString, Vec<u8>)>> {
+        let mut entries = stored(path)?;
+        entries.retain(|(name, _)| self.allowed(name));
+        Ok(entries)
+    }
+
+    /// See [`get()`].
+    pub fn get(&self, path: &Path, name: &OsStr) -> Result<Vec<u8>> {
+        match self.backend.get(Target::Link(path), name) {
+            Err(Errno(libc::ENODATA)) | Err(Errno(libc::ENOTSUP)) => self
+                .stored(path)?
+                .into_iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, value)| value)
+                .ok_or(Errno(libc::ENODATA)),
+            res => res,
+        }
+    }
+
+    /// See [`set()`].
+    pub fn set(
+        &self,
+        path: &Path,
+        name: &OsStr,
+        value: &[u8],
+        flags: Flags,
+    ) -> Result<()> {
+        match self.backend.set(Target::Link(path), name, value, flags) {
+            Err(Errno(libc::ENOTSUP)) if self.allowed(name) => {
+                update(path, |entries, id| {
+                    let key = (id, name.to_owned());
+                    let exists = entries.contains_key(&key);
+                    if flags.contains(Flags::XATTR_CREATE) && exists {
+                        return Err(Errno(libc::EEXIST));
+                    }
+                    if flags.contains(Flags::XATTR_REPLACE) && !exists {
+                        return Err(Errno(libc::ENODATA));
+                    }
+                    entries.insert(key, value.to_vec());
+                    Ok(())
+                })
+            }
+            res => res,
+        }
+    }
+
+    /// See [`list()`].
+    pub fn list(&self, path: &Path) -> Result<Vec<OsString>> {
+        let mut names = match self.backend.list(Target::Link(path)) {
+            Ok(names) => names,
+            Err(Errno(libc::ENOTSUP)) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        for (name, _) in self.stored(path)? {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        Ok(names)
+    }
+
+    /// See [`remove()`].
+    pub fn remove(&self, path: &Path, name: &OsStr) -> Result<()> {
+        let real = match self.backend.remove(Target::Link(path), name) {
+            Ok(()) => true,
+            Err(Errno(libc::ENODATA)) | Err(Errno(libc::ENOTSUP)) => false,
+            Err(e) => return Err(e),
+        };
+        if !self.stored(path)?.iter().any(|(n, _)| n == name) {
+            return if real {
+                Ok(())
+            } else {
+                Err(Errno(libc::ENODATA))
+            };
+        }
+
+        update(path, |entries, id| {
+            match entries.remove(&(id, name.to_owned())) {
+                Some(_) => Ok(()),
+                None if real => Ok(()),
+                // removed concurrently
+                None => Err(Errno(libc::ENODATA)),
+            }
+        })
+    }
+
+    /// See [`migrate()`].
+    pub fn migrate(&self, dir: &Path) -> Result<usize> {
+        if !dir.join(STORE_NAME).exists() {
+            return Ok(0);
+        }
+        let _lock = lock(dir)?;
+        let entries = load(dir)?;
+        if entries.is_empty() {
+            return Ok(0);
+        }
+
+        let mut paths = BTreeMap::new();
+        for entry in fs::read_dir(dir).map_err(io_errno)? {
+            let entry = entry.map_err(io_errno)?;
+            match entry.metadata() {
+                Ok(metadata) => {
+                    paths.insert(file_id(&metadata), entry.path());
+                }
+                // removed concurrently
+                Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+                Err(e) => return Err(io_errno(e)),
+            }
+        }
+
+        let mut kept = Entries::new();
+        let mut moved = 0;
+        let mut res = Ok(());
+        for ((id, name), value) in entries {
+            let path = match paths.get(&id) {
+                Some(path) => path,
+                None => continue,
+            };
+            if res.is_err() || !self.allowed(&name) {
+                kept.insert((id, name), value);
+                continue;
+            }
+            let target = Target::Link(path);
+            match self.backend.set(target, &name, &value, Flags::empty()) {
+                Ok(()) => moved += 1,
+                Err(Errno(libc::ENOTSUP)) => {
+                    kept.insert((id, name), value);
+                }
+                Err(e) => {
+                    kept.insert((id, name), value);
+                    res = Err(e);
+                }
+            }
+        }
+
+        save(dir, &kept)?;
+        res.map(|_| moved)
+    }
+}
+
+/// Retrieves the value of the EA `name` of `path`, from the file or from the
+/// store.
+pub fn get<P, N>(path: P, name: N) -> Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+    N: AsRef<OsStr>,
+{
+    Sidecar::new(SyscallBackend).get(path.as_ref(), name.as_ref())
+}
+
+/// Sets the value of the EA `name` of `path` on the file, or in the store if
+/// the file system does not support EAs. `flags` are honored in both cases.
+pub fn set<P, N, V>(path: P, name: N, value: V, flags: Flags) -> Result<()>
+where
+    P: AsRef<Path>,
+    N: AsRef<OsStr>,
+    V: AsRef<[u8]>,
+{
+    Sidecar::new(SyscallBackend).set(
+        path.as_ref(),
+        name.as_ref(),
+        value.as_ref(),
+        flags,
+    )
+}
+
+/// Lists the EAs of `path`, those of the file followed by those in the store.
+pub fn list<P: AsRef<Path>>(path: P) -> Result<Vec<OsString>> {
+    Sidecar::new(SyscallBackend).list(path.as_ref())
+}
+
+/// Removes the EA `name` of `path` from the file and from the store.
+pub fn remove<P, N>(path: P, name: N) -> Result<()>
+where
+    P: AsRef<Path>,
+    N: AsRef<OsStr>,
+{
+    Sidecar::new(SyscallBackend).remove(path.as_ref(), name.as_ref())
+}
+
+/// Moves the EAs stored for the entries of `dir` to the entries themselves,
+/// and returns how many were moved.
+///
+/// The EAs of files that are no longer in `dir` are dropped. The EAs that
+/// still fail with `ENOTSUP`, and those outside of `user.*`, stay in the
+/// store, other errors stop the migration, keeping the EAs that were not
+/// moved yet.
+pub fn migrate<P: AsRef<Path>>(dir: P) -> Result<usize> {
+    Sidecar::new(SyscallBackend).migrate(dir.as_ref())
+}
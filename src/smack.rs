@@ -0,0 +1,242 @@
+//! Helpers for the Smack labels stored in `security.SMACK64*` EAs
+//!
+//! For more information, see the
+//! [Smack documentation](https://www.kernel.org/doc/html/latest/admin-guide/LSM/Smack.html).
+
+use crate::{
+    fgetxattr, fremovexattr, fsetxattr, getxattr, lgetxattr, lremovexattr,
+    lsetxattr, removexattr, setxattr, Flags, Result,
+};
+use errno::Errno;
+use std::{fmt, os::unix::io::RawFd, path::Path, str::FromStr};
+
+/// The maximum length of a Smack label, excluding the trailing NUL.
+pub const SMACK_LABEL_MAX_LEN: usize = 255;
+
+/// Value of `security.SMACK64TRANSMUTE` when transmuting is enabled.
+const TRANSMUTE_TRUE: &[u8] = b"TRUE";
+
+/// Smack EAs that carry a label.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SmackAttr {
+    /// `security.SMACK64`, the label used for access checks on the object.
+    Access,
+    /// `security.SMACK64EXEC`, the label a task runs with after executing the
+    /// file.
+    Exec,
+    /// `security.SMACK64MMAP`, the label used when the file is mapped.
+    Mmap,
+    /// `security.SMACK64IPIN`, the label of packets delivered to a socket.
+    IpIn,
+    /// `security.SMACK64IPOUT`, the label of packets sent from a socket.
+    IpOut,
+}
+
+impl SmackAttr {
+    /// Returns the name of this EA.
+    pub fn name(self) -> &'static str {
+        match self {
+            SmackAttr::Access => "security.SMACK64",
+            SmackAttr::Exec => "security.SMACK64EXEC",
+            SmackAttr::Mmap => "security.SMACK64MMAP",
+            SmackAttr::IpIn => "security.SMACK64IPIN",
+            SmackAttr::IpOut => "security.SMACK64IPOUT",
+        }
+    }
+}
+
+/// Name of the EA that marks a directory as transmuting.
+pub const XATTR_NAME_SMACK64TRANSMUTE: &str = "security.SMACK64TRANSMUTE";
+
+/// A validated Smack label.
+///
+/// A label is 1 to [`SMACK_LABEL_MAX_LEN`] printable ASCII characters, it must
+/// not start with `-` and must not contain `/`, `"`, `\` or `'`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SmackLabel(String);
+
+impl SmackLabel {
+    /// Validates `label` and creates a `SmackLabel` from it.
+    pub fn new<S: Into<String>>(label: S) -> Result<Self> {
+        let label = label.into();
+        let valid = !label.is_empty()
+            && label.len() <= SMACK_LABEL_MAX_LEN
+            && !label.starts_with('-')
+            && label.bytes().all(|b| {
+                b > b' '
+                    && b <= b'~'
+                    && !matches!(b, b'/' | b'"' | b'\\' | b'\'')
+            });
+
+        if valid {
+            Ok(SmackLabel(label))
+        } else {
+            Err(Errno(libc::EINVAL))
+        }
+    }
+
+    /// Parses a raw EA value, a trailing NUL is tolerated.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let bytes = bytes.strip_suffix(&[0]).unwrap_or(bytes);
+        match std::str::from_utf8(bytes) {
+            Ok(label) => Self::new(label),
+            _ => Err(Errno(libc::EINVAL)),
+        }
+    }
+
+    /// Returns the label as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for SmackLabel {
+    type Err = Errno;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::new(s)
+    }
+}
+
+impl fmt::Display for SmackLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl AsRef<str> for SmackLabel {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Retrieves the Smack label `attr` of `path`. If `path` is a symbolic link, it
+/// will be dereferenced.
+pub fn getlabel<P: AsRef<Path>>(
+    path: P,
+    attr: SmackAttr,
+) -> Result<SmackLabel> {
+    SmackLabel::from_bytes(&getxattr(path, attr.name())?)
+}
+
+/// Retrieves the Smack label `attr` of `path`. If `path` is a symbolic link,
+/// the label of the link *itself* will be returned.
+pub fn lgetlabel<P: AsRef<Path>>(
+    path: P,
+    attr: SmackAttr,
+) -> Result<SmackLabel> {
+    SmackLabel::from_bytes(&lgetxattr(path, attr.name())?)
+}
+
+/// Retrieves the Smack label `attr` of the file specified by the open file
+/// descriptor `fd`.
+pub fn fgetlabel(fd: RawFd, attr: SmackAttr) -> Result<SmackLabel> {
+    SmackLabel::from_bytes(&fgetxattr(fd, attr.name())?)
+}
+
+/// Sets the Smack label `attr` of `path`. If `path` is a symbolic link, it will
+/// be dereferenced.
+pub fn setlabel<P: AsRef<Path>>(
+    path: P,
+    attr: SmackAttr,
+    label: &SmackLabel,
+) -> Result<()> {
+    setxattr(path, attr.name(), label.as_str(), Flags::empty())
+}
+
+/// Sets the Smack label `attr` of `path`. If `path` is a symbolic link, the
+/// label is set on the link *itself*.
+pub fn lsetlabel<P: AsRef<Path>>(
+    path: P,
+    attr: SmackAttr,
+    label: &SmackLabel,
+) -> Result<()> {
+    lsetxattr(path, attr.name(), label.as_str(), Flags::empty())
+}
+
+/// Sets the Smack label `attr` of the file specified by the open file
+/// descriptor `fd`, this is the only way to set `SMACK64IPIN` and
+/// `SMACK64IPOUT` on a socket.
+pub fn fsetlabel(fd: RawFd, attr: SmackAttr, label: &SmackLabel) -> Result<()> {
+    fsetxattr(fd, attr.name(), label.as_str(), Flags::empty())
+}
+
+/// Removes the Smack label `attr` from `path`. If `path` is a symbolic link, it
+/// will be dereferenced.
+pub fn removelabel<P: AsRef<Path>>(path: P, attr: SmackAttr) -> Result<()> {
+    removexattr(path, attr.name())
+}
+
+/// Removes the Smack label `attr` from `path`. If `path` is a symbolic link,
+/// the label is removed from the link *itself*.
+pub fn lremovelabel<P: AsRef<Path>>(path: P, attr: SmackAttr) -> Result<()> {
+    lremovexattr(path, attr.name())
+}
+
+/// Removes the Smack label `attr` from the file specified by the open file
+/// descriptor `fd`.
+pub fn fremovelabel(fd: RawFd, attr: SmackAttr) -> Result<()> {
+    fremovexattr(fd, attr.name())
+}
+
+/// Converts the result of reading `SMACK64TRANSMUTE` into a `bool`.
+fn transmute_from_result(res: Result<Vec<u8>>) -> Result<bool> {
+    match res {
+        Ok(value) => {
+            Ok(value.strip_suffix(&[0]).unwrap_or(&value) == TRANSMUTE_TRUE)
+        }
+        Err(Errno(libc::ENODATA)) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Converts the result of removing `SMACK64TRANSMUTE` so that removing an
+/// unset marker is not an error.
+fn transmute_remove_result(res: Result<()>) -> Result<()> {
+    match res {
+        Err(Errno(libc::ENODATA)) => Ok(()),
+        res => res,
+    }
+}
+
+/// Returns whether the directory `path` is transmuting. If `path` is a symbolic
+/// link, it will be dereferenced.
+pub fn gettransmute<P: AsRef<Path>>(path: P) -> Result<bool> {
+    transmute_from_result(getxattr(path, XATTR_NAME_SMACK64TRANSMUTE))
+}
+
+/// Returns whether the directory specified by the open file descriptor `fd` is
+/// transmuting.
+pub fn fgettransmute(fd: RawFd) -> Result<bool> {
+    transmute_from_result(fgetxattr(fd, XATTR_NAME_SMACK64TRANSMUTE))
+}
+
+/// Marks the directory `path` as transmuting or not. If `path` is a symbolic
+/// link, it will be dereferenced.
+pub fn settransmute<P: AsRef<Path>>(path: P, transmute: bool) -> Result<()> {
+    if transmute {
+        setxattr(
+            path,
+            XATTR_NAME_SMACK64TRANSMUTE,
+            TRANSMUTE_TRUE,
+            Flags::empty(),
+        )
+    } else {
+        transmute_remove_result(removexattr(path, XATTR_NAME_SMACK64TRANSMUTE))
+    }
+}
+
+/// Marks the directory specified by the open file descriptor `fd` as
+/// transmuting or not.
+pub fn fsettransmute(fd: RawFd, transmute: bool) -> Result<()> {
+    if transmute {
+        fsetxattr(
+            fd,
+            XATTR_NAME_SMACK64TRANSMUTE,
+            TRANSMUTE_TRUE,
+            Flags::empty(),
+        )
+    } else {
+        transmute_remove_result(fremovexattr(fd, XATTR_NAME_SMACK64TRANSMUTE))
+    }
+}
@@ -0,0 +1,684 @@
+//! Recording and replaying of EA calls
+//!
+//! [`RecordingBackend`] wraps another [`XattrBackend`], usually a
+//! [`SyscallBackend`](crate::backend::SyscallBackend), and writes every call
+//! with its arguments and its result to a trace. [`parse()`] reads the trace
+//! back, and [`replay()`] makes the same calls on another backend, usually a
+//! [`MemoryBackend`] prepared with [`seed()`], reporting where the results
+//! differ.
+//!
+//! The trace does not record which file a descriptor refers to, so the calls
+//! on a [`Target::Fd`] are not replayed, but reported as such. Nor does it
+//! record the file types: [`seed()`] creates every path as a regular file,
+//! so the calls on a symbolic link replay the same with [`Target::Path`] and
+//! [`Target::Link`], although they did not with the recorded backend.
+//!
+//! # Trace format
+//!
+//! A trace is a text file, starting with the line [`TRACE_HEADER`], followed
+//! by one line per call, terminated by `\n`, whose fields are separated by a
+//! single space:
+//!
+//! ```text
+//! extattr-trace 1
+//! set path 'dir/file 'user.foo 'bar 1 = ok
+//! get link 'dir/file 'user.foo = value 'bar
+//! list fd 3 = names 'user.foo 'user.my%20name
+//! remove path 'dir/file 'user.baz = err ENODATA
+//! get fd -1 'user.foo = err EBADF
+//! ```
+//!
+//! * The operation: `get`, `set`, `list` or `remove`.
+//! * The target: `path` followed by a path for [`Target::Path`], `link`
+//!   followed by a path for [`Target::Link`], or `fd` followed by a decimal
+//!   descriptor for [`Target::Fd`], with a leading `-` if negative.
+//! * The name, except for `list`.
+//! * For `set`, the value and the flags as a decimal number, 1 being
+//!   `XATTR_CREATE` and 2 `XATTR_REPLACE`.
+//! * `=`, followed by the result: `ok` for `set` and `remove`, `value` and
+//!   the value for `get`, `names` and the names for `list`, or `err` and the
+//!   name of the error number, e.g., `ENODATA`. Error numbers without a name
+//!   in this module are written as `E#` and their decimal value.
+//!
+//! Paths, names and values are written as `'` followed by their bytes, where
+//! the bytes that are not printable ASCII, space and `%` are written as `%`
+//! followed by two uppercase hexadecimal digits.
+
+use crate::{
+    backend::{FileKind, MemoryBackend, XattrBackend},
+    io_errno,
+    portable::Target,
+    Flags, Result,
+};
+use errno::Errno;
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    ffi::{OsStr, OsString},
+    io::Write,
+    os::unix::{
+        ffi::{OsStrExt, OsStringExt},
+        io::RawFd,
+    },
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// First line of a trace, including the version of the format.
+pub const TRACE_HEADER: &str = "extattr-trace 1";
+
+/// Names of the error numbers, the others are written as numbers.
+const ERRNO_NAMES: &[(i32, &str)] = &[
+    (libc::E2BIG, "E2BIG"),
+    (libc::EACCES, "EACCES"),
+    (libc::EBADF, "EBADF"),
+    (libc::EDQUOT, "EDQUOT"),
+    (libc::EEXIST, "EEXIST"),
+    (libc::EFAULT, "EFAULT"),
+    (libc::EINTR, "EINTR"),
+    (libc::EINVAL, "EINVAL"),
+    (libc::EIO, "EIO"),
+    (libc::ELOOP, "ELOOP"),
+    (libc::ENAMETOOLONG, "ENAMETOOLONG"),
+    (libc::ENODATA, "ENODATA"),
+    (libc::ENOENT, "ENOENT"),
+    (libc::ENOMEM, "ENOMEM"),
+    (libc::ENOSPC, "ENOSPC"),
+    (libc::ENOTDIR, "ENOTDIR"),
+    (libc::ENOTSUP, "ENOTSUP"),
+    (libc::EPERM, "EPERM"),
+    (libc::ERANGE, "ERANGE"),
+    (libc::EROFS, "EROFS"),
+];
+
+/// The file of a recorded call.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TraceTarget {
+    /// [`Target::Path`]
+    Path(PathBuf),
+    /// [`Target::Link`]
+    Link(PathBuf),
+    /// [`Target::Fd`]
+    Fd(RawFd),
+}
+
+impl TraceTarget {
+    /// Returns the target of the call.
+    pub fn as_target(&self) -> Target<'_> {
+        match self {
+            TraceTarget::Path(path) => Target::Path(path),
+            TraceTarget::Link(path) => Target::Link(path),
+            TraceTarget::Fd(fd) => Target::Fd(*fd),
+        }
+    }
+}
+
+impl From<Target<'_>> for TraceTarget {
+    fn from(target: Target<'_>) -> Self {
+        match target {
+            Target::Path(path) => TraceTarget::Path(path.to_owned()),
+            Target::Link(path) => TraceTarget::Link(path.to_owned()),
+            Target::Fd(fd) => TraceTarget::Fd(fd),
+        }
+    }
+}
+
+/// A recorded call.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TraceCall {
+    /// [`XattrBackend::get()`]
+    Get {
+        /// The file.
+        target: TraceTarget,
+        /// The EA name.
+        name: OsString,
+    },
+    /// [`XattrBackend::set()`]
+    Set {
+        /// The file.
+        target: TraceTarget,
+        /// The EA name.
+        name: OsString,
+        /// The value.
+        value: Vec<u8>,
+        /// The flags.
+        flags: Flags,
+    },
+    /// [`XattrBackend::list()`]
+    List {
+        /// The file.
+        target: TraceTarget,
+    },
+    /// [`XattrBackend::remove()`]
+    Remove {
+        /// The file.
+        target: TraceTarget,
+        /// The EA name.
+        name: OsString,
+    },
+}
+
+impl TraceCall {
+    /// Returns the file of the call.
+    pub fn target(&self) -> &TraceTarget {
+        match self {
+            TraceCall::Get { target, .. }
+            | TraceCall::Set { target, .. }
+            | TraceCall::List { target }
+            | TraceCall::Remove { target, .. } => target,
+        }
+    }
+
+    /// Makes the call on `backend`.
+    pub fn call<B: XattrBackend + ?Sized>(&self, backend: &B) -> TraceResult {
+        let res = match self {
+            TraceCall::Get { target, name } => backend
+                .get(target.as_target(), name)
+                .map(TraceResult::Value),
+            TraceCall::Set {
+                target,
+                name,
+                value,
+                flags,
+            } => backend
+                .set(target.as_target(), name, value, *flags)
+                .map(|_| TraceResult::Ok),
+            TraceCall::List { target } => {
+                backend.list(target.as_target()).map(TraceResult::Names)
+            }
+            TraceCall::Remove { target, name } => backend
+                .remove(target.as_target(), name)
+                .map(|_| TraceResult::Ok),
+        };
+        res.unwrap_or_else(TraceResult::Err)
+    }
+}
+
+/// The result of a recorded call.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TraceResult {
+    /// `set` or `remove` succeeded.
+    Ok,
+    /// `get` succeeded with the value.
+    Value(Vec<u8>),
+    /// `list` succeeded with the names.
+    Names(Vec<OsString>),
+    /// The call failed.
+    Err(Errno),
+}
+
+/// A line of a trace.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Event {
+    /// The call.
+    pub call: TraceCall,
+    /// Its result.
+    pub result: TraceResult,
+}
+
+fn encode_bytes(line: &mut Vec<u8>, bytes: &[u8]) {
+    line.extend_from_slice(b" '");
+    for &b in bytes {
+        if b <= b' ' || b >= 0x7f || b == b'%' {
+            line.extend_from_slice(format!("%{:02X}", b).as_bytes());
+        } else {
+            line.push(b);
+        }
+    }
+}
+
+fn decode_bytes(token: &[u8]) -> Result<Vec<u8>> {
+    let invalid = || Errno(libc::EINVAL);
+    let encoded = token.strip_prefix(b"'").ok_or_else(invalid)?;
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        if encoded[i] == b'%' {
+            let byte = encoded
+                .get(i + 1..i + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(invalid)?;
+            bytes.push(byte);
+            i += 3;
+        } else {
+            bytes.push(encoded[i]);
+            i += 1;
+        }
+    }
+    Ok(bytes)
+}
+
+fn decode_number<T: std::str::FromStr>(token: &[u8]) -> Result<T> {
+    std::str::from_utf8(token)
+        .ok()
+        .filter(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|n| n.parse().ok())
+        .ok_or(Errno(libc::EINVAL))
+}
+
+/// Decodes a descriptor, negative ones being recorded from `EBADF` paths.
+fn decode_fd(token: &[u8]) -> Result<RawFd> {
+    let digits = token.strip_prefix(b"-").unwrap_or(token);
+    if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+        return Err(Errno(libc::EINVAL));
+    }
+    std::str::from_utf8(token)
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .ok_or(Errno(libc::EINVAL))
+}
+
+impl Event {
+    /// Encodes this event as a line of a trace, including the `\n`.
+    pub fn encode(&self) -> Vec<u8> {
+        let (op, target) = match &self.call {
+            TraceCall::Get { target, .. } => ("get", target),
+            TraceCall::Set { target, .. } => ("set", target),
+            TraceCall::List { target } => ("list", target),
+            TraceCall::Remove { target, .. } => ("remove", target),
+        };
+        let mut line = op.as_bytes().to_vec();
+        match target {
+            TraceTarget::Path(path) => {
+                line.extend_from_slice(b" path");
+                encode_bytes(&mut line, path.as_os_str().as_bytes());
+            }
+            TraceTarget::Link(path) => {
+                line.extend_from_slice(b" link");
+                encode_bytes(&mut line, path.as_os_str().as_bytes());
+            }
+            TraceTarget::Fd(fd) => {
+                line.extend_from_slice(format!(" fd {}", fd).as_bytes())
+            }
+        }
+        match &self.call {
+            TraceCall::Get { name, .. } | TraceCall::Remove { name, .. } => {
+                encode_bytes(&mut line, name.as_bytes())
+            }
+            TraceCall::Set {
+                name, value, flags, ..
+            } => {
+                encode_bytes(&mut line, name.as_bytes());
+                encode_bytes(&mut line, value);
+                line.extend_from_slice(format!(" {}", flags.bits()).as_bytes());
+            }
+            TraceCall::List { .. } => (),
+        }
+
+        line.extend_from_slice(b" =");
+        match &self.result {
+            TraceResult::Ok => line.extend_from_slice(b" ok"),
+            TraceResult::Value(value) => {
+                line.extend_from_slice(b" value");
+                encode_bytes(&mut line, value);
+            }
+            TraceResult::Names(names) => {
+                line.extend_from_slice(b" names");
+                for name in names {
+                    encode_bytes(&mut line, name.as_bytes());
+                }
+            }
+            TraceResult::Err(Errno(e)) => {
+                line.extend_from_slice(b" err ");
+                match ERRNO_NAMES.iter().find(|(n, _)| n == e) {
+                    Some((_, name)) => line.extend_from_slice(name.as_bytes()),
+                    None => {
+                        line.extend_from_slice(format!("E#{}", e).as_bytes())
+                    }
+                }
+            }
+        }
+        line.push(b'\n');
+        line
+    }
+
+    /// Decodes a line of a trace, without the `\n`.
+    ///
+    /// Returns `EINVAL` if it is malformed.
+    pub fn decode(line: &[u8]) -> Result<Self> {
+        let invalid = || Errno(libc::EINVAL);
+        let mut tokens = line.split(|b| *b == b' ');
+        let mut next = || tokens.next().ok_or_else(invalid);
+
+        let op = next()?;
+        let target = match next()? {
+            b"path" => TraceTarget::Path(PathBuf::from(OsString::from_vec(
+                decode_bytes(next()?)?,
+            ))),
+            b"link" => TraceTarget::Link(PathBuf::from(OsString::from_vec(
+                decode_bytes(next()?)?,
+            ))),
+            b"fd" => TraceTarget::Fd(decode_fd(next()?)?),
+            _ => return Err(invalid()),
+        };
+        let call = match op {
+            b"get" => TraceCall::Get {
+                target,
+                name: OsString::from_vec(decode_bytes(next()?)?),
+            },
+            b"set" => TraceCall::Set {
+                target,
+                name: OsString::from_vec(decode_bytes(next()?)?),
+                value: decode_bytes(next()?)?,
+                flags: Flags::from_bits(decode_number(next()?)?)
+                    .ok_or_else(invalid)?,
+            },
+            b"list" => TraceCall::List { target },
+            b"remove" => TraceCall::Remove {
+                target,
+                name: OsString::from_vec(decode_bytes(next()?)?),
+            },
+            _ => return Err(invalid()),
+        };
+        if next()? != b"=" {
+            return Err(invalid());
+        }
+
+        let result = match (next()?, &call) {
+            (b"ok", TraceCall::Set { .. })
+            | (b"ok", TraceCall::Remove { .. }) => TraceResult::Ok,
+            (b"value", TraceCall::Get { .. }) => {
+                TraceResult::Value(decode_bytes(next()?)?)
+            }
+            (b"names", TraceCall::List { .. }) => {
+                let mut names = Vec::new();
+                for token in tokens.by_ref() {
+                    names.push(OsString::from_vec(decode_bytes(token)?));
+                }
+                TraceResult::Names(names)
+            }
+            (b"err", _) => {
+                let name = next()?;
+                let e = match ERRNO_NAMES
+                    .iter()
+                    .find(|(_, n)| n.as_bytes() == name)
+                {
+                    Some((e, _)) => *e,
+                    None => decode_number(
+                        name.strip_prefix(b"E#").ok_or_else(invalid)?,
+                    )?,
+                };
+                TraceResult::Err(Errno(e))
+            }
+            _ => return Err(invalid()),
+        };
+        if tokens.next().is_some() {
+            return Err(invalid());
+        }
+
+        Ok(Event { call, result })
+    }
+}
+
+/// Parses a trace, see the [module documentation](self) for the format.
+///
+/// Returns `EINVAL` if the header is missing or a line is malformed.
+pub fn parse(trace: &[u8]) -> Result<Vec<Event>> {
+    let mut lines = trace.split(|b| *b == b'\n');
+    if lines.next() != Some(TRACE_HEADER.as_bytes()) {
+        return Err(Errno(libc::EINVAL));
+    }
+    let mut events = Vec::new();
+    for line in lines {
+        // the `\n` terminating the last line
+        if line.is_empty() {
+            continue;
+        }
+        events.push(Event::decode(line)?);
+    }
+    Ok(events)
+}
+
+#[derive(Debug)]
+struct Output<W> {
+    writer: W,
+    error: Option<Errno>,
+}
+
+/// A backend recording the calls made to another one.
+///
+/// The calls are passed to the inner backend, and their result is returned
+/// even if the trace can not be written, the first write error being kept
+/// for [`RecordingBackend::error()`].
+#[derive(Debug)]
+pub struct RecordingBackend<B, W> {
+    inner: B,
+    output: Mutex<Output<W>>,
+}
+
+impl<B: XattrBackend, W: Write> RecordingBackend<B, W> {
+    /// Wraps `inner`, and writes the header of the trace to `writer`.
+    pub fn new(inner: B, mut writer: W) -> Result<Self> {
+        writer
+            .write_all(format!("{}\n", TRACE_HEADER).as_bytes())
+            .map_err(io_errno)?;
+        Ok(RecordingBackend {
+            inner,
+            output: Mutex::new(Output {
+                writer,
+                error: None,
+            }),
+        })
+    }
+
+    /// Returns the first error that occurred writing the trace.
+    pub fn error(&self) -> Option<Errno> {
+        self.output.lock().unwrap_or_else(|e| e.into_inner()).error
+    }
+
+    /// Returns the inner backend and the writer.
+    pub fn into_inner(self) -> (B, W) {
+        let output =
+            self.output.into_inner().unwrap_or_else(|e| e.into_inner());
+        (self.inner, output.writer)
+    }
+
+    fn record(&self, call: TraceCall, result: TraceResult) {
+        let line = Event { call, result }.encode();
+        let mut output = self.output.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = output.writer.write_all(&line) {
+            output.error.get_or_insert(io_errno(e));
+        }
+    }
+}
+
+impl<B: XattrBackend, W: Write> XattrBackend for RecordingBackend<B, W> {
+    fn get(&self, target: Target<'_>, name: &OsStr) -> Result<Vec<u8>> {
+        let res = self.inner.get(target, name);
+        let call = TraceCall::Get {
+            target: target.into(),
+            name: name.to_owned(),
+        };
+        let result = match &res {
+            Ok(value) => TraceResult::Value(value.clone()),
+            Err(e) => TraceResult::Err(*e),
+        };
+        self.record(call, result);
+        res
+    }
+
+    fn set(
+        &self,
+        target: Target<'_>,
+        name: &OsStr,
+        value: &[u8],
+        flags: Flags,
+    ) -> Result<()> {
+        let res = self.inner.set(target, name, value, flags);
+        let call = TraceCall::Set {
+            target: target.into(),
+            name: name.to_owned(),
+            value: value.to_vec(),
+            flags,
+        };
+        let result = match res {
+            Ok(()) => TraceResult::Ok,
+            Err(e) => TraceResult::Err(e),
+        };
+        self.record(call, result);
+        res
+    }
+
+    fn list(&self, target: Target<'_>) -> Result<Vec<OsString>> {
+        let res = self.inner.list(target);
+        let call = TraceCall::List {
+            target: target.into(),
+        };
+        let result = match &res {
+            Ok(names) => TraceResult::Names(names.clone()),
+            Err(e) => TraceResult::Err(*e),
+        };
+        self.record(call, result);
+        res
+    }
+
+    fn remove(&self, target: Target<'_>, name: &OsStr) -> Result<()> {
+        let res = self.inner.remove(target, name);
+        let call = TraceCall::Remove {
+            target: target.into(),
+            name: name.to_owned(),
+        };
+        let result = match res {
+            Ok(()) => TraceResult::Ok,
+            Err(e) => TraceResult::Err(e),
+        };
+        self.record(call, result);
+        res
+    }
+}
+
+/// Prepares `backend` to replay `events`: every path of the trace is created
+/// as a regular file, and the EAs the trace shows existing before any call
+/// set them are set, with their first value read, or an empty value if the
+/// trace only lists them.
+///
+/// The paths whose first call failed with `ENOENT` are not created, nor are
+/// those whose first call failed with `ENOTDIR`, whose parent is created as a
+/// regular file instead.
+///
+/// Calls on a [`TraceTarget::Fd`] are not seeded, as the file is unknown, and
+/// symbolic links are created as regular files.
+pub fn seed(events: &[Event], backend: &MemoryBackend) -> Result<()> {
+    let mut seen = HashSet::new();
+    let mut missing = HashSet::new();
+    // whether the initial value of an EA is known, listed EAs being only
+    // known once read
+    let mut known: HashMap<(&Path, &OsStr), bool> = HashMap::new();
+    let mut initial: Vec<((&Path, &OsStr), &[u8])> = Vec::new();
+
+    for event in events {
+        let path = match event.call.target() {
+            TraceTarget::Path(path) | TraceTarget::Link(path) => path.as_path(),
+            TraceTarget::Fd(_) => continue,
+        };
+        if seen.insert(path) {
+            match event.result {
+                TraceResult::Err(Errno(libc::ENOENT)) => {
+                    missing.insert(path);
+                }
+                TraceResult::Err(Errno(libc::ENOTDIR)) => {
+                    missing.insert(path);
+                    match path.parent() {
+                        Some(parent)
+                            if !parent.as_os_str().is_empty()
+                                && seen.insert(parent) =>
+                        {
+                            backend.create(parent, FileKind::File)?;
+                        }
+                        _ => (),
+                    }
+                }
+                _ => backend.create(path, FileKind::File)?,
+            }
+        }
+        if missing.contains(path) {
+            continue;
+        }
+
+        match (&event.call, &event.result) {
+            (TraceCall::List { .. }, TraceResult::Names(names)) => {
+                for name in names {
+                    let key = (path, name.as_os_str());
+                    if let Entry::Vacant(entry) = known.entry(key) {
+                        entry.insert(false);
+                        initial.push((key, &[]));
+                    }
+                }
+            }
+            (TraceCall::List { .. }, _) => (),
+            (TraceCall::Get { name, .. }, TraceResult::Value(value)) => {
+                let key = (path, name.as_os_str());
+                match known.insert(key, true) {
+                    None => initial.push((key, value)),
+                    Some(false) => {
+                        if let Some(attr) =
+                            initial.iter_mut().find(|(k, _)| *k == key)
+                        {
+                            attr.1 = value;
+                        }
+                    }
+                    Some(true) => (),
+                }
+            }
+            (TraceCall::Get { name, .. }, _)
+            | (TraceCall::Set { name, .. }, _)
+            | (TraceCall::Remove { name, .. }, _) => {
+                // from now on, the state comes from the replay
+                known.insert((path, name.as_os_str()), true);
+            }
+        }
+    }
+
+    for ((path, name), value) in initial {
+        backend.set(Target::Link(path), name, value, Flags::empty())?;
+    }
+    Ok(())
+}
+
+/// A call whose result differs from the recorded one.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Divergence {
+    /// Index of the event in the trace.
+    pub index: usize,
+    /// The recorded result.
+    pub expected: TraceResult,
+    /// The result of the replay.
+    pub actual: TraceResult,
+}
+
+/// The outcome of a [`replay()`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Replay {
+    /// The calls whose result differs from the recorded one.
+    pub divergences: Vec<Divergence>,
+    /// Indexes of the events that were not replayed, the calls on a
+    /// [`TraceTarget::Fd`], whose descriptor does not exist in the replaying
+    /// backend.
+    pub unreplayable: Vec<usize>,
+}
+
+/// Makes the calls of `events` on `backend`, in order, except those on a
+/// [`TraceTarget::Fd`], and returns those whose result differs from the
+/// recorded one.
+pub fn replay<B: XattrBackend + ?Sized>(
+    events: &[Event],
+    backend: &B,
+) -> Replay {
+    let mut replay = Replay::default();
+    for (index, event) in events.iter().enumerate() {
+        if let TraceTarget::Fd(_) = event.call.target() {
+            replay.unreplayable.push(index);
+            continue;
+        }
+        let actual = event.call.call(backend);
+        if actual != event.result {
+            replay.divergences.push(Divergence {
+                index,
+                expected: event.result.clone(),
+                actual,
+            });
+        }
+    }
+    replay
+}
@@ -0,0 +1,223 @@
+//! Helpers for the EAs described by the freedesktop.org
+//! [Common Extended Attributes](https://www.freedesktop.org/wiki/CommonExtendedAttributes/)
+//! specification
+//!
+//! Browsers record where a download comes from in `user.xdg.origin.url` and
+//! `user.xdg.referrer.url`, file managers store comments and tags, and Baloo
+//! stores ratings. All values are UTF-8 strings.
+
+use crate::{getxattr, removexattr, setxattr, Flags, Result};
+use errno::Errno;
+use std::{fmt, path::Path, str::FromStr};
+
+/// EAs defined by the specification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum XdgAttr {
+    /// `user.xdg.comment`, a comment on the file.
+    Comment,
+    /// `user.xdg.origin.url`, the URL the file was downloaded from.
+    OriginUrl,
+    /// `user.xdg.referrer.url`, the page that linked to the download.
+    ReferrerUrl,
+    /// `user.xdg.language`, the language of the content, e.g., `en_US`.
+    Language,
+    /// `user.xdg.creator`, the application that created the file.
+    Creator,
+    /// `user.xdg.publisher`, the application that published the file.
+    Publisher,
+    /// `user.xdg.tags`, comma-separated tags, see [`Tags`].
+    Tags,
+    /// `user.mime_type`, the MIME type of the content.
+    MimeType,
+    /// `user.charset`, the character set of the content.
+    Charset,
+    /// `user.baloo.rating`, a rating from 0 to 10.
+    BalooRating,
+}
+
+impl XdgAttr {
+    /// Returns the EA name of this attribute.
+    pub fn name(self) -> &'static str {
+        match self {
+            XdgAttr::Comment => "user.xdg.comment",
+            XdgAttr::OriginUrl => "user.xdg.origin.url",
+            XdgAttr::ReferrerUrl => "user.xdg.referrer.url",
+            XdgAttr::Language => "user.xdg.language",
+            XdgAttr::Creator => "user.xdg.creator",
+            XdgAttr::Publisher => "user.xdg.publisher",
+            XdgAttr::Tags => "user.xdg.tags",
+            XdgAttr::MimeType => "user.mime_type",
+            XdgAttr::Charset => "user.charset",
+            XdgAttr::BalooRating => "user.baloo.rating",
+        }
+    }
+}
+
+/// Maximum value of `user.baloo.rating`, which counts half stars.
+pub const BALOO_RATING_MAX: u8 = 10;
+
+/// A set of tags, stored as a comma-separated list in `user.xdg.tags`.
+///
+/// Tags keep their insertion order, surrounding whitespace is ignored, and
+/// duplicated or empty tags are dropped when parsing.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Tags {
+    tags: Vec<String>,
+}
+
+impl Tags {
+    /// Creates an empty set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of tags.
+    pub fn len(&self) -> usize {
+        self.tags.len()
+    }
+
+    /// Returns true if there is no tag.
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+
+    /// Returns true if `tag` is in this set.
+    pub fn contains(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag.trim())
+    }
+
+    /// Iterates over the tags.
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.tags.iter().map(String::as_str)
+    }
+
+    /// Adds `tag`, returns false if it was already present. A tag that is
+    /// empty or contains a comma is rejected with `EINVAL`.
+    pub fn insert(&mut self, tag: &str) -> Result<bool> {
+        let tag = tag.trim();
+        if tag.is_empty() || tag.contains(',') {
+            return Err(Errno(libc::EINVAL));
+        }
+        if self.contains(tag) {
+            return Ok(false);
+        }
+        self.tags.push(tag.to_owned());
+        Ok(true)
+    }
+
+    /// Removes `tag`, returns false if it was not present.
+    pub fn remove(&mut self, tag: &str) -> bool {
+        let len = self.tags.len();
+        self.tags.retain(|t| t != tag.trim());
+        self.tags.len() != len
+    }
+}
+
+impl FromStr for Tags {
+    type Err = Errno;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut tags = Tags::new();
+        for tag in s.split(',').filter(|tag| !tag.trim().is_empty()) {
+            tags.insert(tag)?;
+        }
+        Ok(tags)
+    }
+}
+
+impl fmt::Display for Tags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.tags.join(","))
+    }
+}
+
+/// Retrieves `attr` of `path` as a string, `None` if it is not set. If `path`
+/// is a symbolic link, it will be dereferenced.
+pub fn get_text<P: AsRef<Path>>(
+    path: P,
+    attr: XdgAttr,
+) -> Result<Option<String>> {
+    match getxattr(path, attr.name()) {
+        Ok(value) => String::from_utf8(value)
+            .map(Some)
+            .map_err(|_| Errno(libc::EINVAL)),
+        Err(Errno(libc::ENODATA)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Sets `attr` of `path` to `value`. If `path` is a symbolic link, it will be
+/// dereferenced.
+pub fn set_text<P: AsRef<Path>>(
+    path: P,
+    attr: XdgAttr,
+    value: &str,
+) -> Result<()> {
+    setxattr(path, attr.name(), value, Flags::empty())
+}
+
+/// Removes `attr` from `path`. Removing an EA that is not set is not an error.
+pub fn remove<P: AsRef<Path>>(path: P, attr: XdgAttr) -> Result<()> {
+    match removexattr(path, attr.name()) {
+        Err(Errno(libc::ENODATA)) => Ok(()),
+        res => res,
+    }
+}
+
+/// Retrieves the tags of `path`, an empty set if none is set.
+pub fn get_tags<P: AsRef<Path>>(path: P) -> Result<Tags> {
+    match get_text(path, XdgAttr::Tags)? {
+        Some(tags) => tags.parse(),
+        None => Ok(Tags::new()),
+    }
+}
+
+/// Sets the tags of `path`, an empty set removes `user.xdg.tags`.
+pub fn set_tags<P: AsRef<Path>>(path: P, tags: &Tags) -> Result<()> {
+    if tags.is_empty() {
+        remove(path, XdgAttr::Tags)
+    } else {
+        set_text(path, XdgAttr::Tags, &tags.to_string())
+    }
+}
+
+/// Adds `tag` to the tags of `path`, returns false if it was already present.
+pub fn add_tag<P: AsRef<Path>>(path: P, tag: &str) -> Result<bool> {
+    let path = path.as_ref();
+    let mut tags = get_tags(path)?;
+    if !tags.insert(tag)? {
+        return Ok(false);
+    }
+    set_tags(path, &tags)?;
+    Ok(true)
+}
+
+/// Removes `tag` from the tags of `path`, returns false if it was not present.
+/// Removing the last tag removes `user.xdg.tags`.
+pub fn remove_tag<P: AsRef<Path>>(path: P, tag: &str) -> Result<bool> {
+    let path = path.as_ref();
+    let mut tags = get_tags(path)?;
+    if !tags.remove(tag) {
+        return Ok(false);
+    }
+    set_tags(path, &tags)?;
+    Ok(true)
+}
+
+/// Retrieves the Baloo rating of `path`, from 0 to [`BALOO_RATING_MAX`].
+pub fn get_rating<P: AsRef<Path>>(path: P) -> Result<Option<u8>> {
+    get_text(path, XdgAttr::BalooRating)?
+        .map(|rating| match rating.trim().parse::<u8>() {
+            Ok(rating) if rating <= BALOO_RATING_MAX => Ok(rating),
+            _ => Err(Errno(libc::EINVAL)),
+        })
+        .transpose()
+}
+
+/// Sets the Baloo rating of `path`, from 0 to [`BALOO_RATING_MAX`].
+pub fn set_rating<P: AsRef<Path>>(path: P, rating: u8) -> Result<()> {
+    if rating > BALOO_RATING_MAX {
+        return Err(Errno(libc::EINVAL));
+    }
+    set_text(path, XdgAttr::BalooRating, &rating.to_string())
+}
@@ -0,0 +1,172 @@
+use errno::Errno;
+use extattr::apple::{
+    decode_string_array, encode_string_array, FinderInfo, Quarantine, Timespec,
+};
+use std::time::{Duration, UNIX_EPOCH};
+
+#[test]
+fn test_quarantine() {
+    let value = b"0083;5f2b3c4d;Safari;9A8B7C6D-1234-4E5F-8A9B-0C1D2E3F4A5B";
+    let quarantine = Quarantine::from_bytes(value).unwrap();
+    assert_eq!(
+        quarantine,
+        Quarantine {
+            flags: 0x0083,
+            timestamp: 0x5f2b3c4d,
+            agent: "Safari".to_owned(),
+            event_id: Some("9A8B7C6D-1234-4E5F-8A9B-0C1D2E3F4A5B".to_owned()),
+        }
+    );
+    assert_ne!(quarantine.flags & Quarantine::FLAG_DOWNLOAD, 0);
+    assert_eq!(
+        quarantine.time(),
+        Some(UNIX_EPOCH + Duration::from_secs(0x5f2b3c4d))
+    );
+    assert_eq!(quarantine.to_bytes(), value);
+
+    // no event id, trailing NUL
+    let quarantine = Quarantine::from_bytes(b"0001;00000001;curl\0").unwrap();
+    assert_eq!(quarantine.event_id, None);
+    assert_eq!(quarantine.to_string(), "0001;00000001;curl");
+
+    // out of the range of `SystemTime`
+    let quarantine =
+        Quarantine::from_bytes(b"ffff;ffffffffffffffff;x").unwrap();
+    assert_eq!(quarantine.timestamp, u64::MAX);
+    assert_eq!(quarantine.time(), None);
+
+    // agents may be empty, event ids may contain anything
+    let quarantine: Quarantine = "0002;00000001;;a;b".parse().unwrap();
+    assert_eq!(quarantine.agent, "");
+    assert_eq!(quarantine.event_id.as_deref(), Some("a;b"));
+
+    for value in ["0001;00000001", "10000;1;x", "zz;1;x", ";1;x", "+1;1;x"] {
+        assert_eq!(value.parse::<Quarantine>(), Err(Errno(libc::EINVAL)));
+    }
+    assert_eq!(
+        Quarantine::from_bytes(b"\xff;1;x"),
+        Err(Errno(libc::EINVAL))
+    );
+}
+
+#[test]
+fn test_string_array_bytes() {
+    #[rustfmt::skip]
+    let bytes: &[u8] = &[
+        b'b', b'p', b'l', b'i', b's', b't', b'0', b'0',
+        // array of 1 object: object 1
+        0xA1, 0x01,
+        // ASCII string "a"
+        0x51, b'a',
+        // offset table
+        0x08, 0x0A,
+        // trailer
+        0, 0, 0, 0, 0, 0, 1, 1,
+        0, 0, 0, 0, 0, 0, 0, 2,
+        0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 12,
+    ];
+    assert_eq!(decode_string_array(bytes).unwrap(), ["a"]);
+    assert_eq!(encode_string_array(&["a"]), bytes);
+}
+
+#[test]
+fn test_string_array_round_trip() {
+    let long = "https://example.com/".repeat(20);
+    for strings in [
+        vec![],
+        vec![
+            "https://example.com/file.dmg".to_owned(),
+            "https://example.com/".to_owned(),
+        ],
+        vec!["héllo wörld, ünïcode 😀".to_owned(), long],
+        (0..300).map(|i| i.to_string()).collect(),
+    ] {
+        let bytes = encode_string_array(&strings);
+        assert_eq!(decode_string_array(&bytes).unwrap(), strings);
+    }
+}
+
+#[test]
+fn test_string_array_invalid() {
+    let bytes = encode_string_array(&["a", "b"]);
+    assert_eq!(decode_string_array(b"bplist00"), Err(Errno(libc::EINVAL)));
+    assert_eq!(decode_string_array(&bytes[1..]), Err(Errno(libc::EINVAL)));
+    // truncate every possible way, none should panic
+    for len in 0..bytes.len() {
+        let mut truncated = bytes[..len].to_vec();
+        truncated.extend_from_slice(&bytes[bytes.len() - 32..]);
+        let _ = decode_string_array(&truncated);
+    }
+    // top object is not an array
+    let mut bad = bytes.clone();
+    let top = bad.len() - 9;
+    bad[top] = 1;
+    assert_eq!(decode_string_array(&bad), Err(Errno(libc::EINVAL)));
+    // object reference out of range
+    let mut bad = bytes;
+    bad[9] = 9;
+    assert_eq!(decode_string_array(&bad), Err(Errno(libc::EINVAL)));
+}
+
+#[test]
+fn test_finder_info() {
+    let mut bytes = [0_u8; 32];
+    bytes[..8].copy_from_slice(b"TEXTttxt");
+    // kIsInvisible | kHasCustomIcon | red label (6)
+    bytes[8..10].copy_from_slice(&[0x44, 0x0C]);
+    bytes[10..12].copy_from_slice(&[0xFF, 0xFE]);
+    bytes[24..26].copy_from_slice(&[0x01, 0x00]);
+
+    let info = FinderInfo::from_bytes(&bytes).unwrap();
+    assert_eq!(&info.file_type, b"TEXT");
+    assert_eq!(&info.creator, b"ttxt");
+    assert_ne!(info.flags & FinderInfo::FLAG_IS_INVISIBLE, 0);
+    assert_ne!(info.flags & FinderInfo::FLAG_HAS_CUSTOM_ICON, 0);
+    assert_eq!(info.label(), 6);
+    assert_eq!(info.location_v, -2);
+    assert_eq!(info.extended_flags(), 0x0100);
+    assert_eq!(info.to_bytes(), bytes);
+
+    assert_eq!(
+        FinderInfo::from_bytes(&bytes[..31]),
+        Err(Errno(libc::EINVAL))
+    );
+}
+
+#[test]
+fn test_timespec() {
+    #[rustfmt::skip]
+    let bytes = [
+        0x00, 0x5e, 0xd0, 0xb2, 0x00, 0x00, 0x00, 0x00,
+        0x40, 0x42, 0x0f, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+    let timespec = Timespec::from_bytes(&bytes).unwrap();
+    assert_eq!(
+        timespec,
+        Timespec {
+            sec: 3_000_000_000,
+            nsec: 1_000_000,
+        }
+    );
+    assert_eq!(timespec.to_bytes(), bytes);
+    let time = UNIX_EPOCH + Duration::new(3_000_000_000, 1_000_000);
+    assert_eq!(timespec.to_system_time(), time);
+    assert_eq!(Timespec::from_system_time(time), timespec);
+
+    let before = UNIX_EPOCH - Duration::new(1, 250_000_000);
+    let timespec = Timespec::from_system_time(before);
+    assert_eq!(
+        timespec,
+        Timespec {
+            sec: -2,
+            nsec: 750_000_000,
+        }
+    );
+    assert_eq!(timespec.to_system_time(), before);
+
+    let mut bad = bytes;
+    bad[15] = 0x80;
+    assert_eq!(Timespec::from_bytes(&bad), Err(Errno(libc::EINVAL)));
+    assert_eq!(Timespec::from_bytes(&bytes[1..]), Err(Errno(libc::EINVAL)));
+}
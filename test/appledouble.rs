@@ -0,0 +1,179 @@
+use errno::Errno;
+use extattr::appledouble::{
+    parse_entries, sidecar_path, AppleDouble, Entry, ATTR_MAGIC,
+    ENTRY_FINDER_INFO, ENTRY_RESOURCE_FORK,
+};
+use std::{ffi::OsString, path::Path};
+
+/// The sidecar macOS writes for a file with no EA other than a resource fork:
+/// the Finder Info entry reserves room for an empty `ATTR` block.
+fn macos_sidecar(resource_fork: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![0; 0xEE2];
+    bytes[..4].copy_from_slice(&[0x00, 0x05, 0x16, 0x07]);
+    bytes[4..8].copy_from_slice(&[0x00, 0x02, 0x00, 0x00]);
+    bytes[8..24].copy_from_slice(b"Mac OS X        ");
+    bytes[24..26].copy_from_slice(&2_u16.to_be_bytes());
+    for (i, entry) in [[9, 0x32, 0xEB0], [2, 0xEE2, resource_fork.len()]]
+        .iter()
+        .enumerate()
+    {
+        for (j, field) in entry.iter().enumerate() {
+            let at = 26 + i * 12 + j * 4;
+            bytes[at..at + 4].copy_from_slice(&(*field as u32).to_be_bytes());
+        }
+    }
+    bytes[0x54..0x58].copy_from_slice(&ATTR_MAGIC.to_be_bytes());
+    bytes[0x5C..0x60].copy_from_slice(&0xEE2_u32.to_be_bytes());
+    bytes[0x60..0x64].copy_from_slice(&0x78_u32.to_be_bytes());
+    bytes.extend_from_slice(resource_fork);
+    bytes
+}
+
+#[test]
+fn test_parse_macos() {
+    let bytes = macos_sidecar(b"rsrc");
+    assert_eq!(
+        parse_entries(&bytes).unwrap(),
+        [
+            Entry {
+                id: ENTRY_FINDER_INFO,
+                offset: 0x32,
+                length: 0xEB0,
+            },
+            Entry {
+                id: ENTRY_RESOURCE_FORK,
+                offset: 0xEE2,
+                length: 4,
+            },
+        ]
+    );
+    assert_eq!(
+        AppleDouble::from_bytes(&bytes).unwrap(),
+        AppleDouble {
+            finder_info: None,
+            attrs: Vec::new(),
+            resource_fork: Some(b"rsrc".to_vec()),
+        }
+    );
+}
+
+#[test]
+fn test_round_trip() {
+    let mut finder_info = [0; 32];
+    finder_info[..8].copy_from_slice(b"TEXTttxt");
+    let apple_double = AppleDouble {
+        finder_info: Some(finder_info),
+        attrs: vec![
+            (
+                OsString::from("com.apple.quarantine"),
+                b"0083;5f2b3c4d;Safari;".to_vec(),
+            ),
+            (OsString::from("a"), Vec::new()),
+        ],
+        resource_fork: Some(vec![0xAB; 100]),
+    };
+    let bytes = apple_double.to_bytes().unwrap();
+
+    // laid out like macOS does
+    assert_eq!(&bytes[0x32..0x3A], b"TEXTttxt");
+    assert_eq!(&bytes[0x54..0x58], b"ATTR");
+    assert_eq!(u16::from_be_bytes([bytes[0x76], bytes[0x77]]), 2);
+    // the entries: offset, length, flags, name length and name, the data
+    // starts after the second one
+    assert_eq!(&bytes[0x78..0x7C], &0xA8_u32.to_be_bytes());
+    assert_eq!(&bytes[0x80..0x86], b"\x00\x00\x15com");
+    assert_eq!(&bytes[0x98..0x9C], &0xBD_u32.to_be_bytes());
+    assert_eq!(&bytes[0x9C..0xA5], b"\x00\x00\x00\x00\x00\x00\x02a\x00");
+
+    assert_eq!(AppleDouble::from_bytes(&bytes).unwrap(), apple_double);
+    assert_eq!(AppleDouble::default().to_bytes().unwrap().len(), 0x52);
+    assert_eq!(
+        AppleDouble::from_bytes(&AppleDouble::default().to_bytes().unwrap())
+            .unwrap(),
+        AppleDouble::default()
+    );
+}
+
+#[test]
+fn test_invalid() {
+    let bytes = macos_sidecar(b"rsrc");
+    assert_eq!(
+        AppleDouble::from_bytes(&bytes[..bytes.len() - 1]),
+        Err(Errno(libc::EINVAL))
+    );
+    let mut v1 = bytes.clone();
+    v1[5] = 1;
+    assert_eq!(AppleDouble::from_bytes(&v1), Err(Errno(libc::EINVAL)));
+    assert_eq!(AppleDouble::from_bytes(&[]), Err(Errno(libc::EINVAL)));
+
+    let nul = AppleDouble {
+        attrs: vec![(OsString::from("a\0b"), Vec::new())],
+        ..AppleDouble::default()
+    };
+    assert_eq!(nul.to_bytes(), Err(Errno(libc::EINVAL)));
+}
+
+#[test]
+fn test_sidecar_path() {
+    assert_eq!(
+        sidecar_path("dir/file.txt").unwrap(),
+        Path::new("dir/._file.txt")
+    );
+    assert_eq!(sidecar_path("/"), Err(Errno(libc::EINVAL)));
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[test]
+fn test_capture_apply() {
+    use extattr::{
+        appledouble::capture, lgetxattr, lsetxattr, portable::Target, Flags,
+    };
+    use std::fs::File;
+
+    let dir = tempfile::tempdir_in("./").unwrap();
+    let src = dir.path().join("src");
+    let dst = dir.path().join("dst");
+    File::create(&src).unwrap();
+    File::create(&dst).unwrap();
+
+    let mut finder_info = [0; 32];
+    finder_info[8] = 0x40;
+    match lsetxattr(
+        &src,
+        "user.com.apple.FinderInfo",
+        finder_info,
+        Flags::empty(),
+    ) {
+        // EA not supported
+        Err(Errno(libc::ENOTSUP)) => return,
+        res => res.unwrap(),
+    }
+    lsetxattr(&src, "user.com.apple.ResourceFork", b"rsrc", Flags::empty())
+        .unwrap();
+    lsetxattr(&src, "user.foo", b"bar", Flags::empty()).unwrap();
+
+    let apple_double = capture(Target::Link(&src)).unwrap();
+    assert_eq!(
+        apple_double,
+        AppleDouble {
+            finder_info: Some(finder_info),
+            attrs: vec![(OsString::from("foo"), b"bar".to_vec())],
+            resource_fork: Some(b"rsrc".to_vec()),
+        }
+    );
+
+    let bytes = apple_double.to_bytes().unwrap();
+    AppleDouble::from_bytes(&bytes)
+        .unwrap()
+        .apply(Target::Link(&dst))
+        .unwrap();
+    assert_eq!(lgetxattr(&dst, "user.foo").unwrap(), b"bar");
+    assert_eq!(
+        lgetxattr(&dst, "user.com.apple.ResourceFork").unwrap(),
+        b"rsrc"
+    );
+    assert_eq!(
+        lgetxattr(&dst, "user.com.apple.FinderInfo").unwrap(),
+        finder_info
+    );
+}
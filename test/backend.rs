@@ -0,0 +1,217 @@
+use errno::Errno;
+use extattr::{
+    backend::{
+        FileKind, MemoryBackend, MemoryConfig, SyscallBackend, XattrBackend,
+    },
+    portable::Target,
+    Flags,
+};
+use std::{
+    ffi::{OsStr, OsString},
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+fn name(name: &str) -> &OsStr {
+    OsStr::new(name)
+}
+
+/// Runs the same scenario on any backend, `path` being a regular file.
+fn check_semantics<B: XattrBackend>(backend: &B, path: &Path) {
+    let target = Target::Path(path);
+    let foo = name("user.foo");
+
+    assert_eq!(backend.get(target, foo), Err(Errno(libc::ENODATA)));
+    assert_eq!(
+        backend.set(target, foo, b"1", Flags::XATTR_REPLACE),
+        Err(Errno(libc::ENODATA))
+    );
+    backend.set(target, foo, b"1", Flags::XATTR_CREATE).unwrap();
+    assert_eq!(
+        backend.set(target, foo, b"2", Flags::XATTR_CREATE),
+        Err(Errno(libc::EEXIST))
+    );
+    backend
+        .set(target, foo, b"2", Flags::XATTR_REPLACE)
+        .unwrap();
+    assert_eq!(backend.get(target, foo).unwrap(), b"2");
+    assert!(backend.list(target).unwrap().contains(&OsString::from(foo)));
+
+    assert_eq!(
+        backend.set(target, name("unknown.foo"), b"", Flags::empty()),
+        Err(Errno(libc::ENOTSUP))
+    );
+    assert_eq!(
+        backend.set(target, name(""), b"", Flags::empty()),
+        Err(Errno(libc::ERANGE))
+    );
+    let long = format!("user.{}", "a".repeat(251));
+    assert_eq!(
+        backend.get(target, OsStr::new(&long)),
+        Err(Errno(libc::ERANGE))
+    );
+    assert_eq!(
+        backend.set(target, foo, &vec![0; 65537], Flags::empty()),
+        Err(Errno(libc::E2BIG))
+    );
+
+    backend.remove(target, foo).unwrap();
+    assert_eq!(backend.remove(target, foo), Err(Errno(libc::ENODATA)));
+}
+
+#[test]
+fn test_memory_semantics() {
+    let backend = MemoryBackend::default();
+    backend.create("file", FileKind::File).unwrap();
+    check_semantics(&backend, Path::new("file"));
+
+    assert_eq!(
+        backend.create("file", FileKind::File),
+        Err(Errno(libc::EEXIST))
+    );
+    assert_eq!(
+        backend.get(Target::Path(Path::new("missing")), name("user.foo")),
+        Err(Errno(libc::ENOENT))
+    );
+}
+
+#[test]
+fn test_syscall_semantics() {
+    let dir = tempfile::tempdir_in("./").unwrap();
+    let path = dir.path().join("file");
+    File::create(&path).unwrap();
+    match SyscallBackend.set(
+        Target::Path(&path),
+        name("user.probe"),
+        b"",
+        Flags::empty(),
+    ) {
+        // EA not supported
+        Err(Errno(libc::ENOTSUP)) => return,
+        res => res.unwrap(),
+    }
+    check_semantics(&SyscallBackend, &path);
+}
+
+#[test]
+fn test_memory_symlink_and_fd() {
+    let backend = MemoryBackend::default();
+    backend.create("dir", FileKind::Directory).unwrap();
+    backend.create("dir/file", FileKind::File).unwrap();
+    backend
+        .create("dir/link", FileKind::Symlink(PathBuf::from("file")))
+        .unwrap();
+    backend
+        .create("loop", FileKind::Symlink(PathBuf::from("loop")))
+        .unwrap();
+
+    let foo = name("user.foo");
+    let link = Path::new("dir/link");
+    backend
+        .set(Target::Path(link), foo, b"bar", Flags::empty())
+        .unwrap();
+    assert_eq!(
+        backend
+            .get(Target::Path(Path::new("dir/file")), foo)
+            .unwrap(),
+        b"bar"
+    );
+    // user EAs are not allowed on symbolic links
+    assert_eq!(
+        backend.get(Target::Link(link), foo),
+        Err(Errno(libc::ENODATA))
+    );
+    assert_eq!(
+        backend.set(Target::Link(link), foo, b"", Flags::empty()),
+        Err(Errno(libc::EPERM))
+    );
+    assert_eq!(
+        backend.list(Target::Link(link)).unwrap(),
+        Vec::<OsString>::new()
+    );
+    assert_eq!(
+        backend.get(Target::Path(Path::new("loop")), foo),
+        Err(Errno(libc::ELOOP))
+    );
+
+    let fd = backend.open(link).unwrap();
+    assert_eq!(backend.get(Target::Fd(fd), foo).unwrap(), b"bar");
+    backend.close(fd).unwrap();
+    assert_eq!(backend.get(Target::Fd(fd), foo), Err(Errno(libc::EBADF)));
+    assert_eq!(backend.close(fd), Err(Errno(libc::EBADF)));
+}
+
+#[test]
+fn test_memory_namespaces() {
+    let unprivileged = MemoryBackend::default();
+    let privileged = MemoryBackend::new(MemoryConfig {
+        privileged: true,
+        ..MemoryConfig::default()
+    });
+    for backend in [&unprivileged, &privileged] {
+        backend.create("file", FileKind::File).unwrap();
+    }
+    let target = Target::Path(Path::new("file"));
+    let trusted = name("trusted.foo");
+    let security = name("security.foo");
+
+    for backend in [&unprivileged, &privileged] {
+        assert_eq!(
+            backend.set(target, name("system.foo"), b"", Flags::empty()),
+            Err(Errno(libc::ENOTSUP))
+        );
+    }
+
+    assert_eq!(
+        unprivileged.set(target, trusted, b"", Flags::empty()),
+        Err(Errno(libc::EPERM))
+    );
+    assert_eq!(
+        unprivileged.set(target, security, b"", Flags::empty()),
+        Err(Errno(libc::EPERM))
+    );
+    privileged
+        .set(target, trusted, b"1", Flags::empty())
+        .unwrap();
+    privileged
+        .set(target, security, b"2", Flags::empty())
+        .unwrap();
+    assert_eq!(
+        privileged.list(target).unwrap(),
+        [
+            OsString::from("security.foo"),
+            OsString::from("trusted.foo")
+        ]
+    );
+}
+
+#[test]
+fn test_memory_limits() {
+    let backend = MemoryBackend::new(MemoryConfig {
+        max_value_size: 8,
+        max_total_size: 32,
+        ..MemoryConfig::default()
+    });
+    backend.create("file", FileKind::File).unwrap();
+    let target = Target::Path(Path::new("file"));
+
+    assert_eq!(
+        backend.set(target, name("user.a"), &[0; 9], Flags::empty()),
+        Err(Errno(libc::E2BIG))
+    );
+    backend
+        .set(target, name("user.a"), &[0; 8], Flags::empty())
+        .unwrap();
+    backend
+        .set(target, name("user.b"), &[0; 8], Flags::empty())
+        .unwrap();
+    // 2 * (6 + 8) + 6 + 8 > 32
+    assert_eq!(
+        backend.set(target, name("user.c"), &[0; 8], Flags::empty()),
+        Err(Errno(libc::ENOSPC))
+    );
+    // replacing a value only counts the new one
+    backend
+        .set(target, name("user.b"), &[1; 8], Flags::empty())
+        .unwrap();
+}
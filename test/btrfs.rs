@@ -0,0 +1,122 @@
+use errno::Errno;
+use extattr::btrfs::{
+    get_compression, is_btrfs, set_compression, set_compression_recursive,
+    Compression,
+};
+use std::{
+    fs::{self, File},
+    os::unix::fs::symlink,
+};
+
+#[test]
+fn test_compression_parse() {
+    for (s, compression) in [
+        ("zlib", Compression::Zlib(None)),
+        ("zlib:9", Compression::Zlib(Some(9))),
+        ("lzo", Compression::Lzo),
+        ("zstd", Compression::Zstd(None)),
+        ("zstd:3", Compression::Zstd(Some(3))),
+        ("none", Compression::Disabled),
+    ] {
+        assert_eq!(s.parse::<Compression>().unwrap(), compression);
+        assert_eq!(compression.to_string(), s);
+    }
+    assert_eq!("no".parse::<Compression>().unwrap(), Compression::Disabled);
+
+    for s in [
+        "", "gzip", "zlib:", "zlib:0", "zlib:10", "zstd:16", "zstd:-1",
+        "lzo:1", "none:1", "ZLIB",
+    ] {
+        assert_eq!(s.parse::<Compression>(), Err(Errno(libc::EINVAL)));
+    }
+}
+
+#[test]
+fn test_validate() {
+    assert!(Compression::Zstd(Some(15)).validate().is_ok());
+    assert_eq!(
+        Compression::Zstd(Some(0)).validate(),
+        Err(Errno(libc::EINVAL))
+    );
+    assert_eq!(
+        Compression::Zlib(Some(10)).validate(),
+        Err(Errno(libc::EINVAL))
+    );
+}
+
+#[test]
+fn test_is_btrfs_no_follow() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let link = temp_dir.path().join("test_is_btrfs_no_follow");
+    symlink("/nonexistent", &link).unwrap();
+
+    // the dangling link itself is checked
+    assert_eq!(is_btrfs(&link).unwrap(), is_btrfs(temp_dir.path()).unwrap());
+    assert_eq!(
+        is_btrfs(temp_dir.path().join("missing")),
+        Err(Errno(libc::ENOENT))
+    );
+}
+
+#[test]
+fn test_compression_on_file() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let temp_file_path = temp_dir.path().join("test_compression_on_file");
+    File::create(&temp_file_path).unwrap();
+
+    // an invalid level is rejected before looking at the file system
+    assert_eq!(
+        set_compression(&temp_file_path, Some(Compression::Zlib(Some(42)))),
+        Err(Errno(libc::EINVAL))
+    );
+
+    if !is_btrfs(&temp_file_path).unwrap() {
+        assert_eq!(
+            set_compression(&temp_file_path, Some(Compression::Zstd(None))),
+            Err(Errno(libc::ENOTSUP))
+        );
+        assert_eq!(get_compression(&temp_file_path), Err(Errno(libc::ENOTSUP)));
+        return;
+    }
+
+    assert_eq!(get_compression(&temp_file_path).unwrap(), None);
+    set_compression(&temp_file_path, Some(Compression::Zstd(None))).unwrap();
+    assert_eq!(
+        get_compression(&temp_file_path).unwrap(),
+        Some(Compression::Zstd(None))
+    );
+    set_compression(&temp_file_path, None).unwrap();
+    set_compression(&temp_file_path, None).unwrap();
+    assert_eq!(get_compression(&temp_file_path).unwrap(), None);
+}
+
+#[test]
+fn test_compression_recursive() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let root = temp_dir.path().join("test_compression_recursive");
+    fs::create_dir_all(root.join("a/b")).unwrap();
+    File::create(root.join("a/b/file")).unwrap();
+    std::os::unix::fs::symlink("/nonexistent", root.join("a/link")).unwrap();
+
+    let compression = Some(Compression::Lzo);
+    if !is_btrfs(&root).unwrap() {
+        assert_eq!(
+            set_compression_recursive(&root, compression),
+            Err(Errno(libc::ENOTSUP))
+        );
+        return;
+    }
+
+    set_compression_recursive(&root, compression).unwrap();
+    for path in [root.clone(), root.join("a/b"), root.join("a/b/file")] {
+        assert_eq!(get_compression(&path).unwrap(), compression);
+    }
+}
+
+#[test]
+fn test_is_btrfs_nonexistent() {
+    assert_eq!(
+        is_btrfs("/nonexistent/test_is_btrfs_nonexistent"),
+        Err(Errno(libc::ENOENT))
+    );
+}
@@ -0,0 +1,119 @@
+use errno::Errno;
+use extattr::ceph::{
+    get_dir_layout, parse_counter, parse_quota, parse_rctime, set_pin, DirStat,
+    Layout, Pin,
+};
+use std::{
+    fs::File,
+    time::{Duration, UNIX_EPOCH},
+};
+
+#[test]
+fn test_layout() {
+    let value = b"stripe_unit=4194304 stripe_count=1 object_size=4194304 \
+                  pool=cephfs_data";
+    let layout = Layout::from_bytes(value).unwrap();
+    assert_eq!(
+        layout,
+        Layout {
+            stripe_unit: 4194304,
+            stripe_count: 1,
+            object_size: 4194304,
+            pool: "cephfs_data".to_owned(),
+            pool_namespace: None,
+        }
+    );
+    assert_eq!(layout.to_bytes(), value);
+
+    let layout = Layout::from_bytes(
+        b"pool=3 pool_namespace=ns object_size=65536 stripe_count=4 \
+          stripe_unit=65536\0",
+    )
+    .unwrap();
+    assert_eq!(layout.pool, "3");
+    assert_eq!(layout.pool_namespace.as_deref(), Some("ns"));
+    assert_eq!(
+        layout.to_string(),
+        "stripe_unit=65536 stripe_count=4 object_size=65536 pool=3 \
+         pool_namespace=ns"
+    );
+}
+
+#[test]
+fn test_layout_invalid() {
+    for value in [
+        "",
+        "stripe_unit=1 stripe_count=1 object_size=1",
+        "stripe_unit=1 stripe_count=1 object_size=1 pool=",
+        "stripe_unit=x stripe_count=1 object_size=1 pool=p",
+        "stripe_unit=-1 stripe_count=1 object_size=1 pool=p",
+        "stripe_unit=1 stripe_unit=1 stripe_count=1 object_size=1 pool=p",
+        "stripe_unit=1 stripe_count=1 object_size=1 pool=p foo=bar",
+        "stripe_unit=1 stripe_count=1 object_size=1 pool",
+    ] {
+        assert_eq!(value.parse::<Layout>(), Err(Errno(libc::EINVAL)));
+    }
+    assert_eq!(Layout::from_bytes(b"\xff"), Err(Errno(libc::EINVAL)));
+}
+
+#[test]
+fn test_counters() {
+    assert_eq!(DirStat::Rbytes.name(), "ceph.dir.rbytes");
+    assert_eq!(DirStat::Rentries.name(), "ceph.dir.rentries");
+    assert_eq!(parse_counter(b"123456789012").unwrap(), 123456789012);
+    assert_eq!(parse_counter(b"7\n").unwrap(), 7);
+    assert_eq!(parse_counter(b""), Err(Errno(libc::EINVAL)));
+    assert_eq!(parse_counter(b"-1"), Err(Errno(libc::EINVAL)));
+    assert_eq!(
+        parse_counter(b"99999999999999999999"),
+        Err(Errno(libc::EINVAL))
+    );
+
+    assert_eq!(parse_quota(b"0").unwrap(), None);
+    assert_eq!(parse_quota(b"1073741824").unwrap(), Some(1 << 30));
+}
+
+#[test]
+fn test_rctime() {
+    assert_eq!(
+        parse_rctime(b"1600000000.090000000").unwrap(),
+        UNIX_EPOCH + Duration::new(1_600_000_000, 90_000_000)
+    );
+    assert_eq!(
+        parse_rctime(b"1600000000").unwrap(),
+        UNIX_EPOCH + Duration::from_secs(1_600_000_000)
+    );
+    assert_eq!(parse_rctime(b"1.1000000000"), Err(Errno(libc::EINVAL)));
+    assert_eq!(parse_rctime(b"1."), Err(Errno(libc::EINVAL)));
+    assert_eq!(
+        parse_rctime(b"18446744073709551615.999999999"),
+        Err(Errno(libc::EINVAL))
+    );
+    assert_eq!(
+        parse_rctime(b"1.5").unwrap(),
+        UNIX_EPOCH + Duration::from_millis(1500)
+    );
+}
+
+#[test]
+fn test_pin() {
+    assert_eq!(Pin::from_bytes(b"-1").unwrap(), Pin::Unpinned);
+    assert_eq!(Pin::from_bytes(b"2").unwrap(), Pin::Rank(2));
+    assert_eq!(Pin::Unpinned.to_bytes(), b"-1");
+    assert_eq!(Pin::Rank(10).to_bytes(), b"10");
+    assert_eq!(Pin::from_bytes(b"-2"), Err(Errno(libc::EINVAL)));
+}
+
+#[test]
+fn test_not_cephfs() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let temp_file_path = temp_dir.path().join("test_not_cephfs");
+    File::create(&temp_file_path).unwrap();
+
+    // other file systems do not know the `ceph.` prefix
+    assert_eq!(
+        set_pin(&temp_file_path, Pin::Rank(1)),
+        Err(Errno(libc::ENOTSUP))
+    );
+    assert_eq!(get_dir_layout(&temp_file_path), Err(Errno(libc::ENOTSUP)));
+}
@@ -0,0 +1,189 @@
+use errno::Errno;
+use extattr::{
+    cgroup::{
+        find_unit_cgroup, get_invocation_id, is_delegated, is_marked,
+        set_invocation_id, set_marker, slice_path, CgroupMarker, CgroupPrefix,
+        Id128,
+    },
+    getxattr, setxattr, Flags,
+};
+use std::{fs, os::unix::fs::PermissionsExt, path::Path};
+
+#[test]
+fn test_names() {
+    assert_eq!(
+        CgroupMarker::Delegate.name(CgroupPrefix::Trusted),
+        "trusted.delegate"
+    );
+    assert_eq!(
+        CgroupMarker::OomdOmit.name(CgroupPrefix::User),
+        "user.oomd_omit"
+    );
+}
+
+#[test]
+fn test_id128() {
+    let id: Id128 = "0123456789abcdef0123456789ABCDEF".parse().unwrap();
+    assert_eq!(id.0[0], 0x01);
+    assert_eq!(id.0[15], 0xef);
+    assert_eq!(id.to_string(), "0123456789abcdef0123456789abcdef");
+    assert_eq!(
+        "01234567-89ab-cdef-0123-456789abcdef"
+            .parse::<Id128>()
+            .unwrap(),
+        id
+    );
+    assert_eq!(Id128::from_bytes(&id.to_bytes()).unwrap(), id);
+
+    for s in [
+        "",
+        "0123456789abcdef0123456789abcde",
+        "0123456789abcdef0123456789abcdeg",
+        "01234567-89ab-cdef-0123456789abcdef-",
+        "+123456789abcdef0123456789abcdef",
+    ] {
+        assert_eq!(s.parse::<Id128>(), Err(Errno(libc::EINVAL)));
+    }
+}
+
+#[test]
+fn test_slice_path() {
+    assert_eq!(slice_path("-.slice").unwrap(), Path::new(""));
+    assert_eq!(
+        slice_path("system.slice").unwrap(),
+        Path::new("system.slice")
+    );
+    assert_eq!(
+        slice_path("user-1000.slice").unwrap(),
+        Path::new("user.slice/user-1000.slice")
+    );
+    assert_eq!(
+        slice_path("a-b-c.slice").unwrap(),
+        Path::new("a.slice/a-b.slice/a-b-c.slice")
+    );
+    for slice in [
+        "foo.service",
+        ".slice",
+        "-a.slice",
+        "a--b.slice",
+        "a-.slice",
+    ] {
+        assert_eq!(slice_path(slice), Err(Errno(libc::EINVAL)));
+    }
+}
+
+#[test]
+fn test_find_unit_cgroup() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let root = temp_dir.path();
+    for dir in [
+        "system.slice/sshd.service",
+        "user.slice/user-1000.slice/user@1000.service/app.slice/sshd.service",
+        "system.slice/_cpu.service",
+        "system.slice/docker.service/payload",
+    ] {
+        fs::create_dir_all(root.join(dir)).unwrap();
+    }
+    fs::write(root.join("system.slice/cgroup.procs"), "").unwrap();
+
+    assert_eq!(
+        find_unit_cgroup(root, "sshd.service").unwrap(),
+        Some(root.join("system.slice/sshd.service"))
+    );
+    assert_eq!(
+        find_unit_cgroup(root, "app.slice").unwrap(),
+        Some(
+            root.join("user.slice/user-1000.slice/user@1000.service/app.slice")
+        )
+    );
+    assert_eq!(
+        find_unit_cgroup(root, "cpu.service").unwrap(),
+        Some(root.join("system.slice/_cpu.service"))
+    );
+    assert_eq!(find_unit_cgroup(root, "nope.service").unwrap(), None);
+
+    // an unreadable subtree does not stop the search, root can read it anyway
+    let unreadable = root.join("system.slice/docker.service");
+    fs::set_permissions(&unreadable, fs::Permissions::from_mode(0o000))
+        .unwrap();
+    assert_eq!(
+        find_unit_cgroup(root, "app.slice").unwrap(),
+        Some(
+            root.join("user.slice/user-1000.slice/user@1000.service/app.slice")
+        )
+    );
+    fs::set_permissions(&unreadable, fs::Permissions::from_mode(0o755))
+        .unwrap();
+    assert_eq!(
+        find_unit_cgroup(root.join("missing"), "sshd.service"),
+        Err(Errno(libc::ENOENT))
+    );
+    assert_eq!(
+        find_unit_cgroup(root, "cgroup.procs"),
+        Err(Errno(libc::EINVAL))
+    );
+    assert_eq!(
+        find_unit_cgroup(root, "a/b.service"),
+        Err(Errno(libc::EINVAL))
+    );
+}
+
+#[test]
+fn test_markers() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let cgroup = temp_dir.path().join("test_markers.service");
+    fs::create_dir(&cgroup).unwrap();
+
+    assert!(!is_delegated(&cgroup).unwrap());
+    match set_marker(&cgroup, CgroupPrefix::User, CgroupMarker::Delegate, true)
+    {
+        // EA not supported
+        Err(Errno(libc::ENOTSUP)) => return,
+        res => res.unwrap(),
+    }
+    assert_eq!(getxattr(&cgroup, "user.delegate").unwrap(), b"1");
+    assert!(is_delegated(&cgroup).unwrap());
+    assert!(!is_marked(&cgroup, CgroupMarker::OomdAvoid).unwrap());
+
+    set_marker(&cgroup, CgroupPrefix::User, CgroupMarker::Delegate, false)
+        .unwrap();
+    set_marker(&cgroup, CgroupPrefix::User, CgroupMarker::Delegate, false)
+        .unwrap();
+    assert!(!is_delegated(&cgroup).unwrap());
+
+    setxattr(&cgroup, "user.oomd_omit", "yes", Flags::empty()).unwrap();
+    assert!(is_marked(&cgroup, CgroupMarker::OomdOmit).unwrap());
+    setxattr(&cgroup, "user.oomd_omit", "maybe", Flags::empty()).unwrap();
+    assert_eq!(
+        is_marked(&cgroup, CgroupMarker::OomdOmit),
+        Err(Errno(libc::EINVAL))
+    );
+
+    // `trusted.*` takes precedence
+    match setxattr(&cgroup, "trusted.oomd_omit", "0", Flags::empty()) {
+        // Not privileged
+        Err(Errno(libc::EPERM)) => return,
+        res => res.unwrap(),
+    }
+    assert!(!is_marked(&cgroup, CgroupMarker::OomdOmit).unwrap());
+}
+
+#[test]
+fn test_invocation_id() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let cgroup = temp_dir.path().join("test_invocation_id.service");
+    fs::create_dir(&cgroup).unwrap();
+
+    assert_eq!(get_invocation_id(&cgroup).unwrap(), None);
+    let id = Id128([0xab; 16]);
+    match set_invocation_id(&cgroup, CgroupPrefix::User, id) {
+        // EA not supported
+        Err(Errno(libc::ENOTSUP)) => return,
+        res => res.unwrap(),
+    }
+    assert_eq!(
+        getxattr(&cgroup, "user.invocation_id").unwrap(),
+        b"abababababababababababababababab"
+    );
+    assert_eq!(get_invocation_id(&cgroup).unwrap(), Some(id));
+}
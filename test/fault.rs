@@ -0,0 +1,175 @@
+use errno::Errno;
+use extattr::{
+    backend::{FileKind, MemoryBackend, XattrBackend},
+    fault::{Action, Fault, FaultBackend, Op, Trigger},
+    portable::Target,
+    Flags,
+};
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+
+fn backend() -> FaultBackend<MemoryBackend> {
+    let inner = MemoryBackend::default();
+    inner.create("a", FileKind::File).unwrap();
+    inner.create("b", FileKind::File).unwrap();
+    FaultBackend::new(inner)
+}
+
+fn target(path: &str) -> Target<'_> {
+    Target::Path(Path::new(path))
+}
+
+fn name(name: &str) -> &OsStr {
+    OsStr::new(name)
+}
+
+/// Sets a value, retrying on `EINTR` like code under test should.
+fn set_retrying<B: XattrBackend>(
+    backend: &B,
+    value: &[u8],
+) -> Result<(), Errno> {
+    loop {
+        match backend.set(target("a"), name("user.foo"), value, Flags::empty())
+        {
+            Err(Errno(libc::EINTR)) => continue,
+            res => return res,
+        }
+    }
+}
+
+#[test]
+fn test_filters() {
+    let backend = backend();
+    backend.inject(Fault {
+        op: Some(Op::Set),
+        name: Some("user.full".into()),
+        path: Some(PathBuf::from("a")),
+        ..Fault::new(Trigger::Always, Action::Fail(Errno(libc::ENOSPC)))
+    });
+
+    let full = name("user.full");
+    assert_eq!(
+        backend.set(target("a"), full, b"", Flags::empty()),
+        Err(Errno(libc::ENOSPC))
+    );
+    backend.set(target("b"), full, b"", Flags::empty()).unwrap();
+    backend
+        .set(target("a"), name("user.other"), b"", Flags::empty())
+        .unwrap();
+    assert_eq!(backend.get(target("a"), full), Err(Errno(libc::ENODATA)));
+    assert_eq!(backend.injected(), 1);
+
+    backend.clear();
+    backend.set(target("a"), full, b"", Flags::empty()).unwrap();
+}
+
+#[test]
+fn test_counting_triggers() {
+    let backend = backend();
+    backend.inject(Fault::new(
+        Trigger::FirstN(3),
+        Action::Fail(Errno(libc::EINTR)),
+    ));
+    set_retrying(&backend, b"1").unwrap();
+    assert_eq!(backend.injected(), 3);
+
+    backend.clear();
+    backend.inject(Fault {
+        op: Some(Op::List),
+        ..Fault::new(Trigger::Nth(2), Action::Fail(Errno(libc::EDQUOT)))
+    });
+    backend.list(target("a")).unwrap();
+    assert_eq!(backend.list(target("a")), Err(Errno(libc::EDQUOT)));
+    backend.list(target("a")).unwrap();
+}
+
+#[test]
+fn test_predicate() {
+    let backend = backend();
+    backend.inject(Fault::new(
+        Trigger::Predicate(Box::new(
+            |call| matches!(call.name, Some(name) if name.len() > 8),
+        )),
+        Action::Fail(Errno(libc::EPERM)),
+    ));
+    backend
+        .set(target("a"), name("user.a"), b"", Flags::empty())
+        .unwrap();
+    assert_eq!(
+        backend.remove(target("a"), name("user.long")),
+        Err(Errno(libc::EPERM))
+    );
+}
+
+#[test]
+fn test_probability_is_deterministic() {
+    let run = |seed| {
+        let backend = FaultBackend::with_seed(MemoryBackend::default(), seed);
+        backend.inner().create("a", FileKind::File).unwrap();
+        backend.inject(Fault::new(
+            Trigger::Probability(0.5),
+            Action::Fail(Errno(libc::EIO)),
+        ));
+        (0..64)
+            .map(|_| backend.list(target("a")).is_err())
+            .collect::<Vec<_>>()
+    };
+
+    let failures = run(42);
+    assert_eq!(failures, run(42));
+    let count = failures.iter().filter(|failed| **failed).count();
+    assert!(count > 0 && count < 64);
+
+    let backend = backend();
+    backend.inject(Fault::new(
+        Trigger::Probability(0.0),
+        Action::Fail(Errno(libc::EIO)),
+    ));
+    for _ in 0..64 {
+        backend.list(target("a")).unwrap();
+    }
+}
+
+#[test]
+fn test_resize_value() {
+    let backend = backend();
+    let foo = name("user.foo");
+    backend
+        .set(target("a"), foo, b"short", Flags::empty())
+        .unwrap();
+    backend.inject(Fault {
+        op: Some(Op::Get),
+        ..Fault::new(
+            Trigger::FirstN(1),
+            Action::ResizeValue(b"much longer".to_vec()),
+        )
+    });
+
+    // the value grew after the probe, a retry sees the new one
+    assert_eq!(backend.get(target("a"), foo), Err(Errno(libc::ERANGE)));
+    assert_eq!(backend.get(target("a"), foo).unwrap(), b"much longer");
+
+    backend.inject(Fault::new(
+        Trigger::Always,
+        Action::ResizeValue(b"tiny".to_vec()),
+    ));
+    assert_eq!(backend.get(target("a"), foo).unwrap(), b"tiny");
+    // other operations are not affected, nor counted
+    let injected = backend.injected();
+    backend.remove(target("a"), foo).unwrap();
+    assert_eq!(backend.injected(), injected);
+
+    backend.clear();
+    backend.set(target("a"), foo, b"1", Flags::empty()).unwrap();
+    let injected = backend.injected();
+    backend.inject(Fault::new(
+        Trigger::Nth(2),
+        Action::ResizeValue(b"12".to_vec()),
+    ));
+    backend.list(target("a")).unwrap();
+    backend.get(target("a"), foo).unwrap();
+    assert_eq!(backend.get(target("a"), foo), Err(Errno(libc::ERANGE)));
+    assert_eq!(backend.injected(), injected + 1);
+}
@@ -0,0 +1,261 @@
+use errno::Errno;
+use extattr::{
+    ima::{
+        file_digest, getevm, getima, verify_ima, verify_ima_digest, EvmXattr,
+        HashAlgo, ImaXattr, Signature, XATTR_NAME_EVM, XATTR_NAME_IMA,
+    },
+    setxattr, Flags,
+};
+use std::{fs::File, io::Write};
+
+/// SHA-256 of "hello"
+const HELLO_SHA256: [u8; 32] = [
+    0x2c, 0xf2, 0x4d, 0xba, 0x5f, 0xb0, 0xa3, 0x0e, 0x26, 0xe8, 0x3b, 0x2a,
+    0xc5, 0xb9, 0xe2, 0x9e, 0x1b, 0x16, 0x1e, 0x5c, 0x1f, 0xa7, 0x42, 0x5e,
+    0x73, 0x04, 0x33, 0x62, 0x93, 0x8b, 0x98, 0x24,
+];
+
+/// SHA-1 of "hello"
+const HELLO_SHA1: [u8; 20] = [
+    0xaa, 0xf4, 0xc6, 0x1d, 0xdc, 0xc5, 0xe8, 0xa2, 0xda, 0xbe, 0xde, 0x0f,
+    0x3b, 0x48, 0x2c, 0xd9, 0xae, 0xa9, 0x43, 0x4d,
+];
+
+fn signature_bytes(ty: u8) -> Vec<u8> {
+    let mut bytes = vec![ty, 2, 4, 0xde, 0xad, 0xbe, 0xef, 0, 3];
+    bytes.extend_from_slice(&[1, 2, 3]);
+    bytes
+}
+
+#[test]
+fn test_hash_algo() {
+    assert_eq!(HashAlgo::from_id(4).unwrap(), HashAlgo::Sha256);
+    assert_eq!(HashAlgo::Sha256.id(), 4);
+    assert_eq!(HashAlgo::from_id(22).unwrap(), HashAlgo::Sha3_512);
+    assert_eq!(HashAlgo::Sha3_512.digest_size(), 64);
+    assert_eq!(HashAlgo::from_id(23), Err(Errno(libc::EINVAL)));
+}
+
+#[test]
+fn test_ima_xattr_digest() {
+    let mut bytes = vec![0x01];
+    bytes.extend_from_slice(&HELLO_SHA1);
+    let ima = ImaXattr::from_bytes(&bytes).unwrap();
+
+    assert_eq!(ima, ImaXattr::Digest(HELLO_SHA1.to_vec()));
+    assert_eq!(ima.digest(), Some((HashAlgo::Sha1, &HELLO_SHA1[..])));
+    assert_eq!(ima.to_bytes().unwrap(), bytes);
+    // a 17-byte value is a legacy MD5 digest
+    let md5 = [0x5a; 16];
+    let mut bytes = vec![0x01];
+    bytes.extend_from_slice(&md5);
+    let ima = ImaXattr::from_bytes(&bytes).unwrap();
+    assert_eq!(ima, ImaXattr::LegacyMd5(md5.to_vec()));
+    assert_eq!(ima.digest(), Some((HashAlgo::Md5, &md5[..])));
+    assert_eq!(ima.to_bytes().unwrap(), bytes);
+    assert_eq!(
+        ImaXattr::LegacyMd5(vec![0; 20]).to_bytes(),
+        Err(Errno(libc::EINVAL))
+    );
+    assert_eq!(ImaXattr::from_bytes(&bytes[..16]), Err(Errno(libc::EINVAL)));
+}
+
+#[test]
+fn test_ima_xattr_digest_ng() {
+    let mut bytes = vec![0x04, 0x04];
+    bytes.extend_from_slice(&HELLO_SHA256);
+    let ima = ImaXattr::from_bytes(&bytes).unwrap();
+
+    assert_eq!(
+        ima,
+        ImaXattr::DigestNg {
+            algo: HashAlgo::Sha256,
+            digest: HELLO_SHA256.to_vec()
+        }
+    );
+    assert_eq!(ima.to_bytes().unwrap(), bytes);
+
+    // digest size does not match the algorithm
+    bytes.pop();
+    assert_eq!(ImaXattr::from_bytes(&bytes), Err(Errno(libc::EINVAL)));
+    assert_eq!(
+        ImaXattr::Digest(vec![0; 19]).to_bytes(),
+        Err(Errno(libc::EINVAL))
+    );
+    let ima = ImaXattr::DigestNg {
+        algo: HashAlgo::Sha256,
+        digest: bytes[2..].to_vec(),
+    };
+    assert_eq!(ima.to_bytes(), Err(Errno(libc::EINVAL)));
+}
+
+#[test]
+fn test_ima_xattr_signature() {
+    let bytes = signature_bytes(0x03);
+    let ima = ImaXattr::from_bytes(&bytes).unwrap();
+    let sig = Signature {
+        version: 2,
+        hash_algo: HashAlgo::Sha256,
+        keyid: 0xdeadbeef,
+        signature: vec![1, 2, 3],
+    };
+
+    assert_eq!(ima, ImaXattr::Signature(sig.clone()));
+    assert_eq!(ima.digest(), None);
+    assert_eq!(ima.to_bytes().unwrap(), bytes);
+    assert_eq!(
+        ImaXattr::from_bytes(&signature_bytes(0x06)).unwrap(),
+        ImaXattr::VeritySignature(sig)
+    );
+}
+
+#[test]
+fn test_ima_xattr_invalid() {
+    let mut truncated = signature_bytes(0x03);
+    truncated.pop();
+    let mut v1 = signature_bytes(0x03);
+    v1[1] = 1;
+
+    for bytes in [
+        &[][..],
+        &[0x01, 0x00][..],
+        &[0x02; 21][..],
+        &[0x04][..],
+        &[0x07, 0x00][..],
+        &truncated,
+        &v1,
+    ] {
+        assert_eq!(
+            ImaXattr::from_bytes(bytes),
+            Err(Errno(libc::EINVAL)),
+            "{:?}",
+            bytes
+        );
+    }
+}
+
+#[test]
+fn test_evm_xattr() {
+    let mut hmac = vec![0x02];
+    hmac.extend_from_slice(&[0xab; 20]);
+    assert_eq!(
+        EvmXattr::from_bytes(&hmac).unwrap(),
+        EvmXattr::Hmac(vec![0xab; 20])
+    );
+
+    let bytes = signature_bytes(0x05);
+    let evm = EvmXattr::from_bytes(&bytes).unwrap();
+    assert!(matches!(evm, EvmXattr::PortableSignature(_)));
+    assert_eq!(evm.to_bytes().unwrap(), bytes);
+    assert!(matches!(
+        EvmXattr::from_bytes(&signature_bytes(0x03)).unwrap(),
+        EvmXattr::Signature(_)
+    ));
+    // IMA digests are not valid EVM values
+    assert_eq!(EvmXattr::from_bytes(&[0x01; 21]), Err(Errno(libc::EINVAL)));
+}
+
+#[test]
+fn test_file_digest() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let temp_file_path = temp_dir.path().join("test_file_digest");
+    File::create(temp_file_path.as_path())
+        .unwrap()
+        .write_all(b"hello")
+        .unwrap();
+
+    assert_eq!(
+        file_digest(temp_file_path.as_path(), HashAlgo::Sha256).unwrap(),
+        HELLO_SHA256
+    );
+    assert_eq!(
+        file_digest(temp_file_path.as_path(), HashAlgo::Sha1).unwrap(),
+        HELLO_SHA1
+    );
+    assert_eq!(
+        file_digest(temp_file_path.as_path(), HashAlgo::Md5),
+        Err(Errno(libc::ENOTSUP))
+    );
+}
+
+#[test]
+fn test_verify_ima_digest() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let temp_file_path = temp_dir.path().join("test_verify_ima_digest");
+    File::create(temp_file_path.as_path())
+        .unwrap()
+        .write_all(b"hello")
+        .unwrap();
+    let good = ImaXattr::DigestNg {
+        algo: HashAlgo::Sha256,
+        digest: HELLO_SHA256.to_vec(),
+    };
+    let bad = ImaXattr::Digest(vec![0; 20]);
+
+    assert!(verify_ima_digest(temp_file_path.as_path(), &good).unwrap());
+    assert!(!verify_ima_digest(temp_file_path.as_path(), &bad).unwrap());
+    assert_eq!(
+        verify_ima_digest(
+            temp_file_path.as_path(),
+            &ImaXattr::from_bytes(&signature_bytes(0x03)).unwrap()
+        ),
+        Err(Errno(libc::EINVAL))
+    );
+}
+
+#[test]
+fn test_getima_verify_ima() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let temp_file_path = temp_dir.path().join("test_getima_verify_ima");
+    File::create(temp_file_path.as_path())
+        .unwrap()
+        .write_all(b"hello")
+        .unwrap();
+    let ima = ImaXattr::DigestNg {
+        algo: HashAlgo::Sha256,
+        digest: HELLO_SHA256.to_vec(),
+    };
+
+    match setxattr(
+        temp_file_path.as_path(),
+        XATTR_NAME_IMA,
+        ima.to_bytes().unwrap(),
+        Flags::empty(),
+    ) {
+        // Setting `security.*` EA requires `CAP_SYS_ADMIN`, or IMA appraisal
+        // may reject this value, skip this test.
+        Err(Errno(libc::ENOTSUP | libc::EPERM | libc::EACCES)) => return,
+        res => res.unwrap(),
+    }
+
+    assert_eq!(getima(temp_file_path.as_path()).unwrap(), ima);
+    assert!(verify_ima(temp_file_path.as_path()).unwrap());
+
+    File::create(temp_file_path.as_path())
+        .unwrap()
+        .write_all(b"tampered")
+        .unwrap();
+    assert!(!verify_ima(temp_file_path.as_path()).unwrap());
+}
+
+#[test]
+fn test_getevm() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let temp_file_path = temp_dir.path().join("test_getevm");
+    File::create(temp_file_path.as_path()).unwrap();
+    let evm = EvmXattr::from_bytes(&signature_bytes(0x05)).unwrap();
+
+    match setxattr(
+        temp_file_path.as_path(),
+        XATTR_NAME_EVM,
+        evm.to_bytes().unwrap(),
+        Flags::empty(),
+    ) {
+        // Setting `security.*` EA requires `CAP_SYS_ADMIN`, or EVM may reject
+        // this value, skip this test.
+        Err(Errno(libc::ENOTSUP | libc::EPERM | libc::EACCES)) => return,
+        res => res.unwrap(),
+    }
+
+    assert_eq!(getevm(temp_file_path.as_path()).unwrap(), evm);
+}
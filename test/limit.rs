@@ -0,0 +1,91 @@
+use errno::Errno;
+use extattr::{
+    fgetxattr, flistxattr, getxattr,
+    limit::{max_size, set_max_size, with_max_size, DEFAULT_MAX_SIZE},
+    listxattr, setxattr, Flags,
+};
+use std::{fs::File, os::unix::io::AsRawFd, panic};
+
+#[test]
+fn test_with_max_size() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let path = temp_dir.path().join("test_with_max_size");
+    let file = File::create(&path).unwrap();
+
+    match setxattr(&path, "user.test_with_max_size", [0; 100], Flags::empty()) {
+        // EA not supported
+        Err(Errno(libc::ENOTSUP)) => return,
+        res => res.unwrap(),
+    }
+
+    assert_eq!(
+        with_max_size(99, || getxattr(&path, "user.test_with_max_size")),
+        Err(Errno(libc::EOVERFLOW))
+    );
+    assert_eq!(
+        with_max_size(99, || fgetxattr(
+            file.as_raw_fd(),
+            "user.test_with_max_size"
+        )),
+        Err(Errno(libc::EOVERFLOW))
+    );
+    assert_eq!(
+        with_max_size(100, || getxattr(&path, "user.test_with_max_size"))
+            .unwrap()
+            .len(),
+        100
+    );
+
+    // "user.test_with_max_size\0" takes 24 bytes
+    assert_eq!(
+        with_max_size(23, || listxattr(&path)),
+        Err(Errno(libc::EOVERFLOW))
+    );
+    assert_eq!(
+        with_max_size(23, || flistxattr(file.as_raw_fd())),
+        Err(Errno(libc::EOVERFLOW))
+    );
+    assert_eq!(with_max_size(24, || listxattr(&path)).unwrap().len(), 1);
+
+    // distinct from the `E2BIG` of the kernel
+    assert_eq!(
+        setxattr(&path, "user.test_with_max_size", [0; 65537], Flags::empty()),
+        Err(Errno(libc::E2BIG))
+    );
+
+    // the override only applies to the closure
+    assert_eq!(
+        getxattr(&path, "user.test_with_max_size").unwrap().len(),
+        100
+    );
+}
+
+#[test]
+fn test_override_scope() {
+    let global = max_size();
+    with_max_size(10, || {
+        assert_eq!(max_size(), 10);
+        with_max_size(20, || assert_eq!(max_size(), 20));
+        assert_eq!(max_size(), 10);
+
+        // other threads are not affected
+        std::thread::spawn(|| assert_ne!(max_size(), 10))
+            .join()
+            .unwrap();
+    });
+    assert_eq!(max_size(), global);
+
+    // restored after a panic too
+    let res = panic::catch_unwind(|| with_max_size(10, || panic!()));
+    assert!(res.is_err());
+    assert_eq!(max_size(), global);
+}
+
+#[test]
+fn test_set_max_size() {
+    // only raise the limit, as the other tests run concurrently
+    set_max_size(DEFAULT_MAX_SIZE * 2);
+    assert_eq!(max_size(), DEFAULT_MAX_SIZE * 2);
+    with_max_size(10, || assert_eq!(max_size(), 10));
+    set_max_size(DEFAULT_MAX_SIZE);
+}
@@ -0,0 +1,237 @@
+use errno::Errno;
+use extattr::name::{
+    encode_extattr_list, parse_extattr_list, parse_extattr_list_prefix,
+    AttrName, AttrNamespace, Namespace,
+};
+use std::{
+    ffi::{OsStr, OsString},
+    os::unix::ffi::{OsStrExt, OsStringExt},
+};
+
+#[test]
+fn test_linux() {
+    let name = AttrName::from_linux("user.foo").unwrap();
+    assert_eq!(name.namespace(), Namespace::User);
+    assert_eq!(name.name(), "foo");
+    assert_eq!(name.to_linux(), "user.foo");
+
+    for linux in [
+        "system.posix_acl_access",
+        "security.selinux",
+        "trusted.overlay.opaque",
+        "user.user.nested",
+    ] {
+        assert_eq!(AttrName::from_linux(linux).unwrap().to_linux(), linux);
+    }
+    let binary = OsStr::from_bytes(b"user.\xff\xfe");
+    assert_eq!(AttrName::from_linux(binary).unwrap().to_linux(), binary);
+
+    assert_eq!(AttrName::from_linux("user."), Err(Errno(libc::EINVAL)));
+    assert_eq!(AttrName::from_linux("foo"), Err(Errno(libc::ENOTSUP)));
+    assert_eq!(
+        AttrName::from_linux("btrfs.compression"),
+        Err(Errno(libc::ENOTSUP))
+    );
+    assert_eq!(AttrName::from_linux("User.foo"), Err(Errno(libc::ENOTSUP)));
+}
+
+#[test]
+fn test_bsd() {
+    let name = AttrName::from_bsd(AttrNamespace::EXTATTR_NAMESPACE_USER, "foo")
+        .unwrap();
+    assert_eq!(name, AttrName::from_linux("user.foo").unwrap());
+    assert_eq!(
+        name.to_bsd().unwrap(),
+        (AttrNamespace::EXTATTR_NAMESPACE_USER, OsStr::new("foo"))
+    );
+
+    let name = AttrName::from_bsd(
+        AttrNamespace::EXTATTR_NAMESPACE_SYSTEM,
+        "posix1e.acl_access",
+    )
+    .unwrap();
+    assert_eq!(name.to_linux(), "system.posix1e.acl_access");
+    assert_eq!(
+        AttrName::from_linux(name.to_linux())
+            .unwrap()
+            .to_bsd()
+            .unwrap(),
+        (
+            AttrNamespace::EXTATTR_NAMESPACE_SYSTEM,
+            OsStr::new("posix1e.acl_access")
+        )
+    );
+
+    assert_eq!(
+        AttrName::from_linux("security.selinux").unwrap().to_bsd(),
+        Err(Errno(libc::ENOTSUP))
+    );
+    assert_eq!(
+        AttrName::from_bsd(AttrNamespace::EXTATTR_NAMESPACE_USER, ""),
+        Err(Errno(libc::EINVAL))
+    );
+    assert_eq!(AttrNamespace::EXTATTR_NAMESPACE_USER as i32, 1);
+    assert_eq!(AttrNamespace::EXTATTR_NAMESPACE_SYSTEM as i32, 2);
+}
+
+#[test]
+fn test_darwin() {
+    let name = AttrName::from_darwin("com.apple.quarantine").unwrap();
+    assert_eq!(name.to_linux(), "user.com.apple.quarantine");
+    assert_eq!(name.to_darwin().unwrap(), "com.apple.quarantine");
+    assert_eq!(
+        AttrName::from_linux("user.com.apple.quarantine")
+            .unwrap()
+            .to_darwin()
+            .unwrap(),
+        "com.apple.quarantine"
+    );
+
+    // names that look like Linux ones are kept as they are
+    let name = AttrName::from_darwin("user.foo").unwrap();
+    assert_eq!(name.to_linux(), "user.user.foo");
+    assert_eq!(name.to_darwin().unwrap(), "user.foo");
+
+    assert_eq!(
+        AttrName::from_linux("trusted.foo").unwrap().to_darwin(),
+        Err(Errno(libc::ENOTSUP))
+    );
+    assert_eq!(AttrName::from_darwin("a\0b"), Err(Errno(libc::EINVAL)));
+}
+
+#[test]
+fn test_order() {
+    let mut names = [
+        AttrName::new(Namespace::Trusted, "a").unwrap(),
+        AttrName::new(Namespace::User, "b").unwrap(),
+        AttrName::new(Namespace::User, "a").unwrap(),
+        AttrName::new(Namespace::System, "a").unwrap(),
+    ];
+    names.sort();
+    let names: Vec<_> = names.iter().map(AttrName::to_linux).collect();
+    assert_eq!(names, ["user.a", "user.b", "system.a", "trusted.a"]);
+}
+
+#[test]
+fn test_parse_extattr_list() {
+    assert_eq!(parse_extattr_list(b"").unwrap(), Vec::<OsString>::new());
+    assert_eq!(
+        parse_extattr_list(b"\x08attrname\x0fanotherattrname").unwrap(),
+        ["attrname", "anotherattrname"]
+    );
+    assert_eq!(
+        encode_extattr_list(["attrname", "anotherattrname"]).unwrap(),
+        b"\x08attrname\x0fanotherattrname"
+    );
+
+    // truncated entry
+    assert_eq!(
+        parse_extattr_list(b"\x03abc\x0fanother"),
+        Err(Errno(libc::EINVAL))
+    );
+    assert_eq!(
+        parse_extattr_list_prefix(b"\x03abc\x0fanother"),
+        (vec![OsString::from("abc")], 4)
+    );
+    // empty name
+    assert_eq!(parse_extattr_list(b"\x00"), Err(Errno(libc::EINVAL)));
+    assert_eq!(
+        parse_extattr_list_prefix(b"\x01a\x00\x01b"),
+        (vec![OsString::from("a")], 2)
+    );
+    // length byte without name
+    assert_eq!(parse_extattr_list_prefix(b"\xff"), (Vec::new(), 0));
+
+    assert_eq!(encode_extattr_list([""]), Err(Errno(libc::EINVAL)));
+    let long = OsString::from_vec(vec![b'a'; 256]);
+    assert_eq!(encode_extattr_list([long]), Err(Errno(libc::EINVAL)));
+}
+
+/// xorshift64*, so that the randomized tests are reproducible.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next() as u8).collect()
+    }
+}
+
+/// Checks the properties that hold for any input: the prefix is the valid
+/// part of `bytes`, it encodes back to the same bytes, and the strict parser
+/// only succeeds if the whole input is valid.
+fn check_parse(bytes: &[u8]) {
+    let (names, len) = parse_extattr_list_prefix(bytes);
+    assert!(len <= bytes.len());
+    assert_eq!(encode_extattr_list(&names).unwrap(), &bytes[..len]);
+    if len < bytes.len() {
+        let entry_len = bytes[len] as usize;
+        assert!(entry_len == 0 || len + 1 + entry_len > bytes.len());
+        assert_eq!(parse_extattr_list(bytes), Err(Errno(libc::EINVAL)));
+    } else {
+        assert_eq!(parse_extattr_list(bytes).unwrap(), names);
+    }
+}
+
+#[test]
+fn test_parse_extattr_list_exhaustive() {
+    // every buffer of up to 3 bytes over an alphabet covering the edge cases
+    let alphabet = [0, 1, 2, 3, b'a', 0xff];
+    let mut buffers = vec![Vec::new()];
+    for _ in 0..3 {
+        let mut longer = Vec::new();
+        for buffer in buffers.iter() {
+            check_parse(buffer);
+            for &b in alphabet.iter() {
+                let mut buffer = buffer.clone();
+                buffer.push(b);
+                longer.push(buffer);
+            }
+        }
+        buffers = longer;
+    }
+    buffers.iter().for_each(|buffer| check_parse(buffer));
+}
+
+#[test]
+fn test_parse_extattr_list_fuzz() {
+    let mut rng = Rng(0x5eed_1234_abcd_0042);
+    for _ in 0..10_000 {
+        let len = rng.below(600);
+        let bytes = rng.bytes(len);
+        check_parse(&bytes);
+    }
+}
+
+#[test]
+fn test_extattr_list_round_trip() {
+    let mut rng = Rng(0x0123_4567_89ab_cdef);
+    for _ in 0..2_000 {
+        let names: Vec<OsString> = (0..rng.below(20))
+            .map(|_| {
+                let len = 1 + rng.below(255);
+                OsString::from_vec(rng.bytes(len))
+            })
+            .collect();
+        let bytes = encode_extattr_list(&names).unwrap();
+        assert_eq!(parse_extattr_list(&bytes).unwrap(), names);
+
+        // a racy or truncated buffer yields the complete entries
+        if !bytes.is_empty() {
+            let cut = rng.below(bytes.len());
+            let (prefix, len) = parse_extattr_list_prefix(&bytes[..cut]);
+            assert!(len <= cut);
+            assert_eq!(prefix, names[..prefix.len()]);
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use errno::Errno;
+use extattr::{
+    nfs4_acl::{
+        get_nfs4_acl, set_nfs4_acl, AccessMask, Ace, AceFlags, AceType,
+        Nfs4Acl, NumericIdMapper,
+    },
+    posix_acl::{Perm, PosixAcl, PosixAclEntry, Tag},
+};
+use std::{fs::File, str::FromStr};
+
+fn entry(tag: Tag, perm: Perm) -> PosixAclEntry {
+    PosixAclEntry { tag, perm }
+}
+
+#[test]
+fn test_ace_text() {
+    let ace = Ace::from_str("D:fdig:staff@example.com:rwaDx").unwrap();
+    assert_eq!(ace.ace_type, AceType::Deny);
+    assert_eq!(
+        ace.flags,
+        AceFlags::FILE_INHERIT
+            | AceFlags::DIRECTORY_INHERIT
+            | AceFlags::INHERIT_ONLY
+            | AceFlags::IDENTIFIER_GROUP
+    );
+    assert_eq!(
+        ace.access_mask,
+        AccessMask::READ_DATA
+            | AccessMask::WRITE_DATA
+            | AccessMask::APPEND_DATA
+            | AccessMask::DELETE_CHILD
+            | AccessMask::EXECUTE
+    );
+    assert_eq!(ace.who, "staff@example.com");
+    assert_eq!(ace.to_string(), "D:fdig:staff@example.com:rwaDx");
+
+    // principals may contain colons
+    let ace = Ace::from_str("A::a:b:rtcy").unwrap();
+    assert_eq!(ace.who, "a:b");
+    assert_eq!(ace.to_string(), "A::a:b:rtcy");
+
+    // perms are printed in the nfs4_getfacl order
+    let ace = Ace::from_str("A::OWNER@:yCcTtxdDawr").unwrap();
+    assert_eq!(ace.to_string(), "A::OWNER@:rwaDdxtTcCy");
+
+    assert_eq!(Ace::from_str("X::OWNER@:r"), Err(Errno(libc::EINVAL)));
+    assert_eq!(Ace::from_str("A:z:OWNER@:r"), Err(Errno(libc::EINVAL)));
+    assert_eq!(Ace::from_str("A::OWNER@:q"), Err(Errno(libc::EINVAL)));
+    assert_eq!(Ace::from_str("A:::r"), Err(Errno(libc::EINVAL)));
+    assert_eq!(Ace::from_str("A:OWNER@"), Err(Errno(libc::EINVAL)));
+}
+
+#[test]
+fn test_acl_text() {
+    let text = "# file: foo\nA::OWNER@:rwatTcCy\n\nA::EVERYONE@:rtcy\n";
+    let acl = Nfs4Acl::from_str(text).unwrap();
+    assert_eq!(acl.aces.len(), 2);
+    assert_eq!(acl.to_string(), "A::OWNER@:rwatTcCy\nA::EVERYONE@:rtcy\n");
+}
+
+#[test]
+fn test_xdr_codec() {
+    let acl = Nfs4Acl::from_str("A::OWNER@:rw\nD:g:12345:x\n").unwrap();
+    let bytes = acl.to_bytes();
+    #[rustfmt::skip]
+    let expected: &[u8] = &[
+        0, 0, 0, 2,
+        // A::OWNER@:rw
+        0, 0, 0, 0,
+        0, 0, 0, 0,
+        0, 0, 0, 3,
+        0, 0, 0, 6, b'O', b'W', b'N', b'E', b'R', b'@', 0, 0,
+        // D:g:12345:x
+        0, 0, 0, 1,
+        0, 0, 0, 0x40,
+        0, 0, 0, 0x20,
+        0, 0, 0, 5, b'1', b'2', b'3', b'4', b'5', 0, 0, 0,
+    ];
+    assert_eq!(bytes, expected);
+    assert_eq!(Nfs4Acl::from_bytes(&bytes).unwrap(), acl);
+
+    assert_eq!(Nfs4Acl::from_bytes(&[]), Err(Errno(libc::EINVAL)));
+    // truncated
+    assert_eq!(
+        Nfs4Acl::from_bytes(&bytes[..bytes.len() - 4]),
+        Err(Errno(libc::EINVAL))
+    );
+    // trailing garbage
+    let mut bad = bytes.clone();
+    bad.extend_from_slice(&[0; 4]);
+    assert_eq!(Nfs4Acl::from_bytes(&bad), Err(Errno(libc::EINVAL)));
+    // huge ACE count
+    let mut bad = bytes.clone();
+    bad[0] = 0xff;
+    assert_eq!(Nfs4Acl::from_bytes(&bad), Err(Errno(libc::EINVAL)));
+    // unknown ACE type
+    let mut bad = bytes.clone();
+    bad[7] = 9;
+    assert_eq!(Nfs4Acl::from_bytes(&bad), Err(Errno(libc::EINVAL)));
+
+    // NFSv4.1 and unknown bits are kept
+    let mut newer = bytes;
+    newer[11] = 0x80;
+    newer[12] = 0x80;
+    newer[14] = 0x06;
+    let acl = Nfs4Acl::from_bytes(&newer).unwrap();
+    assert_eq!(acl.aces[0].flags, AceFlags::INHERITED_ACE);
+    assert!(acl.aces[0].access_mask.contains(
+        AccessMask::WRITE_RETENTION | AccessMask::WRITE_RETENTION_HOLD
+    ));
+    assert_eq!(acl.aces[0].access_mask.bits(), 0x8000_0603);
+    assert_eq!(acl.to_bytes(), newer);
+}
+
+#[test]
+fn test_from_posix_mode() {
+    let acl = Nfs4Acl::from_posix(
+        &PosixAcl::from_mode(0o644),
+        None,
+        false,
+        &NumericIdMapper,
+    )
+    .unwrap();
+    assert_eq!(
+        acl.to_string(),
+        "A::OWNER@:rwatTcCy\nA:g:GROUP@:rtcy\nA::EVERYONE@:rtcy\n"
+    );
+
+    let acl = Nfs4Acl::from_posix(
+        &PosixAcl::from_mode(0o604),
+        None,
+        false,
+        &NumericIdMapper,
+    )
+    .unwrap();
+    assert_eq!(
+        acl.to_string(),
+        "A::OWNER@:rwatTcCy\nA:g:GROUP@:tcy\nD:g:GROUP@:r\nA::EVERYONE@:rtcy\n"
+    );
+    let (access, default) = acl.to_posix(false, &NumericIdMapper).unwrap();
+    assert_eq!(access, PosixAcl::from_mode(0o604));
+    assert_eq!(default, None);
+}
+
+#[test]
+fn test_posix_round_trip() {
+    let access = PosixAcl {
+        entries: vec![
+            entry(Tag::UserObj, Perm::all()),
+            entry(Tag::User(1000), Perm::READ | Perm::EXECUTE),
+            entry(Tag::GroupObj, Perm::READ),
+            entry(Tag::Group(100), Perm::empty()),
+            entry(Tag::Mask, Perm::READ | Perm::EXECUTE),
+            entry(Tag::Other, Perm::empty()),
+        ],
+    };
+    let default = PosixAcl::from_mode(0o750);
+
+    let acl =
+        Nfs4Acl::from_posix(&access, Some(&default), true, &NumericIdMapper)
+            .unwrap();
+    assert_eq!(acl.aces[0].to_string(), "A::OWNER@:rwaDxtTcCy");
+    assert_eq!(acl.aces[1].to_string(), "A::1000:rxtcy");
+    let inherited: Vec<String> = acl
+        .aces
+        .iter()
+        .filter(|ace| ace.flags.contains(AceFlags::INHERIT_ONLY))
+        .map(|ace| ace.to_string())
+        .collect();
+    assert_eq!(
+        inherited,
+        [
+            "A:fdi:OWNER@:rwaDxtTcCy",
+            "A:fdig:GROUP@:rxtcy",
+            "A:fdi:EVERYONE@:tcy"
+        ]
+    );
+
+    let (access2, default2) = acl.to_posix(true, &NumericIdMapper).unwrap();
+    assert_eq!(access2, access);
+    assert_eq!(default2, Some(default));
+}
+
+#[test]
+fn test_to_posix_deny() {
+    // a named user is denied what EVERYONE@ is allowed later
+    let acl = Nfs4Acl::from_str(
+        "A::OWNER@:rwatTcCy\nD::1000:w\nA::EVERYONE@:rwatcy\n",
+    )
+    .unwrap();
+    let (access, _) = acl.to_posix(false, &NumericIdMapper).unwrap();
+    assert_eq!(access.get(Tag::User(1000)), Some(Perm::READ));
+    assert_eq!(access.get(Tag::UserObj), Some(Perm::READ | Perm::WRITE));
+    assert_eq!(access.get(Tag::GroupObj), Some(Perm::READ | Perm::WRITE));
+    assert_eq!(access.get(Tag::Other), Some(Perm::READ | Perm::WRITE));
+}
+
+#[test]
+fn test_to_posix_unsupported() {
+    let to_posix = |text: &str, is_dir: bool| {
+        Nfs4Acl::from_str(text)
+            .unwrap()
+            .to_posix(is_dir, &NumericIdMapper)
+    };
+
+    assert_eq!(to_posix("U:S:OWNER@:r", false), Err(Errno(libc::EINVAL)));
+    // inheritable ACE on a file
+    assert_eq!(to_posix("A:f:OWNER@:r", false), Err(Errno(libc::EINVAL)));
+    // inherit-only without inheritance
+    assert_eq!(to_posix("A:i:OWNER@:r", true), Err(Errno(libc::EINVAL)));
+    // non-numeric principal
+    assert_eq!(
+        to_posix("A::alice@example.com:r", false),
+        Err(Errno(libc::EINVAL))
+    );
+    assert!(to_posix("A::1000@example.com:r", false).is_ok());
+}
+
+#[test]
+fn test_set_nfs4_acl() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let temp_file_path = temp_dir.path().join("test_set_nfs4_acl");
+    File::create(&temp_file_path).unwrap();
+
+    let acl = Nfs4Acl::from_str("A::OWNER@:rwatTcCy\n").unwrap();
+    match set_nfs4_acl(&temp_file_path, &acl) {
+        // Only NFSv4 mounts support `system.nfs4_acl`
+        Err(Errno(libc::ENOTSUP)) => return,
+        res => res.unwrap(),
+    }
+    assert_eq!(get_nfs4_acl(&temp_file_path).unwrap(), acl);
+}
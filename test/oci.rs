@@ -0,0 +1,131 @@
+use errno::Errno;
+use extattr::{
+    lsetxattr,
+    oci::{
+        classify, create_whiteout, extract_marker, layer_xattrs,
+        parse_entry_name, walk_upper, whiteout_name, Change, LayerWhiteout,
+        UpperEntry, WhiteoutStyle, XattrFilter,
+    },
+    overlay::{get_opaque, is_whiteout, set_opaque, Opaque, OverlayPrefix},
+    Flags,
+};
+use std::{
+    ffi::{OsStr, OsString},
+    fs::{create_dir, File},
+    path::PathBuf,
+};
+
+#[test]
+fn test_entry_names() {
+    assert_eq!(
+        parse_entry_name(".wh..wh..opq").unwrap(),
+        Some(LayerWhiteout::Opaque)
+    );
+    assert_eq!(
+        parse_entry_name(".wh.foo").unwrap(),
+        Some(LayerWhiteout::Whiteout(OsString::from("foo")))
+    );
+    assert_eq!(
+        parse_entry_name(".wh..hidden").unwrap(),
+        Some(LayerWhiteout::Whiteout(OsString::from(".hidden")))
+    );
+    assert_eq!(parse_entry_name("foo.wh.").unwrap(), None);
+    for invalid in [".wh.", ".wh..wh.foo"] {
+        assert_eq!(parse_entry_name(invalid), Err(Errno(libc::EINVAL)));
+    }
+    assert_eq!(whiteout_name("foo"), OsStr::new(".wh.foo"));
+}
+
+#[test]
+fn test_filter() {
+    let filter = XattrFilter::default();
+    assert!(filter.allows("user.foo"));
+    assert!(filter.allows("security.capability"));
+    assert!(!filter.allows("security.selinux"));
+    assert!(!filter.allows("security.SMACK64EXEC"));
+    assert!(!filter.allows("trusted.overlay.opaque"));
+    assert!(!filter.allows("user.overlay.whiteout"));
+}
+
+#[test]
+fn test_round_trip() {
+    let prefix = OverlayPrefix::User;
+    let upper = tempfile::tempdir_in("./").unwrap();
+    let upper = upper.path();
+    create_dir(upper.join("dir")).unwrap();
+    File::create(upper.join("dir/file")).unwrap();
+    match set_opaque(upper.join("dir"), prefix, Opaque::Opaque) {
+        // EA not supported
+        Err(Errno(libc::ENOTSUP)) => return,
+        res => res.unwrap(),
+    }
+    create_whiteout(upper, "gone", prefix, WhiteoutStyle::Xattr).unwrap();
+    assert_eq!(
+        get_opaque(upper, prefix).unwrap(),
+        Some(Opaque::ContainsWhiteouts)
+    );
+    assert_eq!(
+        classify(upper.join("gone"), prefix).unwrap(),
+        UpperEntry::Whiteout
+    );
+    lsetxattr(upper.join("dir/file"), "user.foo", b"bar", Flags::empty())
+        .unwrap();
+
+    assert_eq!(
+        walk_upper(upper, prefix).unwrap(),
+        [
+            Change::Entry(PathBuf::from("dir")),
+            Change::Opaque(PathBuf::from("dir/.wh..wh..opq")),
+            Change::Entry(PathBuf::from("dir/file")),
+            Change::Whiteout(PathBuf::from(".wh.gone")),
+        ]
+    );
+    assert_eq!(
+        layer_xattrs(upper.join("dir"), &XattrFilter::default()).unwrap(),
+        []
+    );
+    assert_eq!(
+        layer_xattrs(upper.join("dir/file"), &XattrFilter::default()).unwrap(),
+        [(OsString::from("user.foo"), b"bar".to_vec())]
+    );
+
+    let lower = tempfile::tempdir_in("./").unwrap();
+    let lower = lower.path();
+    create_dir(lower.join("dir")).unwrap();
+    let style = WhiteoutStyle::Xattr;
+    assert!(!extract_marker(lower, "dir", prefix, style).unwrap());
+    assert!(
+        extract_marker(lower.join("dir"), ".wh..wh..opq", prefix, style)
+            .unwrap()
+    );
+    assert!(extract_marker(lower, ".wh.gone", prefix, style).unwrap());
+    assert_eq!(
+        get_opaque(lower.join("dir"), prefix).unwrap(),
+        Some(Opaque::Opaque)
+    );
+    assert!(is_whiteout(lower.join("gone"), prefix).unwrap());
+    assert_eq!(
+        extract_marker(lower, ".wh.gone", prefix, style),
+        Err(Errno(libc::EEXIST))
+    );
+}
+
+#[test]
+fn test_device_whiteout() {
+    let dir = tempfile::tempdir_in("./").unwrap();
+    match create_whiteout(
+        dir.path(),
+        "gone",
+        OverlayPrefix::User,
+        WhiteoutStyle::Device,
+    ) {
+        // requires CAP_MKNOD
+        Err(Errno(libc::EPERM)) => return,
+        res => res.unwrap(),
+    }
+    assert_eq!(
+        classify(dir.path().join("gone"), OverlayPrefix::User).unwrap(),
+        UpperEntry::Whiteout
+    );
+    assert_eq!(get_opaque(dir.path(), OverlayPrefix::User).unwrap(), None);
+}
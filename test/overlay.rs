@@ -0,0 +1,204 @@
+use errno::Errno;
+use extattr::{
+    getxattr, lgetxattr,
+    overlay::{
+        get_metacopy, get_nlink, get_opaque, get_origin, get_redirect,
+        get_upper, is_impure, is_whiteout, remove, set_impure, set_metacopy,
+        set_nlink, set_opaque, set_origin, set_redirect, set_upper,
+        set_whiteout, FileHandle, Metacopy, Nlink, Opaque, OverlayAttr,
+        OverlayPrefix, VerityAlgo,
+    },
+};
+use std::{fs::File, path::Path};
+
+fn file_handle() -> FileHandle {
+    FileHandle {
+        flags: FileHandle::FLAG_ANY_ENDIAN,
+        fid_type: 1,
+        uuid: [0x11; 16],
+        fid: vec![1, 0, 0, 0, 2, 0, 0, 0],
+    }
+}
+
+#[test]
+fn test_prefix_name() {
+    assert_eq!(
+        OverlayPrefix::Trusted.name(OverlayAttr::Opaque),
+        "trusted.overlay.opaque"
+    );
+    assert_eq!(
+        OverlayPrefix::User.name(OverlayAttr::Metacopy),
+        "user.overlay.metacopy"
+    );
+}
+
+#[test]
+fn test_opaque_codec() {
+    assert_eq!(Opaque::from_bytes(b"y").unwrap(), Opaque::Opaque);
+    assert_eq!(Opaque::from_bytes(b"x").unwrap(), Opaque::ContainsWhiteouts);
+    assert_eq!(Opaque::from_bytes(b"n"), Err(Errno(libc::EINVAL)));
+    assert_eq!(Opaque::ContainsWhiteouts.to_bytes(), b"x");
+}
+
+#[test]
+fn test_file_handle_codec() {
+    let fh = file_handle();
+    let bytes = fh.to_bytes().unwrap();
+
+    assert_eq!(&bytes[..5], &[0, 0xfb, 29, 2, 1]);
+    assert_eq!(bytes.len(), 29);
+    assert_eq!(FileHandle::from_bytes(&bytes).unwrap(), fh);
+
+    // wrong magic
+    let mut bad = bytes.clone();
+    bad[1] = 0xfa;
+    assert_eq!(FileHandle::from_bytes(&bad), Err(Errno(libc::EINVAL)));
+    // length does not match
+    let mut bad = bytes;
+    bad.pop();
+    assert_eq!(FileHandle::from_bytes(&bad), Err(Errno(libc::EINVAL)));
+}
+
+#[test]
+fn test_metacopy_codec() {
+    assert_eq!(
+        Metacopy::from_bytes(&[]).unwrap(),
+        Metacopy { digest: None }
+    );
+    assert_eq!(
+        Metacopy::from_bytes(&[0, 4, 0, 0]).unwrap(),
+        Metacopy { digest: None }
+    );
+
+    let metacopy = Metacopy {
+        digest: Some((VerityAlgo::Sha256, vec![0xaa; 32])),
+    };
+    let bytes = metacopy.to_bytes().unwrap();
+    assert_eq!(&bytes[..4], &[0, 36, 0, 1]);
+    assert_eq!(Metacopy::from_bytes(&bytes).unwrap(), metacopy);
+
+    // digest size does not match the algorithm
+    assert_eq!(
+        Metacopy::from_bytes(&[0, 8, 0, 2, 1, 2, 3, 4]),
+        Err(Errno(libc::EINVAL))
+    );
+    // unknown version
+    assert_eq!(
+        Metacopy::from_bytes(&[1, 4, 0, 0]),
+        Err(Errno(libc::EINVAL))
+    );
+}
+
+#[test]
+fn test_nlink_codec() {
+    assert_eq!(Nlink::from_bytes(b"U+3").unwrap(), Nlink::Upper(3));
+    assert_eq!(Nlink::from_bytes(b"L-1").unwrap(), Nlink::Lower(-1));
+    assert_eq!(Nlink::Upper(0).to_bytes(), b"U+0");
+    assert_eq!(Nlink::Lower(-2).to_bytes(), b"L-2");
+    for bytes in [&b""[..], b"U", b"X+1", b"U+x"] {
+        assert_eq!(Nlink::from_bytes(bytes), Err(Errno(libc::EINVAL)));
+    }
+}
+
+/// Returns `true` if `user.*` EAs are supported on `path`.
+fn user_xattr_supported(path: &Path) -> bool {
+    !matches!(
+        getxattr(path, "user.overlay.probe"),
+        Err(Errno(libc::ENOTSUP))
+    )
+}
+
+#[test]
+fn test_opaque_redirect_impure() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let dir = temp_dir.path();
+    if !user_xattr_supported(dir) {
+        return;
+    }
+    let prefix = OverlayPrefix::User;
+
+    assert_eq!(get_opaque(dir, prefix).unwrap(), None);
+    set_opaque(dir, prefix, Opaque::Opaque).unwrap();
+    assert_eq!(
+        lgetxattr(dir, "user.overlay.opaque").unwrap(),
+        b"y".to_vec()
+    );
+    assert_eq!(get_opaque(dir, prefix).unwrap(), Some(Opaque::Opaque));
+
+    assert_eq!(get_redirect(dir, prefix).unwrap(), None);
+    set_redirect(dir, prefix, "/a/b").unwrap();
+    assert_eq!(
+        get_redirect(dir, prefix).unwrap().unwrap(),
+        Path::new("/a/b")
+    );
+    assert_eq!(set_redirect(dir, prefix, ""), Err(Errno(libc::EINVAL)));
+
+    assert!(!is_impure(dir, prefix).unwrap());
+    set_impure(dir, prefix, true).unwrap();
+    assert!(is_impure(dir, prefix).unwrap());
+    set_impure(dir, prefix, false).unwrap();
+    assert!(!is_impure(dir, prefix).unwrap());
+
+    remove(dir, prefix, OverlayAttr::Opaque).unwrap();
+    // removing it again is fine
+    remove(dir, prefix, OverlayAttr::Opaque).unwrap();
+    assert_eq!(get_opaque(dir, prefix).unwrap(), None);
+}
+
+#[test]
+fn test_origin_upper_metacopy_nlink_whiteout() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let file = temp_dir.path().join("file");
+    File::create(file.as_path()).unwrap();
+    if !user_xattr_supported(&file) {
+        return;
+    }
+    let prefix = OverlayPrefix::User;
+    let fh = file_handle();
+
+    assert_eq!(get_origin(&file, prefix).unwrap(), None);
+    set_origin(&file, prefix, None).unwrap();
+    assert_eq!(get_origin(&file, prefix).unwrap(), Some(None));
+    set_origin(&file, prefix, Some(&fh)).unwrap();
+    assert_eq!(get_origin(&file, prefix).unwrap(), Some(Some(fh.clone())));
+
+    set_upper(&file, prefix, &fh).unwrap();
+    assert_eq!(get_upper(&file, prefix).unwrap(), Some(fh));
+
+    let metacopy = Metacopy {
+        digest: Some((VerityAlgo::Sha512, vec![0x55; 64])),
+    };
+    set_metacopy(&file, prefix, &metacopy).unwrap();
+    assert_eq!(get_metacopy(&file, prefix).unwrap(), Some(metacopy));
+
+    set_nlink(&file, prefix, Nlink::Upper(-1)).unwrap();
+    assert_eq!(get_nlink(&file, prefix).unwrap(), Some(Nlink::Upper(-1)));
+
+    assert!(!is_whiteout(&file, prefix).unwrap());
+    set_whiteout(&file, prefix, true).unwrap();
+    assert!(is_whiteout(&file, prefix).unwrap());
+    set_whiteout(&file, prefix, false).unwrap();
+    assert!(!is_whiteout(&file, prefix).unwrap());
+}
+
+#[test]
+fn test_trusted_prefix() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let dir = temp_dir.path();
+
+    match set_opaque(dir, OverlayPrefix::Trusted, Opaque::Opaque) {
+        // `trusted.*` EAs require `CAP_SYS_ADMIN`, skip this test.
+        Err(Errno(libc::ENOTSUP | libc::EPERM)) => return,
+        res => res.unwrap(),
+    }
+
+    assert_eq!(
+        lgetxattr(dir, "trusted.overlay.opaque").unwrap(),
+        b"y".to_vec()
+    );
+    assert_eq!(
+        get_opaque(dir, OverlayPrefix::Trusted).unwrap(),
+        Some(Opaque::Opaque)
+    );
+    assert_eq!(get_opaque(dir, OverlayPrefix::User).unwrap(), None);
+}
@@ -0,0 +1,162 @@
+use errno::Errno;
+use extattr::{
+    lgetxattr, lsetxattr,
+    pax::{
+        apply, capture, decode_records, decode_xattrs, encode_record,
+        encode_xattr, NamespacePolicy, PaxFormat,
+    },
+    Flags,
+};
+use std::{
+    ffi::{OsStr, OsString},
+    fs::File,
+    os::unix::ffi::OsStrExt,
+};
+
+fn record(key: &str, value: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    (key.as_bytes().to_vec(), value.to_vec())
+}
+
+#[test]
+fn test_records() {
+    assert_eq!(encode_record(b"path", b"foo"), b"12 path=foo\n");
+    // the length of the length changes the length
+    assert_eq!(encode_record(b"k", &[b'v'; 4]), b"9 k=vvvv\n".to_vec());
+    assert_eq!(encode_record(b"k", &[b'v'; 5]), b"11 k=vvvvv\n".to_vec());
+    assert_eq!(encode_record(b"k", &[b'v'; 93]).len(), 99);
+    assert_eq!(encode_record(b"k", &[b'v'; 94]).len(), 101);
+
+    let mut data = encode_record(b"path", b"a=b\nc");
+    data.extend_from_slice(&encode_record(b"mtime", b"1.5"));
+    data.extend_from_slice(&[0; 10]);
+    assert_eq!(
+        decode_records(&data).unwrap(),
+        [record("path", b"a=b\nc"), record("mtime", b"1.5")]
+    );
+
+    for invalid in [
+        &b"12 path=foo"[..],
+        b"13 path=foo\n",
+        b"11 path=foo\n",
+        b"x path=foo\n",
+        b"10 pathfoo\n",
+        b"12path=foo\n\n",
+        b"-1 k=\n",
+    ] {
+        assert_eq!(decode_records(invalid), Err(Errno(libc::EINVAL)));
+    }
+}
+
+#[test]
+fn test_encode_xattr() {
+    assert_eq!(
+        encode_xattr("user.foo", b"bar", PaxFormat::Schily).unwrap(),
+        b"29 SCHILY.xattr.user.foo=bar\n"
+    );
+    assert_eq!(
+        encode_xattr("user.a b=%", b"\x00\x01", PaxFormat::Libarchive).unwrap(),
+        b"41 LIBARCHIVE.xattr.user.a%20b%3D%25=AAE\n"
+    );
+    let both = encode_xattr("user.foo", b"bar", PaxFormat::Both).unwrap();
+    assert_eq!(
+        decode_records(&both).unwrap(),
+        [
+            record("LIBARCHIVE.xattr.user.foo", b"YmFy"),
+            record("SCHILY.xattr.user.foo", b"bar"),
+        ]
+    );
+    // names with `=` only get the libarchive record
+    let both = encode_xattr("user.a=b", b"", PaxFormat::Both).unwrap();
+    assert_eq!(decode_records(&both).unwrap().len(), 1);
+
+    assert_eq!(
+        encode_xattr("user.a=b", b"", PaxFormat::Schily),
+        Err(Errno(libc::EINVAL))
+    );
+    assert_eq!(
+        encode_xattr("", b"", PaxFormat::Both),
+        Err(Errno(libc::EINVAL))
+    );
+}
+
+#[test]
+fn test_decode_xattrs() {
+    let records = [
+        record("path", b"foo"),
+        record("SCHILY.xattr.user.a", b"schily"),
+        record("LIBARCHIVE.xattr.user.a", b"bGk"),
+        record("LIBARCHIVE.xattr.user.b%3D", b"AP8="),
+        record("SCHILY.xattr.user.b=", b"ignored"),
+        record("SCHILY.xattr.security.selinux", b"label\0"),
+    ];
+    assert_eq!(
+        decode_xattrs(&records).unwrap(),
+        [
+            (OsString::from("user.a"), b"li".to_vec()),
+            (OsString::from("user.b="), b"\x00\xff".to_vec()),
+            (OsString::from("security.selinux"), b"label\0".to_vec()),
+        ]
+    );
+
+    for value in [&b"!!"[..], b"A"] {
+        let records = [record("LIBARCHIVE.xattr.user.a", value)];
+        assert_eq!(decode_xattrs(&records), Err(Errno(libc::EINVAL)));
+    }
+    let records = [record("LIBARCHIVE.xattr.user.%4", b"")];
+    assert_eq!(decode_xattrs(&records), Err(Errno(libc::EINVAL)));
+
+    for format in [PaxFormat::Schily, PaxFormat::Libarchive, PaxFormat::Both] {
+        let value = b"\x00binary\xff value\n";
+        let data = encode_xattr("user.x", value, format).unwrap();
+        assert_eq!(
+            decode_xattrs(&decode_records(&data).unwrap()).unwrap(),
+            [(OsString::from("user.x"), value.to_vec())]
+        );
+    }
+}
+
+#[test]
+fn test_capture_apply() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let src = temp_dir.path().join("test_capture_apply_src");
+    let dst = temp_dir.path().join("test_capture_apply_dst");
+    File::create(&src).unwrap();
+    File::create(&dst).unwrap();
+
+    for (name, value) in
+        [(&b"user.b"[..], &b"\x00\x01"[..]), (b"user.a=\xff", b"v")]
+    {
+        match lsetxattr(&src, OsStr::from_bytes(name), value, Flags::empty()) {
+            // EA not supported
+            Err(Errno(libc::ENOTSUP)) => return,
+            res => res.unwrap(),
+        }
+    }
+    let trusted = lsetxattr(&src, "trusted.test", "t", Flags::empty()).is_ok();
+
+    let data = capture(&src, PaxFormat::Both).unwrap();
+    assert_eq!(capture(&src, PaxFormat::Schily), Err(Errno(libc::EINVAL)));
+    let records = decode_records(&data).unwrap();
+
+    let skipped = apply(&dst, &records, NamespacePolicy::default()).unwrap();
+    assert_eq!(
+        lgetxattr(&dst, OsStr::from_bytes(b"user.a=\xff")).unwrap(),
+        b"v"
+    );
+    assert_eq!(lgetxattr(&dst, "user.b").unwrap(), b"\x00\x01");
+    if trusted {
+        assert_eq!(skipped, [OsString::from("trusted.test")]);
+        assert_eq!(lgetxattr(&dst, "trusted.test"), Err(Errno(libc::ENODATA)));
+
+        let policy = NamespacePolicy::USER | NamespacePolicy::TRUSTED;
+        assert!(apply(&dst, &records, policy).unwrap().is_empty());
+        assert_eq!(lgetxattr(&dst, "trusted.test").unwrap(), b"t");
+    } else {
+        assert!(skipped.is_empty());
+    }
+
+    // unknown namespaces are always skipped
+    let records = [record("SCHILY.xattr.btrfs.compression", b"zstd")];
+    let skipped = apply(&dst, &records, NamespacePolicy::all()).unwrap();
+    assert_eq!(skipped, [OsString::from("btrfs.compression")]);
+}
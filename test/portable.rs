@@ -0,0 +1,136 @@
+use errno::Errno;
+use extattr::{
+    getxattr, lgetxattr,
+    name::{AttrName, Namespace},
+    portable::{get, list, remove, set, SetMode, Target, ENOATTR},
+    setxattr, Flags,
+};
+use std::{fs::File, os::unix::io::AsRawFd};
+
+fn user(name: &str) -> AttrName {
+    AttrName::new(Namespace::User, name).unwrap()
+}
+
+#[test]
+fn test_path() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let path = temp_dir.path().join("test_path");
+    File::create(&path).unwrap();
+    let target = Target::Path(&path);
+    let name = user("test_path");
+
+    match set(target, &name, b"a", SetMode::CreateOrReplace) {
+        // EA not supported
+        Err(Errno(libc::ENOTSUP)) => return,
+        res => res.unwrap(),
+    }
+    assert_eq!(getxattr(&path, "user.test_path").unwrap(), b"a");
+    assert_eq!(get(target, &name).unwrap(), b"a");
+    assert_eq!(list(target).unwrap(), [user("test_path")]);
+
+    set(target, &name, b"b", SetMode::CreateOrReplace).unwrap();
+    assert_eq!(get(target, &name).unwrap(), b"b");
+
+    remove(target, &name).unwrap();
+    assert_eq!(get(target, &name), Err(Errno(ENOATTR)));
+    assert_eq!(remove(target, &name), Err(Errno(ENOATTR)));
+    assert!(list(target).unwrap().is_empty());
+}
+
+#[test]
+fn test_create_replace() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let path = temp_dir.path().join("test_create_replace");
+    File::create(&path).unwrap();
+    let target = Target::Path(&path);
+    let name = user("test_create_replace");
+
+    match set(target, &name, b"a", SetMode::Replace) {
+        // EA not supported
+        Err(Errno(libc::ENOTSUP)) => return,
+        res => assert_eq!(res, Err(Errno(ENOATTR))),
+    }
+    set(target, &name, b"a", SetMode::Create).unwrap();
+    assert_eq!(
+        set(target, &name, b"b", SetMode::Create),
+        Err(Errno(libc::EEXIST))
+    );
+    assert_eq!(get(target, &name).unwrap(), b"a");
+    set(target, &name, b"b", SetMode::Replace).unwrap();
+    assert_eq!(get(target, &name).unwrap(), b"b");
+}
+
+#[test]
+fn test_link() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let path = temp_dir.path().join("test_link");
+    File::create(&path).unwrap();
+    let link = temp_dir.path().join("link");
+    std::os::unix::fs::symlink(&path, &link).unwrap();
+    let name = user("test_link");
+
+    match set(Target::Path(&link), &name, b"a", SetMode::CreateOrReplace) {
+        // EA not supported
+        Err(Errno(libc::ENOTSUP)) => return,
+        res => res.unwrap(),
+    }
+    // the target of the link is modified
+    assert_eq!(getxattr(&path, "user.test_link").unwrap(), b"a");
+    assert_eq!(get(Target::Link(&link), &name), Err(Errno(ENOATTR)));
+    assert!(list(Target::Link(&link)).unwrap().is_empty());
+
+    // Linux does not allow `user.*` EAs on symbolic links
+    assert_eq!(
+        set(Target::Link(&link), &name, b"b", SetMode::CreateOrReplace),
+        Err(Errno(libc::EPERM))
+    );
+    assert_eq!(remove(Target::Link(&link), &name), Err(Errno(libc::EPERM)));
+    remove(Target::Path(&link), &name).unwrap();
+    assert_eq!(lgetxattr(&path, "user.test_link"), Err(Errno(ENOATTR)));
+}
+
+#[test]
+fn test_fd() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let path = temp_dir.path().join("test_fd");
+    let file = File::create(&path).unwrap();
+    let target = Target::Fd(file.as_raw_fd());
+    let name = user("test_fd");
+
+    match set(target, &name, b"a", SetMode::Create) {
+        // EA not supported
+        Err(Errno(libc::ENOTSUP)) => return,
+        res => res.unwrap(),
+    }
+    assert_eq!(getxattr(&path, "user.test_fd").unwrap(), b"a");
+    assert_eq!(get(target, &name).unwrap(), b"a");
+    assert_eq!(list(target).unwrap(), [user("test_fd")]);
+    remove(target, &name).unwrap();
+    assert_eq!(get(target, &name), Err(Errno(ENOATTR)));
+}
+
+#[test]
+fn test_namespaces() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let path = temp_dir.path().join("test_namespaces");
+    File::create(&path).unwrap();
+    let target = Target::Path(&path);
+
+    match setxattr(&path, "user.a", "1", Flags::empty()) {
+        // EA not supported
+        Err(Errno(libc::ENOTSUP)) => return,
+        res => res.unwrap(),
+    }
+    let trusted = AttrName::new(Namespace::Trusted, "test_namespaces").unwrap();
+    match set(target, &trusted, b"1", SetMode::Create) {
+        // Not privileged
+        Err(Errno(libc::EPERM)) => return,
+        res => res.unwrap(),
+    }
+    let mut names = list(target).unwrap();
+    names.sort();
+    assert_eq!(names.len(), 2);
+    assert_eq!(names[0], user("a"));
+    assert_eq!(names[1], trusted);
+    assert_eq!(get(target, &trusted).unwrap(), b"1");
+}
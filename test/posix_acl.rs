@@ -0,0 +1,123 @@
+use errno::Errno;
+use extattr::{
+    getxattr,
+    posix_acl::{
+        Perm, PosixAcl, PosixAclEntry, Tag, XATTR_NAME_POSIX_ACL_ACCESS,
+    },
+    setxattr, Flags,
+};
+use std::fs::File;
+
+fn entry(tag: Tag, perm: Perm) -> PosixAclEntry {
+    PosixAclEntry { tag, perm }
+}
+
+#[test]
+fn test_from_mode() {
+    let acl = PosixAcl::from_mode(0o100754);
+    assert_eq!(acl.get(Tag::UserObj), Some(Perm::all()));
+    assert_eq!(acl.get(Tag::GroupObj), Some(Perm::READ | Perm::EXECUTE));
+    assert_eq!(acl.get(Tag::Other), Some(Perm::READ));
+    assert_eq!(acl.get(Tag::Mask), None);
+}
+
+#[test]
+fn test_codec() {
+    let acl = PosixAcl {
+        entries: vec![
+            entry(Tag::UserObj, Perm::READ | Perm::WRITE),
+            entry(Tag::User(1000), Perm::READ),
+            entry(Tag::GroupObj, Perm::READ),
+            entry(Tag::Mask, Perm::READ),
+            entry(Tag::Other, Perm::empty()),
+        ],
+    };
+    let bytes = acl.to_bytes();
+    assert_eq!(bytes.len(), 4 + 5 * 8);
+    assert_eq!(&bytes[..4], &[2, 0, 0, 0]);
+    // ACL_USER, r--, 1000
+    assert_eq!(&bytes[12..20], &[0x02, 0, 4, 0, 0xe8, 0x03, 0, 0]);
+    // ACL_GROUP_OBJ, r--, ACL_UNDEFINED_ID
+    assert_eq!(&bytes[20..28], &[0x04, 0, 4, 0, 0xff, 0xff, 0xff, 0xff]);
+    assert_eq!(PosixAcl::from_bytes(&bytes).unwrap(), acl);
+
+    // bad version, truncated entry, unknown tag, unknown permission
+    assert_eq!(
+        PosixAcl::from_bytes(&[1, 0, 0, 0]),
+        Err(Errno(libc::EINVAL))
+    );
+    assert_eq!(
+        PosixAcl::from_bytes(&bytes[..bytes.len() - 1]),
+        Err(Errno(libc::EINVAL))
+    );
+    let mut bad = bytes.clone();
+    bad[4] = 0x40;
+    assert_eq!(PosixAcl::from_bytes(&bad), Err(Errno(libc::EINVAL)));
+    let mut bad = bytes;
+    bad[6] = 0x08;
+    assert_eq!(PosixAcl::from_bytes(&bad), Err(Errno(libc::EINVAL)));
+}
+
+#[test]
+fn test_normalize() {
+    let mut acl = PosixAcl {
+        entries: vec![
+            entry(Tag::Other, Perm::empty()),
+            entry(Tag::Mask, Perm::READ),
+            entry(Tag::Group(7), Perm::READ),
+            entry(Tag::GroupObj, Perm::READ),
+            entry(Tag::UserObj, Perm::all()),
+        ],
+    };
+    acl.normalize().unwrap();
+    let tags: Vec<Tag> = acl.entries.iter().map(|entry| entry.tag).collect();
+    assert_eq!(
+        tags,
+        [
+            Tag::UserObj,
+            Tag::GroupObj,
+            Tag::Group(7),
+            Tag::Mask,
+            Tag::Other
+        ]
+    );
+
+    // named entry without a mask
+    acl.entries.retain(|entry| entry.tag != Tag::Mask);
+    assert_eq!(acl.normalize(), Err(Errno(libc::EINVAL)));
+
+    // duplicated entry
+    let mut acl = PosixAcl::from_mode(0o644);
+    acl.entries.push(entry(Tag::Other, Perm::READ));
+    assert_eq!(acl.normalize(), Err(Errno(libc::EINVAL)));
+}
+
+#[test]
+fn test_access_acl_on_file() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let temp_file_path = temp_dir.path().join("test_access_acl_on_file");
+    File::create(&temp_file_path).unwrap();
+
+    let acl = PosixAcl {
+        entries: vec![
+            entry(Tag::UserObj, Perm::READ | Perm::WRITE),
+            entry(Tag::User(1000), Perm::READ),
+            entry(Tag::GroupObj, Perm::READ),
+            entry(Tag::Mask, Perm::READ),
+            entry(Tag::Other, Perm::empty()),
+        ],
+    };
+    match setxattr(
+        &temp_file_path,
+        XATTR_NAME_POSIX_ACL_ACCESS,
+        acl.to_bytes(),
+        Flags::empty(),
+    ) {
+        // POSIX ACLs are not supported
+        Err(Errno(libc::ENOTSUP)) => return,
+        res => res.unwrap(),
+    }
+
+    let value = getxattr(&temp_file_path, XATTR_NAME_POSIX_ACL_ACCESS).unwrap();
+    assert_eq!(PosixAcl::from_bytes(&value).unwrap(), acl);
+}
@@ -0,0 +1,189 @@
+use errno::Errno;
+use extattr::{
+    getxattr, lgetxattr,
+    rootless::{
+        effective_stat, get_override_stat, get_rsync_stat, set_override_stat,
+        set_ownership, set_rsync_stat, OverrideFileType, OverrideStat,
+        RsyncStat, StatFormat, StatSource,
+    },
+};
+use std::{fs, os::unix::fs::MetadataExt};
+
+#[test]
+fn test_rsync_stat_codec() {
+    let stat: RsyncStat = "20644 4,5 1000:100".parse().unwrap();
+    assert_eq!(
+        stat,
+        RsyncStat {
+            mode: 0o20644,
+            rdev_major: 4,
+            rdev_minor: 5,
+            uid: 1000,
+            gid: 100,
+        }
+    );
+    assert_eq!(stat.to_string(), "20644 4,5 1000:100");
+    assert_eq!(RsyncStat::from_bytes(&stat.to_bytes()).unwrap(), stat);
+    assert_eq!(
+        RsyncStat::from_bytes(b"100600 0,0 0:0\0").unwrap().mode,
+        0o100600
+    );
+
+    for s in [
+        "",
+        "644 0,0 0:0 extra",
+        "644 0,0 0-0",
+        "648 0,0 0:0",
+        "644 0;0 0:0",
+        "644 0,0 -1:0",
+        "644 0,0 0:4294967296",
+    ] {
+        assert_eq!(s.parse::<RsyncStat>(), Err(Errno(libc::EINVAL)));
+    }
+}
+
+#[test]
+fn test_override_stat_codec() {
+    let stat: OverrideStat = "0:0:755".parse().unwrap();
+    assert_eq!(
+        stat,
+        OverrideStat {
+            uid: 0,
+            gid: 0,
+            mode: 0o755,
+            file_type: None,
+        }
+    );
+    assert_eq!(stat.to_string(), "0:0:755");
+
+    let stat: OverrideStat = "1:2:0660:char-1-3".parse().unwrap();
+    assert_eq!(stat.mode, 0o660);
+    assert_eq!(stat.file_type, Some(OverrideFileType::Char(1, 3)));
+    assert_eq!(stat.to_string(), "1:2:660:char-1-3");
+    assert_eq!(OverrideStat::from_bytes(&stat.to_bytes()).unwrap(), stat);
+
+    for file_type in [
+        OverrideFileType::Directory,
+        OverrideFileType::File,
+        OverrideFileType::Symlink,
+        OverrideFileType::Pipe,
+        OverrideFileType::Socket,
+        OverrideFileType::Block(8, 0),
+        OverrideFileType::Char(4, 64),
+    ] {
+        assert_eq!(
+            file_type.to_string().parse::<OverrideFileType>().unwrap(),
+            file_type
+        );
+    }
+
+    for s in [
+        "",
+        "0:0",
+        "0:0:100644",
+        "0:0:8",
+        "0:0:644:fifo",
+        "0:0:644:block-8",
+    ] {
+        assert_eq!(s.parse::<OverrideStat>(), Err(Errno(libc::EINVAL)));
+    }
+}
+
+#[test]
+fn test_effective_stat() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let path = temp_dir.path().join("test_effective_stat");
+    fs::write(&path, "").unwrap();
+    let metadata = fs::symlink_metadata(&path).unwrap();
+
+    let stat = effective_stat(&path).unwrap();
+    assert_eq!(stat.source, StatSource::Lstat);
+    assert_eq!(stat.mode, metadata.mode());
+    assert_eq!(stat.uid, metadata.uid());
+
+    let null = effective_stat("/dev/null").unwrap();
+    assert_eq!(null.source, StatSource::Lstat);
+    assert_eq!((null.rdev_major, null.rdev_minor), (1, 3));
+
+    let rsync = RsyncStat {
+        mode: 0o20600,
+        rdev_major: 1,
+        rdev_minor: 3,
+        uid: 0,
+        gid: 5,
+    };
+    match set_rsync_stat(&path, &rsync) {
+        // EA not supported
+        Err(Errno(libc::ENOTSUP)) => return,
+        res => res.unwrap(),
+    }
+    assert_eq!(get_rsync_stat(&path).unwrap(), Some(rsync));
+    let stat = effective_stat(&path).unwrap();
+    assert_eq!(stat.source, StatSource::Rsync);
+    assert_eq!((stat.mode, stat.uid, stat.gid), (0o20600, 0, 5));
+    assert_eq!((stat.rdev_major, stat.rdev_minor), (1, 3));
+
+    // `user.containers.override_stat` takes precedence
+    let containers = OverrideStat {
+        uid: 7,
+        gid: 8,
+        mode: 0o4750,
+        file_type: None,
+    };
+    set_override_stat(&path, &containers).unwrap();
+    assert_eq!(get_override_stat(&path).unwrap(), Some(containers));
+    let stat = effective_stat(&path).unwrap();
+    assert_eq!(stat.source, StatSource::Containers);
+    assert_eq!((stat.mode, stat.uid, stat.gid), (0o104750, 7, 8));
+
+    assert_eq!(
+        set_override_stat(
+            &path,
+            &OverrideStat {
+                mode: 0o100644,
+                ..containers
+            }
+        ),
+        Err(Errno(libc::EINVAL))
+    );
+}
+
+#[test]
+fn test_set_ownership() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let path = temp_dir.path().join("test_set_ownership");
+    fs::write(&path, "").unwrap();
+    let mode = fs::symlink_metadata(&path).unwrap().mode();
+
+    match set_ownership(&path, 0, 0, StatFormat::Rsync) {
+        // EA not supported
+        Err(Errno(libc::ENOTSUP)) => return,
+        res => res.unwrap(),
+    }
+    assert_eq!(
+        getxattr(&path, "user.rsync.%stat").unwrap(),
+        format!("{:o} 0,0 0:0", mode).into_bytes()
+    );
+
+    // the emulated mode survives, and the rsync EA is replaced
+    set_rsync_stat(
+        &path,
+        &RsyncStat {
+            mode: 0o60640,
+            rdev_major: 8,
+            rdev_minor: 1,
+            uid: 0,
+            gid: 0,
+        },
+    )
+    .unwrap();
+    set_ownership(&path, 1000, 1000, StatFormat::Containers).unwrap();
+    assert_eq!(
+        lgetxattr(&path, "user.containers.override_stat").unwrap(),
+        b"1000:1000:640:block-8-1"
+    );
+    assert_eq!(get_rsync_stat(&path).unwrap(), None);
+    let stat = effective_stat(&path).unwrap();
+    assert_eq!((stat.mode, stat.uid, stat.gid), (0o60640, 1000, 1000));
+    assert_eq!((stat.rdev_major, stat.rdev_minor), (8, 1));
+}
@@ -0,0 +1,150 @@
+use errno::Errno;
+use extattr::{
+    getxattr,
+    s3::{
+        decode_key, decode_value, encode_key, encode_value, from_headers,
+        read_headers, to_headers, write_headers, Issue, Limits,
+    },
+    setxattr, Flags,
+};
+use std::{
+    ffi::{OsStr, OsString},
+    fs::File,
+    os::unix::ffi::OsStrExt,
+};
+
+#[test]
+fn test_keys() {
+    assert_eq!(encode_key("user.checksum").unwrap(), "x-amz-meta-checksum");
+    assert_eq!(
+        encode_key("user.Foo Bar").unwrap(),
+        "x-amz-meta-%46oo%20%42ar"
+    );
+    assert_eq!(
+        encode_key(OsStr::from_bytes(b"user.\xe9%")).unwrap(),
+        "x-amz-meta-%e9%25"
+    );
+    assert_eq!(encode_key("security.selinux"), Err(Issue::NotUserAttr));
+    assert_eq!(encode_key("user."), Err(Issue::InvalidName));
+
+    for name in ["user.checksum", "user.Foo Bar", "user.a%b.c-d_e"] {
+        let key = encode_key(name).unwrap();
+        assert_eq!(decode_key(&key).unwrap(), name);
+        assert_eq!(decode_key(&key.to_ascii_uppercase()).unwrap(), name);
+    }
+    assert_eq!(decode_key("X-Amz-Meta-Foo").unwrap(), "user.foo");
+    assert_eq!(decode_key("content-type"), Err(Issue::NotMetadataHeader));
+    assert_eq!(decode_key("x-amz-meta-"), Err(Issue::InvalidName));
+    assert_eq!(decode_key("x-amz-meta-%4"), Err(Issue::InvalidName));
+    assert_eq!(decode_key("x-amz-meta-%zz"), Err(Issue::InvalidName));
+    assert_eq!(decode_key("x-amz-meta-a b"), Err(Issue::InvalidName));
+}
+
+#[test]
+fn test_values() {
+    assert_eq!(encode_value(b"plain text"), "plain text");
+    assert_eq!(encode_value(b""), "");
+    assert_eq!(encode_value(b"\x00\xff"), "b64:AP8=");
+    assert_eq!(encode_value(" padded".as_bytes()), "b64:IHBhZGRlZA==");
+    assert_eq!(encode_value("caf\u{e9}".as_bytes()), "b64:Y2Fmw6k=");
+    assert_eq!(encode_value(b"b64:AP8="), "b64:YjY0OkFQOD0=");
+
+    for value in [&b"plain text"[..], b"", b"\x00\xff", b" padded", b"b64:x"] {
+        assert_eq!(decode_value(&encode_value(value)).unwrap(), value);
+    }
+    assert_eq!(decode_value("b64:AP8"), Err(Issue::InvalidValue));
+}
+
+#[test]
+fn test_to_headers() {
+    let attrs = vec![
+        ("user.b", vec![b'x'; 12]),
+        ("user.a", b"\x01".to_vec()),
+        ("trusted.overlay.opaque", b"y".to_vec()),
+        ("user.c", vec![b'x'; 100]),
+        ("user.d", vec![b'x'; 5]),
+    ];
+    let limits = Limits {
+        max_entry_size: 50,
+        max_total_size: 20,
+    };
+    let res = to_headers(attrs, limits);
+
+    assert_eq!(
+        res.headers,
+        vec![
+            ("x-amz-meta-a".to_owned(), "b64:AQ==".to_owned()),
+            ("x-amz-meta-d".to_owned(), "xxxxx".to_owned()),
+        ]
+    );
+    assert_eq!(
+        res.rejected,
+        vec![
+            (OsString::from("trusted.overlay.opaque"), Issue::NotUserAttr),
+            (OsString::from("user.b"), Issue::TotalTooLarge),
+            (OsString::from("user.c"), Issue::EntryTooLarge),
+        ]
+    );
+    assert_eq!(Limits::default(), Limits::S3);
+}
+
+#[test]
+fn test_from_headers() {
+    let res = from_headers(vec![
+        ("x-amz-meta-foo", "bar"),
+        ("Content-Type", "text/plain"),
+        ("X-Amz-Meta-FOO", "baz"),
+        ("x-amz-meta-bin", "b64:AP8="),
+        ("x-amz-meta-bad", "b64:!"),
+    ]);
+    assert_eq!(
+        res.attrs,
+        vec![
+            (OsString::from("user.foo"), b"bar".to_vec()),
+            (OsString::from("user.bin"), b"\x00\xff".to_vec()),
+        ]
+    );
+    assert_eq!(
+        res.rejected,
+        vec![
+            ("Content-Type".to_owned(), Issue::NotMetadataHeader),
+            ("X-Amz-Meta-FOO".to_owned(), Issue::Duplicate),
+            ("x-amz-meta-bad".to_owned(), Issue::InvalidValue),
+        ]
+    );
+}
+
+#[test]
+fn test_round_trip() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let src = temp_dir.path().join("test_round_trip_src");
+    let dst = temp_dir.path().join("test_round_trip_dst");
+    File::create(&src).unwrap();
+    File::create(&dst).unwrap();
+
+    let attrs: [(&[u8], &[u8]); 3] = [
+        (b"user.Mixed Case", b"value"),
+        (b"user.binary", b"\x00\x01\x02"),
+        (b"user.\xff", b" "),
+    ];
+    for (name, value) in attrs.iter() {
+        match setxattr(&src, OsStr::from_bytes(name), value, Flags::empty()) {
+            // EA not supported
+            Err(Errno(libc::ENOTSUP)) => return,
+            res => res.unwrap(),
+        }
+    }
+
+    let headers = read_headers(&src, Limits::S3).unwrap();
+    assert!(headers.rejected.is_empty());
+    assert!(headers
+        .headers
+        .iter()
+        .all(|(k, v)| k.is_ascii() && v.bytes().all(|b| b.is_ascii_graphic())));
+
+    let res = write_headers(&dst, headers.headers).unwrap();
+    assert!(res.rejected.is_empty());
+    for (name, value) in attrs.iter() {
+        assert_eq!(getxattr(&dst, OsStr::from_bytes(name)).unwrap(), *value);
+    }
+}
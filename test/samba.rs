@@ -0,0 +1,274 @@
+use errno::Errno;
+use extattr::samba::{
+    get_dosattrib, get_ntacl, set_dosattrib, set_ntacl, DosInfo, DosInfoValid,
+    FileAttributes, NtAcl, NtTime, SecurityDescriptor, XATTR_SD_HASH_SIZE,
+    XATTR_SD_HASH_TYPE_SHA256,
+};
+use std::{
+    fs::File,
+    time::{Duration, UNIX_EPOCH},
+};
+
+/// `O:BAG:SYD:` with owner S-1-5-32-544 and group S-1-5-18, no ACLs.
+#[rustfmt::skip]
+const SD: &[u8] = &[
+    // revision, sbz1, control (SE_SELF_RELATIVE)
+    1, 0, 0x00, 0x80,
+    // owner, group, sacl, dacl offsets
+    20, 0, 0, 0,
+    36, 0, 0, 0,
+    0, 0, 0, 0,
+    0, 0, 0, 0,
+    // S-1-5-32-544
+    1, 2, 0, 0, 0, 0, 0, 5, 32, 0, 0, 0, 0x20, 0x02, 0, 0,
+    // S-1-5-18
+    1, 1, 0, 0, 0, 0, 0, 5, 18, 0, 0, 0,
+];
+
+#[test]
+fn test_nttime() {
+    assert_eq!(NtTime(116_444_736_000_000_000).to_system_time(), UNIX_EPOCH);
+    let time = UNIX_EPOCH + Duration::new(1_600_000_000, 123_456_700);
+    let nttime = NtTime::from_system_time(time);
+    assert_eq!(nttime, NtTime(132_444_736_001_234_567));
+    assert_eq!(nttime.to_system_time(), time);
+    assert_eq!(
+        NtTime(0).to_system_time() + Duration::new(11_644_473_600, 0),
+        UNIX_EPOCH
+    );
+}
+
+#[test]
+fn test_dosinfo_v4() {
+    #[rustfmt::skip]
+    let bytes: &[u8] = &[
+        b'0', b'x', b'2', b'0', 0,
+        // padding, version, level, padding
+        0, 4, 0, 4, 0, 0, 0,
+        // valid_flags, attrib
+        0x51, 0, 0, 0, 0x20, 0, 0, 0,
+        // itime, create_time
+        1, 0, 0, 0, 0, 0, 0, 0,
+        2, 0, 0, 0, 0, 0, 0, 0,
+    ];
+    let info = DosInfo::from_bytes(bytes).unwrap();
+    assert_eq!(
+        info,
+        DosInfo::V4 {
+            valid_flags: DosInfoValid::ATTRIB
+                | DosInfoValid::CREATE_TIME
+                | DosInfoValid::ITIME,
+            attrib: 0x20,
+            itime: NtTime(1),
+            create_time: NtTime(2),
+        }
+    );
+    assert_eq!(info.attributes(), FileAttributes::ARCHIVE);
+    assert_eq!(info.itime(), Some(NtTime(1)));
+    assert_eq!(info.create_time(), Some(NtTime(2)));
+    assert_eq!(info.to_bytes(), bytes);
+}
+
+#[test]
+fn test_dosinfo_v5() {
+    let info = DosInfo::new(
+        FileAttributes::HIDDEN | FileAttributes::READONLY,
+        Some(NtTime(42)),
+    );
+    let bytes = info.to_bytes();
+    assert_eq!(&bytes[..4], b"0x3\0");
+    assert_eq!(&bytes[4..8], &[5, 0, 5, 0]);
+    assert_eq!(bytes.len(), 8 + 16);
+    assert_eq!(DosInfo::from_bytes(&bytes).unwrap(), info);
+    assert_eq!(info.create_time(), Some(NtTime(42)));
+    assert_eq!(info.itime(), None);
+
+    let info = DosInfo::new(FileAttributes::ARCHIVE, None);
+    assert_eq!(info.create_time(), None);
+}
+
+#[test]
+fn test_dosinfo_old_versions() {
+    for info in [
+        DosInfo::Compat { attrib: 0x21 },
+        DosInfo::V1 {
+            attrib: 0x10,
+            ea_size: 1,
+            size: 2,
+            alloc_size: 3,
+            create_time: NtTime(4),
+            change_time: NtTime(5),
+        },
+        DosInfo::V2 {
+            flags: 0,
+            attrib: 0x20,
+            ea_size: 1,
+            size: 2,
+            alloc_size: 3,
+            create_time: NtTime(4),
+            change_time: NtTime(5),
+            write_time: NtTime(6),
+            name: "foo".to_owned(),
+        },
+        DosInfo::V3 {
+            valid_flags: DosInfoValid::ATTRIB,
+            attrib: 0x20,
+            ea_size: 1,
+            size: 2,
+            alloc_size: 3,
+            create_time: NtTime(4),
+            change_time: NtTime(5),
+        },
+    ] {
+        assert_eq!(DosInfo::from_bytes(&info.to_bytes()).unwrap(), info);
+    }
+
+    // Samba 3.0, with and without the NUL
+    assert_eq!(
+        DosInfo::from_bytes(b"0x22\0").unwrap(),
+        DosInfo::Legacy { attrib: 0x22 }
+    );
+    assert_eq!(
+        DosInfo::from_bytes(b"0X22").unwrap(),
+        DosInfo::Legacy { attrib: 0x22 }
+    );
+    assert_eq!(DosInfo::Legacy { attrib: 0x22 }.to_bytes(), b"0x22\0");
+}
+
+#[test]
+fn test_dosinfo_invalid() {
+    assert_eq!(DosInfo::from_bytes(b"junk"), Err(Errno(libc::EINVAL)));
+    // version and level mismatch
+    assert_eq!(
+        DosInfo::from_bytes(b"0x20\0\0\x05\0\x04\0"),
+        Err(Errno(libc::EINVAL))
+    );
+    // unknown version
+    assert_eq!(
+        DosInfo::from_bytes(b"0x20\0\0\x09\0\x09\0"),
+        Err(Errno(libc::EINVAL))
+    );
+    // truncated
+    let bytes = DosInfo::new(FileAttributes::ARCHIVE, None).to_bytes();
+    assert_eq!(
+        DosInfo::from_bytes(&bytes[..bytes.len() - 1]),
+        Err(Errno(libc::EINVAL))
+    );
+}
+
+#[test]
+fn test_security_descriptor() {
+    let sd = SecurityDescriptor::from_bytes(SD).unwrap();
+    assert_eq!(sd.control(), 0x8000);
+    assert_eq!(sd.owner().unwrap().to_string(), "S-1-5-32-544");
+    assert_eq!(sd.group().unwrap().to_string(), "S-1-5-18");
+
+    let hash = sd.sha256();
+    assert_eq!(hash.len(), XATTR_SD_HASH_SIZE);
+    assert!(hash[32..].iter().all(|b| *b == 0));
+
+    assert_eq!(
+        SecurityDescriptor::from_bytes(&SD[..SD.len() - 1]),
+        Err(Errno(libc::EINVAL))
+    );
+    let mut trailing = SD.to_vec();
+    trailing.push(0);
+    assert_eq!(
+        SecurityDescriptor::from_bytes(&trailing),
+        Err(Errno(libc::EINVAL))
+    );
+    // offsets at the end of the address space on 32-bit targets
+    let mut huge = SD.to_vec();
+    huge[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert_eq!(
+        SecurityDescriptor::from_bytes(&huge),
+        Err(Errno(libc::EINVAL))
+    );
+}
+
+#[test]
+fn test_ntacl_v3() {
+    let acl = NtAcl::new(SecurityDescriptor::from_bytes(SD).unwrap());
+    assert!(acl.hash_matches());
+
+    let bytes = acl.to_bytes();
+    assert_eq!(&bytes[..4], &[3, 0, 3, 0]);
+    // union pointer, sd pointer, hash type, hash, padding, sd
+    assert_eq!(u16::from_le_bytes([bytes[12], bytes[13]]), 1);
+    assert_eq!(bytes.len(), 14 + 64 + 2 + SD.len());
+    assert_eq!(&bytes[80..], SD);
+    assert_eq!(NtAcl::from_bytes(&bytes).unwrap(), acl);
+
+    if let NtAcl::V3 { sd, hash_type, .. } = acl {
+        let tampered = NtAcl::V3 {
+            sd,
+            hash_type,
+            hash: [0; XATTR_SD_HASH_SIZE],
+        };
+        assert!(!tampered.hash_matches());
+    }
+}
+
+#[test]
+fn test_ntacl_v4() {
+    let sd = SecurityDescriptor::from_bytes(SD).unwrap();
+    for description in [Some("posix_acl".to_owned()), None] {
+        let acl = NtAcl::V4 {
+            hash: sd.sha256(),
+            sd: sd.clone(),
+            hash_type: XATTR_SD_HASH_TYPE_SHA256,
+            description,
+            time: NtTime(132_444_736_001_234_567),
+            sys_acl_hash: [7; XATTR_SD_HASH_SIZE],
+        };
+        let bytes = acl.to_bytes();
+        assert_eq!(&bytes[156..156 + SD.len()], SD);
+        assert_eq!(NtAcl::from_bytes(&bytes).unwrap(), acl);
+        assert!(acl.hash_matches());
+    }
+}
+
+#[test]
+fn test_ntacl_v1_v2() {
+    let sd = SecurityDescriptor::from_bytes(SD).unwrap();
+    let acl = NtAcl::V1 { sd: sd.clone() };
+    let bytes = acl.to_bytes();
+    assert_eq!(&bytes[8..], SD);
+    assert_eq!(NtAcl::from_bytes(&bytes).unwrap(), acl);
+    assert!(!acl.hash_matches());
+
+    let acl = NtAcl::V2 { sd, hash: [1; 16] };
+    assert_eq!(NtAcl::from_bytes(&acl.to_bytes()).unwrap(), acl);
+
+    assert_eq!(NtAcl::from_bytes(&[9, 0, 9, 0]), Err(Errno(libc::EINVAL)));
+}
+
+#[test]
+fn test_set_dosattrib() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let temp_file_path = temp_dir.path().join("test_set_dosattrib");
+    File::create(&temp_file_path).unwrap();
+
+    let info = DosInfo::new(FileAttributes::HIDDEN, Some(NtTime(1)));
+    match set_dosattrib(&temp_file_path, &info) {
+        // EA not supported
+        Err(Errno(libc::ENOTSUP)) => return,
+        res => res.unwrap(),
+    }
+    assert_eq!(get_dosattrib(&temp_file_path).unwrap(), info);
+}
+
+#[test]
+fn test_set_ntacl() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let temp_file_path = temp_dir.path().join("test_set_ntacl");
+    File::create(&temp_file_path).unwrap();
+
+    let acl = NtAcl::new(SecurityDescriptor::from_bytes(SD).unwrap());
+    match set_ntacl(&temp_file_path, &acl) {
+        // `security.*` EAs are not supported, or we are not privileged
+        Err(Errno(libc::ENOTSUP | libc::EPERM)) => return,
+        res => res.unwrap(),
+    }
+    assert_eq!(get_ntacl(&temp_file_path).unwrap(), acl);
+}
@@ -0,0 +1,323 @@
+use errno::Errno;
+use extattr::{
+    getxattr,
+    selinux::{
+        context_bytes_eq, fgetfilecon, fsetfilecon, getfilecon, lgetfilecon,
+        setfilecon, CategorySpan, MlsLevel, SecurityContext,
+        XATTR_NAME_SELINUX,
+    },
+};
+use std::{fs::File, os::unix::io::AsRawFd};
+
+#[test]
+fn test_parse_context_without_range() {
+    let ctx: SecurityContext = "system_u:object_r:etc_t".parse().unwrap();
+
+    assert_eq!(ctx.user, "system_u");
+    assert_eq!(ctx.role, "object_r");
+    assert_eq!(ctx.type_, "etc_t");
+    assert_eq!(ctx.range, None);
+    assert_eq!(ctx.to_string(), "system_u:object_r:etc_t");
+}
+
+#[test]
+fn test_parse_context_mcs_range() {
+    let raw = "system_u:system_r:container_t:s0-s0:c0.c1023";
+    let ctx: SecurityContext = raw.parse().unwrap();
+    let range = ctx.range.as_ref().unwrap();
+
+    assert_eq!(
+        range.low,
+        MlsLevel {
+            sensitivity: 0,
+            categories: Vec::new()
+        }
+    );
+    assert_eq!(
+        range.high,
+        Some(MlsLevel {
+            sensitivity: 0,
+            categories: vec![CategorySpan { low: 0, high: 1023 }],
+        })
+    );
+    assert_eq!(ctx.to_string(), raw);
+}
+
+#[test]
+fn test_parse_context_category_list() {
+    let raw = "user_u:user_r:user_t:s0:c1,c3.c5";
+    let ctx: SecurityContext = raw.parse().unwrap();
+    let range = ctx.range.as_ref().unwrap();
+
+    assert_eq!(
+        range.low.categories,
+        vec![
+            CategorySpan { low: 1, high: 1 },
+            CategorySpan { low: 3, high: 5 }
+        ]
+    );
+    assert_eq!(range.high, None);
+    assert_eq!(ctx.to_string(), raw);
+}
+
+#[test]
+fn test_parse_context_invalid() {
+    for raw in [
+        "",
+        "system_u:object_r",
+        "system_u::etc_t",
+        "system_u:object_r:etc_t:",
+        "system_u:object_r:etc_t:x0",
+        "system_u:object_r:etc_t:s0:c5.c1",
+        "system_u:object_r:etc_t:s0:c",
+    ] {
+        assert_eq!(
+            raw.parse::<SecurityContext>(),
+            Err(Errno(libc::EINVAL)),
+            "{}",
+            raw
+        );
+    }
+}
+
+#[test]
+fn test_context_bytes() {
+    let ctx: SecurityContext = "system_u:object_r:etc_t:s0".parse().unwrap();
+
+    assert_eq!(ctx.to_bytes(), b"system_u:object_r:etc_t:s0\0");
+    assert_eq!(
+        SecurityContext::from_bytes(b"system_u:object_r:etc_t:s0\0").unwrap(),
+        ctx
+    );
+    assert_eq!(
+        SecurityContext::from_bytes(b"system_u:object_r:etc_t:s0").unwrap(),
+        ctx
+    );
+}
+
+#[test]
+fn test_context_bytes_eq() {
+    assert!(context_bytes_eq("a:b:c:s0\0", "a:b:c:s0"));
+    assert!(context_bytes_eq("a:b:c:s0", "a:b:c:s0"));
+    assert!(!context_bytes_eq("a:b:c:s0\0", "a:b:d:s0"));
+}
+
+#[test]
+fn test_setfilecon_getfilecon() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let temp_file_path = temp_dir.path().join("test_setfilecon_getfilecon");
+    File::create(temp_file_path.as_path()).unwrap();
+    let ctx: SecurityContext = "system_u:object_r:tmp_t:s0".parse().unwrap();
+
+    match setfilecon(temp_file_path.as_path(), &ctx) {
+        // Setting `security.*` EA requires `CAP_SYS_ADMIN` when SELinux is
+        // disabled, or the loaded policy may reject this label, skip this test.
+        Err(Errno(libc::ENOTSUP | libc::EPERM | libc::EINVAL)) => return,
+        res => res.unwrap(),
+    }
+
+    assert_eq!(
+        getxattr(temp_file_path.as_path(), XATTR_NAME_SELINUX).unwrap(),
+        b"system_u:object_r:tmp_t:s0\0"
+    );
+    assert_eq!(getfilecon(temp_file_path.as_path()).unwrap(), ctx);
+    assert_eq!(lgetfilecon(temp_file_path.as_path()).unwrap(), ctx);
+}
+
+#[test]
+fn test_fsetfilecon_fgetfilecon() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let temp_file_path = temp_dir.path().join("test_fsetfilecon_fgetfilecon");
+    let temp_file = File::create(temp_file_path.as_path()).unwrap();
+    let temp_file_fd = temp_file.as_raw_fd();
+    let ctx: SecurityContext =
+        "system_u:object_r:tmp_t:s0-s0:c0.c1023".parse().unwrap();
+
+    match fsetfilecon(temp_file_fd, &ctx) {
+        // Setting `security.*` EA requires `CAP_SYS_ADMIN` when SELinux is
+        // disabled, or the loaded policy may reject this label, skip this test.
+        Err(Errno(libc::ENOTSUP | libc::EPERM | libc::EINVAL)) => return,
+        res => res.unwrap(),
+    }
+
+    assert_eq!(fgetfilecon(temp_file_fd).unwrap(), ctx);
+}
+
+mod file_contexts {
+    use errno::Errno;
+    use extattr::{
+        lgetxattr,
+        selinux::{
+            file_contexts::{
+                restorecon, FileContexts, FileType, RestoreconFlags,
+            },
+            SecurityContext, XATTR_NAME_SELINUX,
+        },
+    };
+    use std::fs::{self, File};
+
+    const SPEC: &str = r"
+# A comment
+
+/.*                 system_u:object_r:default_t:s0
+/etc(/.*)?          system_u:object_r:etc_t:s0
+/etc/shadow.*   --  system_u:object_r:shadow_t:s0
+/etc/passwd     --  system_u:object_r:passwd_file_t:s0
+/run            -d  system_u:object_r:var_run_t:s0
+/run(/.*)?          system_u:object_r:var_run_t:s0
+/proc(/.*)?         <<none>>
+/etc/dup            system_u:object_r:first_t:s0
+/etc/dup            system_u:object_r:second_t:s0
+";
+
+    fn label(s: &str) -> SecurityContext {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_file_contexts_parse() {
+        let contexts = FileContexts::parse(SPEC).unwrap();
+
+        assert_eq!(contexts.specs().len(), 9);
+        // the least specific one comes first
+        assert_eq!(contexts.specs()[0].regex, "/.*");
+        assert_eq!(
+            "-d".parse::<FileType>().unwrap().to_string(),
+            FileType::Directory.to_string()
+        );
+    }
+
+    #[test]
+    fn test_file_contexts_parse_invalid() {
+        for spec in [
+            "/etc",
+            "/etc -x system_u:object_r:etc_t:s0",
+            "/etc -- system_u:object_r:etc_t:s0 extra",
+            "/etc(  system_u:object_r:etc_t:s0",
+            "/etc   not_a_context",
+        ] {
+            assert_eq!(
+                FileContexts::parse(spec).unwrap_err(),
+                Errno(libc::EINVAL),
+                "{}",
+                spec
+            );
+        }
+    }
+
+    #[test]
+    fn test_file_contexts_lookup() {
+        let contexts = FileContexts::parse(SPEC).unwrap();
+
+        assert_eq!(
+            contexts.expected_label("/etc/passwd", FileType::Regular),
+            Some(&label("system_u:object_r:passwd_file_t:s0"))
+        );
+        // file type qualifier does not match, falls back to `/etc(/.*)?`
+        assert_eq!(
+            contexts.expected_label("/etc/passwd", FileType::Directory),
+            Some(&label("system_u:object_r:etc_t:s0"))
+        );
+        assert_eq!(
+            contexts.expected_label("/etc/shadow-", FileType::Regular),
+            Some(&label("system_u:object_r:shadow_t:s0"))
+        );
+        assert_eq!(
+            contexts.expected_label("/etc", FileType::Directory),
+            Some(&label("system_u:object_r:etc_t:s0"))
+        );
+        // must match the whole path
+        assert_eq!(
+            contexts.expected_label("/etcfoo", FileType::Regular),
+            Some(&label("system_u:object_r:default_t:s0"))
+        );
+        // equally specific, the last one wins
+        assert_eq!(
+            contexts.expected_label("/etc/dup", FileType::Regular),
+            Some(&label("system_u:object_r:second_t:s0"))
+        );
+        // `<<none>>`
+        assert!(contexts.lookup("/proc/1", FileType::Regular).is_some());
+        assert_eq!(contexts.expected_label("/proc/1", FileType::Regular), None);
+        assert!(FileContexts::parse("/etc  system_u:object_r:etc_t:s0")
+            .unwrap()
+            .lookup("/usr", FileType::Directory)
+            .is_none());
+    }
+
+    #[test]
+    fn test_restorecon_nochange() {
+        let temp_dir = tempfile::tempdir_in("./").unwrap();
+        let root = temp_dir.path();
+        fs::create_dir(root.join("etc")).unwrap();
+        File::create(root.join("etc/passwd")).unwrap();
+        fs::create_dir_all(root.join("proc/1")).unwrap();
+        let contexts = FileContexts::parse(SPEC).unwrap();
+        let before = lgetxattr(root.join("etc/passwd"), XATTR_NAME_SELINUX);
+
+        let relabels = restorecon(
+            root,
+            &contexts,
+            RestoreconFlags::RECURSE | RestoreconFlags::NOCHANGE,
+        );
+        let relabels = match relabels {
+            // The underlying file system does not support EA, skip this test.
+            Err(Errno(libc::ENOTSUP)) => return,
+            res => res.unwrap(),
+        };
+
+        let paths = relabels
+            .iter()
+            .map(|relabel| relabel.path.strip_prefix(root).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![
+                std::path::Path::new(""),
+                std::path::Path::new("etc"),
+                std::path::Path::new("etc/passwd"),
+            ]
+        );
+        assert_eq!(
+            relabels[2].expected,
+            label("system_u:object_r:passwd_file_t:s0")
+        );
+        // nothing changed
+        assert_eq!(
+            lgetxattr(root.join("etc/passwd"), XATTR_NAME_SELINUX),
+            before
+        );
+    }
+
+    #[test]
+    fn test_restorecon() {
+        let temp_dir = tempfile::tempdir_in("./").unwrap();
+        let root = temp_dir.path();
+        fs::create_dir(root.join("etc")).unwrap();
+        File::create(root.join("etc/passwd")).unwrap();
+        let contexts = FileContexts::parse(SPEC).unwrap();
+
+        match restorecon(root, &contexts, RestoreconFlags::RECURSE) {
+            // Setting `security.*` EA requires `CAP_SYS_ADMIN` when SELinux is
+            // disabled, or the loaded policy may reject these labels, skip
+            // this test.
+            Err(Errno(libc::ENOTSUP | libc::EPERM | libc::EINVAL)) => return,
+            res => res.unwrap(),
+        };
+
+        assert_eq!(
+            lgetxattr(root.join("etc/passwd"), XATTR_NAME_SELINUX).unwrap(),
+            b"system_u:object_r:passwd_file_t:s0\0"
+        );
+        // everything is labeled now
+        assert_eq!(
+            restorecon(
+                root,
+                &contexts,
+                RestoreconFlags::RECURSE | RestoreconFlags::NOCHANGE
+            )
+            .unwrap(),
+            Vec::new()
+        );
+    }
+}
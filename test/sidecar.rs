@@ -0,0 +1,272 @@
+use errno::Errno;
+use extattr::{
+    backend::SyscallBackend,
+    fault::{Action, Fault, FaultBackend, Trigger},
+    lgetxattr, lsetxattr,
+    name::Namespace,
+    sidecar::{
+        get, list, migrate, remove, set, Sidecar, LOCK_NAME, STORE_MAGIC,
+        STORE_NAME,
+    },
+    Flags,
+};
+use std::{
+    ffi::{CString, OsStr, OsString},
+    fs::{self, File},
+    os::unix::{ffi::OsStrExt, fs::MetadataExt},
+    path::Path,
+    time::UNIX_EPOCH,
+};
+
+/// Returns the inode number and the birth time keying the EAs of `path`.
+fn file_id(path: &Path) -> (u64, u64) {
+    let metadata = fs::symlink_metadata(path).unwrap();
+    let birth = metadata.created().map_or(0, |time| {
+        time.duration_since(UNIX_EPOCH).unwrap().as_nanos()
+    });
+    (metadata.ino(), birth as u64)
+}
+
+/// Writes a store, since the EAs are only stored on file systems without EA
+/// support.
+fn write_store(dir: &Path, entries: &[((u64, u64), &str, &[u8])]) {
+    let mut bytes = STORE_MAGIC.to_vec();
+    for ((ino, birth), name, value) in entries {
+        bytes.extend_from_slice(&ino.to_le_bytes());
+        bytes.extend_from_slice(&birth.to_le_bytes());
+        bytes.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.extend_from_slice(value);
+    }
+    fs::write(dir.join(STORE_NAME), bytes).unwrap();
+}
+
+#[test]
+fn test_merge() {
+    let dir = tempfile::tempdir_in("./").unwrap();
+    let file = dir.path().join("file");
+    File::create(&file).unwrap();
+    let (ino, birth) = file_id(&file);
+
+    match set(&file, "user.real", b"1", Flags::empty()) {
+        // EA not supported, and the store can not be checked
+        Err(Errno(libc::ENOTSUP)) => return,
+        res => res.unwrap(),
+    }
+    // stored on the file itself
+    assert_eq!(lgetxattr(&file, "user.real").unwrap(), b"1");
+    assert!(!dir.path().join(STORE_NAME).exists());
+    assert!(!dir.path().join(LOCK_NAME).exists());
+
+    write_store(
+        dir.path(),
+        &[
+            ((ino, birth), "user.real", b"stale"),
+            ((ino, birth), "user.stored", b"2"),
+            ((ino + 1, birth), "user.other", b"3"),
+            // a deleted file whose inode number was reused
+            ((ino, birth + 1), "user.reused", b"4"),
+        ],
+    );
+    assert_eq!(get(&file, "user.real").unwrap(), b"1");
+    assert_eq!(get(&file, "user.stored").unwrap(), b"2");
+    assert_eq!(get(&file, "user.other"), Err(Errno(libc::ENODATA)));
+    assert_eq!(get(&file, "user.reused"), Err(Errno(libc::ENODATA)));
+    assert_eq!(
+        list(&file).unwrap(),
+        [OsString::from("user.real"), OsString::from("user.stored")]
+    );
+
+    remove(&file, "user.stored").unwrap();
+    assert_eq!(get(&file, "user.stored"), Err(Errno(libc::ENODATA)));
+    assert_eq!(remove(&file, "user.stored"), Err(Errno(libc::ENODATA)));
+    remove(&file, "user.real").unwrap();
+    assert_eq!(get(&file, "user.real"), Err(Errno(libc::ENODATA)));
+    assert_eq!(list(&file).unwrap(), Vec::<OsString>::new());
+}
+
+#[test]
+fn test_migrate() {
+    let dir = tempfile::tempdir_in("./").unwrap();
+    let file = dir.path().join("file");
+    File::create(&file).unwrap();
+    let (ino, birth) = file_id(&file);
+    match lsetxattr(&file, "user.probe", b"", Flags::empty()) {
+        // EA not supported
+        Err(Errno(libc::ENOTSUP)) => return,
+        res => res.unwrap(),
+    }
+
+    assert_eq!(migrate(dir.path()).unwrap(), 0);
+    write_store(
+        dir.path(),
+        &[
+            ((ino, birth), "user.foo", b"bar"),
+            ((ino, birth), "user.empty", b""),
+            // no longer in the directory
+            ((u64::MAX, birth), "user.gone", b"baz"),
+            ((ino, birth + 1), "user.reused", b"qux"),
+        ],
+    );
+    assert_eq!(migrate(dir.path()).unwrap(), 2);
+    assert!(!dir.path().join(STORE_NAME).exists());
+    assert_eq!(lgetxattr(&file, "user.foo").unwrap(), b"bar");
+    assert_eq!(lgetxattr(&file, "user.empty").unwrap(), b"");
+    assert_eq!(lgetxattr(&file, "user.reused"), Err(Errno(libc::ENODATA)));
+
+    fs::write(dir.path().join(STORE_NAME), b"XATTRSC1\x01").unwrap();
+    assert_eq!(get(&file, "user.missing"), Err(Errno(libc::EINVAL)));
+}
+
+#[test]
+fn test_fallback() {
+    let dir = tempfile::tempdir_in("./").unwrap();
+    let file = dir.path().join("file");
+    File::create(&file).unwrap();
+    let store = dir.path().join(STORE_NAME);
+    // a file system without EA support
+    let sidecar = Sidecar::new(FaultBackend::new(SyscallBackend));
+    sidecar.backend().inject(Fault::new(
+        Trigger::Always,
+        Action::Fail(Errno(libc::ENOTSUP)),
+    ));
+    let foo = OsStr::new("user.foo");
+
+    assert_eq!(
+        sidecar.set(&file, foo, b"1", Flags::XATTR_REPLACE),
+        Err(Errno(libc::ENODATA))
+    );
+    assert!(!store.exists());
+    sidecar.set(&file, foo, b"1", Flags::XATTR_CREATE).unwrap();
+    assert!(store.exists());
+    // updates lock a regular file opened read-write, not the directory
+    let lock = fs::symlink_metadata(dir.path().join(LOCK_NAME)).unwrap();
+    assert!(lock.file_type().is_file());
+    assert_eq!(lock.mode() & 0o777, 0o600);
+    assert_eq!(
+        sidecar.set(&file, foo, b"2", Flags::XATTR_CREATE),
+        Err(Errno(libc::EEXIST))
+    );
+    sidecar.set(&file, foo, b"2", Flags::XATTR_REPLACE).unwrap();
+    sidecar
+        .set(&file, OsStr::new("user.bar"), b"", Flags::empty())
+        .unwrap();
+    assert_eq!(
+        sidecar.set(&file, OsStr::new("unknown.foo"), b"", Flags::empty()),
+        Err(Errno(libc::ENOTSUP))
+    );
+    assert_eq!(sidecar.get(&file, foo).unwrap(), b"2");
+    assert_eq!(
+        sidecar.list(&file).unwrap(),
+        [OsString::from("user.bar"), OsString::from("user.foo")]
+    );
+    // not on the file itself, whether or not the file system supports EAs
+    assert!(matches!(
+        lgetxattr(&file, foo),
+        Err(Errno(libc::ENODATA)) | Err(Errno(libc::ENOTSUP))
+    ));
+
+    sidecar.remove(&file, OsStr::new("user.bar")).unwrap();
+    assert_eq!(
+        sidecar.remove(&file, OsStr::new("user.bar")),
+        Err(Errno(libc::ENODATA))
+    );
+    // the EAs stay in the store
+    assert_eq!(sidecar.migrate(dir.path()).unwrap(), 0);
+    assert_eq!(get(&file, foo).unwrap(), b"2");
+
+    // the file system now supports EAs
+    sidecar.backend().clear();
+    match lsetxattr(&file, "user.probe", b"", Flags::empty()) {
+        // EA not supported
+        Err(Errno(libc::ENOTSUP)) => return,
+        res => res.unwrap(),
+    }
+    assert_eq!(sidecar.migrate(dir.path()).unwrap(), 1);
+    assert!(!store.exists());
+    assert_eq!(lgetxattr(&file, foo).unwrap(), b"2");
+}
+
+#[test]
+fn test_permissions() {
+    let dir = tempfile::tempdir_in("./").unwrap();
+    let file = dir.path().join("file");
+    File::create(&file).unwrap();
+    let store = dir.path().join(STORE_NAME);
+    let sidecar = Sidecar::new(FaultBackend::new(SyscallBackend));
+    sidecar.backend().inject(Fault::new(
+        Trigger::Always,
+        Action::Fail(Errno(libc::ENOTSUP)),
+    ));
+    let foo = OsStr::new("user.foo");
+
+    sidecar.set(&file, foo, b"1", Flags::empty()).unwrap();
+    assert_eq!(fs::metadata(&store).unwrap().mode() & 0o777, 0o600);
+
+    // only user.* by default
+    let trusted = OsStr::new("trusted.foo");
+    assert_eq!(
+        sidecar.set(&file, trusted, b"2", Flags::empty()),
+        Err(Errno(libc::ENOTSUP))
+    );
+    let all = Sidecar::with_namespaces(
+        FaultBackend::new(SyscallBackend),
+        &[Namespace::User, Namespace::Trusted],
+    );
+    all.backend().inject(Fault::new(
+        Trigger::Always,
+        Action::Fail(Errno(libc::ENOTSUP)),
+    ));
+    all.set(&file, trusted, b"2", Flags::empty()).unwrap();
+    assert_eq!(all.get(&file, trusted).unwrap(), b"2");
+    assert_eq!(sidecar.get(&file, trusted), Err(Errno(libc::ENODATA)));
+    assert_eq!(sidecar.list(&file).unwrap(), [OsString::from("user.foo")]);
+
+    // a store planted by another user
+    let path = CString::new(store.as_os_str().as_bytes()).unwrap();
+    let euid = unsafe { libc::geteuid() };
+    if unsafe { libc::lchown(path.as_ptr(), euid + 1, u32::MAX) } == 0 {
+        assert_eq!(sidecar.get(&file, foo), Err(Errno(libc::EPERM)));
+        assert_eq!(
+            sidecar.set(&file, foo, b"3", Flags::empty()),
+            Err(Errno(libc::EPERM))
+        );
+    }
+}
+
+#[test]
+fn test_migrate_namespaces() {
+    let dir = tempfile::tempdir_in("./").unwrap();
+    let file = dir.path().join("file");
+    File::create(&file).unwrap();
+    let id = file_id(&file);
+    match lsetxattr(&file, "trusted.probe", b"", Flags::empty()) {
+        // EA not supported, or not privileged
+        Err(Errno(libc::ENOTSUP)) | Err(Errno(libc::EPERM)) => return,
+        res => res.unwrap(),
+    }
+
+    write_store(
+        dir.path(),
+        &[
+            (id, "user.foo", b"1"),
+            (id, "trusted.foo", b"2"),
+            (id, "security.capability", b"3"),
+        ],
+    );
+    // the other namespaces stay in the store
+    assert_eq!(migrate(dir.path()).unwrap(), 1);
+    assert_eq!(lgetxattr(&file, "user.foo").unwrap(), b"1");
+    assert_eq!(lgetxattr(&file, "trusted.foo"), Err(Errno(libc::ENODATA)));
+    assert!(dir.path().join(STORE_NAME).exists());
+
+    let sidecar =
+        Sidecar::with_namespaces(SyscallBackend, &[Namespace::Trusted]);
+    assert_eq!(sidecar.migrate(dir.path()).unwrap(), 1);
+    assert_eq!(lgetxattr(&file, "trusted.foo").unwrap(), b"2");
+    assert_eq!(
+        lgetxattr(&file, "security.capability"),
+        Err(Errno(libc::ENODATA))
+    );
+}
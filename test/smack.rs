@@ -0,0 +1,156 @@
+use errno::Errno;
+use extattr::{
+    getxattr,
+    smack::{
+        fgetlabel, fgettransmute, fsetlabel, fsettransmute, getlabel,
+        gettransmute, removelabel, setlabel, settransmute, SmackAttr,
+        SmackLabel, SMACK_LABEL_MAX_LEN, XATTR_NAME_SMACK64TRANSMUTE,
+    },
+};
+use std::{fs::File, os::unix::io::AsRawFd};
+
+#[test]
+fn test_smack_label_valid() {
+    for label in ["_", "*", "^", "User", "System::Shared", "a-b.c_d"] {
+        assert_eq!(SmackLabel::new(label).unwrap().as_str(), label);
+    }
+    let longest = "a".repeat(SMACK_LABEL_MAX_LEN);
+    assert!(SmackLabel::new(longest).is_ok());
+}
+
+#[test]
+fn test_smack_label_invalid() {
+    let too_long = "a".repeat(SMACK_LABEL_MAX_LEN + 1);
+    for label in [
+        "",
+        "-starts-with-dash",
+        "has space",
+        "has/slash",
+        "has\"quote",
+        "has'quote",
+        "has\\backslash",
+        "non-ascii-é",
+        "tab\t",
+        too_long.as_str(),
+    ] {
+        assert_eq!(
+            SmackLabel::new(label),
+            Err(Errno(libc::EINVAL)),
+            "{}",
+            label
+        );
+    }
+}
+
+#[test]
+fn test_smack_label_from_bytes() {
+    assert_eq!(
+        SmackLabel::from_bytes(b"System\0").unwrap(),
+        "System".parse().unwrap()
+    );
+    assert_eq!(
+        SmackLabel::from_bytes(b"System").unwrap().to_string(),
+        "System"
+    );
+    assert_eq!(SmackLabel::from_bytes(b"\xff"), Err(Errno(libc::EINVAL)));
+}
+
+#[test]
+fn test_smack_attr_name() {
+    assert_eq!(SmackAttr::Access.name(), "security.SMACK64");
+    assert_eq!(SmackAttr::Exec.name(), "security.SMACK64EXEC");
+    assert_eq!(SmackAttr::Mmap.name(), "security.SMACK64MMAP");
+    assert_eq!(SmackAttr::IpIn.name(), "security.SMACK64IPIN");
+    assert_eq!(SmackAttr::IpOut.name(), "security.SMACK64IPOUT");
+}
+
+#[test]
+fn test_setlabel_getlabel() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let temp_file_path = temp_dir.path().join("test_setlabel_getlabel");
+    File::create(temp_file_path.as_path()).unwrap();
+    let label = SmackLabel::new("Test").unwrap();
+
+    match setlabel(temp_file_path.as_path(), SmackAttr::Exec, &label) {
+        // Setting `security.*` EA requires `CAP_SYS_ADMIN` (or
+        // `CAP_MAC_ADMIN` with Smack enabled), skip this test.
+        Err(Errno(libc::ENOTSUP | libc::EPERM)) => return,
+        res => res.unwrap(),
+    }
+
+    assert_eq!(
+        getxattr(temp_file_path.as_path(), "security.SMACK64EXEC").unwrap(),
+        b"Test"
+    );
+    assert_eq!(
+        getlabel(temp_file_path.as_path(), SmackAttr::Exec).unwrap(),
+        label
+    );
+
+    removelabel(temp_file_path.as_path(), SmackAttr::Exec).unwrap();
+    assert_eq!(
+        getlabel(temp_file_path.as_path(), SmackAttr::Exec),
+        Err(Errno(libc::ENODATA))
+    );
+}
+
+#[test]
+fn test_fsetlabel_fgetlabel() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let temp_file_path = temp_dir.path().join("test_fsetlabel_fgetlabel");
+    let temp_file = File::create(temp_file_path.as_path()).unwrap();
+    let temp_file_fd = temp_file.as_raw_fd();
+    let label = SmackLabel::new("Test").unwrap();
+
+    match fsetlabel(temp_file_fd, SmackAttr::Mmap, &label) {
+        // Setting `security.*` EA requires `CAP_SYS_ADMIN` (or
+        // `CAP_MAC_ADMIN` with Smack enabled), skip this test.
+        Err(Errno(libc::ENOTSUP | libc::EPERM)) => return,
+        res => res.unwrap(),
+    }
+
+    assert_eq!(fgetlabel(temp_file_fd, SmackAttr::Mmap).unwrap(), label);
+}
+
+#[test]
+fn test_settransmute_gettransmute() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let dir = temp_dir.path();
+
+    match gettransmute(dir) {
+        Err(Errno(libc::ENOTSUP)) => return,
+        res => assert!(!res.unwrap()),
+    }
+
+    match settransmute(dir, true) {
+        // Setting `security.*` EA requires `CAP_SYS_ADMIN` (or
+        // `CAP_MAC_ADMIN` with Smack enabled), skip this test.
+        Err(Errno(libc::ENOTSUP | libc::EPERM)) => return,
+        res => res.unwrap(),
+    }
+    assert_eq!(getxattr(dir, XATTR_NAME_SMACK64TRANSMUTE).unwrap(), b"TRUE");
+    assert!(gettransmute(dir).unwrap());
+
+    settransmute(dir, false).unwrap();
+    assert!(!gettransmute(dir).unwrap());
+    // clearing an unset marker is fine
+    settransmute(dir, false).unwrap();
+}
+
+#[test]
+fn test_fsettransmute_fgettransmute() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let dir = File::open(temp_dir.path()).unwrap();
+    let dir_fd = dir.as_raw_fd();
+
+    match fsettransmute(dir_fd, true) {
+        // Setting `security.*` EA requires `CAP_SYS_ADMIN` (or
+        // `CAP_MAC_ADMIN` with Smack enabled), skip this test.
+        Err(Errno(libc::ENOTSUP | libc::EPERM)) => return,
+        res => res.unwrap(),
+    }
+    assert!(fgettransmute(dir_fd).unwrap());
+
+    fsettransmute(dir_fd, false).unwrap();
+    assert!(!fgettransmute(dir_fd).unwrap());
+}
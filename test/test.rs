@@ -1257,3 +1257,96 @@ mod test_darwin {
         .is_ok());
     }
 }
+
+#[cfg(test)]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod selinux;
+
+#[cfg(test)]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod smack;
+
+#[cfg(test)]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod ima;
+
+#[cfg(test)]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod overlay;
+
+#[cfg(test)]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod posix_acl;
+
+#[cfg(test)]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod nfs4_acl;
+
+#[cfg(test)]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod samba;
+
+#[cfg(test)]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod xdg;
+
+#[cfg(test)]
+mod apple;
+
+#[cfg(test)]
+mod appledouble;
+
+#[cfg(test)]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod btrfs;
+
+#[cfg(test)]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod ceph;
+
+#[cfg(test)]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod cgroup;
+
+#[cfg(test)]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod rootless;
+
+#[cfg(test)]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod s3;
+
+#[cfg(test)]
+mod name;
+
+#[cfg(test)]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod portable;
+
+#[cfg(test)]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod limit;
+
+#[cfg(test)]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod pax;
+
+#[cfg(test)]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod oci;
+
+#[cfg(test)]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod sidecar;
+
+#[cfg(test)]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod backend;
+
+#[cfg(test)]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod fault;
+
+#[cfg(test)]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod trace;
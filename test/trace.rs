@@ -0,0 +1,210 @@
+use errno::Errno;
+use extattr::{
+    backend::{
+        FileKind, MemoryBackend, MemoryConfig, SyscallBackend, XattrBackend,
+    },
+    portable::Target,
+    trace::{
+        parse, replay, seed, Divergence, Event, RecordingBackend, Replay,
+        TraceCall, TraceResult, TraceTarget, TRACE_HEADER,
+    },
+    Flags,
+};
+use std::{
+    ffi::{OsStr, OsString},
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+const TRACE: &str = "extattr-trace 1
+set path 'dir/file 'user.foo 'bar 1 = ok
+get link 'dir/file 'user.foo = value 'bar
+list fd 3 = names 'user.foo 'user.my%20name
+remove path 'dir/file 'user.baz = err ENODATA
+get path 'dir/file 'user.empty = value '
+list path 'odd = err E#4242
+get fd -1 'user.foo = err EBADF
+";
+
+#[test]
+fn test_format() {
+    let events = parse(TRACE.as_bytes()).unwrap();
+    assert_eq!(events.len(), 7);
+    assert_eq!(
+        events[0],
+        Event {
+            call: TraceCall::Set {
+                target: TraceTarget::Path(PathBuf::from("dir/file")),
+                name: OsString::from("user.foo"),
+                value: b"bar".to_vec(),
+                flags: Flags::XATTR_CREATE,
+            },
+            result: TraceResult::Ok,
+        }
+    );
+    assert_eq!(
+        events[2].result,
+        TraceResult::Names(vec![
+            OsString::from("user.foo"),
+            OsString::from("user.my name"),
+        ])
+    );
+    assert_eq!(events[3].result, TraceResult::Err(Errno(libc::ENODATA)));
+    assert_eq!(events[4].result, TraceResult::Value(Vec::new()));
+    assert_eq!(events[5].result, TraceResult::Err(Errno(4242)));
+    assert_eq!(events[6].call.target(), &TraceTarget::Fd(-1));
+
+    let mut encoded = format!("{}\n", TRACE_HEADER).into_bytes();
+    for event in events.iter() {
+        encoded.extend_from_slice(&event.encode());
+    }
+    assert_eq!(encoded, TRACE.as_bytes());
+
+    for invalid in [
+        "get path 'f 'user.foo",
+        "get path 'f 'user.foo = ok",
+        "get path f 'user.foo = value 'bar",
+        "set path 'f 'user.foo 'bar 4 = ok",
+        "list path 'f = err ENOPE",
+        "list path 'f = names 'a%2",
+        "get path 'f 'user.foo = value 'bar 'baz",
+        "get  path 'f 'user.foo = value 'bar",
+        "list fd - = names",
+        "list fd +1 = names",
+        "list fd --1 = names",
+    ] {
+        assert_eq!(
+            Event::decode(invalid.as_bytes()),
+            Err(Errno(libc::EINVAL)),
+            "{}",
+            invalid
+        );
+    }
+    assert_eq!(parse(b"get path 'f 'a = ok\n"), Err(Errno(libc::EINVAL)));
+}
+
+#[test]
+fn test_record_and_replay() {
+    let dir = tempfile::tempdir_in("./").unwrap();
+    let path = dir.path().join("file");
+    File::create(&path).unwrap();
+    match SyscallBackend.set(
+        Target::Path(&path),
+        OsStr::new("user.existing"),
+        b"old",
+        Flags::empty(),
+    ) {
+        // EA not supported
+        Err(Errno(libc::ENOTSUP)) => return,
+        res => res.unwrap(),
+    }
+
+    let recorder = RecordingBackend::new(SyscallBackend, Vec::new()).unwrap();
+    let target = Target::Link(&path);
+    let foo = OsStr::new("user.foo");
+    recorder.list(target).unwrap();
+    recorder.get(target, OsStr::new("user.existing")).unwrap();
+    assert_eq!(recorder.get(target, foo), Err(Errno(libc::ENODATA)));
+    recorder
+        .set(target, foo, b"a b\n", Flags::XATTR_CREATE)
+        .unwrap();
+    assert_eq!(
+        recorder.set(target, foo, b"", Flags::XATTR_CREATE),
+        Err(Errno(libc::EEXIST))
+    );
+    recorder.remove(target, foo).unwrap();
+    assert_eq!(recorder.error(), None);
+
+    let (_, trace) = recorder.into_inner();
+    let events = parse(&trace).unwrap();
+    assert_eq!(events.len(), 6);
+
+    let store = MemoryBackend::new(MemoryConfig::default());
+    seed(&events, &store).unwrap();
+    assert_eq!(replay(&events, &store), Replay::default());
+
+    // a store behaving differently is caught
+    let store = MemoryBackend::default();
+    seed(&events, &store).unwrap();
+    store.set(target, foo, b"", Flags::empty()).unwrap();
+    let divergences = replay(&events, &store).divergences;
+    assert_eq!(
+        divergences[0],
+        Divergence {
+            index: 0,
+            expected: TraceResult::Names(vec![OsString::from("user.existing")]),
+            actual: TraceResult::Names(vec![
+                OsString::from("user.existing"),
+                OsString::from("user.foo"),
+            ]),
+        }
+    );
+}
+
+#[test]
+fn test_seed() {
+    let trace = "extattr-trace 1
+list path 'a = names 'user.listed 'user.read
+get path 'a 'user.read = value 'v
+set path 'a 'user.listed 'new 0 = ok
+get path 'a 'user.listed = value 'new
+get path 'b 'user.missing = err ENODATA
+";
+    let events = parse(trace.as_bytes()).unwrap();
+    let store = MemoryBackend::default();
+    seed(&events, &store).unwrap();
+
+    let a = Target::Path(Path::new("a"));
+    assert_eq!(store.get(a, OsStr::new("user.read")).unwrap(), b"v");
+    // the value is not known before it is set
+    assert_eq!(store.get(a, OsStr::new("user.listed")).unwrap(), b"");
+    assert_eq!(store.create("b", FileKind::File), Err(Errno(libc::EEXIST)));
+    assert_eq!(replay(&events, &store), Replay::default());
+}
+
+#[test]
+fn test_replay_fd() {
+    let trace = "extattr-trace 1
+set path 'a 'user.foo 'bar 0 = ok
+get fd 3 'user.foo = value 'bar
+list fd 3 = names 'user.foo
+get path 'a 'user.foo = value 'baz
+";
+    let events = parse(trace.as_bytes()).unwrap();
+    let store = MemoryBackend::default();
+    seed(&events, &store).unwrap();
+
+    // descriptors are not known to the replaying backend
+    let replay = replay(&events, &store);
+    assert_eq!(replay.unreplayable, [1, 2]);
+    assert_eq!(
+        replay.divergences,
+        [Divergence {
+            index: 3,
+            expected: TraceResult::Value(b"baz".to_vec()),
+            actual: TraceResult::Value(b"bar".to_vec()),
+        }]
+    );
+}
+
+#[test]
+fn test_seed_missing() {
+    let trace = "extattr-trace 1
+get path 'missing 'user.x = err ENOENT
+set path 'missing 'user.x 'v 0 = err ENOENT
+get link 'file/sub 'user.x = err ENOTDIR
+list path 'file = names
+get path 'a 'user.x = err ENODATA
+";
+    let events = parse(trace.as_bytes()).unwrap();
+    let store = MemoryBackend::default();
+    seed(&events, &store).unwrap();
+
+    // the failing paths are not created
+    let missing = Target::Path(Path::new("missing"));
+    assert_eq!(
+        store.get(missing, OsStr::new("user.x")),
+        Err(Errno(libc::ENOENT))
+    );
+    assert_eq!(replay(&events, &store), Replay::default());
+}
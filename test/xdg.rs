@@ -0,0 +1,129 @@
+use errno::Errno;
+use extattr::{
+    getxattr, setxattr,
+    xdg::{
+        add_tag, get_rating, get_tags, get_text, remove, remove_tag,
+        set_rating, set_tags, set_text, Tags, XdgAttr,
+    },
+    Flags,
+};
+use std::fs::File;
+
+#[test]
+fn test_names() {
+    assert_eq!(XdgAttr::OriginUrl.name(), "user.xdg.origin.url");
+    assert_eq!(XdgAttr::MimeType.name(), "user.mime_type");
+    assert_eq!(XdgAttr::BalooRating.name(), "user.baloo.rating");
+}
+
+#[test]
+fn test_tags_parse() {
+    let tags: Tags = " work, rust,,work ,todo list".parse().unwrap();
+    assert_eq!(
+        tags.iter().collect::<Vec<_>>(),
+        ["work", "rust", "todo list"]
+    );
+    assert_eq!(tags.to_string(), "work,rust,todo list");
+    assert!(tags.contains(" rust"));
+    assert!("".parse::<Tags>().unwrap().is_empty());
+}
+
+#[test]
+fn test_tags_insert_remove() {
+    let mut tags = Tags::new();
+    assert!(tags.insert("a").unwrap());
+    assert!(!tags.insert(" a ").unwrap());
+    assert_eq!(tags.insert("a,b"), Err(Errno(libc::EINVAL)));
+    assert_eq!(tags.insert("  "), Err(Errno(libc::EINVAL)));
+    assert!(tags.remove("a"));
+    assert!(!tags.remove("a"));
+    assert!(tags.is_empty());
+}
+
+#[test]
+fn test_text() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let temp_file_path = temp_dir.path().join("test_text");
+    File::create(&temp_file_path).unwrap();
+
+    match set_text(
+        &temp_file_path,
+        XdgAttr::OriginUrl,
+        "https://example.com/file.tar.gz",
+    ) {
+        // EA not supported
+        Err(Errno(libc::ENOTSUP)) => return,
+        res => res.unwrap(),
+    }
+    assert_eq!(
+        get_text(&temp_file_path, XdgAttr::OriginUrl)
+            .unwrap()
+            .as_deref(),
+        Some("https://example.com/file.tar.gz")
+    );
+    assert_eq!(get_text(&temp_file_path, XdgAttr::Comment).unwrap(), None);
+
+    remove(&temp_file_path, XdgAttr::OriginUrl).unwrap();
+    remove(&temp_file_path, XdgAttr::OriginUrl).unwrap();
+    assert_eq!(get_text(&temp_file_path, XdgAttr::OriginUrl).unwrap(), None);
+
+    setxattr(&temp_file_path, "user.xdg.comment", [0xff], Flags::empty())
+        .unwrap();
+    assert_eq!(
+        get_text(&temp_file_path, XdgAttr::Comment),
+        Err(Errno(libc::EINVAL))
+    );
+}
+
+#[test]
+fn test_tags() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let temp_file_path = temp_dir.path().join("test_tags");
+    File::create(&temp_file_path).unwrap();
+
+    match add_tag(&temp_file_path, "work") {
+        // EA not supported
+        Err(Errno(libc::ENOTSUP)) => return,
+        res => assert!(res.unwrap()),
+    }
+    assert!(add_tag(&temp_file_path, "rust").unwrap());
+    assert!(!add_tag(&temp_file_path, "work").unwrap());
+    assert_eq!(
+        getxattr(&temp_file_path, "user.xdg.tags").unwrap(),
+        b"work,rust"
+    );
+
+    assert!(remove_tag(&temp_file_path, "work").unwrap());
+    assert!(!remove_tag(&temp_file_path, "work").unwrap());
+    assert!(remove_tag(&temp_file_path, "rust").unwrap());
+    assert_eq!(
+        getxattr(&temp_file_path, "user.xdg.tags"),
+        Err(Errno(libc::ENODATA))
+    );
+    assert!(get_tags(&temp_file_path).unwrap().is_empty());
+
+    set_tags(&temp_file_path, &"a,b".parse().unwrap()).unwrap();
+    assert_eq!(get_tags(&temp_file_path).unwrap().len(), 2);
+}
+
+#[test]
+fn test_rating() {
+    let temp_dir = tempfile::tempdir_in("./").unwrap();
+    let temp_file_path = temp_dir.path().join("test_rating");
+    File::create(&temp_file_path).unwrap();
+
+    assert_eq!(set_rating(&temp_file_path, 11), Err(Errno(libc::EINVAL)));
+    match set_rating(&temp_file_path, 7) {
+        // EA not supported
+        Err(Errno(libc::ENOTSUP)) => return,
+        res => res.unwrap(),
+    }
+    assert_eq!(
+        getxattr(&temp_file_path, "user.baloo.rating").unwrap(),
+        b"7"
+    );
+    assert_eq!(get_rating(&temp_file_path).unwrap(), Some(7));
+
+    set_text(&temp_file_path, XdgAttr::BalooRating, "42").unwrap();
+    assert_eq!(get_rating(&temp_file_path), Err(Errno(libc::EINVAL)));
+}